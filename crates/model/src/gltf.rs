@@ -0,0 +1,309 @@
+mod accessor;
+mod base64;
+mod glb;
+mod json;
+mod node;
+
+pub use json::Json;
+pub use node::Transform;
+
+use std::{
+    error::Error,
+    fmt::{Debug, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use rs42::extensions::PipeLine;
+
+use base64::decode_base64;
+use glb::{parse_glb, GLB_MAGIC};
+use json::JsonParsingError;
+
+pub struct GltfFile<'a>(pub &'a str);
+
+// Only the JSON document and the buffers are loaded, everything else is read on demand
+pub struct Gltf {
+    json: Json,
+    buffers: Box<[Vec<u8>]>,
+    // External files are relative to the directory of the glTF file
+    directory: Option<PathBuf>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum GltfParsingError {
+    FailedToReadFile(io::Error),
+    FailedToReadBuffer { uri: String, err: io::Error },
+
+    InvalidGlb(&'static str),
+    InvalidUtf8,
+    InvalidJson(JsonParsingError),
+    UnsupportedVersion(String),
+    UnsupportedRequiredExtension(String),
+
+    MissingProperty(String),
+    InvalidProperty(String),
+    InvalidBase64Uri(String),
+    UnsupportedUri(String),
+    BufferTooSmall { buffer: usize, expected_len: usize },
+    AccessorOutOfBounds(usize),
+    UnsupportedAccessor(usize),
+    NodeHierarchyIsNotATree,
+}
+
+impl Display for GltfParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GltfParsingError({self:?})")
+    }
+}
+
+impl Error for GltfParsingError {}
+
+impl TryFrom<GltfFile<'_>> for Gltf {
+    type Error = GltfParsingError;
+
+    fn try_from(file_name: GltfFile) -> Result<Self, Self::Error> {
+        let content = fs::read(file_name.0).map_err(GltfParsingError::FailedToReadFile)?;
+        Self::from_bytes(&content, Path::new(file_name.0).parent())
+    }
+}
+
+// Constructors:
+
+impl Gltf {
+    // Accepts both .gltf and .glb content, .glb files are recognized by their magic number
+    pub fn from_bytes(content: &[u8], directory: Option<&Path>) -> Result<Self, GltfParsingError> {
+        let (json, glb_buffer) = if content.starts_with(&GLB_MAGIC) {
+            parse_glb(content)?
+        } else {
+            (content, None)
+        };
+
+        let json = std::str::from_utf8(json).map_err(|_| GltfParsingError::InvalidUtf8)?;
+        // The JSON chunk of a GLB might start with a byte order mark
+        let json = Json::try_from(json.trim_start_matches('\u{feff}'))
+            .map_err(GltfParsingError::InvalidJson)?;
+        check_asset(&json)?;
+
+        let mut gltf = Self {
+            json,
+            buffers: Box::default(),
+            directory: directory.map(Path::to_path_buf),
+        };
+        gltf.buffers = gltf.load_buffers(glb_buffer)?;
+        Ok(gltf)
+    }
+
+    fn load_buffers(&self, glb_buffer: Option<&[u8]>) -> Result<Box<[Vec<u8>]>, GltfParsingError> {
+        self.array("buffers")
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let byte_length = get_usize(buffer, "byteLength", || format!("buffers[{i}]"))?;
+
+                // Only the first buffer can refer to the binary chunk of a GLB
+                let data = match buffer.get("uri").map(|uri| (uri, uri.as_str())) {
+                    Some((_, Some(uri))) => self.load_uri(uri)?,
+                    Some(_) => {
+                        return Err(GltfParsingError::InvalidProperty(format!(
+                            "buffers[{i}].uri"
+                        )))
+                    }
+                    None if i == 0 => glb_buffer
+                        .ok_or_else(|| GltfParsingError::MissingProperty("buffers[0].uri".into()))?
+                        .to_vec(),
+                    None => {
+                        return Err(GltfParsingError::MissingProperty(format!(
+                            "buffers[{i}].uri"
+                        )))
+                    }
+                };
+
+                if data.len() < byte_length {
+                    return Err(GltfParsingError::BufferTooSmall {
+                        buffer: i,
+                        expected_len: byte_length,
+                    });
+                }
+                Ok(data)
+            })
+            .collect()
+    }
+
+    fn load_uri(&self, uri: &str) -> Result<Vec<u8>, GltfParsingError> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, base64) = data
+                .split_once(";base64,")
+                .ok_or_else(|| GltfParsingError::UnsupportedUri(uri.to_owned()))?;
+            return decode_base64(base64)
+                .ok_or_else(|| GltfParsingError::InvalidBase64Uri(uri.to_owned()));
+        }
+
+        let path = self.resolve_path(uri);
+        fs::read(&path).map_err(|err| GltfParsingError::FailedToReadBuffer {
+            uri: path.to_string_lossy().into_owned(),
+            err,
+        })
+    }
+}
+
+fn check_asset(json: &Json) -> Result<(), GltfParsingError> {
+    let version = json
+        .get("asset")
+        .and_then(|asset| asset.get("version"))
+        .ok_or_else(|| GltfParsingError::MissingProperty("asset.version".into()))?
+        .as_str()
+        .ok_or_else(|| GltfParsingError::InvalidProperty("asset.version".into()))?;
+    if version.split('.').next() != Some("2") {
+        return Err(GltfParsingError::UnsupportedVersion(version.to_owned()));
+    }
+
+    // No extension is supported, optional ones are ignored
+    if let Some(extension) = json
+        .get("extensionsRequired")
+        .and_then(Json::as_array)
+        .and_then(|extensions| extensions.first())
+    {
+        return Err(GltfParsingError::UnsupportedRequiredExtension(
+            extension.as_str().unwrap_or_default().to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+// Getters:
+
+impl Gltf {
+    pub fn json(&self) -> &Json {
+        &self.json
+    }
+
+    // Missing top level arrays are equivalent to empty ones
+    pub fn array(&self, name: &str) -> &[Json] {
+        self.json
+            .get(name)
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+    }
+
+    pub fn get(&self, name: &str, index: usize) -> Result<&Json, GltfParsingError> {
+        self.array(name)
+            .get(index)
+            .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{name}[{index}]")))
+    }
+
+    // Paths of the external buffers, images are not included as they are loaded separately
+    pub fn dependencies(&self) -> Vec<String> {
+        self.array("buffers")
+            .iter()
+            .filter_map(|buffer| buffer.get("uri")?.as_str())
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| self.resolve_path(uri).to_string_lossy().into_owned())
+            .collect()
+    }
+
+    // Data URIs and URIs with a scheme can not be resolved to a path
+    pub fn image_path(&self, image: usize) -> Option<String> {
+        let uri = self.get("images", image).ok()?.get("uri")?.as_str()?;
+        if uri.contains(':') {
+            return None;
+        }
+        self.resolve_path(uri)
+            .to_string_lossy()
+            .into_owned()
+            .pipe(Some)
+    }
+
+    fn resolve_path(&self, uri: &str) -> PathBuf {
+        let uri = decode_percent_encoding(uri);
+        match self.directory.as_ref() {
+            Some(directory) => directory.join(uri),
+            None => PathBuf::from(uri),
+        }
+    }
+}
+
+pub fn get_usize(
+    json: &Json,
+    key: &str,
+    path: impl Fn() -> String,
+) -> Result<usize, GltfParsingError> {
+    json.get(key)
+        .ok_or_else(|| GltfParsingError::MissingProperty(format!("{}.{key}", path())))?
+        .as_usize()
+        .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.{key}", path())))
+}
+
+pub fn get_optional_usize(
+    json: &Json,
+    key: &str,
+    path: impl Fn() -> String,
+) -> Result<Option<usize>, GltfParsingError> {
+    if json.get(key).is_none() {
+        return Ok(None);
+    }
+    get_usize(json, key, path).map(Some)
+}
+
+pub fn get_floats<const N: usize>(
+    json: &Json,
+    key: &str,
+    default: [f32; N],
+    path: impl Fn() -> String,
+) -> Result<[f32; N], GltfParsingError> {
+    let Some(floats) = json.get(key) else {
+        return Ok(default);
+    };
+    floats
+        .as_array()
+        .filter(|floats| floats.len() == N)
+        .and_then(|floats| {
+            let floats = floats
+                .iter()
+                .map(|float| float.as_f64().map(|float| float as f32))
+                .collect::<Option<Vec<f32>>>()?;
+            floats.try_into().ok()
+        })
+        .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.{key}", path())))
+}
+
+pub fn get_f32(
+    json: &Json,
+    key: &str,
+    default: f32,
+    path: impl Fn() -> String,
+) -> Result<f32, GltfParsingError> {
+    let Some(float) = json.get(key) else {
+        return Ok(default);
+    };
+    float
+        .as_f64()
+        .map(|float| float as f32)
+        .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.{key}", path())))
+}
+
+// URIs are percent encoded, e.g. spaces are written as %20
+fn decode_percent_encoding(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,194 @@
+use rs42::extensions::PipeLine;
+
+use super::{get_optional_usize, get_usize, Gltf, GltfParsingError};
+
+#[derive(Clone, Copy, PartialEq)]
+enum ComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    U32,
+    F32,
+}
+
+impl ComponentType {
+    fn from_gl_enum(gl_enum: usize) -> Option<Self> {
+        match gl_enum {
+            5120 => Some(Self::I8),
+            5121 => Some(Self::U8),
+            5122 => Some(Self::I16),
+            5123 => Some(Self::U16),
+            5125 => Some(Self::U32),
+            5126 => Some(Self::F32),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::U32 | Self::F32 => 4,
+        }
+    }
+
+    fn read_f32(self, bytes: &[u8], normalized: bool) -> f32 {
+        let (value, max) = match self {
+            Self::I8 => (i8::from_le_bytes([bytes[0]]) as f32, i8::MAX as f32),
+            Self::U8 => (bytes[0] as f32, u8::MAX as f32),
+            Self::I16 => (
+                i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                i16::MAX as f32,
+            ),
+            Self::U16 => (
+                u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+                u16::MAX as f32,
+            ),
+            Self::U32 => (self.read_u32(bytes) as f32, u32::MAX as f32),
+            Self::F32 => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        };
+        if !normalized {
+            return value;
+        }
+        (value / max).max(-1.)
+    }
+
+    // Only called on unsigned types
+    fn read_u32(self, bytes: &[u8]) -> u32 {
+        match self {
+            Self::U8 => bytes[0] as u32,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            _ => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
+impl Gltf {
+    pub fn read_float_accessor<const N: usize>(
+        &self,
+        index: usize,
+    ) -> Result<Vec<[f32; N]>, GltfParsingError> {
+        self.read_accessor(index, |component_type, normalized, bytes| {
+            component_type.read_f32(bytes, normalized)
+        })
+    }
+
+    pub fn read_index_accessor(&self, index: usize) -> Result<Vec<u32>, GltfParsingError> {
+        let component_type = self.get_component_type(index)?;
+        if ![ComponentType::U8, ComponentType::U16, ComponentType::U32].contains(&component_type) {
+            return Err(GltfParsingError::UnsupportedAccessor(index));
+        }
+
+        self.read_accessor::<_, 1>(index, |component_type, _, bytes| {
+            component_type.read_u32(bytes)
+        })?
+        .into_iter()
+        .map(|[index]| index)
+        .collect::<Vec<u32>>()
+        .pipe(Ok)
+    }
+
+    fn get_component_type(&self, index: usize) -> Result<ComponentType, GltfParsingError> {
+        let path = || format!("accessors[{index}]");
+        get_usize(self.get("accessors", index)?, "componentType", path)?
+            .pipe(ComponentType::from_gl_enum)
+            .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.componentType", path())))
+    }
+
+    fn read_accessor<T: Copy, const N: usize>(
+        &self,
+        index: usize,
+        read_component: impl Fn(ComponentType, bool, &[u8]) -> T,
+    ) -> Result<Vec<[T; N]>, GltfParsingError> {
+        let accessor = self.get("accessors", index)?;
+        let path = || format!("accessors[{index}]");
+
+        if accessor.get("sparse").is_some() {
+            return Err(GltfParsingError::UnsupportedAccessor(index));
+        }
+        let components = match accessor.get("type").and_then(|r#type| r#type.as_str()) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") => 4,
+            _ => 0,
+        };
+        if components != N {
+            return Err(GltfParsingError::UnsupportedAccessor(index));
+        }
+
+        let count = get_usize(accessor, "count", path)?;
+        let component_type = self.get_component_type(index)?;
+        let normalized = accessor
+            .get("normalized")
+            .and_then(|normalized| normalized.as_bool())
+            .unwrap_or(false);
+        let element_size = component_type.size() * N;
+
+        // Accessors without buffer view are filled with zeros
+        let Some(buffer_view) = get_optional_usize(accessor, "bufferView", path)? else {
+            let zero = read_component(component_type, normalized, &[0; 4]);
+            return Ok(vec![[zero; N]; count]);
+        };
+        let (data, stride) = self.get_buffer_view(buffer_view, element_size)?;
+
+        let offset = get_optional_usize(accessor, "byteOffset", path)?.unwrap_or(0);
+        let end = count
+            .checked_sub(1)
+            .map_or(Some(offset), |last| {
+                last.checked_mul(stride)?
+                    .checked_add(offset)?
+                    .checked_add(element_size)
+            })
+            .ok_or(GltfParsingError::AccessorOutOfBounds(index))?;
+        if end > data.len() {
+            return Err(GltfParsingError::AccessorOutOfBounds(index));
+        }
+
+        (0..count)
+            .map(|i| {
+                let element = &data[offset + i * stride..];
+                std::array::from_fn(|j| {
+                    read_component(
+                        component_type,
+                        normalized,
+                        &element[j * component_type.size()..],
+                    )
+                })
+            })
+            .collect::<Vec<[T; N]>>()
+            .pipe(Ok)
+    }
+
+    // Returns the data of the buffer view and its stride
+    fn get_buffer_view(
+        &self,
+        index: usize,
+        element_size: usize,
+    ) -> Result<(&[u8], usize), GltfParsingError> {
+        let buffer_view = self.get("bufferViews", index)?;
+        let path = || format!("bufferViews[{index}]");
+
+        let buffer = get_usize(buffer_view, "buffer", path)?;
+        let buffer = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.buffer", path())))?;
+        let offset = get_optional_usize(buffer_view, "byteOffset", path)?.unwrap_or(0);
+        let length = get_usize(buffer_view, "byteLength", path)?;
+        let stride = get_optional_usize(buffer_view, "byteStride", path)?.unwrap_or(element_size);
+        if stride < element_size {
+            return Err(GltfParsingError::InvalidProperty(format!(
+                "{}.byteStride",
+                path()
+            )));
+        }
+
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.byteLength", path())))?;
+        Ok((data, stride))
+    }
+}
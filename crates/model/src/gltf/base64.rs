@@ -0,0 +1,51 @@
+// Decodes standard base64 (RFC 4648), padding is optional
+pub fn decode_base64(str: &str) -> Option<Vec<u8>> {
+    let str = str.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(str.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut buffer_bits = 0;
+
+    for char in str.bytes() {
+        let value = match char {
+            b'A'..=b'Z' => char - b'A',
+            b'a'..=b'z' => char - b'a' + 26,
+            b'0'..=b'9' => char - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        buffer_bits += 6;
+        if buffer_bits >= 8 {
+            buffer_bits -= 8;
+            bytes.push((buffer >> buffer_bits) as u8);
+            buffer &= (1 << buffer_bits) - 1;
+        }
+    }
+
+    // A single character can not encode a full byte
+    if buffer_bits >= 6 {
+        return None;
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_base64("").as_deref(), Some(&b""[..]));
+        assert_eq!(decode_base64("Zg==").as_deref(), Some(&b"f"[..]));
+        assert_eq!(decode_base64("Zm8=").as_deref(), Some(&b"fo"[..]));
+        assert_eq!(decode_base64("Zm9v").as_deref(), Some(&b"foo"[..]));
+        assert_eq!(decode_base64("Zm9vYmFy").as_deref(), Some(&b"foobar"[..]));
+        assert_eq!(
+            decode_base64("AAEC/w").as_deref(),
+            Some(&[0, 1, 2, 255][..])
+        );
+        assert_eq!(decode_base64("Zm9v!"), None);
+        assert_eq!(decode_base64("Zm9vY"), None);
+    }
+}
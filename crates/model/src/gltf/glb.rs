@@ -0,0 +1,99 @@
+use super::GltfParsingError;
+
+pub const GLB_MAGIC: [u8; 4] = *b"glTF";
+const JSON_CHUNK_TYPE: u32 = 0x4E4F534A;
+const BIN_CHUNK_TYPE: u32 = 0x004E4942;
+const HEADER_LEN: usize = 12;
+const CHUNK_HEADER_LEN: usize = 8;
+
+// Returns the JSON chunk and the optional binary chunk
+pub fn parse_glb(content: &[u8]) -> Result<(&[u8], Option<&[u8]>), GltfParsingError> {
+    let version = read_u32(content, 4).ok_or(GltfParsingError::InvalidGlb("truncated header"))?;
+    if version != 2 {
+        return Err(GltfParsingError::UnsupportedVersion(version.to_string()));
+    }
+    let length = read_u32(content, 8).ok_or(GltfParsingError::InvalidGlb("truncated header"))?;
+    let content = content
+        .get(..length as usize)
+        .ok_or(GltfParsingError::InvalidGlb(
+            "file is smaller than its header says",
+        ))?;
+
+    let (json_chunk_type, json, offset) = read_chunk(content, HEADER_LEN)?;
+    if json_chunk_type != JSON_CHUNK_TYPE {
+        return Err(GltfParsingError::InvalidGlb("first chunk is not JSON"));
+    }
+
+    let mut bin = None;
+    let mut offset = offset;
+    // Chunks of unknown types must be ignored
+    while offset < content.len() {
+        let (chunk_type, chunk, next_offset) = read_chunk(content, offset)?;
+        if chunk_type == BIN_CHUNK_TYPE && bin.is_none() {
+            bin = Some(chunk);
+        }
+        offset = next_offset;
+    }
+
+    Ok((json, bin))
+}
+
+fn read_chunk(content: &[u8], offset: usize) -> Result<(u32, &[u8], usize), GltfParsingError> {
+    let (Some(length), Some(chunk_type)) =
+        (read_u32(content, offset), read_u32(content, offset + 4))
+    else {
+        return Err(GltfParsingError::InvalidGlb("truncated chunk header"));
+    };
+
+    let start = offset + CHUNK_HEADER_LEN;
+    let end = start + length as usize;
+    let chunk = content
+        .get(start..end)
+        .ok_or(GltfParsingError::InvalidGlb("truncated chunk"))?;
+    // Chunks are padded to 4 bytes
+    Ok((chunk_type, chunk, end.next_multiple_of(4)))
+}
+
+fn read_u32(content: &[u8], offset: usize) -> Option<u32> {
+    content
+        .get(offset..offset + 4)?
+        .try_into()
+        .ok()
+        .map(u32::from_le_bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_glb(json: &[u8], bin: &[u8]) -> Vec<u8> {
+        let mut glb = GLB_MAGIC.to_vec();
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(0u32.to_le_bytes());
+        for (chunk_type, chunk, padding) in
+            [(JSON_CHUNK_TYPE, json, b' '), (BIN_CHUNK_TYPE, bin, 0)]
+        {
+            glb.extend((chunk.len().next_multiple_of(4) as u32).to_le_bytes());
+            glb.extend(chunk_type.to_le_bytes());
+            glb.extend(chunk);
+            glb.resize(glb.len().next_multiple_of(4), padding);
+        }
+        let len = (glb.len() as u32).to_le_bytes();
+        glb[8..12].copy_from_slice(&len);
+        glb
+    }
+
+    #[test]
+    fn parse_chunks() {
+        let glb = get_glb(b"{}", &[1, 2, 3, 4, 5]);
+        let (json, bin) = parse_glb(&glb).unwrap();
+        assert_eq!(json, b"{}  ");
+        assert_eq!(bin, Some(&[1, 2, 3, 4, 5, 0, 0, 0][..]));
+    }
+
+    #[test]
+    fn truncated_file() {
+        let glb = get_glb(b"{}", &[1, 2, 3, 4, 5]);
+        assert!(parse_glb(&glb[..glb.len() - 4]).is_err());
+    }
+}
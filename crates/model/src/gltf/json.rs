@@ -0,0 +1,427 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{Debug, Display},
+    iter::Peekable,
+    str::CharIndices,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(HashMap<String, Json>),
+}
+
+#[allow(dead_code)]
+pub struct JsonParsingError {
+    position: usize,
+    detail: JsonParsingErrorDetail,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum JsonParsingErrorDetail {
+    UnexpectedEndOfFile,
+    UnexpectedCharacter(char),
+    InvalidNumber(String),
+    InvalidEscapeSequence,
+    InvalidUnicodeCodePoint(u32),
+    TrailingCharacters,
+    TooMuchNesting,
+}
+
+impl Debug for JsonParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "JsonParsingError {{\n\tposition: {}\n\tdetails: {:?}\n}}",
+            self.position, self.detail,
+        )
+    }
+}
+
+impl Display for JsonParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for JsonParsingError {}
+
+// Protects against stack overflows on malicious files
+const MAX_DEPTH: usize = 512;
+
+impl TryFrom<&str> for Json {
+    type Error = JsonParsingError;
+
+    fn try_from(str: &str) -> Result<Self, Self::Error> {
+        let mut parser = Parser {
+            str,
+            chars: str.char_indices().peekable(),
+        };
+
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespaces();
+        if parser.chars.peek().is_some() {
+            return Err(parser.error(JsonParsingErrorDetail::TrailingCharacters));
+        }
+        Ok(value)
+    }
+}
+
+// Getters:
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(object) => object.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        let number = self.as_f64()?;
+        if number < 0. || number.fract() != 0. || number > usize::MAX as f64 {
+            return None;
+        }
+        Some(number as usize)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    str: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn parse_value(&mut self, depth: usize) -> Result<Json, JsonParsingError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error(JsonParsingErrorDetail::TooMuchNesting));
+        }
+
+        self.skip_whitespaces();
+        match self.peek()? {
+            '{' => self.parse_object(depth),
+            '[' => self.parse_array(depth),
+            '"' => self.parse_string().map(Json::String),
+            't' => self.parse_keyword("true", Json::Bool(true)),
+            'f' => self.parse_keyword("false", Json::Bool(false)),
+            'n' => self.parse_keyword("null", Json::Null),
+            '-' | '0'..='9' => self.parse_number(),
+            char => Err(self.error(JsonParsingErrorDetail::UnexpectedCharacter(char))),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Json, JsonParsingError> {
+        self.expect('{')?;
+        let mut object = HashMap::new();
+
+        self.skip_whitespaces();
+        if self.peek()? == '}' {
+            self.chars.next();
+            return Ok(Json::Object(object));
+        }
+
+        loop {
+            self.skip_whitespaces();
+            let key = self.parse_string()?;
+            self.skip_whitespaces();
+            self.expect(':')?;
+            let value = self.parse_value(depth + 1)?;
+            object.insert(key, value);
+
+            self.skip_whitespaces();
+            match self.next()? {
+                ',' => continue,
+                '}' => return Ok(Json::Object(object)),
+                char => return Err(self.error(JsonParsingErrorDetail::UnexpectedCharacter(char))),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Json, JsonParsingError> {
+        self.expect('[')?;
+        let mut array = Vec::new();
+
+        self.skip_whitespaces();
+        if self.peek()? == ']' {
+            self.chars.next();
+            return Ok(Json::Array(array));
+        }
+
+        loop {
+            array.push(self.parse_value(depth + 1)?);
+
+            self.skip_whitespaces();
+            match self.next()? {
+                ',' => continue,
+                ']' => return Ok(Json::Array(array)),
+                char => return Err(self.error(JsonParsingErrorDetail::UnexpectedCharacter(char))),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonParsingError> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => string.push(self.parse_escape_sequence()?),
+                char if char < ' ' => {
+                    return Err(self.error(JsonParsingErrorDetail::UnexpectedCharacter(char)))
+                }
+                char => string.push(char),
+            }
+        }
+    }
+
+    fn parse_escape_sequence(&mut self) -> Result<char, JsonParsingError> {
+        let char = match self.next()? {
+            '"' => '"',
+            '\\' => '\\',
+            '/' => '/',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => return self.parse_unicode_escape_sequence(),
+            _ => return Err(self.error(JsonParsingErrorDetail::InvalidEscapeSequence)),
+        };
+        Ok(char)
+    }
+
+    fn parse_unicode_escape_sequence(&mut self) -> Result<char, JsonParsingError> {
+        let high = self.parse_hex_code_unit()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high)
+                .ok_or_else(|| self.error(JsonParsingErrorDetail::InvalidUnicodeCodePoint(high)));
+        }
+
+        // Characters outside of the basic multilingual plane are encoded as a surrogate pair
+        self.expect('\\')?;
+        self.expect('u')?;
+        let low = self.parse_hex_code_unit()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error(JsonParsingErrorDetail::InvalidUnicodeCodePoint(low)));
+        }
+        let code_point = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+        char::from_u32(code_point)
+            .ok_or_else(|| self.error(JsonParsingErrorDetail::InvalidUnicodeCodePoint(code_point)))
+    }
+
+    fn parse_hex_code_unit(&mut self) -> Result<u32, JsonParsingError> {
+        (0..4).try_fold(0, |code_unit, _| {
+            let digit = self
+                .next()?
+                .to_digit(16)
+                .ok_or_else(|| self.error(JsonParsingErrorDetail::InvalidEscapeSequence))?;
+            Ok(code_unit * 16 + digit)
+        })
+    }
+
+    fn parse_keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonParsingError> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Json, JsonParsingError> {
+        let start = self.position();
+        while self
+            .chars
+            .peek()
+            .is_some_and(|(_, char)| matches!(char, '-' | '+' | '.' | 'e' | 'E' | '0'..='9'))
+        {
+            self.chars.next();
+        }
+        let number = &self.str[start..self.position()];
+
+        // Rust accepts formats that JSON does not, such as "+1", ".5" or "inf"
+        if !is_valid_json_number(number) {
+            return Err(self.error(JsonParsingErrorDetail::InvalidNumber(number.to_owned())));
+        }
+        number
+            .parse::<f64>()
+            .map(Json::Number)
+            .map_err(|_| self.error(JsonParsingErrorDetail::InvalidNumber(number.to_owned())))
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while self
+            .chars
+            .peek()
+            .is_some_and(|(_, char)| matches!(char, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), JsonParsingError> {
+        match self.next()? {
+            char if char == expected => Ok(()),
+            char => Err(self.error(JsonParsingErrorDetail::UnexpectedCharacter(char))),
+        }
+    }
+
+    fn peek(&mut self) -> Result<char, JsonParsingError> {
+        match self.chars.peek() {
+            Some((_, char)) => Ok(*char),
+            None => Err(self.error(JsonParsingErrorDetail::UnexpectedEndOfFile)),
+        }
+    }
+
+    fn next(&mut self) -> Result<char, JsonParsingError> {
+        match self.chars.next() {
+            Some((_, char)) => Ok(char),
+            None => Err(self.error(JsonParsingErrorDetail::UnexpectedEndOfFile)),
+        }
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.str.len(), |(position, _)| *position)
+    }
+
+    fn error(&mut self, detail: JsonParsingErrorDetail) -> JsonParsingError {
+        JsonParsingError {
+            position: self.position(),
+            detail,
+        }
+    }
+}
+
+// -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+fn is_valid_json_number(number: &str) -> bool {
+    let mut bytes = number.as_bytes();
+    let skip_digits = |bytes: &mut &[u8]| {
+        let digits = bytes
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        *bytes = &bytes[digits..];
+        digits
+    };
+
+    bytes = bytes.strip_prefix(b"-").unwrap_or(bytes);
+    if bytes.starts_with(b"0") {
+        bytes = &bytes[1..];
+    } else if skip_digits(&mut bytes) == 0 {
+        return false;
+    }
+
+    if let Some(fraction) = bytes.strip_prefix(b".") {
+        bytes = fraction;
+        if skip_digits(&mut bytes) == 0 {
+            return false;
+        }
+    }
+
+    if let Some(exponent) = bytes.strip_prefix(b"e").or(bytes.strip_prefix(b"E")) {
+        bytes = exponent;
+        bytes = bytes
+            .strip_prefix(b"+")
+            .or(bytes.strip_prefix(b"-"))
+            .unwrap_or(bytes);
+        if skip_digits(&mut bytes) == 0 {
+            return false;
+        }
+    }
+
+    bytes.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_document() {
+        let json = Json::try_from(
+            r#" {"asset": {"version": "2.0"}, "list": [1, -2.5e2, true, null, "a\"\u00e9\ud83d\ude00"], "empty": {}} "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            json.get("asset").and_then(|asset| asset.get("version")),
+            Some(&Json::String("2.0".to_owned()))
+        );
+        assert_eq!(
+            json.get("list").and_then(Json::as_array),
+            Some(
+                &[
+                    Json::Number(1.),
+                    Json::Number(-250.),
+                    Json::Bool(true),
+                    Json::Null,
+                    Json::String("a\"é😀".to_owned()),
+                ][..]
+            )
+        );
+        assert_eq!(json.get("empty"), Some(&Json::Object(HashMap::new())));
+    }
+
+    #[test]
+    fn invalid_documents() {
+        for str in [
+            "",
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "01",
+            "+1",
+            ".5",
+            "1.",
+            "tru",
+            "\"\\x\"",
+            "[] []",
+        ] {
+            assert!(Json::try_from(str).is_err(), "{str:?} should not be valid");
+        }
+    }
+
+    #[test]
+    fn too_much_nesting() {
+        let str = "[".repeat(MAX_DEPTH + 2) + &"]".repeat(MAX_DEPTH + 2);
+        assert!(matches!(
+            Json::try_from(str.as_str()).unwrap_err().detail,
+            JsonParsingErrorDetail::TooMuchNesting
+        ));
+    }
+}
@@ -0,0 +1,168 @@
+use super::{get_floats, get_optional_usize, Gltf, GltfParsingError, Json};
+
+// Column major 4x4 matrix, like in glTF files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform(pub [[f32; 4]; 4]);
+
+// Mesh instantiated by a node of the scene
+#[derive(Debug)]
+pub struct Node {
+    pub mesh: usize,
+    pub transform: Transform,
+}
+
+impl Gltf {
+    // Flattens the node hierarchy of the default scene
+    pub fn get_scene_nodes(&self) -> Result<Vec<Node>, GltfParsingError> {
+        let mut nodes = Vec::new();
+        let mut visited = vec![false; self.array("nodes").len()];
+
+        for root in self.get_root_nodes()? {
+            self.visit_node(root, Transform::IDENTITY, &mut visited, &mut nodes)?;
+        }
+        Ok(nodes)
+    }
+
+    fn get_root_nodes(&self) -> Result<Vec<usize>, GltfParsingError> {
+        let path = || "scene".to_owned();
+        let scene = match get_optional_usize(self.json(), "scene", path)? {
+            Some(scene) => Some(scene),
+            None if !self.array("scenes").is_empty() => Some(0),
+            None => None,
+        };
+
+        let Some(scene) = scene else {
+            // Without scenes, every node that is not the child of another one is a root
+            let mut is_child = vec![false; self.array("nodes").len()];
+            for (i, node) in self.array("nodes").iter().enumerate() {
+                for child in get_indices(node, "children", || format!("nodes[{i}]"))? {
+                    if let Some(is_child) = is_child.get_mut(child) {
+                        *is_child = true;
+                    }
+                }
+            }
+            return Ok((0..is_child.len()).filter(|&i| !is_child[i]).collect());
+        };
+
+        get_indices(self.get("scenes", scene)?, "nodes", || {
+            format!("scenes[{scene}]")
+        })
+    }
+
+    fn visit_node(
+        &self,
+        index: usize,
+        parent_transform: Transform,
+        visited: &mut [bool],
+        nodes: &mut Vec<Node>,
+    ) -> Result<(), GltfParsingError> {
+        let node = self.get("nodes", index)?;
+        let path = || format!("nodes[{index}]");
+
+        // A node can only have one parent, this also prevents infinite recursion
+        if std::mem::replace(&mut visited[index], true) {
+            return Err(GltfParsingError::NodeHierarchyIsNotATree);
+        }
+
+        let transform = parent_transform.mul(&Transform::from_node(node, path)?);
+        if let Some(mesh) = get_optional_usize(node, "mesh", path)? {
+            nodes.push(Node { mesh, transform });
+        }
+
+        for child in get_indices(node, "children", path)? {
+            self.visit_node(child, transform, visited, nodes)?;
+        }
+        Ok(())
+    }
+}
+
+fn get_indices(
+    json: &Json,
+    key: &str,
+    path: impl Fn() -> String,
+) -> Result<Vec<usize>, GltfParsingError> {
+    let Some(indices) = json.get(key) else {
+        return Ok(Vec::new());
+    };
+    indices
+        .as_array()
+        .and_then(|indices| indices.iter().map(Json::as_usize).collect())
+        .ok_or_else(|| GltfParsingError::InvalidProperty(format!("{}.{key}", path())))
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self([
+        [1., 0., 0., 0.],
+        [0., 1., 0., 0.],
+        [0., 0., 1., 0.],
+        [0., 0., 0., 1.],
+    ]);
+
+    // Nodes either have a matrix or a translation, rotation and scale
+    fn from_node(node: &Json, path: impl Fn() -> String) -> Result<Self, GltfParsingError> {
+        if node.get("matrix").is_some() {
+            let matrix = get_floats::<16>(node, "matrix", [0.; 16], &path)?;
+            return Ok(Self(std::array::from_fn(|column| {
+                std::array::from_fn(|row| matrix[column * 4 + row])
+            })));
+        }
+
+        let [tx, ty, tz] = get_floats(node, "translation", [0.; 3], &path)?;
+        let [x, y, z, w] = get_floats(node, "rotation", [0., 0., 0., 1.], &path)?;
+        let [sx, sy, sz] = get_floats(node, "scale", [1.; 3], &path)?;
+
+        Ok(Self([
+            [
+                (1. - 2. * (y * y + z * z)) * sx,
+                2. * (x * y + z * w) * sx,
+                2. * (x * z - y * w) * sx,
+                0.,
+            ],
+            [
+                2. * (x * y - z * w) * sy,
+                (1. - 2. * (x * x + z * z)) * sy,
+                2. * (y * z + x * w) * sy,
+                0.,
+            ],
+            [
+                2. * (x * z + y * w) * sz,
+                2. * (y * z - x * w) * sz,
+                (1. - 2. * (x * x + y * y)) * sz,
+                0.,
+            ],
+            [tx, ty, tz, 1.],
+        ]))
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self(std::array::from_fn(|column| {
+            std::array::from_fn(|row| (0..4).map(|k| self.0[k][row] * other.0[column][k]).sum())
+        }))
+    }
+
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        std::array::from_fn(|row| {
+            (0..3).map(|k| self.0[k][row] * point[k]).sum::<f32>() + self.0[3][row]
+        })
+    }
+
+    // Uses the cofactor matrix, which is the inverse transpose scaled by the determinant, the
+    // result has to be normalized
+    pub fn transform_normal(&self, normal: [f32; 3]) -> [f32; 3] {
+        let m = |row: usize, column: usize| self.0[column][row];
+        let cofactor = |row: usize, column: usize| {
+            let (r0, r1) = ((row + 1) % 3, (row + 2) % 3);
+            let (c0, c1) = ((column + 1) % 3, (column + 2) % 3);
+            m(r0, c0) * m(r1, c1) - m(r0, c1) * m(r1, c0)
+        };
+        let sign = self.determinant().signum();
+        std::array::from_fn(|row| (0..3).map(|k| cofactor(row, k) * normal[k]).sum::<f32>() * sign)
+    }
+
+    // Of the upper 3x3 matrix, negative when the transform mirrors the geometry
+    pub fn determinant(&self) -> f32 {
+        let [a, b, c] = [self.0[0], self.0[1], self.0[2]];
+        a[0] * (b[1] * c[2] - b[2] * c[1]) - b[0] * (a[1] * c[2] - a[2] * c[1])
+            + c[0] * (a[1] * b[2] - a[2] * b[1])
+    }
+}
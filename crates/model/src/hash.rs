@@ -0,0 +1,6 @@
+// Fowler–Noll–Vo hash, not cryptographic but enough to detect changes
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -1,7 +1,16 @@
+mod gltf;
+mod hash;
+mod material;
 mod model;
+mod mtl;
 mod obj;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 mod vertex;
 
-pub use model::Model;
+pub use gltf::GltfFile;
+pub use hash::fnv1a;
+pub use material::{Material, ShadingModel};
+pub use model::{Model, ModelCacheError, SubMesh};
 pub use obj::ObjFile;
 pub use vertex::Vertex;
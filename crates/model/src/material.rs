@@ -0,0 +1,77 @@
+use crate::vertex::Color;
+
+// Lighting model the material was authored for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadingModel {
+    // Ks and Ns of MTL files
+    BlinnPhong,
+    // glTF materials and MTL files using the PBR extension
+    MetallicRoughness,
+}
+
+// Material described by a MTL or glTF file, texture paths are resolved against the directory
+// of the file
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub shading_model: ShadingModel,
+
+    pub ambient_color: Color,
+    pub diffuse_color: Color,
+    pub specular_color: Color,
+    pub emissive_color: Color,
+    pub specular_exponent: f32,
+    pub dissolve: f32,
+    pub optical_density: f32,
+    pub illumination_model: u32,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+
+    pub ambient_texture: Option<String>,
+    pub diffuse_texture: Option<String>,
+    pub specular_texture: Option<String>,
+    pub specular_exponent_texture: Option<String>,
+    pub dissolve_texture: Option<String>,
+    pub bump_texture: Option<String>,
+    // Blue channel is the metalness and green channel the roughness
+    pub metallic_roughness_texture: Option<String>,
+    // Tangent space normals
+    pub normal_texture: Option<String>,
+    // Red channel is the ambient occlusion
+    pub occlusion_texture: Option<String>,
+    pub emissive_texture: Option<String>,
+}
+
+impl Material {
+    pub fn new(name: String) -> Self {
+        // Default values are the ones used by most exporters when a statement is missing
+        Self {
+            name,
+            shading_model: ShadingModel::BlinnPhong,
+            ambient_color: [0.; 3].into(),
+            diffuse_color: [1.; 3].into(),
+            specular_color: [0.; 3].into(),
+            emissive_color: [0.; 3].into(),
+            specular_exponent: 0.,
+            dissolve: 1.,
+            optical_density: 1.,
+            illumination_model: 2,
+            metallic: 0.,
+            roughness: 1.,
+            normal_scale: 1.,
+            occlusion_strength: 1.,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            specular_exponent_texture: None,
+            dissolve_texture: None,
+            bump_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
@@ -1,15 +1,33 @@
+mod cache;
 mod model_builder;
 
+pub use cache::ModelCacheError;
+
 use model_builder::ModelBuilder;
 use rs42::extensions::PipeLine;
 
-use crate::{vertex::Vertex, ObjFile};
+use crate::{gltf::GltfFile, vertex::Vertex, Material, ObjFile};
 
 type VertexIndex = u32;
 
 pub struct Model {
     vertices: Box<[Vertex]>,
     vertex_indices: Box<[VertexIndex]>,
+    materials: Box<[Material]>,
+    sub_meshes: Box<[SubMesh]>,
+    // Descriptions of the textures of the source that the materials couldn't reference, they are
+    // left out of the materials
+    unsupported_textures: Box<[String]>,
+    // Paths of the other files that were read to load the model, the cache depends on them too
+    dependencies: Box<[String]>,
+}
+
+// Range of vertex_indices drawn with the same material
+#[derive(Debug, Clone, Copy)]
+pub struct SubMesh {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub material: Option<usize>,
 }
 
 // Constructors:
@@ -22,6 +40,14 @@ impl<'a> TryFrom<ObjFile<'a>> for Model {
     }
 }
 
+impl<'a> TryFrom<GltfFile<'a>> for Model {
+    type Error = <ModelBuilder as TryFrom<GltfFile<'a>>>::Error;
+
+    fn try_from(gltf_file: GltfFile<'a>) -> Result<Self, Self::Error> {
+        ModelBuilder::try_from(gltf_file)?.build().pipe(Ok)
+    }
+}
+
 // Getters:
 
 impl Model {
@@ -32,4 +58,20 @@ impl Model {
     pub fn vertex_indices(&self) -> &[VertexIndex] {
         &self.vertex_indices
     }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    pub fn sub_meshes(&self) -> &[SubMesh] {
+        &self.sub_meshes
+    }
+
+    pub fn unsupported_textures(&self) -> &[String] {
+        &self.unsupported_textures
+    }
+
+    pub fn dependencies(&self) -> &[String] {
+        &self.dependencies
+    }
 }
@@ -0,0 +1,497 @@
+mod reader;
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    io::{self, ErrorKind},
+    time::UNIX_EPOCH,
+};
+
+use reader::Reader;
+
+use crate::{fnv1a, vertex::Vertex, Material, ShadingModel, SubMesh};
+
+use super::Model;
+
+const MAGIC: [u8; 8] = *b"HHMODEL\0";
+// Must be incremented every time the layout of the cache or of Vertex changes
+const VERSION: u32 = 2;
+
+// Used to know if the cache is still up to date, the mtime is checked first as hashing the
+// source is as slow as reading it
+// The source and each of its dependencies (material libraries, glTF buffers) have their own info
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SourceInfo {
+    len: u64,
+    mtime: (u64, u32),
+    hash: u64,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum ModelCacheError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u32),
+    ChecksumMismatch,
+    Truncated,
+    InvalidUtf8,
+}
+
+impl Display for ModelCacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ModelCacheError({self:?})")
+    }
+}
+
+impl Error for ModelCacheError {}
+
+impl From<io::Error> for ModelCacheError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Model {
+    // Loads the cache if it is up to date, otherwise loads the source and rewrites the cache
+    // Cache errors are only warnings as the source can always be used instead
+    pub fn load_with_cache<E>(
+        source_path: &str,
+        cache_path: &str,
+        load_source: impl FnOnce(&str) -> Result<Model, E>,
+    ) -> Result<Model, E> {
+        match Self::load_cache(source_path, cache_path) {
+            Ok(Some(model)) => return Ok(model),
+            Ok(None) => {}
+            Err(err) => eprintln!("WARNING: Failed to load model cache \"{cache_path}\": {err}"),
+        }
+
+        let model = load_source(source_path)?;
+        if let Err(err) = model.save_cache(source_path, cache_path) {
+            eprintln!("WARNING: Failed to save model cache \"{cache_path}\": {err}");
+        }
+        Ok(model)
+    }
+
+    // Returns None if there is no cache or if it is outdated
+    pub fn load_cache(
+        source_path: &str,
+        cache_path: &str,
+    ) -> Result<Option<Model>, ModelCacheError> {
+        let cache = match fs::read(cache_path) {
+            Ok(cache) => cache,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let (cached_source_info, cached_dependencies, payload) = decode_header(&cache)?;
+        if !is_source_unchanged(source_path, &cached_source_info)? {
+            return Ok(None);
+        }
+        for (path, cached_info) in cached_dependencies.iter() {
+            if !is_source_unchanged(path, cached_info)? {
+                return Ok(None);
+            }
+        }
+
+        let mut model = decode_model(payload)?;
+        model.dependencies = cached_dependencies
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        Ok(Some(model))
+    }
+
+    pub fn save_cache(&self, source_path: &str, cache_path: &str) -> Result<(), ModelCacheError> {
+        let source_info = get_source_info(source_path)?;
+        let dependencies = self
+            .dependencies
+            .iter()
+            .map(|path| Ok((path.as_str(), get_source_info(path)?)))
+            .collect::<Result<Vec<_>, ModelCacheError>>()?;
+        let cache = encode(self, &source_info, &dependencies);
+
+        // Written to a temporary file first so that an interrupted write can't leave a truncated
+        // cache behind
+        let temporary_path = format!("{cache_path}.tmp");
+        fs::write(&temporary_path, cache)?;
+        fs::rename(&temporary_path, cache_path)?;
+        Ok(())
+    }
+}
+
+fn get_source_info(source_path: &str) -> Result<SourceInfo, ModelCacheError> {
+    let content = fs::read(source_path)?;
+    Ok(SourceInfo {
+        len: content.len() as u64,
+        mtime: get_mtime(source_path)?,
+        hash: fnv1a(&content),
+    })
+}
+
+fn get_mtime(path: &str) -> Result<(u64, u32), ModelCacheError> {
+    let mtime = fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+fn is_source_unchanged(source_path: &str, cached: &SourceInfo) -> Result<bool, ModelCacheError> {
+    let metadata = match fs::metadata(source_path) {
+        Ok(metadata) => metadata,
+        // Loading the source again will report the missing file
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    if metadata.len() != cached.len {
+        return Ok(false);
+    }
+    if get_mtime(source_path)? == cached.mtime {
+        return Ok(true);
+    }
+    // The file was touched, it might still have the same content
+    Ok(fnv1a(&fs::read(source_path)?) == cached.hash)
+}
+
+// Layout, everything is little endian:
+// magic, version, source info, dependency count, (dependency path, dependency info) * count,
+// payload len, payload checksum, payload
+// An info is made of the len, mtime and hash of the file
+fn encode(model: &Model, source_info: &SourceInfo, dependencies: &[(&str, SourceInfo)]) -> Vec<u8> {
+    let payload = encode_model(model);
+
+    let mut cache = Vec::with_capacity(64 + payload.len());
+    cache.extend(MAGIC);
+    cache.extend(VERSION.to_le_bytes());
+    encode_source_info(&mut cache, source_info);
+    cache.extend((dependencies.len() as u64).to_le_bytes());
+    for (path, info) in dependencies {
+        encode_string(&mut cache, path);
+        encode_source_info(&mut cache, info);
+    }
+    cache.extend((payload.len() as u64).to_le_bytes());
+    cache.extend(fnv1a(&payload).to_le_bytes());
+    cache.extend(payload);
+    cache
+}
+
+fn encode_source_info(cache: &mut Vec<u8>, source_info: &SourceInfo) {
+    cache.extend(source_info.len.to_le_bytes());
+    cache.extend(source_info.mtime.0.to_le_bytes());
+    cache.extend(source_info.mtime.1.to_le_bytes());
+    cache.extend(source_info.hash.to_le_bytes());
+}
+
+type Dependencies = Vec<(String, SourceInfo)>;
+
+fn decode_header(cache: &[u8]) -> Result<(SourceInfo, Dependencies, &[u8]), ModelCacheError> {
+    let mut reader = Reader::new(cache);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(ModelCacheError::InvalidMagic);
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(ModelCacheError::UnsupportedVersion(version));
+    }
+
+    let source_info = decode_source_info(&mut reader)?;
+    let dependencies = (0..reader.count()?)
+        .map(|_| Ok((reader.string()?, decode_source_info(&mut reader)?)))
+        .collect::<Result<Dependencies, ModelCacheError>>()?;
+    let payload_len = reader.u64()? as usize;
+    let checksum = reader.u64()?;
+    let payload = reader.bytes(payload_len)?;
+    if fnv1a(payload) != checksum {
+        return Err(ModelCacheError::ChecksumMismatch);
+    }
+    Ok((source_info, dependencies, payload))
+}
+
+fn decode_source_info(reader: &mut Reader) -> Result<SourceInfo, ModelCacheError> {
+    Ok(SourceInfo {
+        len: reader.u64()?,
+        mtime: (reader.u64()?, reader.u32()?),
+        hash: reader.u64()?,
+    })
+}
+
+fn encode_model(model: &Model) -> Vec<u8> {
+    let mut payload = Vec::new();
+
+    payload.extend((model.vertices.len() as u64).to_le_bytes());
+    for vertex in model.vertices.iter() {
+        payload.extend(vertex.to_scalars().iter().flat_map(|e| e.to_le_bytes()));
+    }
+
+    payload.extend((model.vertex_indices.len() as u64).to_le_bytes());
+    payload.extend(model.vertex_indices.iter().flat_map(|e| e.to_le_bytes()));
+
+    payload.extend((model.sub_meshes.len() as u64).to_le_bytes());
+    for sub_mesh in model.sub_meshes.iter() {
+        payload.extend(sub_mesh.first_index.to_le_bytes());
+        payload.extend(sub_mesh.index_count.to_le_bytes());
+        encode_option(
+            &mut payload,
+            sub_mesh.material.map(|material| material as u64),
+        );
+    }
+
+    payload.extend((model.materials.len() as u64).to_le_bytes());
+    for material in model.materials.iter() {
+        encode_material(&mut payload, material);
+    }
+
+    payload.extend((model.unsupported_textures.len() as u64).to_le_bytes());
+    for unsupported_texture in model.unsupported_textures.iter() {
+        encode_string(&mut payload, unsupported_texture);
+    }
+    payload
+}
+
+fn encode_material(payload: &mut Vec<u8>, material: &Material) {
+    encode_string(payload, &material.name);
+    for color in [
+        &material.ambient_color,
+        &material.diffuse_color,
+        &material.specular_color,
+        &material.emissive_color,
+    ] {
+        payload.extend(
+            color
+                .clone()
+                .into_scalars()
+                .iter()
+                .flat_map(|e| e.to_le_bytes()),
+        );
+    }
+    for scalar in [
+        material.specular_exponent,
+        material.dissolve,
+        material.optical_density,
+        material.metallic,
+        material.roughness,
+        material.normal_scale,
+        material.occlusion_strength,
+    ] {
+        payload.extend(scalar.to_le_bytes());
+    }
+    payload.extend(material.illumination_model.to_le_bytes());
+    payload.push(match material.shading_model {
+        ShadingModel::BlinnPhong => 0,
+        ShadingModel::MetallicRoughness => 1,
+    });
+    for texture in [
+        &material.ambient_texture,
+        &material.diffuse_texture,
+        &material.specular_texture,
+        &material.specular_exponent_texture,
+        &material.dissolve_texture,
+        &material.bump_texture,
+        &material.metallic_roughness_texture,
+        &material.normal_texture,
+        &material.occlusion_texture,
+        &material.emissive_texture,
+    ] {
+        payload.push(texture.is_some() as u8);
+        if let Some(texture) = texture {
+            encode_string(payload, texture);
+        }
+    }
+}
+
+fn encode_string(payload: &mut Vec<u8>, string: &str) {
+    payload.extend((string.len() as u64).to_le_bytes());
+    payload.extend(string.as_bytes());
+}
+
+fn encode_option(payload: &mut Vec<u8>, value: Option<u64>) {
+    payload.push(value.is_some() as u8);
+    payload.extend(value.unwrap_or_default().to_le_bytes());
+}
+
+fn decode_model(payload: &[u8]) -> Result<Model, ModelCacheError> {
+    let mut reader = Reader::new(payload);
+
+    let vertices = (0..reader.count()?)
+        .map(|_| reader.f32_array().map(Vertex::from_scalars))
+        .collect::<Result<Box<[Vertex]>, _>>()?;
+
+    let vertex_indices = (0..reader.count()?)
+        .map(|_| reader.u32())
+        .collect::<Result<Box<[u32]>, _>>()?;
+
+    let sub_meshes = (0..reader.count()?)
+        .map(|_| {
+            Ok(SubMesh {
+                first_index: reader.u32()?,
+                index_count: reader.u32()?,
+                material: reader.option_u64()?.map(|material| material as usize),
+            })
+        })
+        .collect::<Result<Box<[SubMesh]>, ModelCacheError>>()?;
+
+    let materials = (0..reader.count()?)
+        .map(|_| decode_material(&mut reader))
+        .collect::<Result<Box<[Material]>, _>>()?;
+
+    let unsupported_textures = (0..reader.count()?)
+        .map(|_| reader.string())
+        .collect::<Result<Box<[String]>, _>>()?;
+
+    Ok(Model {
+        vertices,
+        vertex_indices,
+        materials,
+        sub_meshes,
+        unsupported_textures,
+        dependencies: Box::default(),
+    })
+}
+
+fn decode_material(reader: &mut Reader) -> Result<Material, ModelCacheError> {
+    let mut material = Material::new(reader.string()?);
+    material.ambient_color = reader.f32_array::<3>()?.into();
+    material.diffuse_color = reader.f32_array::<3>()?.into();
+    material.specular_color = reader.f32_array::<3>()?.into();
+    material.emissive_color = reader.f32_array::<3>()?.into();
+    [
+        material.specular_exponent,
+        material.dissolve,
+        material.optical_density,
+        material.metallic,
+        material.roughness,
+        material.normal_scale,
+        material.occlusion_strength,
+    ] = reader.f32_array()?;
+    material.illumination_model = reader.u32()?;
+    material.shading_model = match reader.u8()? {
+        0 => ShadingModel::BlinnPhong,
+        _ => ShadingModel::MetallicRoughness,
+    };
+    for texture in [
+        &mut material.ambient_texture,
+        &mut material.diffuse_texture,
+        &mut material.specular_texture,
+        &mut material.specular_exponent_texture,
+        &mut material.dissolve_texture,
+        &mut material.bump_texture,
+        &mut material.metallic_roughness_texture,
+        &mut material.normal_texture,
+        &mut material.occlusion_texture,
+        &mut material.emissive_texture,
+    ] {
+        *texture = match reader.u8()? {
+            0 => None,
+            _ => Some(reader.string()?),
+        };
+    }
+    Ok(material)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{test_utils::TestDirectory, ObjFile};
+
+    use super::*;
+
+    const OBJ: &str = "mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvt 0.5 0.5\n\
+                       usemtl red\nf 1/1 2/1 3/1\nusemtl off\nf 2 4 3\n";
+    const MTL: &str = "newmtl red\nKd 1 0 0\nmap_Kd red.ppm\n";
+
+    fn test_directory(name: &str) -> TestDirectory {
+        let directory = TestDirectory::new(&format!("model_cache_{name}"));
+        directory.write("test.obj", OBJ);
+        directory.write("test.mtl", MTL);
+        directory
+    }
+
+    fn load_obj(path: &str) -> Model {
+        Model::try_from(ObjFile(path)).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let directory = test_directory("round_trip");
+        let (obj, cache) = (directory.path("test.obj"), directory.path("test.cache"));
+        let model = load_obj(&obj);
+        model.save_cache(&obj, &cache).unwrap();
+
+        let cached = Model::load_cache(&obj, &cache).unwrap().unwrap();
+        assert!(cached.vertices() == model.vertices());
+        assert_eq!(cached.vertex_indices(), model.vertex_indices());
+        assert_eq!(cached.sub_meshes().len(), 2);
+        assert_eq!(cached.sub_meshes()[1].material, Some(0));
+        assert_eq!(cached.materials()[0].name, "red");
+        assert_eq!(cached.materials()[0].diffuse_color[1], 0.);
+        assert_eq!(
+            cached.materials()[0].diffuse_texture,
+            Some(directory.path("red.ppm"))
+        );
+    }
+
+    #[test]
+    fn invalidated_when_source_changes() {
+        let directory = test_directory("invalidated");
+        let (obj, cache) = (directory.path("test.obj"), directory.path("test.cache"));
+        load_obj(&obj).save_cache(&obj, &cache).unwrap();
+
+        // Same length, so only the mtime or the hash can tell that the file changed
+        fs::write(&obj, OBJ.replace("v 1 1 0", "v 2 2 0")).unwrap();
+        assert!(Model::load_cache(&obj, &cache).unwrap().is_none());
+
+        let model =
+            Model::load_with_cache(&obj, &cache, |path| Model::try_from(ObjFile(path))).unwrap();
+        let cached = Model::load_cache(&obj, &cache).unwrap().unwrap();
+        assert!(cached.vertices() == model.vertices());
+    }
+
+    #[test]
+    fn invalidated_when_material_library_changes() {
+        let directory = test_directory("invalidated_mtl");
+        let (obj, cache) = (directory.path("test.obj"), directory.path("test.cache"));
+        load_obj(&obj).save_cache(&obj, &cache).unwrap();
+        let cached = Model::load_cache(&obj, &cache).unwrap().unwrap();
+        assert_eq!(cached.dependencies(), [directory.path("test.mtl")]);
+
+        // Same length, so only the mtime or the hash can tell that the file changed
+        fs::write(
+            directory.path("test.mtl"),
+            MTL.replace("Kd 1 0 0", "Kd 0 1 0"),
+        )
+        .unwrap();
+        assert!(Model::load_cache(&obj, &cache).unwrap().is_none());
+
+        let model =
+            Model::load_with_cache(&obj, &cache, |path| Model::try_from(ObjFile(path))).unwrap();
+        assert_eq!(model.materials()[0].diffuse_color[1], 1.);
+        let cached = Model::load_cache(&obj, &cache).unwrap().unwrap();
+        assert_eq!(cached.materials()[0].diffuse_color[1], 1.);
+
+        fs::remove_file(directory.path("test.mtl")).unwrap();
+        assert!(Model::load_cache(&obj, &cache).unwrap().is_none());
+    }
+
+    #[test]
+    fn corrupted_cache() {
+        let directory = test_directory("corrupted");
+        let (obj, cache) = (directory.path("test.obj"), directory.path("test.cache"));
+        load_obj(&obj).save_cache(&obj, &cache).unwrap();
+
+        let mut bytes = fs::read(&cache).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&cache, &bytes).unwrap();
+        assert!(matches!(
+            Model::load_cache(&obj, &cache),
+            Err(ModelCacheError::ChecksumMismatch)
+        ));
+
+        fs::write(&cache, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(matches!(
+            Model::load_cache(&obj, &cache),
+            Err(ModelCacheError::Truncated)
+        ));
+    }
+}
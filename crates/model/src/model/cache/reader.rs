@@ -0,0 +1,68 @@
+use super::ModelCacheError;
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], ModelCacheError> {
+        if len > self.bytes.len() {
+            return Err(ModelCacheError::Truncated);
+        }
+        let (bytes, remaining) = self.bytes.split_at(len);
+        self.bytes = remaining;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ModelCacheError> {
+        Ok(self
+            .bytes(N)?
+            .try_into()
+            .expect("bytes() returns exactly N bytes"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ModelCacheError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ModelCacheError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, ModelCacheError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    pub fn f32_array<const N: usize>(&mut self) -> Result<[f32; N], ModelCacheError> {
+        let mut array = [0.; N];
+        for elem in array.iter_mut() {
+            *elem = f32::from_le_bytes(self.array()?);
+        }
+        Ok(array)
+    }
+
+    // Lengths are checked against the remaining bytes so that a corrupted length can't make us
+    // allocate gigabytes
+    pub fn count(&mut self) -> Result<usize, ModelCacheError> {
+        let len = self.u64()?;
+        if len > self.bytes.len() as u64 {
+            return Err(ModelCacheError::Truncated);
+        }
+        Ok(len as usize)
+    }
+
+    pub fn option_u64(&mut self) -> Result<Option<u64>, ModelCacheError> {
+        let is_some = self.u8()? != 0;
+        let value = self.u64()?;
+        Ok(is_some.then_some(value))
+    }
+
+    pub fn string(&mut self) -> Result<String, ModelCacheError> {
+        let len = self.count()?;
+        String::from_utf8(self.bytes(len)?.to_vec()).map_err(|_| ModelCacheError::InvalidUtf8)
+    }
+}
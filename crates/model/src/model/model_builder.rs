@@ -1,13 +1,26 @@
+mod from_gltf;
+mod generate_normals;
+
 use std::collections::HashMap;
 
-use crate::{obj::Obj, vertex::TextureCoordinate, ObjFile, Vertex};
+use generate_normals::generate_normals;
+
+use crate::{
+    obj::{MaterialUsage, Obj},
+    vertex::{Color, TextureCoordinate},
+    Material, ObjFile, Vertex,
+};
 
-use super::{Model, VertexIndex};
+use super::{Model, SubMesh, VertexIndex};
 
 #[derive(Default)]
 pub struct ModelBuilder {
     vertices: Vec<Vertex>,
     vertex_indices: Vec<VertexIndex>,
+    materials: Vec<Material>,
+    sub_meshes: Vec<SubMesh>,
+    unsupported_textures: Vec<String>,
+    dependencies: Vec<String>,
 
     vertex_map: HashMap<Vertex, VertexIndex>,
 }
@@ -17,6 +30,10 @@ impl ModelBuilder {
         Model {
             vertices: self.vertices.into_boxed_slice(),
             vertex_indices: self.vertex_indices.into_boxed_slice(),
+            materials: self.materials.into_boxed_slice(),
+            sub_meshes: self.sub_meshes.into_boxed_slice(),
+            unsupported_textures: self.unsupported_textures.into_boxed_slice(),
+            dependencies: self.dependencies.into_boxed_slice(),
         }
     }
 }
@@ -26,32 +43,98 @@ impl<'a> TryFrom<ObjFile<'a>> for ModelBuilder {
 
     fn try_from(obj_file: ObjFile) -> Result<Self, Self::Error> {
         let obj: Obj = obj_file.try_into()?;
+        Ok(Self::from(obj))
+    }
+}
+
+impl From<Obj> for ModelBuilder {
+    fn from(obj: Obj) -> Self {
         let mut builder = Self::default();
 
-        for (i, face_geometry) in obj.faces_geometry.iter().enumerate() {
+        // Hard edges are kept as vertices with different normals are not merged by add_vertex()
+        let generated_normals = if obj.faces_normals.iter().any(Option::is_none) {
+            generate_normals(&obj)
+        } else {
+            Box::default()
+        };
+
+        // Faces are grouped by material so that each material is drawn only once
+        let faces_material = get_faces_material(&obj.material_usages, obj.faces_geometry.len());
+        let mut faces_order = (0..obj.faces_geometry.len()).collect::<Vec<usize>>();
+        faces_order.sort_by_key(|&i| faces_material[i]);
+
+        for i in faces_order {
+            let material = faces_material[i];
+            builder.start_sub_mesh_if_needed(material);
+
+            let color = material.map_or_else(
+                || Color::from([1., 1., 1.]),
+                |material| obj.materials[material].diffuse_color.clone(),
+            );
+
+            let face_geometry = &obj.faces_geometry[i];
             #[allow(clippy::needless_range_loop)]
             for j in 0..3 {
                 let position = obj.geometry[face_geometry[j] as usize].take::<3>();
 
-                let texture_coordinate = if i < obj.faces_textures.len() {
-                    let texture = &obj.textures[obj.faces_textures[i][j] as usize];
-                    TextureCoordinate::from([texture[0], 1. - texture[1]])
-                } else {
-                    [0.; 2].into()
+                let texture_coordinate = match obj.faces_textures[i] {
+                    Some(face_textures) => {
+                        let texture = &obj.textures[face_textures[j] as usize];
+                        TextureCoordinate::from([texture[0], 1. - texture[1]])
+                    }
+                    None => [0.; 2].into(),
+                };
+
+                let normal = match obj.faces_normals[i] {
+                    Some(face_normals) => obj.normals[face_normals[j] as usize].clone(),
+                    None => generated_normals[i][j].clone(),
                 };
 
-                let vertex = Vertex::new(position, [1., 1., 1.], texture_coordinate);
+                let vertex = Vertex::new(position, color.clone(), texture_coordinate, normal);
 
                 builder.add_vertex(vertex);
             }
         }
 
-        Ok(builder)
+        builder.materials = obj.materials.into_vec();
+        builder.dependencies = obj.dependencies.into_vec();
+        builder
+    }
+}
+
+fn get_faces_material(material_usages: &[MaterialUsage], face_count: usize) -> Vec<Option<usize>> {
+    let mut faces_material = vec![None; face_count];
+
+    for (i, usage) in material_usages.iter().enumerate() {
+        let last_face = material_usages
+            .get(i + 1)
+            .map_or(face_count, |next_usage| next_usage.first_face);
+        faces_material[usage.first_face..last_face].fill(usage.material);
     }
+    faces_material
 }
 
 impl ModelBuilder {
+    fn start_sub_mesh_if_needed(&mut self, material: Option<usize>) {
+        if self
+            .sub_meshes
+            .last()
+            .is_some_and(|sub_mesh| sub_mesh.material == material)
+        {
+            return;
+        }
+        self.sub_meshes.push(SubMesh {
+            first_index: self.vertex_indices.len() as u32,
+            index_count: 0,
+            material,
+        });
+    }
+
     fn add_vertex(&mut self, vertex: Vertex) {
+        if let Some(sub_mesh) = self.sub_meshes.last_mut() {
+            sub_mesh.index_count += 1;
+        }
+
         self.vertex_map
             .entry(vertex.clone())
             .and_modify(|index| self.vertex_indices.push(*index))
@@ -63,3 +146,48 @@ impl ModelBuilder {
             });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::BufReader;
+
+    use super::*;
+
+    #[test]
+    fn vertices_with_different_normals_are_not_merged() {
+        let mut builder = ModelBuilder::default();
+        builder.add_vertex(Vertex::new([0.; 3], [1.; 3], [0.5, 0.5], [0., 0., 1.]));
+        builder.add_vertex(Vertex::new([0.; 3], [1.; 3], [0.5, 0.5], [0., 1., 0.]));
+        builder.add_vertex(Vertex::new([0.; 3], [1.; 3], [0.5, 0.5], [0., 0., 1.]));
+
+        assert_eq!(builder.vertices.len(), 2);
+        assert_eq!(builder.vertex_indices, [0, 1, 0]);
+    }
+
+    #[test]
+    fn faces_with_and_without_normals_and_textures_can_be_mixed() {
+        let obj = Obj::try_from(BufReader::new(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 1 1\nvt 0.25 0.75\nvn 0 0 -1\n\
+             s off\nf 3 2 4\nf 1/1/1 2/1/1 3/1/1\n"
+                .as_bytes(),
+        ))
+        .unwrap();
+        let model = ModelBuilder::from(obj).build();
+        let vertex = |i: usize| &model.vertices()[model.vertex_indices()[i] as usize];
+
+        // The first face has neither, it gets a generated flat normal
+        let generated_normal = [-1. / 2_f32.sqrt(), -1. / 2_f32.sqrt(), 0.];
+        for (i, position) in [[0., 1., 0.], [1., 0., 0.], [0., 1., 1.]]
+            .into_iter()
+            .enumerate()
+        {
+            assert!(*vertex(i) == Vertex::new(position, [1.; 3], [0.; 2], generated_normal));
+        }
+        for (i, position) in [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]]
+            .into_iter()
+            .enumerate()
+        {
+            assert!(*vertex(i + 3) == Vertex::new(position, [1.; 3], [0.25; 2], [0., 0., -1.]));
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{
+    gltf::{
+        get_f32, get_floats, get_optional_usize, get_usize, Gltf, GltfFile, GltfParsingError, Json,
+        Transform,
+    },
+    vertex::Color,
+    Material, ShadingModel, Vertex,
+};
+
+use super::{
+    generate_normals::{cross, normalize, sub},
+    ModelBuilder,
+};
+
+const TRIANGLES: usize = 4;
+const TRIANGLE_STRIP: usize = 5;
+const TRIANGLE_FAN: usize = 6;
+
+impl<'a> TryFrom<GltfFile<'a>> for ModelBuilder {
+    type Error = GltfParsingError;
+
+    fn try_from(gltf_file: GltfFile) -> Result<Self, Self::Error> {
+        Gltf::try_from(gltf_file)?.try_into()
+    }
+}
+
+impl TryFrom<Gltf> for ModelBuilder {
+    type Error = GltfParsingError;
+
+    fn try_from(gltf: Gltf) -> Result<Self, Self::Error> {
+        let mut unsupported_textures = Vec::new();
+        let materials = get_materials(&gltf, &mut unsupported_textures)?;
+
+        let mut triangles = Vec::new();
+        for node in gltf.get_scene_nodes()? {
+            let mesh = gltf.get("meshes", node.mesh)?;
+            let primitives = mesh
+                .get("primitives")
+                .and_then(Json::as_array)
+                .ok_or_else(|| {
+                    GltfParsingError::MissingProperty(format!("meshes[{}].primitives", node.mesh))
+                })?;
+
+            for (i, primitive) in primitives.iter().enumerate() {
+                let path = || format!("meshes[{}].primitives[{i}]", node.mesh);
+                add_primitive(
+                    &gltf,
+                    primitive,
+                    path,
+                    &node.transform,
+                    &materials,
+                    &mut triangles,
+                )?;
+            }
+        }
+
+        // Triangles are grouped by material so that each material is drawn only once
+        triangles.sort_by_key(|(material, _)| *material);
+
+        let mut builder = Self::default();
+        for (material, vertices) in triangles {
+            builder.start_sub_mesh_if_needed(material);
+            for vertex in vertices {
+                builder.add_vertex(vertex);
+            }
+        }
+
+        builder.materials = materials;
+        builder.unsupported_textures = unsupported_textures;
+        builder.dependencies = gltf.dependencies();
+        Ok(builder)
+    }
+}
+
+fn add_primitive(
+    gltf: &Gltf,
+    primitive: &Json,
+    path: impl Fn() -> String,
+    transform: &Transform,
+    materials: &[Material],
+    triangles: &mut Vec<(Option<usize>, [Vertex; 3])>,
+) -> Result<(), GltfParsingError> {
+    let mode = get_optional_usize(primitive, "mode", &path)?.unwrap_or(TRIANGLES);
+    if ![TRIANGLES, TRIANGLE_STRIP, TRIANGLE_FAN].contains(&mode) {
+        eprintln!(
+            "WARNING: glTF parser: {} has mode {mode} which is not supported",
+            path()
+        );
+        return Ok(());
+    }
+
+    let attributes = primitive
+        .get("attributes")
+        .ok_or_else(|| GltfParsingError::MissingProperty(format!("{}.attributes", path())))?;
+    let attributes_path = || format!("{}.attributes", path());
+
+    let positions =
+        gltf.read_float_accessor::<3>(get_usize(attributes, "POSITION", attributes_path)?)?;
+    let normals = get_optional_usize(attributes, "NORMAL", attributes_path)?
+        .map(|accessor| gltf.read_float_accessor::<3>(accessor))
+        .transpose()?;
+    let texture_coordinates = get_optional_usize(attributes, "TEXCOORD_0", attributes_path)?
+        .map(|accessor| gltf.read_float_accessor::<2>(accessor))
+        .transpose()?;
+    if normals
+        .as_ref()
+        .is_some_and(|normals| normals.len() != positions.len())
+        || texture_coordinates
+            .as_ref()
+            .is_some_and(|texture_coordinates| texture_coordinates.len() != positions.len())
+    {
+        return Err(GltfParsingError::InvalidProperty(attributes_path()));
+    }
+
+    let indices = match get_optional_usize(primitive, "indices", &path)? {
+        Some(accessor) => gltf.read_index_accessor(accessor)?,
+        None => (0..positions.len() as u32).collect(),
+    };
+    if indices
+        .iter()
+        .any(|&index| index as usize >= positions.len())
+    {
+        return Err(GltfParsingError::InvalidProperty(format!(
+            "{}.indices",
+            path()
+        )));
+    }
+
+    let material = get_optional_usize(primitive, "material", &path)?;
+    if material.is_some_and(|material| material >= materials.len()) {
+        return Err(GltfParsingError::InvalidProperty(format!(
+            "{}.material",
+            path()
+        )));
+    }
+    let color = material.map_or_else(
+        || Color::from([1., 1., 1.]),
+        |material| materials[material].diffuse_color.clone(),
+    );
+
+    // Mirroring transforms flip the winding order
+    let is_mirrored = transform.determinant() < 0.;
+
+    for triangle in get_triangles(&indices, mode) {
+        let mut triangle = triangle.map(|index| index as usize);
+        if is_mirrored {
+            triangle.swap(1, 2);
+        }
+
+        let world_positions = triangle.map(|index| transform.transform_point(positions[index]));
+        let flat_normal = normalize(cross(
+            sub(world_positions[1], world_positions[0]),
+            sub(world_positions[2], world_positions[0]),
+        ));
+
+        let vertices = std::array::from_fn(|i| {
+            let index = triangle[i];
+            // Primitives without normals must be rendered with flat normals
+            let normal = normals.as_ref().map_or(flat_normal, |normals| {
+                normalize(transform.transform_normal(normals[index]))
+            });
+            // glTF and Vulkan both have the origin of texture coordinates at the top left
+            let texture_coordinate = texture_coordinates
+                .as_ref()
+                .map_or([0.; 2], |texture_coordinates| texture_coordinates[index]);
+
+            Vertex::new(
+                world_positions[i],
+                color.clone(),
+                texture_coordinate,
+                normal,
+            )
+        });
+        triangles.push((material, vertices));
+    }
+    Ok(())
+}
+
+fn get_triangles(indices: &[u32], mode: usize) -> Vec<[u32; 3]> {
+    match mode {
+        TRIANGLE_STRIP => (0..indices.len().saturating_sub(2))
+            .map(|i| {
+                // Every other triangle has to be flipped to keep the same winding order
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i + 1], indices[i], indices[i + 2]]
+                }
+            })
+            .collect(),
+        TRIANGLE_FAN => (1..indices.len().saturating_sub(1))
+            .map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect(),
+        _ => indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect(),
+    }
+}
+
+// The textures that can't be referenced by a path are described in unsupported_textures
+fn get_materials(
+    gltf: &Gltf,
+    unsupported_textures: &mut Vec<String>,
+) -> Result<Vec<Material>, GltfParsingError> {
+    gltf.array("materials")
+        .iter()
+        .enumerate()
+        .map(|(i, json)| {
+            let path = || format!("materials[{i}]");
+            let name = json
+                .get("name")
+                .and_then(Json::as_str)
+                .map_or_else(|| format!("material_{i}"), str::to_owned);
+            let mut material = Material::new(name);
+            material.shading_model = ShadingModel::MetallicRoughness;
+
+            // Default values are the ones of the glTF specification
+            let empty_object = Json::Object(Default::default());
+            let pbr = json.get("pbrMetallicRoughness").unwrap_or(&empty_object);
+            let pbr_path = || format!("{}.pbrMetallicRoughness", path());
+
+            let [r, g, b, a] = get_floats(pbr, "baseColorFactor", [1.; 4], pbr_path)?;
+            material.diffuse_color = [r, g, b].into();
+            material.dissolve = a;
+            material.metallic = get_f32(pbr, "metallicFactor", 1., pbr_path)?;
+            material.roughness = get_f32(pbr, "roughnessFactor", 1., pbr_path)?;
+            let mut get_texture_path = |json, key, path: &dyn Fn() -> String| {
+                get_texture_path(gltf, json, key, path, unsupported_textures)
+            };
+            material.diffuse_texture = get_texture_path(pbr, "baseColorTexture", &pbr_path)?;
+            material.metallic_roughness_texture =
+                get_texture_path(pbr, "metallicRoughnessTexture", &pbr_path)?;
+
+            material.emissive_color = get_floats(json, "emissiveFactor", [0.; 3], path)?.into();
+            material.emissive_texture = get_texture_path(json, "emissiveTexture", &path)?;
+            material.normal_texture = get_texture_path(json, "normalTexture", &path)?;
+            material.occlusion_texture = get_texture_path(json, "occlusionTexture", &path)?;
+            if let Some(normal_texture) = json.get("normalTexture") {
+                material.normal_scale = get_f32(normal_texture, "scale", 1., || {
+                    format!("{}.normalTexture", path())
+                })?;
+            }
+            if let Some(occlusion_texture) = json.get("occlusionTexture") {
+                material.occlusion_strength = get_f32(occlusion_texture, "strength", 1., || {
+                    format!("{}.occlusionTexture", path())
+                })?;
+            }
+            Ok(material)
+        })
+        .collect()
+}
+
+// Embedded images (data URIs or buffer views) have no path, they are described in
+// unsupported_textures instead and the material is loaded without them
+fn get_texture_path(
+    gltf: &Gltf,
+    json: &Json,
+    key: &str,
+    path: &dyn Fn() -> String,
+    unsupported_textures: &mut Vec<String>,
+) -> Result<Option<String>, GltfParsingError> {
+    let Some(texture_info) = json.get(key) else {
+        return Ok(None);
+    };
+    let texture = get_usize(texture_info, "index", || format!("{}.{key}", path()))?;
+    let Some(image) = get_optional_usize(gltf.get("textures", texture)?, "source", || {
+        format!("textures[{texture}]")
+    })?
+    else {
+        return Ok(None);
+    };
+
+    let image_path = gltf.image_path(image);
+    if image_path.is_none() {
+        unsupported_textures.push(format!("{}.{key} uses embedded images[{image}]", path()));
+    }
+    Ok(image_path)
+}
+
+#[cfg(test)]
+mod test {
+    use linear_algebra::assert_approximately_equal;
+
+    use super::*;
+
+    // Triangle (0, 0, 0), (1, 0, 0), (0, 1, 0) with u16 indices
+    const BUFFER: &str = "data:application/octet-stream;base64,\
+        AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=";
+
+    fn get_gltf(nodes: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": {nodes},
+                "meshes": [{{"primitives": [
+                    {{"attributes": {{"POSITION": 0}}, "indices": 1, "material": 0}},
+                    {{"attributes": {{"POSITION": 0}}}}
+                ]}}],
+                "materials": [{{"pbrMetallicRoughness": {{
+                    "baseColorFactor": [1, 0, 0, 0.5], "roughnessFactor": 0.25
+                }}}}],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+                ],
+                "buffers": [{{"byteLength": 44, "uri": "{BUFFER}"}}]
+            }}"#
+        )
+    }
+
+    fn get_model(nodes: &str) -> crate::Model {
+        let gltf = Gltf::from_bytes(get_gltf(nodes).as_bytes(), None).unwrap();
+        ModelBuilder::try_from(gltf).unwrap().build()
+    }
+
+    #[test]
+    fn primitives_are_split_by_material() {
+        let model = get_model(r#"[{"mesh": 0}]"#);
+
+        assert_eq!(model.vertices().len(), 6);
+        assert_eq!(model.sub_meshes().len(), 2);
+        assert_eq!(model.sub_meshes()[0].material, None);
+        assert_eq!(model.sub_meshes()[1].material, Some(0));
+        assert_eq!(model.sub_meshes()[1].index_count, 3);
+
+        let material = &model.materials()[0];
+        assert_eq!(material.diffuse_color[1], 0.);
+        assert_eq!(material.dissolve, 0.5);
+        assert_eq!(material.metallic, 1.);
+        assert_eq!(material.roughness, 0.25);
+        assert_eq!(material.shading_model, ShadingModel::MetallicRoughness);
+    }
+
+    #[test]
+    fn node_transforms_are_applied() {
+        let model = get_model(
+            r#"[
+                {"translation": [0, 0, 5], "children": [1]},
+                {"mesh": 0, "scale": [-2, 2, 2]}
+            ]"#,
+        );
+
+        let indices = model.vertex_indices();
+        let vertices = model.vertices();
+        let triangle = [0, 1, 2].map(|i| &vertices[indices[i] as usize]);
+        // Mirrored, so the winding order is flipped to keep the triangle front facing
+        for (vertex, expected) in triangle
+            .iter()
+            .zip([[0., 0., 5.], [0., 2., 5.], [-2., 0., 5.]])
+        {
+            for (i, expected) in expected.into_iter().enumerate() {
+                assert_approximately_equal(vertex.position()[i], expected);
+            }
+            assert_approximately_equal(vertex.normal()[2], 1.);
+        }
+    }
+
+    #[test]
+    fn embedded_images_are_reported() {
+        let gltf = get_gltf(r#"[{"mesh": 0}]"#)
+            .replace(
+                r#""roughnessFactor": 0.25"#,
+                r#""roughnessFactor": 0.25, "baseColorTexture": {"index": 0}"#,
+            )
+            .replace(
+                r#""buffers": ["#,
+                r#""textures": [{"source": 0}],
+                "images": [{"bufferView": 1, "mimeType": "image/png"}],
+                "buffers": ["#,
+            );
+        let model = Gltf::from_bytes(gltf.as_bytes(), None)
+            .and_then(ModelBuilder::try_from)
+            .unwrap()
+            .build();
+
+        assert_eq!(model.materials()[0].diffuse_texture, None);
+        assert_eq!(
+            model.unsupported_textures(),
+            ["materials[0].pbrMetallicRoughness.baseColorTexture uses embedded images[0]"]
+        );
+    }
+
+    #[test]
+    fn accessor_out_of_bounds() {
+        let gltf = get_gltf(r#"[{"mesh": 0}]"#).replace(
+            r#""count": 3, "type": "SCALAR""#,
+            r#""count": 4, "type": "SCALAR""#,
+        );
+        assert!(Gltf::from_bytes(gltf.as_bytes(), None)
+            .and_then(ModelBuilder::try_from)
+            .is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::{obj::Obj, vertex::Normal};
+
+// Returns a normal for each vertex of each face
+// Faces sharing a vertex and a smoothing group share its normal, weighted by the area of the
+// faces, faces without smoothing group get a flat normal
+pub fn generate_normals(obj: &Obj) -> Box<[[Normal; 3]]> {
+    let faces_normal = obj
+        .faces_geometry
+        .iter()
+        .map(|face_geometry| get_area_weighted_face_normal(obj, face_geometry))
+        .collect::<Vec<[f32; 3]>>();
+
+    let mut smooth_normals = HashMap::<(u32, u32), [f32; 3]>::new();
+    for (i, face_geometry) in obj.faces_geometry.iter().enumerate() {
+        let smoothing_group = obj.faces_smoothing_groups[i];
+        if smoothing_group == 0 {
+            continue;
+        }
+        for &geometry_index in face_geometry {
+            let normal = smooth_normals
+                .entry((geometry_index, smoothing_group))
+                .or_insert([0.; 3]);
+            *normal = add(*normal, faces_normal[i]);
+        }
+    }
+
+    obj.faces_geometry
+        .iter()
+        .enumerate()
+        .map(|(i, face_geometry)| {
+            let smoothing_group = obj.faces_smoothing_groups[i];
+            face_geometry.map(|geometry_index| {
+                if smoothing_group == 0 {
+                    return Normal::from(normalize(faces_normal[i]));
+                }
+                Normal::from(normalize(
+                    smooth_normals[&(geometry_index, smoothing_group)],
+                ))
+            })
+        })
+        .collect()
+}
+
+// The length of the cross product of 2 edges is twice the area of the triangle
+fn get_area_weighted_face_normal(obj: &Obj, face_geometry: &[u32; 3]) -> [f32; 3] {
+    let [a, b, c] = face_geometry.map(|index| {
+        let position = &obj.geometry[index as usize];
+        [position[0], position[1], position[2]]
+    });
+    cross(sub(b, a), sub(c, a))
+}
+
+pub fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = vector.iter().map(|e| e * e).sum::<f32>().sqrt();
+    // Degenerated faces don't have a normal
+    if length == 0. {
+        return vector;
+    }
+    vector.map(|e| e / length)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::BufReader;
+
+    use linear_algebra::assert_approximately_equal;
+
+    use super::*;
+
+    // 2 faces folded at 90 degrees along the edge between vertices 2 and 3
+    const FOLDED_QUAD: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 1 1\n";
+
+    fn get_normals(content: &str) -> (Obj, Box<[[Normal; 3]]>) {
+        let obj = Obj::try_from(BufReader::new(content.as_bytes())).unwrap();
+        let normals = generate_normals(&obj);
+        (obj, normals)
+    }
+
+    fn assert_normal_eq(normal: &Normal, expected: [f32; 3]) {
+        for (i, expected) in expected.into_iter().enumerate() {
+            assert_approximately_equal(normal[i], expected);
+        }
+    }
+
+    #[test]
+    fn flat_normals() {
+        let (_, normals) = get_normals(&format!("{FOLDED_QUAD}s off\nf 1 2 3\nf 3 2 4\n"));
+        for normal in &normals[0] {
+            assert_normal_eq(normal, [0., 0., 1.]);
+        }
+        let expected = normalize([1., 1., 0.]).map(|e| -e);
+        for normal in &normals[1] {
+            assert_normal_eq(normal, expected);
+        }
+    }
+
+    #[test]
+    fn smooth_normals() {
+        let (obj, normals) = get_normals(&format!("{FOLDED_QUAD}s 1\nf 1 2 3\nf 3 2 4\n"));
+        let shared = normalize(add([0., 0., 1.], [-1., -1., 0.]));
+        for (i, face_geometry) in obj.faces_geometry.iter().enumerate() {
+            for (j, &geometry_index) in face_geometry.iter().enumerate() {
+                match geometry_index {
+                    1 | 2 => assert_normal_eq(&normals[i][j], shared),
+                    0 => assert_normal_eq(&normals[i][j], [0., 0., 1.]),
+                    _ => assert_normal_eq(&normals[i][j], normalize([-1., -1., 0.])),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn different_smoothing_groups_keep_hard_edges() {
+        let (_, normals) = get_normals(&format!("{FOLDED_QUAD}s 1\nf 1 2 3\ns 2\nf 3 2 4\n"));
+        for normal in &normals[0] {
+            assert_normal_eq(normal, [0., 0., 1.]);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+mod color;
+mod handle_unrecognized_line;
+mod scalar;
+mod texture_map;
+
+use color::parse_color_line;
+use handle_unrecognized_line::handle_unrecognized_line;
+use scalar::parse_scalar_line;
+use texture_map::parse_texture_map_line;
+
+use std::{
+    error::Error,
+    fmt::{Debug, Display},
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::{Material, ShadingModel};
+
+pub struct MtlFile<'a>(pub &'a str);
+
+#[derive(Debug)]
+pub struct Mtl {
+    pub materials: Box<[Material]>,
+}
+
+#[derive(Default)]
+struct MtlBuilder<'a> {
+    materials: Vec<Material>,
+    // Texture paths are relative to the directory of the MTL file
+    directory: Option<&'a Path>,
+}
+
+#[allow(dead_code)]
+pub struct MtlParsingError {
+    line: Option<(usize, String)>,
+    detail: MtlParsingErrorDetail,
+}
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum MtlParsingErrorDetail {
+    FailedToOpenFile(io::Error),
+    FailedToReadFile(io::Error),
+
+    StatementBeforeNewmtl,
+    MissingMaterialName,
+
+    NotEnoughComponentsInColor,
+    TooManyComponentsInColor,
+    InvalidComponentInColor(<f32 as std::str::FromStr>::Err),
+
+    NotEnoughComponentsInScalar,
+    TooManyComponentsInScalar,
+    InvalidFloatScalar(<f32 as std::str::FromStr>::Err),
+    InvalidIntegerScalar(<u32 as std::str::FromStr>::Err),
+
+    MissingTextureMapPath,
+}
+
+impl Debug for MtlParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(line) = self.line.as_ref() {
+            return write!(
+                f,
+                "MtlParsingError {{\n\tline: {}\n\tline_content: \"{}\"\n\tdetails: {:?}\n}}",
+                line.0, line.1, self.detail,
+            );
+        }
+        write!(f, "MtlParsingError({:?})", self.detail)
+    }
+}
+
+impl Display for MtlParsingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Error for MtlParsingError {}
+
+impl MtlBuilder<'_> {
+    fn build(self) -> Mtl {
+        Mtl {
+            materials: self.materials.into_boxed_slice(),
+        }
+    }
+
+    fn current_material(&mut self) -> Result<&mut Material, MtlParsingErrorDetail> {
+        self.materials
+            .last_mut()
+            .ok_or(MtlParsingErrorDetail::StatementBeforeNewmtl)
+    }
+}
+
+impl TryFrom<MtlFile<'_>> for Mtl {
+    type Error = MtlParsingError;
+
+    fn try_from(file_name: MtlFile) -> Result<Self, Self::Error> {
+        let file = File::open(file_name.0).map_err(|err| MtlParsingError {
+            line: None,
+            detail: MtlParsingErrorDetail::FailedToOpenFile(err),
+        })?;
+        parse_lines(BufReader::new(file), Path::new(file_name.0).parent())
+    }
+}
+
+impl<R> TryFrom<BufReader<R>> for Mtl
+where
+    R: Read,
+{
+    type Error = MtlParsingError;
+
+    fn try_from(buf_reader: BufReader<R>) -> Result<Self, MtlParsingError> {
+        parse_lines(buf_reader, None)
+    }
+}
+
+fn parse_lines<R: Read>(
+    buf_reader: BufReader<R>,
+    directory: Option<&Path>,
+) -> Result<Mtl, MtlParsingError> {
+    let mut mtl_builder = MtlBuilder {
+        directory,
+        ..Default::default()
+    };
+
+    for (line_count, line) in buf_reader.lines().enumerate() {
+        let line = line.map_err(|err| MtlParsingError {
+            line: None,
+            detail: MtlParsingErrorDetail::FailedToReadFile(err),
+        })?;
+
+        parse_line(line_count, &line, &mut mtl_builder).map_err(|err| MtlParsingError {
+            line: Some((line_count, line)),
+            detail: err,
+        })?;
+    }
+
+    Ok(mtl_builder.build())
+}
+
+fn parse_line(
+    line_count: usize,
+    line: &str,
+    mtl_builder: &mut MtlBuilder,
+) -> Result<(), MtlParsingErrorDetail> {
+    // Unlike OBJ files, MTL files are often indented with tabs
+    let mut split = line.split_whitespace();
+    let Some(first_word) = split.next() else {
+        return Ok(());
+    };
+
+    match first_word {
+        "newmtl" => {
+            let name = split.collect::<Vec<&str>>().join(" ");
+            if name.is_empty() {
+                return Err(MtlParsingErrorDetail::MissingMaterialName);
+            }
+            mtl_builder.materials.push(Material::new(name));
+            Ok(())
+        }
+        "Ka" => {
+            mtl_builder.current_material()?.ambient_color = parse_color_line(&mut split)?;
+            Ok(())
+        }
+        "Kd" => {
+            mtl_builder.current_material()?.diffuse_color = parse_color_line(&mut split)?;
+            Ok(())
+        }
+        "Ks" => {
+            mtl_builder.current_material()?.specular_color = parse_color_line(&mut split)?;
+            Ok(())
+        }
+        "Ke" => {
+            mtl_builder.current_material()?.emissive_color = parse_color_line(&mut split)?;
+            Ok(())
+        }
+        "Ns" => {
+            mtl_builder.current_material()?.specular_exponent = parse_scalar_line(&mut split)?;
+            Ok(())
+        }
+        "Ni" => {
+            mtl_builder.current_material()?.optical_density = parse_scalar_line(&mut split)?;
+            Ok(())
+        }
+        "d" => {
+            mtl_builder.current_material()?.dissolve = parse_scalar_line(&mut split)?;
+            Ok(())
+        }
+        "Tr" => {
+            mtl_builder.current_material()?.dissolve = 1. - parse_scalar_line::<f32>(&mut split)?;
+            Ok(())
+        }
+        // PBR extension, see http://exocortex.com/blog/extending_wavefront_mtl_to_support_pbr
+        "Pr" | "Pm" => {
+            let material = mtl_builder.current_material()?;
+            let value = parse_scalar_line(&mut split)?;
+            match first_word {
+                "Pr" => material.roughness = value,
+                _ => material.metallic = value,
+            }
+            material.shading_model = ShadingModel::MetallicRoughness;
+            Ok(())
+        }
+        "illum" => {
+            mtl_builder.current_material()?.illumination_model = parse_scalar_line(&mut split)?;
+            Ok(())
+        }
+        "map_Ka" | "map_Kd" | "map_Ks" | "map_Ns" | "map_d" | "map_Ke" | "map_Bump"
+        | "map_bump" | "bump" | "norm" => {
+            let path = parse_texture_map_line(&mut split, mtl_builder.directory)?;
+            let material = mtl_builder.current_material()?;
+            let texture = match first_word {
+                "map_Ka" => &mut material.ambient_texture,
+                "map_Kd" => &mut material.diffuse_texture,
+                "map_Ks" => &mut material.specular_texture,
+                "map_Ns" => &mut material.specular_exponent_texture,
+                "map_d" => &mut material.dissolve_texture,
+                "map_Ke" => &mut material.emissive_texture,
+                "norm" => &mut material.normal_texture,
+                _ => &mut material.bump_texture,
+            };
+            *texture = Some(path);
+            Ok(())
+        }
+        _ => {
+            handle_unrecognized_line(first_word, line_count, line);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(content: &str) -> Result<Mtl, MtlParsingError> {
+        BufReader::new(content.as_bytes()).try_into()
+    }
+
+    #[test]
+    fn parse_materials() {
+        let mtl = parse(
+            "# comment\n\
+             newmtl first\n\
+             \tKa 0.1 0.2 0.3\n\
+             \tKd 0.5\n\
+             \tNs 96.08\n\
+             \tTr 0.25\n\
+             \tillum 1\n\
+             \tmap_Kd -bm 1.0 textures/first.ppm\n\
+             \n\
+             newmtl second\n\
+             d 0.5\n",
+        )
+        .unwrap();
+
+        assert_eq!(mtl.materials.len(), 2);
+        let first = &mtl.materials[0];
+        assert_eq!(first.name, "first");
+        assert_eq!(first.ambient_color[1], 0.2);
+        assert_eq!(first.diffuse_color[2], 0.5);
+        assert_eq!(first.specular_exponent, 96.08);
+        assert_eq!(first.dissolve, 0.75);
+        assert_eq!(first.illumination_model, 1);
+        assert_eq!(first.diffuse_texture.as_deref(), Some("textures/first.ppm"));
+
+        let second = &mtl.materials[1];
+        assert_eq!(second.name, "second");
+        assert_eq!(second.dissolve, 0.5);
+        assert!(second.diffuse_texture.is_none());
+    }
+
+    #[test]
+    fn parse_pbr_extension() {
+        let mtl = parse(
+            "newmtl metal
+             Pm 1
+             Pr 0.3
+             norm metal_normal.ppm
+             map_Ke metal_emissive.ppm
+             newmtl plastic
+             Ks 0.5 0.5 0.5
+",
+        )
+        .unwrap();
+
+        let metal = &mtl.materials[0];
+        assert_eq!(metal.shading_model, ShadingModel::MetallicRoughness);
+        assert_eq!(metal.metallic, 1.);
+        assert_eq!(metal.roughness, 0.3);
+        assert_eq!(metal.normal_texture.as_deref(), Some("metal_normal.ppm"));
+        assert_eq!(
+            metal.emissive_texture.as_deref(),
+            Some("metal_emissive.ppm")
+        );
+        assert_eq!(mtl.materials[1].shading_model, ShadingModel::BlinnPhong);
+    }
+
+    #[test]
+    fn texture_map_paths_can_contain_spaces() {
+        let mtl = parse(
+            "newmtl a\n\
+             map_Kd my textures/first.ppm\n\
+             map_Ks -o 0.5 0.5 -clamp on my textures/second.ppm\n\
+             map_Ka -s 2 third texture.ppm\n\
+             map_bump -mm 0 1 -bm 0.5 fourth.ppm\n",
+        )
+        .unwrap();
+
+        let material = &mtl.materials[0];
+        assert_eq!(
+            material.diffuse_texture.as_deref(),
+            Some("my textures/first.ppm")
+        );
+        assert_eq!(
+            material.specular_texture.as_deref(),
+            Some("my textures/second.ppm")
+        );
+        assert_eq!(
+            material.ambient_texture.as_deref(),
+            Some("third texture.ppm")
+        );
+        assert_eq!(material.bump_texture.as_deref(), Some("fourth.ppm"));
+    }
+
+    #[test]
+    fn missing_texture_map_path() {
+        let err = parse("newmtl a\nmap_Kd -bm 1.0\n").unwrap_err();
+        assert!(matches!(
+            err.detail,
+            MtlParsingErrorDetail::MissingTextureMapPath
+        ));
+    }
+
+    #[test]
+    fn statement_before_newmtl() {
+        let err = parse("Kd 1 1 1\n").unwrap_err();
+        assert!(matches!(
+            err.detail,
+            MtlParsingErrorDetail::StatementBeforeNewmtl
+        ));
+    }
+
+    #[test]
+    fn invalid_color() {
+        let err = parse("newmtl a\nKd 1 1\n").unwrap_err();
+        assert!(matches!(
+            err.detail,
+            MtlParsingErrorDetail::NotEnoughComponentsInColor
+        ));
+        assert_eq!(err.line.map(|line| line.0), Some(1));
+    }
+}
@@ -0,0 +1,27 @@
+use rs42::extensions::PipeLine;
+
+use crate::vertex::Color;
+
+use super::MtlParsingErrorDetail;
+
+pub fn parse_color_line<'a>(
+    components: &mut impl Iterator<Item = &'a str>,
+) -> Result<Color, MtlParsingErrorDetail> {
+    // "spectral" and "xyz" colors are not supported
+    let components = components
+        .map(|component| {
+            component
+                .parse::<f32>()
+                .map_err(MtlParsingErrorDetail::InvalidComponentInColor)
+        })
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    // When only r is given, g and b are equal to r
+    match components.as_slice() {
+        [] => Err(MtlParsingErrorDetail::NotEnoughComponentsInColor),
+        [r] => [*r; 3].pipe(Color::from).pipe(Ok),
+        [r, g, b] => [*r, *g, *b].pipe(Color::from).pipe(Ok),
+        [_, _] => Err(MtlParsingErrorDetail::NotEnoughComponentsInColor),
+        _ => Err(MtlParsingErrorDetail::TooManyComponentsInColor),
+    }
+}
@@ -0,0 +1,34 @@
+use std::str::FromStr;
+
+use super::MtlParsingErrorDetail;
+
+pub trait Scalar: FromStr {
+    fn map_parse_error(err: Self::Err) -> MtlParsingErrorDetail;
+}
+
+impl Scalar for f32 {
+    fn map_parse_error(err: Self::Err) -> MtlParsingErrorDetail {
+        MtlParsingErrorDetail::InvalidFloatScalar(err)
+    }
+}
+
+impl Scalar for u32 {
+    fn map_parse_error(err: Self::Err) -> MtlParsingErrorDetail {
+        MtlParsingErrorDetail::InvalidIntegerScalar(err)
+    }
+}
+
+pub fn parse_scalar_line<'a, T: Scalar>(
+    components: &mut impl Iterator<Item = &'a str>,
+) -> Result<T, MtlParsingErrorDetail> {
+    let scalar = components
+        .next()
+        .ok_or(MtlParsingErrorDetail::NotEnoughComponentsInScalar)?
+        .parse::<T>()
+        .map_err(T::map_parse_error)?;
+
+    if components.next().is_some() {
+        return Err(MtlParsingErrorDetail::TooManyComponentsInScalar);
+    }
+    Ok(scalar)
+}
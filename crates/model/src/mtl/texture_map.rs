@@ -0,0 +1,48 @@
+use std::{iter::Peekable, path::Path};
+
+use rs42::extensions::PipeLine;
+
+use super::MtlParsingErrorDetail;
+
+pub fn parse_texture_map_line<'a>(
+    components: &mut impl Iterator<Item = &'a str>,
+    directory: Option<&Path>,
+) -> Result<String, MtlParsingErrorDetail> {
+    // Options such as "-bm 1.0" or "-clamp on" come before the file name, they are ignored
+    let mut components = components.peekable();
+    while let Some(option) = components.next_if(|component| component.starts_with('-')) {
+        skip_option_values(&mut components, option);
+    }
+
+    // The file name can contain spaces
+    let file_name = components.collect::<Vec<&str>>().join(" ");
+    if file_name.is_empty() {
+        return Err(MtlParsingErrorDetail::MissingTextureMapPath);
+    }
+
+    match directory {
+        Some(directory) => directory.join(file_name).to_string_lossy().into_owned(),
+        None => file_name,
+    }
+    .pipe(Ok)
+}
+
+// -o, -s and -t take 1 to 3 numbers, -mm takes 2 and the other options take 1 value
+fn skip_option_values<'a>(components: &mut Peekable<impl Iterator<Item = &'a str>>, option: &str) {
+    let (min_value_count, max_value_count) = match option {
+        "-o" | "-s" | "-t" => (1, 3),
+        "-mm" => (2, 2),
+        _ => (1, 1),
+    };
+
+    for i in 0..max_value_count {
+        if i >= min_value_count
+            && components
+                .peek()
+                .is_none_or(|value| value.parse::<f32>().is_err())
+        {
+            return;
+        }
+        components.next();
+    }
+}
@@ -1,12 +1,16 @@
 mod face;
 mod handle_unrecognized_line;
+mod material;
 mod normal;
+mod smoothing_group;
 mod texture;
 mod vertex;
 
 use face::parse_face_line;
 use handle_unrecognized_line::handle_unrecognized_line;
+use material::{parse_material_library_line, parse_use_material_line};
 use normal::parse_normal_line;
+use smoothing_group::parse_smoothing_group_line;
 use texture::parse_texture_line;
 use vertex::parse_vertex_line;
 
@@ -15,10 +19,13 @@ use std::{
     fmt::{Debug, Display},
     fs::File,
     io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
 };
 
 use linear_algebra::Vector;
 
+use crate::{mtl::MtlParsingError, Material};
+
 pub struct ObjFile<'a>(pub &'a str);
 
 #[allow(dead_code)]
@@ -29,8 +36,22 @@ pub struct Obj {
     pub normals: Box<[Vector<f32, 3>]>,
     pub textures: Box<[Vector<f32, 3>]>,
     pub faces_geometry: Box<[[u32; 3]]>,
-    pub faces_textures: Box<[[u32; 3]]>,
-    pub faces_normals: Box<[[u32; 3]]>,
+    // None for the faces that have no texture coordinates/normals
+    pub faces_textures: Box<[Option<[u32; 3]>]>,
+    pub faces_normals: Box<[Option<[u32; 3]>]>,
+    // 0 means that the face is not smoothed
+    pub faces_smoothing_groups: Box<[u32]>,
+    pub materials: Box<[Material]>,
+    pub material_usages: Box<[MaterialUsage]>,
+    // Paths of the material libraries that were read
+    pub dependencies: Box<[String]>,
+}
+
+// Every face starting at first_face uses material, until the next MaterialUsage
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialUsage {
+    pub first_face: usize,
+    pub material: Option<usize>,
 }
 
 #[derive(Default, Debug)]
@@ -40,8 +61,17 @@ struct ObjBuilder {
     normals: Vec<Vector<f32, 3>>,
     textures: Vec<Vector<f32, 3>>,
     faces_geometry: Vec<[u32; 3]>,
-    faces_textures: Vec<[u32; 3]>,
-    faces_normals: Vec<[u32; 3]>,
+    faces_textures: Vec<Option<[u32; 3]>>,
+    faces_normals: Vec<Option<[u32; 3]>>,
+    faces_smoothing_groups: Vec<u32>,
+    materials: Vec<Material>,
+    material_usages: Vec<MaterialUsage>,
+    dependencies: Vec<String>,
+
+    current_smoothing_group: u32,
+
+    // Material libraries are relative to the directory of the OBJ file
+    directory: Option<PathBuf>,
 }
 
 #[allow(dead_code)]
@@ -79,6 +109,14 @@ pub enum ObjParsingErrorDetail {
     TooManySubComponentsInFace,
     InvalidSubComponentInFace(<i32 as std::str::FromStr>::Err),
     FaceSubComponentCanNotBe0,
+
+    MissingMaterialLibraryName,
+    FailedToLoadMaterialLibrary(MtlParsingError),
+    MissingMaterialName,
+
+    NotEnoughComponentsInSmoothingGroup,
+    TooManyComponentsInSmoothingGroup,
+    InvalidSmoothingGroup(<u32 as std::str::FromStr>::Err),
 }
 
 impl Debug for ObjParsingError {
@@ -112,6 +150,10 @@ impl ObjBuilder {
             faces_geometry: self.faces_geometry.into_boxed_slice(),
             faces_textures: self.faces_textures.into_boxed_slice(),
             faces_normals: self.faces_normals.into_boxed_slice(),
+            faces_smoothing_groups: self.faces_smoothing_groups.into_boxed_slice(),
+            materials: self.materials.into_boxed_slice(),
+            material_usages: self.material_usages.into_boxed_slice(),
+            dependencies: self.dependencies.into_boxed_slice(),
         }
     }
 }
@@ -124,7 +166,7 @@ impl TryFrom<ObjFile<'_>> for Obj {
             line: None,
             detail: ObjParsingErrorDetail::FailedToOpenFile(err),
         })?;
-        BufReader::new(file).try_into()
+        parse_lines(BufReader::new(file), Path::new(file_name.0).parent())
     }
 }
 
@@ -135,22 +177,32 @@ where
     type Error = ObjParsingError;
 
     fn try_from(buf_reader: BufReader<R>) -> Result<Self, ObjParsingError> {
-        let mut obj_builder = ObjBuilder::default();
-
-        for (line_count, line) in buf_reader.lines().enumerate() {
-            let line = line.map_err(|err| ObjParsingError {
-                line: None,
-                detail: ObjParsingErrorDetail::FailedToReadFile(err),
-            })?;
-
-            parse_line(line_count, &line, &mut obj_builder).map_err(|err| ObjParsingError {
-                line: Some((line_count, line)),
-                detail: err,
-            })?;
-        }
+        parse_lines(buf_reader, None)
+    }
+}
+
+fn parse_lines<R: Read>(
+    buf_reader: BufReader<R>,
+    directory: Option<&Path>,
+) -> Result<Obj, ObjParsingError> {
+    let mut obj_builder = ObjBuilder {
+        directory: directory.map(Path::to_path_buf),
+        ..Default::default()
+    };
+
+    for (line_count, line) in buf_reader.lines().enumerate() {
+        let line = line.map_err(|err| ObjParsingError {
+            line: None,
+            detail: ObjParsingErrorDetail::FailedToReadFile(err),
+        })?;
 
-        Ok(obj_builder.build())
+        parse_line(line_count, &line, &mut obj_builder).map_err(|err| ObjParsingError {
+            line: Some((line_count, line)),
+            detail: err,
+        })?;
     }
+
+    Ok(obj_builder.build())
 }
 
 fn parse_line(
@@ -168,6 +220,9 @@ fn parse_line(
         "vt" => parse_texture_line(&mut split, &mut obj_builder.textures),
         "vn" => parse_normal_line(&mut split, &mut obj_builder.normals),
         "f" => parse_face_line(&mut split, obj_builder),
+        "mtllib" => parse_material_library_line(&mut split, obj_builder),
+        "usemtl" => parse_use_material_line(&mut split, obj_builder),
+        "s" => parse_smoothing_group_line(&mut split, obj_builder),
         _ => {
             handle_unrecognized_line(first_word, line_count, line);
             Ok(())
@@ -21,11 +21,10 @@ pub fn parse_face_line<'a>(
     let triangles = triangulate_face(face, obj_builder);
 
     obj_builder.faces_geometry.extend(triangles.geometries);
-
-    // TODO check if it is possible for an object to have textures/normals on some faces but not
-    // others
-    // If it is this won't work as textures and normals indices will not be associated with the
-    // right geometry
+    obj_builder.faces_smoothing_groups.resize(
+        obj_builder.faces_geometry.len(),
+        obj_builder.current_smoothing_group,
+    );
     obj_builder.faces_textures.extend(triangles.textures);
     obj_builder.faces_normals.extend(triangles.normals);
 
@@ -28,24 +28,27 @@ fn parse_face_sub_components<'a>(
         &mut face.geometries_indices,
     )?;
 
-    if let Err(err) = push_face_sub_component_to_index_vec(
-        sub_components.next(),
-        &obj_builder.textures,
-        ObjParsingErrorDetail::FaceTextureDoesNotExist,
-        &mut face.textures_indices,
-    ) {
-        if let ObjParsingErrorDetail::NotEnoughSubComponentsInFace = err {
-            return Ok(());
-        }
-        return Err(err);
-    };
+    // The texture can be omitted while still having a normal: "v//vn"
+    match sub_components.next() {
+        None => return Ok(()),
+        Some("") => {}
+        texture => push_face_sub_component_to_index_vec(
+            texture,
+            &obj_builder.textures,
+            ObjParsingErrorDetail::FaceTextureDoesNotExist,
+            &mut face.textures_indices,
+        )?,
+    }
 
-    push_face_sub_component_to_index_vec(
-        sub_components.next(),
-        &obj_builder.normals,
-        ObjParsingErrorDetail::FaceNormalDoesNotExist,
-        &mut face.normals_indices,
-    )?;
+    match sub_components.next() {
+        None => return Ok(()),
+        normal => push_face_sub_component_to_index_vec(
+            normal,
+            &obj_builder.normals,
+            ObjParsingErrorDetail::FaceNormalDoesNotExist,
+            &mut face.normals_indices,
+        )?,
+    }
 
     if sub_components.next().is_some() {
         return Err(ObjParsingErrorDetail::TooManySubComponentsInFace);
@@ -61,17 +64,26 @@ fn push_face_sub_component_to_index_vec<T>(
 ) -> Result<(), ObjParsingErrorDetail> {
     let index = sub_component
         .ok_or(ObjParsingErrorDetail::NotEnoughSubComponentsInFace)?
-        .parse::<u32>()
-        .map_err(ObjParsingErrorDetail::InvalidSubComponentInFace)?
-        .checked_sub(1)
-        .ok_or(ObjParsingErrorDetail::FaceSubComponentCanNotBe0)?;
-    if index as usize >= associated_vec.len() {
-        return Err(invalid_index_err);
+        .parse::<i32>()
+        .map_err(ObjParsingErrorDetail::InvalidSubComponentInFace)?;
+    if index == 0 {
+        return Err(ObjParsingErrorDetail::FaceSubComponentCanNotBe0);
     }
+    let index = resolve_index(index, associated_vec.len()).ok_or(invalid_index_err)?;
     dest_index_vec.push(index);
     Ok(())
 }
 
+// Indices start at 1, negative indices are relative to the end of associated_vec
+fn resolve_index(index: i32, associated_vec_len: usize) -> Option<u32> {
+    let index = if index > 0 {
+        index as usize - 1
+    } else {
+        associated_vec_len.checked_sub(index.unsigned_abs() as usize)?
+    };
+    (index < associated_vec_len).then_some(index as u32)
+}
+
 fn check_face_validity(face: Face) -> Result<Face, ObjParsingErrorDetail> {
     if face.geometries_indices.len() < 3 {
         return Err(ObjParsingErrorDetail::FaceShouldHaveAtLeast3Components);
@@ -88,3 +100,53 @@ fn check_face_validity(face: Face) -> Result<Face, ObjParsingErrorDetail> {
     }
     Ok(face)
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::BufReader;
+
+    use crate::obj::{Obj, ObjParsingError};
+
+    use super::*;
+
+    const VERTICES: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n";
+
+    fn parse(faces: &str) -> Result<Obj, ObjParsingError> {
+        Obj::try_from(BufReader::new(format!("{VERTICES}{faces}").as_bytes()))
+    }
+
+    #[test]
+    fn relative_indices() {
+        let obj = parse("f -3 -2 -1\n").unwrap();
+        assert_eq!(&*obj.faces_geometry, &[[1, 2, 3]]);
+
+        let obj = parse("f -4/-3/-1 -3/-2/-1 -2/-1/-1\n").unwrap();
+        assert_eq!(&*obj.faces_geometry, &[[0, 1, 2]]);
+        assert_eq!(&*obj.faces_textures, &[Some([0, 1, 2])]);
+        assert_eq!(&*obj.faces_normals, &[Some([0, 0, 0])]);
+    }
+
+    #[test]
+    fn relative_index_out_of_range() {
+        let err = parse("f -5 -2 -1\n").unwrap_err();
+        assert!(matches!(
+            err.detail,
+            ObjParsingErrorDetail::FaceGeometryDoesNotExist
+        ));
+    }
+
+    #[test]
+    fn geometry_and_normal_without_texture() {
+        let obj = parse("f 1//1 2//1 3//-1\n").unwrap();
+        assert_eq!(&*obj.faces_geometry, &[[0, 1, 2]]);
+        assert_eq!(&*obj.faces_textures, &[None]);
+        assert_eq!(&*obj.faces_normals, &[Some([0, 0, 0])]);
+    }
+
+    #[test]
+    fn geometry_and_texture_without_normal() {
+        let obj = parse("f 1/1 2/2 3/3\n").unwrap();
+        assert_eq!(&*obj.faces_textures, &[Some([0, 1, 2])]);
+        assert_eq!(&*obj.faces_normals, &[None]);
+    }
+}
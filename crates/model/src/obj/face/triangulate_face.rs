@@ -7,41 +7,146 @@ use super::Face;
 #[derive(Default)]
 pub struct Triangles {
     pub geometries: Vec<[u32; 3]>,
-    pub textures: Vec<[u32; 3]>,
-    pub normals: Vec<[u32; 3]>,
+    pub textures: Vec<Option<[u32; 3]>>,
+    pub normals: Vec<Option<[u32; 3]>>,
 }
 
-pub fn triangulate_face(face: Face, _obj_builder: &ObjBuilder) -> Triangles {
-    // TODO write an algorithm that works with concave polygons
+pub fn triangulate_face(face: Face, obj_builder: &ObjBuilder) -> Triangles {
+    let triangles_order = get_triangles_order(&obj_builder.geometry, &face.geometries_indices);
 
-    (1..face.geometries_indices.len() - 1).fold(Triangles::default(), |mut triangles, i| {
-        push_triangle(&mut triangles.geometries, &face.geometries_indices, i);
-        if !face.textures_indices.is_empty() {
-            push_triangle(&mut triangles.textures, &face.textures_indices, i);
-        }
-        if !face.normals_indices.is_empty() {
-            push_triangle(&mut triangles.normals, &face.normals_indices, i);
-        }
-        triangles
-    })
+    triangles_order
+        .into_iter()
+        .fold(Triangles::default(), |mut triangles, triangle| {
+            triangles
+                .geometries
+                .push(get_triangle(&face.geometries_indices, triangle));
+            triangles.textures.push(
+                (!face.textures_indices.is_empty())
+                    .then(|| get_triangle(&face.textures_indices, triangle)),
+            );
+            triangles.normals.push(
+                (!face.normals_indices.is_empty())
+                    .then(|| get_triangle(&face.normals_indices, triangle)),
+            );
+            triangles
+        })
+}
+
+fn get_triangle(indices: &[u32], triangle: [usize; 3]) -> [u32; 3] {
+    triangle.map(|i| indices[i])
+}
+
+// Returns triangles made of indices into geometries_indices, using ear clipping so that concave
+// polygons are supported
+fn get_triangles_order(geometry: &[Vec4<f32>], geometries_indices: &[u32]) -> Vec<[usize; 3]> {
+    if geometries_indices.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    let rotation = get_rotation_matrix_to_flatten_polygon_z_axis(geometry, geometries_indices);
+    let points = geometries_indices
+        .iter()
+        .map(|&index| {
+            let point = &rotation * &geometry[index as usize];
+            [point[0], point[1]]
+        })
+        .collect::<Vec<[f32; 2]>>();
+
+    ear_clipping(&points)
+}
+
+fn ear_clipping(points: &[[f32; 2]]) -> Vec<[usize; 3]> {
+    let orientation = get_signed_area(points).signum();
+    let mut remaining = (0..points.len()).collect::<Vec<usize>>();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let get_triangle = |i: usize| {
+            [
+                remaining[(i + len - 1) % len],
+                remaining[i],
+                remaining[(i + 1) % len],
+            ]
+        };
+
+        let ear = (0..len).find(|&i| {
+            let triangle = get_triangle(i);
+            is_convex(points, triangle, orientation)
+                && !remaining
+                    .iter()
+                    .filter(|vertex| !triangle.contains(vertex))
+                    .any(|&vertex| is_inside_triangle(points, triangle, vertex))
+        });
+
+        // Degenerate or self intersecting polygons might not have any ear, in this case the
+        // remaining vertices are triangulated as a fan
+        let Some(ear) = ear else {
+            break;
+        };
+
+        triangles.push(get_triangle(ear));
+        remaining.remove(ear);
+    }
+
+    triangles
+        .extend((1..remaining.len() - 1).map(|i| [remaining[0], remaining[i], remaining[i + 1]]));
+    triangles
 }
 
-fn push_triangle(dest_triangles_vec: &mut Vec<[u32; 3]>, indices: &[u32], i: usize) {
-    dest_triangles_vec.push([indices[0], indices[i], indices[i + 1]]);
+fn get_signed_area(points: &[[f32; 2]]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let [ax, ay] = points[i];
+            let [bx, by] = points[(i + 1) % points.len()];
+            ax * by - bx * ay
+        })
+        .sum::<f32>()
+        / 2.
+}
+
+fn cross(a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+fn is_convex(points: &[[f32; 2]], [a, b, c]: [usize; 3], orientation: f32) -> bool {
+    cross(points[a], points[b], points[c]) * orientation > 0.
+}
+
+fn is_inside_triangle(points: &[[f32; 2]], [a, b, c]: [usize; 3], vertex: usize) -> bool {
+    let point = points[vertex];
+    // Polygons can have duplicated vertices (e.g. holes bridged to the outer contour)
+    if [a, b, c].iter().any(|&i| points[i] == point) {
+        return false;
+    }
+
+    let ab = cross(points[a], points[b], point);
+    let bc = cross(points[b], points[c], point);
+    let ca = cross(points[c], points[a], point);
+    (ab >= 0. && bc >= 0. && ca >= 0.) || (ab <= 0. && bc <= 0. && ca <= 0.)
 }
 
-// This will be used when we will support triangulation of concave polygons
-#[allow(dead_code)]
 fn get_rotation_matrix_to_flatten_polygon_z_axis(
     geometry: &[Vec4<f32>],
     geometries_indices: &[u32],
 ) -> Matrix<f32, 4, 4> {
-    let a = Vec3::from_fn(|i| geometry[geometries_indices[0] as usize][i]);
-    let b = Vec3::from_fn(|i| geometry[geometries_indices[1] as usize][i]);
-    let c = Vec3::from_fn(|i| geometry[geometries_indices[2] as usize][i]);
-    let ab = b - &a;
-    let ac = c - a;
-    let normal = ab ^ ac;
+    // Newell's method, works with concave and slightly non planar polygons unlike the cross
+    // product of the first 2 edges
+    let normal = (0..geometries_indices.len()).fold([0f32; 3], |mut normal, i| {
+        let current = &geometry[geometries_indices[i] as usize];
+        let next = &geometry[geometries_indices[(i + 1) % geometries_indices.len()] as usize];
+        normal[0] += (current[1] - next[1]) * (current[2] + next[2]);
+        normal[1] += (current[2] - next[2]) * (current[0] + next[0]);
+        normal[2] += (current[0] - next[0]) * (current[1] + next[1]);
+        normal
+    });
+
+    if normal[0].hypot(normal[1]) <= normal[2].abs() * f32::EPSILON {
+        // Already flat, the winding order is taken care of by ear_clipping()
+        return Matrix::identity();
+    }
+
+    let normal = Vec3::from(normal);
     let angle = normal.angle_cos(&[0., 0., 1.].into()).acos();
     let axis = normal ^ Vec3::from([0., 0., 1.]);
     Matrix::rotate(&Matrix::identity(), axis, angle)
@@ -68,4 +173,95 @@ mod test {
             assert_approximately_equal(vec[2], expect);
         }
     }
+
+    fn check_triangulation(geometry: &[Vec4<f32>], expected_area: f32) {
+        let indices = (0..geometry.len() as u32).collect::<Vec<u32>>();
+        let triangles = get_triangles_order(geometry, &indices);
+        assert_eq!(triangles.len(), geometry.len() - 2);
+
+        let rotation = get_rotation_matrix_to_flatten_polygon_z_axis(geometry, &indices);
+        let points = geometry
+            .iter()
+            .map(|v| {
+                let v = &rotation * v;
+                [v[0], v[1]]
+            })
+            .collect::<Vec<[f32; 2]>>();
+        let orientation = get_signed_area(&points).signum();
+
+        let mut area = 0.;
+        for [a, b, c] in triangles {
+            // Triangles outside of the polygon would have the opposite winding order
+            let triangle_area = cross(points[a], points[b], points[c]) / 2.;
+            assert!(triangle_area * orientation > 0.);
+            area += triangle_area.abs();
+        }
+        assert_approximately_equal(area, expected_area);
+    }
+
+    #[test]
+    fn triangulate_convex_polygon() {
+        let geometry = [[0., 0.], [2., 0.], [3., 1.], [2., 2.], [0., 2.]]
+            .map(|[x, y]| Vec4::from([x, y, 0., 1.]));
+        check_triangulation(&geometry, 5.);
+    }
+
+    #[test]
+    fn triangulate_concave_polygon() {
+        // Arrow head, a fan starting at the first vertex would go outside of the polygon
+        let geometry =
+            [[0., 0.], [2., 1.], [4., 0.], [2., 4.]].map(|[x, y]| Vec4::from([x, y, 0., 1.]));
+        check_triangulation(&geometry, 6.);
+
+        // Rotated so that the first vertex is the reflex one
+        let geometry =
+            [[2., 1.], [4., 0.], [2., 4.], [0., 0.]].map(|[x, y]| Vec4::from([x, y, 0., 1.]));
+        check_triangulation(&geometry, 6.);
+    }
+
+    #[test]
+    fn triangulate_clockwise_concave_polygon() {
+        // L shape
+        let geometry = [[0., 0.], [0., 3.], [1., 3.], [1., 1.], [3., 1.], [3., 0.]]
+            .map(|[x, y]| Vec4::from([x, y, 0., 1.]));
+        check_triangulation(&geometry, 5.);
+    }
+
+    #[test]
+    fn triangulate_concave_polygon_not_aligned_with_axes() {
+        // Comb shape in the x = y + z plane
+        let geometry = [
+            [0., 0.],
+            [5., 0.],
+            [5., 3.],
+            [4., 3.],
+            [4., 1.],
+            [3., 1.],
+            [3., 3.],
+            [2., 3.],
+            [2., 1.],
+            [1., 1.],
+            [1., 3.],
+            [0., 3.],
+        ]
+        .map(|[u, v]| Vec4::from([u + v, u, v, 1.]));
+        // Going from (u, v) to this plane scales areas by |(1, 1, 0) ^ (1, 0, 1)| = sqrt(3)
+        check_triangulation(&geometry, 11. * 3f32.sqrt());
+    }
+
+    #[test]
+    fn triangulate_slightly_non_planar_concave_polygon() {
+        let geometry = [
+            [0., 0., 0.],
+            [2., 1., 0.01],
+            [4., 0., -0.01],
+            [2., 4., 0.02],
+        ]
+        .map(|[x, y, z]| Vec4::from([x, y, z, 1.]));
+        let indices = [0, 1, 2, 3];
+        let triangles = get_triangles_order(&geometry, &indices);
+        assert_eq!(triangles.len(), 2);
+        // The reflex vertex can't be an ear
+        assert!(!triangles.contains(&[0, 1, 2]));
+    }
 }
@@ -0,0 +1,59 @@
+use crate::mtl::{Mtl, MtlFile};
+
+use super::{MaterialUsage, ObjBuilder, ObjParsingErrorDetail};
+
+pub fn parse_material_library_line<'a>(
+    components: &mut impl Iterator<Item = &'a str>,
+    obj_builder: &mut ObjBuilder,
+) -> Result<(), ObjParsingErrorDetail> {
+    let mut components = components.peekable();
+    if components.peek().is_none() {
+        return Err(ObjParsingErrorDetail::MissingMaterialLibraryName);
+    }
+
+    for file_name in components {
+        let path = match obj_builder.directory.as_ref() {
+            Some(directory) => directory.join(file_name).to_string_lossy().into_owned(),
+            None => file_name.to_owned(),
+        };
+        let mtl = Mtl::try_from(MtlFile(&path))
+            .map_err(ObjParsingErrorDetail::FailedToLoadMaterialLibrary)?;
+        obj_builder.materials.extend(mtl.materials);
+        obj_builder.dependencies.push(path);
+    }
+    Ok(())
+}
+
+pub fn parse_use_material_line<'a>(
+    components: &mut impl Iterator<Item = &'a str>,
+    obj_builder: &mut ObjBuilder,
+) -> Result<(), ObjParsingErrorDetail> {
+    let name = components.collect::<Vec<&str>>().join(" ");
+    if name.is_empty() {
+        return Err(ObjParsingErrorDetail::MissingMaterialName);
+    }
+
+    // Last definition wins when several libraries define the same material
+    let material = obj_builder
+        .materials
+        .iter()
+        .rposition(|material| material.name == name);
+    if material.is_none() && cfg!(debug_assertions) {
+        eprintln!("WARNING: OBJ parser: material \"{name}\" is not defined, using default");
+    }
+
+    let first_face = obj_builder.faces_geometry.len();
+    // A usemtl without faces since the previous one has no effect
+    if obj_builder
+        .material_usages
+        .last()
+        .is_some_and(|usage| usage.first_face == first_face)
+    {
+        obj_builder.material_usages.pop();
+    }
+    obj_builder.material_usages.push(MaterialUsage {
+        first_face,
+        material,
+    });
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use super::{ObjBuilder, ObjParsingErrorDetail};
+
+pub fn parse_smoothing_group_line<'a>(
+    components: &mut impl Iterator<Item = &'a str>,
+    obj_builder: &mut ObjBuilder,
+) -> Result<(), ObjParsingErrorDetail> {
+    let smoothing_group = match components
+        .next()
+        .ok_or(ObjParsingErrorDetail::NotEnoughComponentsInSmoothingGroup)?
+    {
+        "off" => 0,
+        smoothing_group => smoothing_group
+            .parse::<u32>()
+            .map_err(ObjParsingErrorDetail::InvalidSmoothingGroup)?,
+    };
+
+    if components.next().is_some() {
+        return Err(ObjParsingErrorDetail::TooManyComponentsInSmoothingGroup);
+    }
+
+    obj_builder.current_smoothing_group = smoothing_group;
+    Ok(())
+}
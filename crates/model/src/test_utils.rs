@@ -0,0 +1,34 @@
+use std::{fs, path::PathBuf};
+
+// Directory in the temporary directory of the system, removed with its content when dropped so
+// that failing tests don't leave it behind
+pub struct TestDirectory(PathBuf);
+
+impl TestDirectory {
+    // name has to be unique among the tests of every crate, as they can run at the same time
+    pub fn new(name: &str) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("hitchhikers_{name}_{}", std::process::id()));
+        // Left behind by a killed run of a process with the same id
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        Self(directory)
+    }
+
+    pub fn path(&self, file_name: &str) -> String {
+        self.0.join(file_name).to_string_lossy().into_owned()
+    }
+
+    // Returns the path of the file
+    pub fn write(&self, file_name: &str, content: impl AsRef<[u8]>) -> String {
+        let path = self.path(file_name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
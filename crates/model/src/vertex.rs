@@ -6,6 +6,7 @@ use linear_algebra::Vector;
 pub type Position = Vector<f32, 3>;
 pub type Color = Vector<f32, 3>;
 pub type TextureCoordinate = Vector<f32, 2>;
+pub type Normal = Vector<f32, 3>;
 
 // TODO research doing different buffers for positions and texture coordinates
 #[derive(Clone)]
@@ -14,14 +15,34 @@ pub struct Vertex {
     position: Position,
     color: Color, // TODO Might remove this
     texture_coordinate: TextureCoordinate,
+    normal: Normal,
 }
 
 impl Vertex {
-    fn into_tuple_of_bits(self) -> ([u32; 3], [u32; 3], [u32; 2]) {
+    fn into_tuple_of_bits(self) -> ([u32; 3], [u32; 3], [u32; 2], [u32; 3]) {
         (
             self.position.into_scalars().map(|e| e.to_bits()),
             self.color.into_scalars().map(|e| e.to_bits()),
             self.texture_coordinate.into_scalars().map(|e| e.to_bits()),
+            self.normal.into_scalars().map(|e| e.to_bits()),
+        )
+    }
+
+    pub(crate) fn to_scalars(&self) -> [f32; 11] {
+        let mut scalars = [0.; 11];
+        scalars[0..3].copy_from_slice(&self.position.clone().into_scalars());
+        scalars[3..6].copy_from_slice(&self.color.clone().into_scalars());
+        scalars[6..8].copy_from_slice(&self.texture_coordinate.clone().into_scalars());
+        scalars[8..11].copy_from_slice(&self.normal.clone().into_scalars());
+        scalars
+    }
+
+    pub(crate) fn from_scalars(scalars: [f32; 11]) -> Self {
+        Self::new(
+            [scalars[0], scalars[1], scalars[2]],
+            [scalars[3], scalars[4], scalars[5]],
+            [scalars[6], scalars[7]],
+            [scalars[8], scalars[9], scalars[10]],
         )
     }
 }
@@ -47,14 +68,24 @@ impl Vertex {
         position: impl Into<Position>,
         color: impl Into<Color>,
         texture_coordinate: impl Into<TextureCoordinate>,
+        normal: impl Into<Normal>,
     ) -> Self {
         Self {
             position: position.into(),
             color: color.into(),
             texture_coordinate: texture_coordinate.into(),
+            normal: normal.into(),
         }
     }
 
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn normal(&self) -> &Normal {
+        &self.normal
+    }
+
     pub fn get_binding_description() -> vk::VertexInputBindingDescription {
         vk::VertexInputBindingDescription::default()
             .binding(0)
@@ -62,7 +93,7 @@ impl Vertex {
             .input_rate(vk::VertexInputRate::VERTEX)
     }
 
-    pub fn get_attributes_descriptions() -> [vk::VertexInputAttributeDescription; 3] {
+    pub fn get_attributes_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
         [
             vk::VertexInputAttributeDescription::default()
                 .binding(0)
@@ -79,6 +110,11 @@ impl Vertex {
                 .location(2)
                 .format(vk::Format::R32G32_SFLOAT) // TODO maybe a macro can extrapolate this
                 .offset(offset_of!(Self, texture_coordinate) as u32),
+            vk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(3)
+                .format(vk::Format::R32G32B32_SFLOAT) // TODO maybe a macro can extrapolate this
+                .offset(offset_of!(Self, normal) as u32),
         ]
     }
 }
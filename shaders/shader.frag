@@ -1,12 +1,224 @@
 #version 450
 
-layout(binding = 1) uniform sampler2D textureSampler;
+struct Light {
+    // w is the type of the light
+    vec4 position;
+    // w is the range
+    vec4 direction;
+    // w is the intensity
+    vec4 color;
+    // Cosines of the inner and outer angles, z is the index of the shadow map or -1
+    vec4 cone;
+};
+
+// Has to match MAX_LIGHTS and MAX_SHADOW_MAPS in uniform_buffer_object.rs
+const uint MAX_LIGHTS = 16;
+const uint MAX_SHADOW_MAPS = 4;
+
+const uint DIRECTIONAL_LIGHT = 0;
+const uint POINT_LIGHT = 1;
+const uint SPOT_LIGHT = 2;
+
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    mat4 shadowMatrices[MAX_SHADOW_MAPS];
+    vec4 cameraPosition;
+    vec4 ambientColor;
+    uint lightCount;
+    uint shadowPcfRadius;
+    Light lights[MAX_LIGHTS];
+} ubo;
+
+// One layer per shadow casting light, rendered by the shadow map pass
+layout(set = 0, binding = 1) uniform sampler2DArrayShadow shadowMaps;
+
+// Has to match the values in material_uniform.rs
+const uint BLINN_PHONG = 0;
+const uint METALLIC_ROUGHNESS = 1;
+
+layout(set = 1, binding = 0) uniform MaterialUniform {
+    vec4 baseColorFactor;
+    vec4 emissiveFactor;
+    // w is the shininess
+    vec4 specularFactor;
+    float metallicFactor;
+    float roughnessFactor;
+    float normalScale;
+    float occlusionStrength;
+    uint shadingModel;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D baseColorTexture;
+// Roughness is in the green channel and metallic in the blue channel
+layout(set = 1, binding = 2) uniform sampler2D metallicRoughnessTexture;
+layout(set = 1, binding = 3) uniform sampler2D normalTexture;
+layout(set = 1, binding = 4) uniform sampler2D occlusionTexture;
+layout(set = 1, binding = 5) uniform sampler2D emissiveTexture;
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragTextureCoordinate;
+layout(location = 2) in vec3 fragNormal;
+layout(location = 3) in vec3 fragPosition;
 
 layout (location = 0) out vec4 outColor;
 
+const float PI = 3.14159265359;
+
+struct Surface {
+    vec3 albedo;
+    vec3 normal;
+    float metallic;
+    float roughness;
+};
+
+// Reaches 0 at the range of the light without the sudden cut a plain inverse square would have
+float attenuation(float distance, float range) {
+    float falloff = clamp(1. - pow(distance / range, 4.), 0., 1.);
+    return falloff * falloff / max(distance * distance, 0.0001);
+}
+
+// Direction towards the light and the intensity of the light at the fragment
+vec4 incomingLight(Light light) {
+    uint lightType = uint(light.position.w);
+
+    if (lightType == DIRECTIONAL_LIGHT) {
+        return vec4(-normalize(light.direction.xyz), light.color.w);
+    }
+
+    vec3 offset = light.position.xyz - fragPosition;
+    float distance = length(offset);
+    vec3 toLight = offset / distance;
+    float intensity = light.color.w * attenuation(distance, light.direction.w);
+
+    if (lightType == SPOT_LIGHT) {
+        float angleCos = dot(-toLight, normalize(light.direction.xyz));
+        intensity *= smoothstep(light.cone.y, light.cone.x, angleCos);
+    }
+    return vec4(toLight, intensity);
+}
+
+vec3 blinnPhong(Surface surface, vec3 toLight, vec3 viewDirection) {
+    float diffuse = max(dot(surface.normal, toLight), 0.);
+    float specular = 0.;
+    if (diffuse > 0.) {
+        vec3 halfway = normalize(toLight + viewDirection);
+        specular = pow(max(dot(surface.normal, halfway), 0.), material.specularFactor.w);
+    }
+    return diffuse * surface.albedo + specular * material.specularFactor.rgb;
+}
+
+// Fraction of the light reaching the fragment, averaged over the neighbouring texels of the
+// shadow map to soften the edges
+float shadowFactor(Light light) {
+    if (light.cone.z < 0.) {
+        return 1.;
+    }
+    float layer = light.cone.z;
+
+    vec4 lightSpacePosition = ubo.shadowMatrices[uint(layer)] * vec4(fragPosition, 1.);
+    vec3 projected = lightSpacePosition.xyz / lightSpacePosition.w;
+    // Behind the far plane of the light
+    if (projected.z > 1.) {
+        return 1.;
+    }
+    vec2 coordinate = projected.xy * 0.5 + 0.5;
+
+    vec2 texelSize = 1. / vec2(textureSize(shadowMaps, 0).xy);
+    int radius = int(ubo.shadowPcfRadius);
+    float lit = 0.;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 offset = vec2(x, y) * texelSize;
+            lit += texture(shadowMaps, vec4(coordinate + offset, layer, projected.z));
+        }
+    }
+    float sampleCount = float((2 * radius + 1) * (2 * radius + 1));
+    return lit / sampleCount;
+}
+
+// GGX / Trowbridge-Reitz normal distribution
+float distributionGGX(float normalDotHalfway, float roughness) {
+    float alpha = roughness * roughness;
+    float alpha2 = alpha * alpha;
+    float denominator = normalDotHalfway * normalDotHalfway * (alpha2 - 1.) + 1.;
+    return alpha2 / max(PI * denominator * denominator, 0.0001);
+}
+
+float geometrySchlickGGX(float normalDotDirection, float roughness) {
+    float k = (roughness + 1.) * (roughness + 1.) / 8.;
+    return normalDotDirection / (normalDotDirection * (1. - k) + k);
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 f0) {
+    return f0 + (1. - f0) * pow(clamp(1. - cosTheta, 0., 1.), 5.);
+}
+
+// Cook-Torrance specular and Lambertian diffuse
+vec3 metallicRoughness(Surface surface, vec3 toLight, vec3 viewDirection) {
+    float normalDotLight = max(dot(surface.normal, toLight), 0.);
+    if (normalDotLight <= 0.) {
+        return vec3(0.);
+    }
+    float normalDotView = max(dot(surface.normal, viewDirection), 0.0001);
+    vec3 halfway = normalize(toLight + viewDirection);
+
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 fresnel = fresnelSchlick(max(dot(halfway, viewDirection), 0.), f0);
+    float distribution = distributionGGX(max(dot(surface.normal, halfway), 0.), surface.roughness);
+    float geometry = geometrySchlickGGX(normalDotView, surface.roughness)
+        * geometrySchlickGGX(normalDotLight, surface.roughness);
+
+    vec3 specular = distribution * geometry * fresnel / (4. * normalDotView * normalDotLight);
+    vec3 diffuse = (1. - fresnel) * (1. - surface.metallic) * surface.albedo / PI;
+    return (diffuse + specular) * normalDotLight;
+}
+
+// The vertices don't have tangents, the tangent frame is built from the screen space derivatives
+vec3 perturbNormal(vec3 normal) {
+    vec3 sampled = texture(normalTexture, fragTextureCoordinate).xyz * 2. - 1.;
+    sampled.xy *= material.normalScale;
+
+    vec3 positionDx = dFdx(fragPosition);
+    vec3 positionDy = dFdy(fragPosition);
+    vec2 coordinateDx = dFdx(fragTextureCoordinate);
+    vec2 coordinateDy = dFdy(fragTextureCoordinate);
+
+    vec3 tangent = positionDx * coordinateDy.t - positionDy * coordinateDx.t;
+    vec3 bitangent = positionDy * coordinateDx.s - positionDx * coordinateDy.s;
+    float scale = max(dot(tangent, tangent), dot(bitangent, bitangent));
+    if (scale < 1e-12) {
+        return normal;
+    }
+    mat3 tangentFrame = mat3(tangent * inversesqrt(scale), bitangent * inversesqrt(scale), normal);
+    return normalize(tangentFrame * sampled);
+}
+
 void main() {
-    outColor = texture(textureSampler, fragTextureCoordinate);
+    vec4 baseColor = texture(baseColorTexture, fragTextureCoordinate) * material.baseColorFactor;
+    vec4 metallicRoughnessSample = texture(metallicRoughnessTexture, fragTextureCoordinate);
+    float occlusion = 1. + material.occlusionStrength
+        * (texture(occlusionTexture, fragTextureCoordinate).r - 1.);
+
+    Surface surface;
+    surface.albedo = baseColor.rgb * fragColor;
+    surface.normal = perturbNormal(normalize(fragNormal));
+    surface.metallic = clamp(material.metallicFactor * metallicRoughnessSample.b, 0., 1.);
+    // A roughness of 0 would make the highlights infinitely small
+    surface.roughness = clamp(material.roughnessFactor * metallicRoughnessSample.g, 0.04, 1.);
+
+    vec3 viewDirection = normalize(ubo.cameraPosition.xyz - fragPosition);
+
+    vec3 color = ubo.ambientColor.rgb * surface.albedo * occlusion;
+    for (uint i = 0; i < min(ubo.lightCount, MAX_LIGHTS); i++) {
+        Light light = ubo.lights[i];
+        vec4 incoming = incomingLight(light);
+        vec3 contribution = material.shadingModel == BLINN_PHONG
+            ? blinnPhong(surface, incoming.xyz, viewDirection)
+            : metallicRoughness(surface, incoming.xyz, viewDirection);
+        color += light.color.rgb * incoming.w * shadowFactor(light) * contribution;
+    }
+    color += material.emissiveFactor.rgb * texture(emissiveTexture, fragTextureCoordinate).rgb;
+
+    outColor = vec4(color, baseColor.a);
 }
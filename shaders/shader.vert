@@ -1,20 +1,31 @@
 #version 450
 
-layout(binding = 0) uniform UniformBufferObject {
-    mat4 model;
+// Only the beginning of the buffer is declared, the lights are used by the fragment shader
+layout(set = 0, binding = 0) uniform UniformBufferObject {
     mat4 view;
     mat4 proj;
 } ubo;
 
+layout(push_constant) uniform ObjectPushConstants {
+    mat4 model;
+} object;
+
 layout(location = 0) in vec3 inPosition;
 layout(location = 1) in vec3 inColor;
 layout(location = 2) in vec2 inTextureCoordinate;
+layout(location = 3) in vec3 inNormal;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragTextureCoordinate;
+layout(location = 2) out vec3 fragNormal;
+layout(location = 3) out vec3 fragPosition;
 
 void main() {
-    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(inPosition, 1.);
+    vec4 worldPosition = object.model * vec4(inPosition, 1.);
+    gl_Position = ubo.proj * ubo.view * worldPosition;
     fragColor = inColor;
     fragTextureCoordinate = inTextureCoordinate;
+    // World space normal, stays perpendicular to the surface with non uniform scaling
+    fragNormal = mat3(transpose(inverse(object.model))) * inNormal;
+    fragPosition = worldPosition.xyz;
 }
@@ -0,0 +1,22 @@
+#version 450
+
+// Has to match MAX_SHADOW_MAPS in uniform_buffer_object.rs
+const uint MAX_SHADOW_MAPS = 4;
+
+// Only the beginning of the buffer is declared, the lights are used by the fragment shader
+layout(set = 0, binding = 0) uniform UniformBufferObject {
+    mat4 view;
+    mat4 proj;
+    mat4 shadowMatrices[MAX_SHADOW_MAPS];
+} ubo;
+
+layout(push_constant) uniform ShadowPushConstants {
+    mat4 model;
+    uint shadowMapIndex;
+} object;
+
+layout(location = 0) in vec3 inPosition;
+
+void main() {
+    gl_Position = ubo.shadowMatrices[object.shadowMapIndex] * object.model * vec4(inPosition, 1.);
+}
@@ -1,22 +1,52 @@
+use rs42::Result;
 use winit::application::ApplicationHandler;
 use winit::event::WindowEvent;
-use winit::event_loop::ActiveEventLoop;
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::window::WindowId;
 
+use crate::config::Config;
 use crate::engine::Engine;
+use crate::game::Game;
+use crate::headless;
+use crate::vulkan_renderer::VulkanRenderer;
 
-#[derive(Default)]
-pub struct App {
+// Opens a window and runs the game until it is closed.
+// If the config has a headless output path, a single frame is rendered to it instead and only
+// the init hook of the game is called. With --list-gpus, nothing is rendered
+pub fn run(config: Config, mut game: impl Game) -> Result<()> {
+    if config.list_gpus {
+        print!("{}", VulkanRenderer::describe_physical_devices()?);
+        return Ok(());
+    }
+    if let Some(output_path) = config.headless_output_path.as_ref() {
+        let scene = game.init(&config)?;
+        return headless::render_to_file(output_path, &config, scene);
+    }
+
+    let event_loop = EventLoop::new()?;
+    let mut app = App::new(config, game);
+
+    event_loop.run_app(&mut app)?;
+    Ok(())
+}
+
+struct App<G: Game> {
+    config: Config,
+    game: G,
     engine: Option<Engine>,
 }
 
-impl ApplicationHandler for App {
+impl<G: Game> ApplicationHandler for App<G> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         if self.engine.is_some() || event_loop.exiting() {
             return;
         }
 
-        let engine = match Engine::new(event_loop) {
+        let engine = match self
+            .game
+            .init(&self.config)
+            .and_then(|scene| Engine::new(event_loop, &self.config, scene))
+        {
             Ok(engine) => engine,
             Err(err) => {
                 eprintln!("Failed to init Engine: {err}");
@@ -24,10 +54,24 @@ impl ApplicationHandler for App {
                 return;
             }
         };
-        engine.window().request_redraw();
         self.engine = Some(engine);
     }
 
+    // Requests the next frame once the frame rate cap allows it, the event loop sleeps until then
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(engine) = &self.engine else {
+            return;
+        };
+
+        match engine.next_frame_deadline() {
+            Some(deadline) => event_loop.set_control_flow(ControlFlow::WaitUntil(deadline)),
+            None => {
+                event_loop.set_control_flow(ControlFlow::Wait);
+                engine.window().request_redraw();
+            }
+        }
+    }
+
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
         let Some(engine) = &mut self.engine else {
             return;
@@ -38,15 +82,18 @@ impl ApplicationHandler for App {
                 self.exit(event_loop);
             }
             WindowEvent::RedrawRequested => {
-                if let Err(err) = engine.render_frame() {
+                if let Err(err) = render_frame(&mut self.game, engine) {
                     eprintln!("Failed to render frame: {err}");
                     self.exit(event_loop);
-                    return;
+                } else if engine.is_recording_finished() {
+                    self.exit(event_loop);
                 }
-                engine.window().request_redraw();
             }
             _ => {
-                if let Err(err) = engine.handle_event(&event) {
+                if let Err(err) = engine
+                    .handle_event(&event)
+                    .and_then(|()| self.game.handle_event(engine, &event))
+                {
                     eprintln!("Failed to handle event ({event:?}): {err}");
                     self.exit(event_loop);
 
@@ -61,11 +108,29 @@ impl ApplicationHandler for App {
     }
 }
 
-impl App {
+impl<G: Game> App<G> {
+    fn new(config: Config, game: G) -> Self {
+        Self {
+            config,
+            game,
+            engine: None,
+        }
+    }
+
     fn exit(&mut self, event_loop: &ActiveEventLoop) {
         event_loop.exit();
         if let Some(mut engine) = self.engine.take() {
+            self.game.shutdown(&mut engine);
             unsafe { engine.destroy() };
         }
     }
 }
+
+fn render_frame(game: &mut impl Game, engine: &mut Engine) -> Result<()> {
+    engine.start_frame();
+    while let Some(delta_time) = engine.next_update() {
+        game.update(engine, delta_time)?;
+    }
+    game.render(engine)?;
+    engine.render_frame()
+}
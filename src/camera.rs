@@ -0,0 +1,241 @@
+mod camera_input;
+mod fly_camera;
+mod orbit_camera;
+
+use camera_input::CameraInput;
+use fly_camera::FlyCamera;
+use linear_algebra::{Degree, Matrix};
+use orbit_camera::OrbitCamera;
+use winit::{
+    event::{ElementState, KeyEvent, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+type Mat4 = Matrix<f32, 4, 4>;
+
+const UP: [f32; 3] = [0., 0., 1.];
+const FIELD_OF_VIEW: f32 = 45.;
+const NEAR_PLANE: f32 = 0.1;
+const FAR_PLANE: f32 = 100.;
+
+const MODE_TOGGLE_KEY: KeyCode = KeyCode::Tab;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    // Dragging the mouse rotates around a target, the wheel zooms
+    Orbit,
+    // WASD moves, E and Q go up and down, dragging the mouse looks around
+    Fly,
+}
+
+pub struct Camera {
+    mode: CameraMode,
+    orbit: OrbitCamera,
+    fly: FlyCamera,
+    input: CameraInput,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        let orbit = OrbitCamera::looking_at([2., 2., 2.], [0., 0., 0.]);
+        Self {
+            mode: CameraMode::Orbit,
+            fly: FlyCamera::from_orbit(&orbit),
+            orbit,
+            input: CameraInput::default(),
+        }
+    }
+}
+
+impl Camera {
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(MODE_TOGGLE_KEY),
+                    state: ElementState::Pressed,
+                    repeat: false,
+                    ..
+                },
+            ..
+        } = event
+        {
+            self.toggle_mode();
+            return;
+        }
+        self.input.handle_event(event);
+    }
+
+    // Applies the input received since the last update
+    pub fn update(&mut self, delta_time_sec: f32) {
+        let cursor_delta = self.input.take_cursor_delta();
+        let scroll_delta = self.input.take_scroll_delta();
+
+        match self.mode {
+            CameraMode::Orbit => {
+                self.orbit.rotate(cursor_delta);
+                self.orbit.zoom(scroll_delta);
+            }
+            CameraMode::Fly => {
+                self.fly.rotate(cursor_delta);
+                self.fly.translate(
+                    self.input.movement(),
+                    self.input.is_sprinting(),
+                    delta_time_sec,
+                );
+            }
+        }
+    }
+
+    // The camera keeps looking at the same point when switching mode
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => {
+                self.fly = FlyCamera::from_orbit(&self.orbit);
+                CameraMode::Fly
+            }
+            CameraMode::Fly => {
+                self.orbit = OrbitCamera::from_fly(&self.fly, self.orbit.distance());
+                CameraMode::Orbit
+            }
+        };
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        match self.mode {
+            CameraMode::Orbit => self.orbit.eye(),
+            CameraMode::Fly => self.fly.position(),
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        let target = match self.mode {
+            CameraMode::Orbit => self.orbit.target(),
+            CameraMode::Fly => self.fly.target(),
+        };
+        Matrix::look_at(self.position(), target, UP)
+    }
+
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        Matrix::perspective_opengl(
+            Degree::from(FIELD_OF_VIEW),
+            aspect_ratio,
+            NEAR_PLANE,
+            FAR_PLANE,
+        )
+    }
+}
+
+// Unit vector pointing towards yaw (around the up axis) and pitch (above the horizon)
+fn direction(yaw: f32, pitch: f32) -> [f32; 3] {
+    [
+        pitch.cos() * yaw.cos(),
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+    ]
+}
+
+// Inverse of direction(), the vector doesn't need to be normalized
+fn yaw_and_pitch(vector: [f32; 3]) -> (f32, f32) {
+    let horizontal_length = (vector[0] * vector[0] + vector[1] * vector[1]).sqrt();
+    (
+        vector[1].atan2(vector[0]),
+        vector[2].atan2(horizontal_length),
+    )
+}
+
+// Keeps the view from flipping when looking straight up or down
+fn clamp_pitch(pitch: f32) -> f32 {
+    let max_pitch = 89_f32.to_radians();
+    pitch.clamp(-max_pitch, max_pitch)
+}
+
+fn add_scaled(a: [f32; 3], b: [f32; 3], scale: f32) -> [f32; 3] {
+    [
+        a[0] + b[0] * scale,
+        a[1] + b[1] * scale,
+        a[2] + b[2] * scale,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    pub fn assert_approximately_equal(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 1e-4, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn yaw_and_pitch_is_the_inverse_of_direction() {
+        for yaw in [-PI + 0.1, -1., 0., 0.5, 2., PI - 0.1] {
+            for pitch in [-1.5, -0.7, 0., 0.3, 1.5] {
+                let (result_yaw, result_pitch) = yaw_and_pitch(direction(yaw, pitch));
+                assert!((result_yaw - yaw).abs() < 1e-4, "{result_yaw} != {yaw}");
+                assert!(
+                    (result_pitch - pitch).abs() < 1e-4,
+                    "{result_pitch} != {pitch}"
+                );
+
+                let scaled = direction(yaw, pitch).map(|e| e * 3.);
+                assert_approximately_equal(
+                    direction(yaw_and_pitch(scaled).0, yaw_and_pitch(scaled).1),
+                    direction(yaw, pitch),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn toggling_mode_keeps_eye_and_target() {
+        let mut camera = Camera::default();
+        let eye = camera.orbit.eye();
+        let target = camera.orbit.target();
+
+        camera.toggle_mode();
+        assert_eq!(camera.mode, CameraMode::Fly);
+        assert_approximately_equal(camera.fly.position(), eye);
+        assert_approximately_equal(
+            add_scaled(
+                camera.fly.position(),
+                camera.fly.forward(),
+                camera.orbit.distance(),
+            ),
+            target,
+        );
+
+        camera.toggle_mode();
+        assert_eq!(camera.mode, CameraMode::Orbit);
+        assert_approximately_equal(camera.orbit.eye(), eye);
+        assert_approximately_equal(camera.orbit.target(), target);
+    }
+
+    #[test]
+    fn orbiting_around_the_fly_camera_target_keeps_its_position() {
+        let mut camera = Camera::default();
+        camera.toggle_mode();
+        camera.fly.rotate([40., -25.]);
+        camera.fly.translate([1., 0.5, 0.], false, 0.25);
+        let position = camera.fly.position();
+        let forward = camera.fly.forward();
+
+        camera.toggle_mode();
+        assert_approximately_equal(camera.orbit.eye(), position);
+        assert_approximately_equal(
+            camera.orbit.target(),
+            add_scaled(position, forward, camera.orbit.distance()),
+        );
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let max_pitch = 89_f32.to_radians();
+        assert_eq!(clamp_pitch(PI), max_pitch);
+        assert_eq!(clamp_pitch(-PI), -max_pitch);
+        assert_eq!(clamp_pitch(0.5), 0.5);
+    }
+}
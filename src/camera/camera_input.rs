@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+// Pixel deltas are converted to lines with this, it roughly matches one wheel notch
+const PIXELS_PER_SCROLL_LINE: f32 = 50.;
+
+// Accumulates the keyboard and mouse state between two camera updates
+#[derive(Default)]
+pub struct CameraInput {
+    pressed_keys: HashSet<KeyCode>,
+    is_dragging: bool,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    cursor_delta: [f32; 2],
+    scroll_delta: f32,
+}
+
+impl CameraInput {
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => _ = self.pressed_keys.insert(*key),
+                ElementState::Released => _ = self.pressed_keys.remove(key),
+            },
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left | MouseButton::Right,
+                ..
+            } => self.is_dragging = *state == ElementState::Pressed,
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some(previous_position)) = (self.is_dragging, self.cursor_position) {
+                    self.cursor_delta[0] += (position.x - previous_position.x) as f32;
+                    self.cursor_delta[1] += (position.y - previous_position.y) as f32;
+                }
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor_position = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / PIXELS_PER_SCROLL_LINE
+                    }
+                }
+            }
+            // Release events are not received while the window is unfocused
+            WindowEvent::Focused(false) => {
+                self.pressed_keys.clear();
+                self.is_dragging = false;
+            }
+            _ => {}
+        }
+    }
+
+    // In pixels, only accumulated while a mouse button is held
+    pub fn take_cursor_delta(&mut self) -> [f32; 2] {
+        std::mem::take(&mut self.cursor_delta)
+    }
+
+    // In wheel lines, positive when scrolling up
+    pub fn take_scroll_delta(&mut self) -> f32 {
+        std::mem::take(&mut self.scroll_delta)
+    }
+
+    // Forward, right and up, each between -1 and 1
+    pub fn movement(&self) -> [f32; 3] {
+        let axis = |positive, negative| {
+            self.is_pressed(positive) as i32 as f32 - self.is_pressed(negative) as i32 as f32
+        };
+        [
+            axis(KeyCode::KeyW, KeyCode::KeyS),
+            axis(KeyCode::KeyD, KeyCode::KeyA),
+            axis(KeyCode::KeyE, KeyCode::KeyQ),
+        ]
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.is_pressed(KeyCode::ShiftLeft) || self.is_pressed(KeyCode::ShiftRight)
+    }
+
+    fn is_pressed(&self, key: KeyCode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+}
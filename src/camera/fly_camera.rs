@@ -0,0 +1,102 @@
+use std::f32::consts::PI;
+
+use super::{add_scaled, clamp_pitch, direction, orbit_camera::OrbitCamera, UP};
+
+const RADIANS_PER_PIXEL: f32 = 0.003;
+// In units per second
+const SPEED: f32 = 2.;
+const SPRINT_SPEED: f32 = 8.;
+
+pub struct FlyCamera {
+    position: [f32; 3],
+    // Angles of the view direction, in radians
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FlyCamera {
+    // Takes the place of the orbit camera, looking at its target
+    pub fn from_orbit(orbit: &OrbitCamera) -> Self {
+        Self {
+            position: orbit.eye(),
+            yaw: orbit.yaw() + PI,
+            pitch: -orbit.pitch(),
+        }
+    }
+
+    // Dragging the mouse drags the view, like grabbing the scene
+    pub fn rotate(&mut self, cursor_delta: [f32; 2]) {
+        self.yaw += cursor_delta[0] * RADIANS_PER_PIXEL;
+        self.pitch = clamp_pitch(self.pitch - cursor_delta[1] * RADIANS_PER_PIXEL);
+    }
+
+    // movement is forward, right and up relative to the view, up always being the world up
+    pub fn translate(&mut self, movement: [f32; 3], is_sprinting: bool, delta_time_sec: f32) {
+        let distance = if is_sprinting { SPRINT_SPEED } else { SPEED } * delta_time_sec;
+        let forward = self.forward();
+        let right = direction(self.yaw - PI / 2., 0.);
+
+        self.position = add_scaled(self.position, forward, movement[0] * distance);
+        self.position = add_scaled(self.position, right, movement[1] * distance);
+        self.position = add_scaled(self.position, UP, movement[2] * distance);
+    }
+
+    pub fn forward(&self) -> [f32; 3] {
+        direction(self.yaw, self.pitch)
+    }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    pub fn target(&self) -> [f32; 3] {
+        add_scaled(self.position, self.forward(), 1.)
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::camera::test::assert_approximately_equal;
+
+    use super::*;
+
+    #[test]
+    fn looks_at_the_orbit_target() {
+        let orbit = OrbitCamera::looking_at([2., 2., 2.], [0., 0., 0.]);
+        let fly = FlyCamera::from_orbit(&orbit);
+        assert_approximately_equal(fly.position(), [2., 2., 2.]);
+        assert_approximately_equal(fly.forward(), [-1. / 3_f32.sqrt(); 3]);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let max_pitch = 89_f32.to_radians();
+        let mut fly = FlyCamera::from_orbit(&OrbitCamera::looking_at([2., 2., 2.], [0., 0., 0.]));
+        fly.rotate([0., -100_000.]);
+        assert_eq!(fly.pitch(), max_pitch);
+        fly.rotate([0., 100_000.]);
+        assert_eq!(fly.pitch(), -max_pitch);
+    }
+
+    #[test]
+    fn translation_is_relative_to_the_view() {
+        let mut fly = FlyCamera::from_orbit(&OrbitCamera::looking_at([0., 0., 0.], [1., 0., 0.]));
+
+        // Looking towards +x, right is -y
+        fly.translate([1., 1., 1.], false, 0.5);
+        assert_approximately_equal(fly.position(), [SPEED * 0.5, -SPEED * 0.5, SPEED * 0.5]);
+        fly.translate([-1., 0., 0.], true, 0.5);
+        assert_approximately_equal(
+            fly.position(),
+            [(SPEED - SPRINT_SPEED) * 0.5, -SPEED * 0.5, SPEED * 0.5],
+        );
+    }
+}
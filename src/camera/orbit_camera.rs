@@ -0,0 +1,120 @@
+use std::f32::consts::PI;
+
+use super::{add_scaled, clamp_pitch, direction, fly_camera::FlyCamera, yaw_and_pitch};
+
+const RADIANS_PER_PIXEL: f32 = 0.005;
+// Each wheel line moves the camera this fraction closer to the target
+const ZOOM_PER_LINE: f32 = 0.1;
+const MIN_DISTANCE: f32 = 0.1;
+const MAX_DISTANCE: f32 = 50.;
+
+pub struct OrbitCamera {
+    target: [f32; 3],
+    distance: f32,
+    // Angles of the eye as seen from the target, in radians
+    yaw: f32,
+    pitch: f32,
+}
+
+impl OrbitCamera {
+    pub fn looking_at(eye: [f32; 3], target: [f32; 3]) -> Self {
+        let offset = [eye[0] - target[0], eye[1] - target[1], eye[2] - target[2]];
+        let (yaw, pitch) = yaw_and_pitch(offset);
+        Self {
+            target,
+            distance: (offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2])
+                .sqrt()
+                .clamp(MIN_DISTANCE, MAX_DISTANCE),
+            yaw,
+            pitch: clamp_pitch(pitch),
+        }
+    }
+
+    // Orbits around the point the fly camera is looking at, distance units in front of it
+    pub fn from_fly(fly: &FlyCamera, distance: f32) -> Self {
+        Self {
+            target: add_scaled(fly.position(), fly.forward(), distance),
+            distance,
+            yaw: fly.yaw() + PI,
+            pitch: -fly.pitch(),
+        }
+    }
+
+    // Dragging right moves the camera right around the target, dragging up moves it up
+    pub fn rotate(&mut self, cursor_delta: [f32; 2]) {
+        self.yaw -= cursor_delta[0] * RADIANS_PER_PIXEL;
+        self.pitch = clamp_pitch(self.pitch + cursor_delta[1] * RADIANS_PER_PIXEL);
+    }
+
+    pub fn zoom(&mut self, scroll_delta: f32) {
+        self.distance = (self.distance * (1. - ZOOM_PER_LINE).powf(scroll_delta))
+            .clamp(MIN_DISTANCE, MAX_DISTANCE);
+    }
+
+    pub fn eye(&self) -> [f32; 3] {
+        add_scaled(self.target, direction(self.yaw, self.pitch), self.distance)
+    }
+
+    pub fn target(&self) -> [f32; 3] {
+        self.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> f32 {
+        self.pitch
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::camera::test::assert_approximately_equal;
+
+    use super::*;
+
+    #[test]
+    fn looking_at_keeps_eye_and_target() {
+        let camera = OrbitCamera::looking_at([1., -2., 3.], [0.5, 0.5, 0.5]);
+        assert_approximately_equal(camera.eye(), [1., -2., 3.]);
+        assert_approximately_equal(camera.target(), [0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn pitch_is_clamped() {
+        let max_pitch = 89_f32.to_radians();
+
+        let camera = OrbitCamera::looking_at([0., 0., 5.], [0., 0., 0.]);
+        assert_eq!(camera.pitch(), max_pitch);
+
+        let mut camera = OrbitCamera::looking_at([2., 2., 2.], [0., 0., 0.]);
+        camera.rotate([0., 100_000.]);
+        assert_eq!(camera.pitch(), max_pitch);
+        camera.rotate([0., -100_000.]);
+        assert_eq!(camera.pitch(), -max_pitch);
+    }
+
+    #[test]
+    fn zoom_stays_within_bounds() {
+        let mut camera = OrbitCamera::looking_at([2., 0., 0.], [0., 0., 0.]);
+        camera.zoom(1.);
+        assert!((camera.distance() - 1.8).abs() < 1e-5);
+        camera.zoom(-1.);
+        assert!((camera.distance() - 2.).abs() < 1e-5);
+
+        camera.zoom(1000.);
+        assert_eq!(camera.distance(), MIN_DISTANCE);
+        camera.zoom(-1000.);
+        assert_eq!(camera.distance(), MAX_DISTANCE);
+
+        let camera = OrbitCamera::looking_at([1000., 0., 0.], [0., 0., 0.]);
+        assert_eq!(camera.distance(), MAX_DISTANCE);
+        let camera = OrbitCamera::looking_at([0., 0., 0.], [0., 0., 0.]);
+        assert_eq!(camera.distance(), MIN_DISTANCE);
+    }
+}
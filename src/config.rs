@@ -0,0 +1,566 @@
+mod errors;
+
+use std::{env, fmt, fs};
+
+use ash::vk;
+use rs42::Result;
+
+use errors::{
+    FailedToReadConfigFile, InvalidConfigFileLine, InvalidExtent, InvalidOptionValue,
+    MissingOptionValue, UnknownOption,
+};
+
+// Used like --gpu if the option isn't given
+pub const GPU_ENV_VAR: &str = "HITCHHIKERS_GPU";
+
+// Every fragment takes (2 * radius + 1)^2 samples of each shadow map
+pub const MAX_SHADOW_PCF_RADIUS: u32 = 4;
+
+// Higher rates would make the simulation steps and the recorded frames so short that they round
+// down to no time at all
+pub const MAX_UPDATE_RATE: u32 = 1000;
+pub const MAX_RECORDING_FRAME_RATE: u32 = 1000;
+
+pub const DEFAULT_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 800,
+    height: 600,
+};
+
+const HELP: &str = "\
+Usage: hitchhikers-engine [options]
+
+Options:
+    --config <path>           Reads options from a file, one \"<option> = <value>\" per line
+                              without the leading \"--\", command line options take precedence
+    --model <path>            OBJ or glTF model to render
+    --scene <path>            Scene file listing the models to render and their transforms,
+                              replaces --model
+    --texture <path>          PPM texture used by parts of the model without material
+    --vertex-shader <path>    Compiled SPIR-V vertex shader
+    --fragment-shader <path>  Compiled SPIR-V fragment shader
+    --shadow-vertex-shader <path>
+                              Compiled SPIR-V vertex shader of the shadow map pass
+    --shadow-map-size <pixels>
+                              Resolution of the shadow maps
+    --shadow-bias-constant <value>
+                              Depth bias added to every fragment of the shadow maps
+    --shadow-bias-slope <value>
+                              Depth bias scaled by the slope of the fragments of the shadow maps
+    --shadow-pcf-radius <texels>
+                              Radius of the shadow map filter, 0 gives hard edges, at most 4
+    --shadow-distance <distance>
+                              Distance from the camera covered by directional light shadows
+    --pipeline-cache <path>   Directory where compiled pipelines are kept between runs
+    --update-rate <hz>        Number of simulation updates per second, at most 1000
+    --max-frame-rate <fps>    Frames per second above which the engine waits instead of
+                              rendering, 0 renders as fast as possible
+    --record <directory>      Writes the first frames to numbered PPM files in the directory,
+                              then closes the window. The simulation advances by exactly one
+                              frame of the recording frame rate per frame so that recordings
+                              are reproducible
+    --record-frames <count>   Number of frames to record
+    --record-frame-rate <fps> Frame rate the recording is simulated at, at most 1000
+    --size <width>x<height>   Size of the window, or of the image in headless mode
+    --gpu <device>            Forces the physical device, given by index:<n> with its index in
+                              --list-gpus, by its type (discrete, integrated, virtual, cpu,
+                              other) or by a part of its name, name:<part> if the part is also
+                              a type. Can also be set with the HITCHHIKERS_GPU environment
+                              variable
+    --list-gpus               Lists the physical devices and why they can't be used
+    --headless <path>         Renders a single frame to a PPM file without opening a window
+    --help                    Prints this message";
+
+// Paths of the files loaded by the renderer
+#[derive(Debug, Clone)]
+pub struct AssetPaths {
+    pub model: String,
+    pub texture: String,
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+    pub shadow_vertex_shader: String,
+    pub pipeline_cache: String,
+}
+
+// Directional and spot lights cast shadows, point lights don't
+#[derive(Debug, Clone)]
+pub struct ShadowSettings {
+    pub map_size: u32,
+    pub depth_bias_constant: f32,
+    pub depth_bias_slope: f32,
+    pub pcf_radius: u32,
+    pub distance: f32,
+}
+
+// The simulation runs at a fixed rate, independently of the frame rate
+#[derive(Debug, Clone)]
+pub struct TimingSettings {
+    pub update_rate: u32,
+    // 0 if the frame rate isn't capped
+    pub max_frame_rate: u32,
+}
+
+// Only used with a window
+#[derive(Debug, Clone)]
+pub struct RecordingSettings {
+    // None if nothing is recorded
+    pub directory: Option<String>,
+    pub frame_count: u32,
+    pub frame_rate: u32,
+}
+
+// Restricts the physical devices the renderer can pick from, the best one among them is used
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceSelector {
+    Index(usize),
+    Type(vk::PhysicalDeviceType),
+    // Case insensitive part of the name
+    Name(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub assets: AssetPaths,
+    pub scene: Option<String>,
+    pub shadows: ShadowSettings,
+    pub timing: TimingSettings,
+    pub recording: RecordingSettings,
+    pub extent: vk::Extent2D,
+    pub headless_output_path: Option<String>,
+    pub gpu: Option<DeviceSelector>,
+    // Nothing is rendered if set, the physical devices are only listed
+    pub list_gpus: bool,
+}
+
+impl Default for AssetPaths {
+    fn default() -> Self {
+        Self {
+            model: "assets/obj/viking_room.obj".to_owned(),
+            texture: "assets/textures/viking_room.ppm".to_owned(),
+            vertex_shader: "./shaders/build/shader.vert.spv".to_owned(),
+            fragment_shader: "./shaders/build/shader.frag.spv".to_owned(),
+            shadow_vertex_shader: "./shaders/build/shadow.vert.spv".to_owned(),
+            pipeline_cache: "./pipeline_cache".to_owned(),
+        }
+    }
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            map_size: 2048,
+            depth_bias_constant: 1.25,
+            depth_bias_slope: 1.75,
+            pcf_radius: 1,
+            distance: 10.,
+        }
+    }
+}
+
+impl Default for TimingSettings {
+    fn default() -> Self {
+        Self {
+            update_rate: 60,
+            max_frame_rate: 144,
+        }
+    }
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            frame_count: 120,
+            frame_rate: 30,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            assets: AssetPaths::default(),
+            scene: None,
+            shadows: ShadowSettings::default(),
+            timing: TimingSettings::default(),
+            recording: RecordingSettings::default(),
+            extent: DEFAULT_EXTENT,
+            headless_output_path: None,
+            gpu: None,
+            list_gpus: false,
+        }
+    }
+}
+
+impl Config {
+    // Returns None if --help was given
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Option<Self>> {
+        Self::from_args_and_gpu_env_var(args, env::var(GPU_ENV_VAR).ok())
+    }
+
+    // Precedence from lowest to highest: the environment variable, the config file, the command line
+    fn from_args_and_gpu_env_var(
+        args: impl IntoIterator<Item = String>,
+        gpu_env_var: Option<String>,
+    ) -> Result<Option<Self>> {
+        let mut options = Vec::new();
+        let mut config_file_path = None;
+        let mut list_gpus = false;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--help" {
+                println!("{HELP}");
+                return Ok(None);
+            }
+            if arg == "--list-gpus" {
+                list_gpus = true;
+                continue;
+            }
+            let Some(option) = arg.strip_prefix("--") else {
+                return Err(UnknownOption::new(arg).into());
+            };
+            let value = args
+                .next()
+                .ok_or_else(|| MissingOptionValue::new(arg.clone()))?;
+
+            if option == "config" {
+                config_file_path = Some(value);
+            } else {
+                options.push((option.to_owned(), value));
+            }
+        }
+
+        let mut config = Self {
+            list_gpus,
+            ..Self::default()
+        };
+        if let Some(gpu) = gpu_env_var {
+            config.set("gpu", gpu)?;
+        }
+        if let Some(path) = config_file_path {
+            for (option, value) in parse_config_file(&path)? {
+                config.set(&option, value)?;
+            }
+        }
+        for (option, value) in options {
+            config.set(&option, value)?;
+        }
+        Ok(Some(config))
+    }
+
+    fn set(&mut self, option: &str, value: String) -> Result<()> {
+        match option {
+            "model" => self.assets.model = value,
+            "scene" => self.scene = Some(value),
+            "texture" => self.assets.texture = value,
+            "vertex-shader" => self.assets.vertex_shader = value,
+            "fragment-shader" => self.assets.fragment_shader = value,
+            "shadow-vertex-shader" => self.assets.shadow_vertex_shader = value,
+            "shadow-map-size" => {
+                self.shadows.map_size = parse_value(option, &value, |size| *size > 0)?
+            }
+            "shadow-bias-constant" => {
+                self.shadows.depth_bias_constant = parse_value(option, &value, |_| true)?
+            }
+            "shadow-bias-slope" => {
+                self.shadows.depth_bias_slope = parse_value(option, &value, |_| true)?
+            }
+            "shadow-pcf-radius" => {
+                self.shadows.pcf_radius =
+                    parse_value(option, &value, |radius| *radius <= MAX_SHADOW_PCF_RADIUS)?
+            }
+            "shadow-distance" => {
+                self.shadows.distance = parse_value(option, &value, |distance| *distance > 0.)?
+            }
+            "pipeline-cache" => self.assets.pipeline_cache = value,
+            "update-rate" => {
+                self.timing.update_rate =
+                    parse_value(option, &value, |rate| (1..=MAX_UPDATE_RATE).contains(rate))?
+            }
+            "max-frame-rate" => self.timing.max_frame_rate = parse_value(option, &value, |_| true)?,
+            "record" => self.recording.directory = Some(value),
+            "record-frames" => {
+                self.recording.frame_count = parse_value(option, &value, |count| *count > 0)?
+            }
+            "record-frame-rate" => {
+                self.recording.frame_rate = parse_value(option, &value, |rate| {
+                    (1..=MAX_RECORDING_FRAME_RATE).contains(rate)
+                })?
+            }
+            "size" => self.extent = parse_extent(&value)?,
+            "headless" => self.headless_output_path = Some(value),
+            "gpu" => self.gpu = Some(parse_value(option, &value, |_| true)?),
+            _ => return Err(UnknownOption::new(format!("--{option}")).into()),
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for DeviceSelector {
+    type Err = ();
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        if let Some(index) = selector.strip_prefix("index:") {
+            return index.parse().map(Self::Index).map_err(|_| ());
+        }
+        if let Some(name) = selector.strip_prefix("name:") {
+            return Self::from_name(name);
+        }
+        let device_type = match selector.to_lowercase().as_str() {
+            "discrete" => vk::PhysicalDeviceType::DISCRETE_GPU,
+            "integrated" => vk::PhysicalDeviceType::INTEGRATED_GPU,
+            "virtual" => vk::PhysicalDeviceType::VIRTUAL_GPU,
+            "cpu" => vk::PhysicalDeviceType::CPU,
+            "other" => vk::PhysicalDeviceType::OTHER,
+            _ => return Self::from_name(selector),
+        };
+        Ok(Self::Type(device_type))
+    }
+}
+
+impl DeviceSelector {
+    // Numbers are names too, so that "--gpu 3090" picks the device with 3090 in its name
+    fn from_name(name: &str) -> Result<Self, ()> {
+        if name.is_empty() {
+            return Err(());
+        }
+        Ok(Self::Name(name.to_owned()))
+    }
+}
+
+impl fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "device {index}"),
+            Self::Type(device_type) => write!(f, "type {device_type:?}"),
+            Self::Name(name) => write!(f, "name \"{name}\""),
+        }
+    }
+}
+
+fn parse_config_file(path: &str) -> Result<Vec<(String, String)>> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| FailedToReadConfigFile::new(path.to_owned(), err))?;
+
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let (option, value) = line
+                .split_once('=')
+                .map(|(option, value)| (option.trim(), value.trim()))
+                .filter(|(option, value)| !option.is_empty() && !value.is_empty())
+                .ok_or_else(|| {
+                    InvalidConfigFileLine::new(path.to_owned(), line_number, line.to_owned())
+                })?;
+            Ok((option.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn parse_value<T: std::str::FromStr>(
+    option: &str,
+    value: &str,
+    is_valid: impl FnOnce(&T) -> bool,
+) -> Result<T, InvalidOptionValue> {
+    value
+        .parse()
+        .ok()
+        .filter(is_valid)
+        .ok_or_else(|| InvalidOptionValue::new(format!("--{option}"), value.to_owned()))
+}
+
+pub fn parse_extent(extent: &str) -> Result<vk::Extent2D, InvalidExtent> {
+    let invalid_extent = || InvalidExtent::new(extent.to_string());
+
+    let (width, height) = extent.split_once('x').ok_or_else(invalid_extent)?;
+    let width = width.parse::<u32>().map_err(|_| invalid_extent())?;
+    let height = height.parse::<u32>().map_err(|_| invalid_extent())?;
+    if width == 0 || height == 0 {
+        return Err(invalid_extent());
+    }
+
+    Ok(vk::Extent2D { width, height })
+}
+
+#[cfg(test)]
+mod test {
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn extents() {
+        let extent = parse_extent("1920x1080").unwrap();
+        assert_eq!((extent.width, extent.height), (1920, 1080));
+
+        for extent in [
+            "",
+            "1920",
+            "1920x",
+            "x1080",
+            "0x1080",
+            "1920x0",
+            "-1x1",
+            "1920X1080",
+        ] {
+            assert!(parse_extent(extent).is_err(), "{extent}");
+        }
+    }
+
+    #[test]
+    fn set_options() {
+        let mut config = Config::default();
+        config.set("model", "cube.obj".to_owned()).unwrap();
+        config.set("size", "640x480".to_owned()).unwrap();
+        config.set("shadow-map-size", "1024".to_owned()).unwrap();
+        assert_eq!(config.assets.model, "cube.obj");
+        assert_eq!(config.extent.width, 640);
+        assert_eq!(config.shadows.map_size, 1024);
+
+        let err = config.set("shadow-map-size", "0".to_owned()).unwrap_err();
+        assert!(err.downcast_ref::<InvalidOptionValue>().is_some());
+        let err = config.set("shadow-map-size", "big".to_owned()).unwrap_err();
+        assert!(err.downcast_ref::<InvalidOptionValue>().is_some());
+        config.set("shadow-pcf-radius", "4".to_owned()).unwrap();
+        let err = config.set("shadow-pcf-radius", "5".to_owned()).unwrap_err();
+        assert!(err.downcast_ref::<InvalidOptionValue>().is_some());
+        assert_eq!(config.shadows.pcf_radius, MAX_SHADOW_PCF_RADIUS);
+        config.set("update-rate", "1000".to_owned()).unwrap();
+        for rate in ["0", "1001", "2000000000"] {
+            let err = config.set("update-rate", rate.to_owned()).unwrap_err();
+            assert!(err.downcast_ref::<InvalidOptionValue>().is_some());
+            let err = config
+                .set("record-frame-rate", rate.to_owned())
+                .unwrap_err();
+            assert!(err.downcast_ref::<InvalidOptionValue>().is_some());
+        }
+        assert_eq!(config.timing.update_rate, MAX_UPDATE_RATE);
+        let err = config.set("size", "640".to_owned()).unwrap_err();
+        assert!(err.downcast_ref::<InvalidExtent>().is_some());
+        let err = config.set("colour", "red".to_owned()).unwrap_err();
+        assert!(err.downcast_ref::<UnknownOption>().is_some());
+        assert_eq!(config.extent.width, 640);
+    }
+
+    #[test]
+    fn invalid_args() {
+        let err = Config::from_args_and_gpu_env_var(args(&["model.obj"]), None).unwrap_err();
+        assert!(err.downcast_ref::<UnknownOption>().is_some());
+
+        let err = Config::from_args_and_gpu_env_var(args(&["--model"]), None).unwrap_err();
+        assert!(err.downcast_ref::<MissingOptionValue>().is_some());
+
+        let err = Config::from_args_and_gpu_env_var(args(&["--colour", "red"]), None).unwrap_err();
+        assert!(err.downcast_ref::<UnknownOption>().is_some());
+
+        let help = Config::from_args_and_gpu_env_var(args(&["--help"]), None).unwrap();
+        assert!(help.is_none());
+    }
+
+    #[test]
+    fn config_file() {
+        let directory = TestDirectory::new("config_file");
+        let path = directory.write(
+            "valid.conf",
+            "# comment\n\n  model = cube.obj  \nsize=640x480\nscene = a = b\n",
+        );
+        let options = parse_config_file(&path).unwrap();
+        assert_eq!(
+            options,
+            [
+                ("model".to_owned(), "cube.obj".to_owned()),
+                ("size".to_owned(), "640x480".to_owned()),
+                ("scene".to_owned(), "a = b".to_owned()),
+            ]
+        );
+
+        for (i, content) in ["model cube.obj", "model =", "= cube.obj"]
+            .iter()
+            .enumerate()
+        {
+            let path = directory.write(&format!("invalid_{i}.conf"), content);
+            let err = parse_config_file(&path).unwrap_err();
+            assert!(err.downcast_ref::<InvalidConfigFileLine>().is_some());
+        }
+
+        let err = parse_config_file("/nonexistent/hitchhikers.conf").unwrap_err();
+        assert!(err.downcast_ref::<FailedToReadConfigFile>().is_some());
+    }
+
+    #[test]
+    fn precedence() {
+        let directory = TestDirectory::new("config_precedence");
+        let path = directory.write(
+            "hitchhikers.conf",
+            "gpu = integrated\nmodel = file.obj\ntexture = file.ppm\n",
+        );
+
+        let config = Config::from_args_and_gpu_env_var(
+            args(&["--model", "cli.obj", "--config", &path]),
+            Some("cpu".to_owned()),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.assets.model, "cli.obj");
+        assert_eq!(config.assets.texture, "file.ppm");
+        assert!(matches!(
+            config.gpu,
+            Some(DeviceSelector::Type(vk::PhysicalDeviceType::INTEGRATED_GPU))
+        ));
+
+        let config = Config::from_args_and_gpu_env_var(
+            args(&["--config", &path, "--gpu", "discrete"]),
+            Some("cpu".to_owned()),
+        )
+        .unwrap()
+        .unwrap();
+        assert!(matches!(
+            config.gpu,
+            Some(DeviceSelector::Type(vk::PhysicalDeviceType::DISCRETE_GPU))
+        ));
+
+        let config = Config::from_args_and_gpu_env_var(args(&[]), Some("cpu".to_owned()))
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            config.gpu,
+            Some(DeviceSelector::Type(vk::PhysicalDeviceType::CPU))
+        ));
+        assert_eq!(config.assets.model, AssetPaths::default().model);
+    }
+
+    #[test]
+    fn device_selectors() {
+        let parse = |selector: &str| selector.parse::<DeviceSelector>();
+
+        assert_eq!(parse("index:1"), Ok(DeviceSelector::Index(1)));
+        assert_eq!(
+            parse("Discrete"),
+            Ok(DeviceSelector::Type(vk::PhysicalDeviceType::DISCRETE_GPU))
+        );
+        assert_eq!(parse("3090"), Ok(DeviceSelector::Name("3090".to_owned())));
+        assert_eq!(
+            parse("GeForce RTX"),
+            Ok(DeviceSelector::Name("GeForce RTX".to_owned()))
+        );
+        assert_eq!(
+            parse("name:cpu"),
+            Ok(DeviceSelector::Name("cpu".to_owned()))
+        );
+        assert_eq!(
+            parse("name:index:1"),
+            Ok(DeviceSelector::Name("index:1".to_owned()))
+        );
+
+        for selector in ["", "index:", "index:-1", "index:first", "name:"] {
+            assert_eq!(parse(selector), Err(()), "{selector}");
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use rs42::error_struct_custom_display;
+
+error_struct_custom_display!(
+    UnknownOption { option: String },
+    "Unknown option \"{}\", use --help to list the available options",
+    option
+);
+
+error_struct_custom_display!(
+    MissingOptionValue { option: String },
+    "Option \"{}\" expects a value",
+    option
+);
+
+error_struct_custom_display!(
+    InvalidExtent { extent: String },
+    "Invalid extent \"{}\", expected <width>x<height>",
+    extent
+);
+
+error_struct_custom_display!(
+    FailedToReadConfigFile {
+        path: String,
+        err: std::io::Error,
+    },
+    "Failed to read config file \"{}\": {}",
+    path,
+    err
+);
+
+error_struct_custom_display!(
+    InvalidConfigFileLine {
+        path: String,
+        line: usize,
+        content: String,
+    },
+    "Invalid line {} in config file \"{}\": \"{}\", expected <key> = <value>",
+    line,
+    path,
+    content
+);
+
+error_struct_custom_display!(
+    InvalidOptionValue {
+        option: String,
+        value: String,
+    },
+    "Invalid value \"{}\" for option \"{}\"",
+    value,
+    option
+);
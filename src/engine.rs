@@ -1,13 +1,23 @@
 mod errors;
+mod frame_clock;
+mod frame_recorder;
 
+use crate::camera::Camera;
+use crate::config::Config;
 use crate::engine::errors::{FailedToCreateWindow, FailedToInitVulkan};
-use crate::vulkan_renderer::VulkanRenderer;
+use crate::engine::frame_clock::FrameClock;
+use crate::engine::frame_recorder::FrameRecorder;
+use crate::scene::{Light, Scene, SceneObject};
+use crate::vulkan_renderer::{RenderedFrame, VulkanRenderer};
 use ash::vk;
 use rs42::const_str_to_cstr;
 use rs42::Result;
 use std::ffi::CStr;
-use winit::event::WindowEvent;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, KeyEvent, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 
 pub const ENGINE_NAME: &str = "Hitchhiker's Engine";
@@ -15,30 +25,141 @@ pub const ENGINE_NAME_CSTR: &CStr = const_str_to_cstr!(ENGINE_NAME);
 
 pub const ENGINE_VERSION: u32 = vk::make_api_version(0, 0, 0, 0);
 
+const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+
 pub struct Engine {
     vulkan_renderer: VulkanRenderer,
     window: Window,
+
+    camera: Camera,
+    clock: FrameClock,
+    // None if the frames aren't recorded
+    recorder: Option<FrameRecorder>,
+    // While recording, the simulation only advances once the previous frame is recorded so that
+    // no step of the recording is missing
+    is_previous_frame_skipped: bool,
+    // Numbers the screenshots so that their names are reserved as soon as they are requested
+    screenshot_count: u32,
 }
 
 impl Engine {
-    pub fn new(event_loop: &ActiveEventLoop) -> Result<Self> {
-        let window_attributes = Window::default_attributes().with_title(ENGINE_NAME);
+    pub(crate) fn new(event_loop: &ActiveEventLoop, config: &Config, scene: Scene) -> Result<Self> {
+        let window_attributes = Window::default_attributes()
+            .with_title(ENGINE_NAME)
+            .with_inner_size(PhysicalSize::new(config.extent.width, config.extent.height));
 
         let window = event_loop
             .create_window(window_attributes)
             .map_err(FailedToCreateWindow::new)?;
 
+        let recorder = FrameRecorder::new(&config.recording)?;
+
+        let mut vulkan_renderer = VulkanRenderer::new(
+            &window,
+            config.assets.clone(),
+            config.shadows.clone(),
+            scene,
+            config.gpu.clone(),
+        )
+        .map_err(FailedToInitVulkan::new)?;
+        // Recordings shouldn't depend on how fast the assets load
+        if recorder.is_some() {
+            vulkan_renderer.wait_for_assets()?;
+        }
+
+        let fixed_frame_time = recorder
+            .is_some()
+            .then(|| Duration::from_secs(1) / config.recording.frame_rate);
         Ok(Self {
-            vulkan_renderer: VulkanRenderer::new(&window).map_err(FailedToInitVulkan::new)?,
+            vulkan_renderer,
             window,
+            camera: Camera::default(),
+            clock: FrameClock::new(&config.timing, fixed_frame_time),
+            recorder,
+            is_previous_frame_skipped: false,
+            screenshot_count: 0,
         })
     }
 
-    pub fn render_frame(&mut self) -> Result<()> {
-        self.vulkan_renderer.render_frame(&self.window)
+    // The camera follows the input every frame rather than at the update rate
+    pub(crate) fn start_frame(&mut self) {
+        if self.recorder.is_some() && self.is_previous_frame_skipped {
+            return;
+        }
+        let elapsed_time = self.clock.start_frame();
+        self.camera.update(elapsed_time.as_secs_f32());
+    }
+
+    // Returns the fixed step in seconds while there is time left to simulate for this frame
+    pub(crate) fn next_update(&mut self) -> Option<f32> {
+        if !self.clock.take_step() {
+            return None;
+        }
+        self.vulkan_renderer.scene_mut().save_previous_transforms();
+        Some(self.clock.fixed_step().as_secs_f32())
+    }
+
+    // How far the frame is between the last two updates, from 0 to 1
+    pub fn interpolation(&self) -> f32 {
+        self.clock.interpolation()
     }
 
-    pub fn handle_event(&mut self, event: &WindowEvent) -> Result<()> {
+    // None if the next frame can be rendered right away
+    pub(crate) fn next_frame_deadline(&self) -> Option<Instant> {
+        self.clock.next_frame_deadline()
+    }
+
+    // Failing to write a frame of the recording stops it, other screenshots are only reported
+    pub(crate) fn render_frame(&mut self) -> Result<()> {
+        let recorded_frame_path = self
+            .recorder
+            .as_ref()
+            .and_then(FrameRecorder::next_frame_path);
+        if let Some(path) = recorded_frame_path.clone() {
+            self.vulkan_renderer.request_screenshot(path);
+        }
+
+        let rendered_frame = self.vulkan_renderer.render_frame(
+            &self.window,
+            &self.camera,
+            self.clock.interpolation(),
+        )?;
+        let RenderedFrame::Drawn { screenshots } = rendered_frame else {
+            self.is_previous_frame_skipped = true;
+            return Ok(());
+        };
+        self.is_previous_frame_skipped = false;
+
+        for (path, result) in screenshots {
+            match self.recorder.as_mut() {
+                Some(recorder) if recorded_frame_path.as_ref() == Some(&path) => {
+                    result?;
+                    recorder.frame_recorded();
+                    if recorder.is_finished() {
+                        println!(
+                            "Recorded {} frames to \"{}\"",
+                            recorder.frame_count(),
+                            recorder.directory()
+                        );
+                    }
+                }
+                _ => match result {
+                    Ok(()) => println!("Took screenshot \"{path}\""),
+                    Err(err) => eprintln!("WARNING: Failed to take screenshot \"{path}\": {err}"),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    // True once the last frame of the recording is written, always false without a recording
+    pub(crate) fn is_recording_finished(&self) -> bool {
+        self.recorder
+            .as_ref()
+            .is_some_and(FrameRecorder::is_finished)
+    }
+
+    pub(crate) fn handle_event(&mut self, event: &WindowEvent) -> Result<()> {
         match event {
             WindowEvent::ScaleFactorChanged {
                 scale_factor: _,
@@ -48,7 +169,25 @@ impl Engine {
                 // TODO maybe handle minimization differently?
                 unsafe { self.vulkan_renderer.recreate_swapchain(&self.window) }
             }
-            _ => Ok(()),
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(SCREENSHOT_KEY),
+                        state: ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let path = screenshot_path(SystemTime::now(), self.screenshot_count);
+                self.screenshot_count += 1;
+                self.take_screenshot(path);
+                Ok(())
+            }
+            _ => {
+                self.camera.handle_event(event);
+                Ok(())
+            }
         }
     }
 
@@ -56,7 +195,71 @@ impl Engine {
         &self.window
     }
 
-    pub unsafe fn destroy(&mut self) {
+    // The next presented frame is written to path as a PPM file, the result is reported once it
+    // is written
+    pub fn take_screenshot(&mut self, path: String) {
+        self.vulkan_renderer.request_screenshot(path);
+    }
+
+    // Gives back the GPU memory left between the buffers, the rendering stalls while the buffers
+    // are moved
+    pub fn defragment_memory(&mut self) -> Result<()> {
+        self.vulkan_renderer.defragment_memory()
+    }
+
+    // The usage of the GPU memory, in total and per memory type
+    pub fn memory_stats(&self) -> String {
+        self.vulkan_renderer.memory_stats().to_string()
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    pub fn scene(&self) -> &Scene {
+        self.vulkan_renderer.scene()
+    }
+
+    // Only the objects and the lights can be changed once the renderer is created, adding a mesh
+    // would need it to be loaded
+    pub fn objects_mut(&mut self) -> &mut [SceneObject] {
+        self.vulkan_renderer.scene_mut().objects_mut()
+    }
+
+    pub fn lights_mut(&mut self) -> &mut [Light] {
+        self.vulkan_renderer.scene_mut().lights_mut()
+    }
+
+    pub(crate) unsafe fn destroy(&mut self) {
         self.vulkan_renderer.destroy();
     }
 }
+
+// Named after the time in milliseconds and the number of the screenshot, screenshots requested
+// in the same millisecond don't overwrite each other
+fn screenshot_path(time: SystemTime, number: u32) -> String {
+    let timestamp = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis());
+    format!("screenshot_{timestamp}_{number}.ppm")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn screenshots_dont_overwrite_each_other() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(screenshot_path(time, 0), "screenshot_1700000000123_0.ppm");
+        assert_eq!(screenshot_path(time, 1), "screenshot_1700000000123_1.ppm");
+        assert_eq!(
+            screenshot_path(time + Duration::from_millis(1), 2),
+            "screenshot_1700000000124_2.ppm"
+        );
+    }
+}
@@ -8,6 +8,16 @@ error_struct_custom_display!(
     err
 );
 
+error_struct_custom_display!(
+    FailedToCreateRecordingDirectory {
+        path: String,
+        err: std::io::Error,
+    },
+    "Failed to create recording directory \"{}\": {}",
+    path,
+    err
+);
+
 error_struct_custom_display!(FailedToInitVulkan {
     err: Box<dyn std::error::Error>,
 }, "Failed to init vulkan: {}", err);
@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use crate::config::TimingSettings;
+
+// Longer frames are shortened so that a stall (a breakpoint, a window drag...) doesn't have to be
+// caught up with hundreds of updates
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+// Splits the time between frames into fixed simulation steps, the time left over is carried to
+// the next frame
+pub struct FrameClock {
+    fixed_step: Duration,
+    // None if the frame rate isn't capped
+    min_frame_time: Option<Duration>,
+    // Replaces the wall clock time between frames if set, the frame rate isn't capped then
+    fixed_frame_time: Option<Duration>,
+    frame_start_time: Instant,
+    accumulated_time: Duration,
+}
+
+impl FrameClock {
+    pub fn new(timing: &TimingSettings, fixed_frame_time: Option<Duration>) -> Self {
+        Self {
+            fixed_frame_time,
+            fixed_step: Duration::from_secs(1) / timing.update_rate,
+            min_frame_time: (timing.max_frame_rate != 0)
+                .then(|| Duration::from_secs(1) / timing.max_frame_rate),
+            frame_start_time: Instant::now(),
+            accumulated_time: Duration::ZERO,
+        }
+    }
+
+    // Returns the time elapsed since the start of the previous frame
+    pub fn start_frame(&mut self) -> Duration {
+        let current_time = Instant::now();
+        let elapsed_time = self
+            .fixed_frame_time
+            .unwrap_or_else(|| (current_time - self.frame_start_time).min(MAX_FRAME_TIME));
+        self.frame_start_time = current_time;
+
+        self.accumulate(elapsed_time);
+        elapsed_time
+    }
+
+    fn accumulate(&mut self, elapsed_time: Duration) {
+        self.accumulated_time += elapsed_time;
+    }
+
+    // Returns false once less than a step of time is left for this frame
+    pub fn take_step(&mut self) -> bool {
+        if self.accumulated_time < self.fixed_step {
+            return false;
+        }
+        self.accumulated_time -= self.fixed_step;
+        true
+    }
+
+    pub fn fixed_step(&self) -> Duration {
+        self.fixed_step
+    }
+
+    // How far the current time is between the last two steps, from 0 to 1
+    pub fn interpolation(&self) -> f32 {
+        self.accumulated_time.as_secs_f32() / self.fixed_step.as_secs_f32()
+    }
+
+    // None if the next frame can start right away
+    pub fn next_frame_deadline(&self) -> Option<Instant> {
+        if self.fixed_frame_time.is_some() {
+            return None;
+        }
+        self.min_frame_time
+            .map(|min_frame_time| self.frame_start_time + min_frame_time)
+            .filter(|deadline| *deadline > Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn clock(update_rate: u32) -> FrameClock {
+        FrameClock::new(
+            &TimingSettings {
+                update_rate,
+                max_frame_rate: 0,
+            },
+            None,
+        )
+    }
+
+    fn count_steps(clock: &mut FrameClock) -> usize {
+        std::iter::from_fn(|| clock.take_step().then_some(())).count()
+    }
+
+    #[test]
+    fn frames_are_split_into_steps() {
+        let frame_time = Duration::from_secs(1) / 30;
+        let mut clock = clock(60);
+        assert_eq!(clock.fixed_step(), Duration::from_secs(1) / 60);
+
+        for _ in 0..3 {
+            clock.accumulate(frame_time);
+            assert_eq!(count_steps(&mut clock), 2);
+            assert!(clock.interpolation() < 0.001);
+        }
+    }
+
+    #[test]
+    fn the_time_left_is_carried_to_the_next_frame() {
+        let frame_time = Duration::from_millis(15);
+        let mut clock = clock(50);
+
+        clock.accumulate(frame_time);
+        assert_eq!(count_steps(&mut clock), 0);
+        assert!((clock.interpolation() - 0.75).abs() < 0.001);
+
+        clock.accumulate(frame_time);
+        assert_eq!(count_steps(&mut clock), 1);
+        assert!((clock.interpolation() - 0.5).abs() < 0.001);
+
+        clock.accumulate(frame_time);
+        assert_eq!(count_steps(&mut clock), 1);
+        assert!((clock.interpolation() - 0.25).abs() < 0.001);
+    }
+
+    #[test]
+    fn the_highest_update_rate_takes_steps() {
+        let mut clock = clock(crate::config::MAX_UPDATE_RATE);
+
+        clock.accumulate(MAX_FRAME_TIME);
+        assert_eq!(count_steps(&mut clock), 250);
+        assert!(clock.interpolation().is_finite());
+    }
+
+    #[test]
+    fn recordings_use_a_fixed_frame_time() {
+        let frame_time = Duration::from_secs(1) / 30;
+        let timing = TimingSettings {
+            update_rate: 60,
+            max_frame_rate: 0,
+        };
+        let mut clock = FrameClock::new(&timing, Some(frame_time));
+
+        for _ in 0..3 {
+            assert_eq!(clock.start_frame(), frame_time);
+            assert_eq!(count_steps(&mut clock), 2);
+        }
+    }
+
+    #[test]
+    fn frame_rate_cap() {
+        let timing = TimingSettings {
+            update_rate: 60,
+            max_frame_rate: 1,
+        };
+        let clock = FrameClock::new(&timing, None);
+        assert!(clock.next_frame_deadline().is_some());
+
+        // Recordings render as fast as possible
+        let clock = FrameClock::new(&timing, Some(Duration::from_secs(1)));
+        assert!(clock.next_frame_deadline().is_none());
+
+        let timing = TimingSettings {
+            update_rate: 60,
+            max_frame_rate: 0,
+        };
+        let clock = FrameClock::new(&timing, None);
+        assert!(clock.next_frame_deadline().is_none());
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+
+use rs42::Result;
+
+use crate::config::RecordingSettings;
+
+use super::errors::FailedToCreateRecordingDirectory;
+
+// Writes consecutive frames to numbered PPM files until frame_count of them are recorded
+pub struct FrameRecorder {
+    directory: String,
+    frame_count: u32,
+    recorded_frame_count: u32,
+}
+
+impl FrameRecorder {
+    // Returns None if the settings don't ask for a recording
+    pub fn new(settings: &RecordingSettings) -> Result<Option<Self>> {
+        let Some(directory) = settings.directory.as_ref() else {
+            return Ok(None);
+        };
+        fs::create_dir_all(directory)
+            .map_err(|err| FailedToCreateRecordingDirectory::new(directory.clone(), err))?;
+
+        Ok(Some(Self {
+            directory: directory.clone(),
+            frame_count: settings.frame_count,
+            recorded_frame_count: 0,
+        }))
+    }
+
+    // Returns None once every frame was recorded
+    pub fn next_frame_path(&self) -> Option<String> {
+        if self.is_finished() {
+            return None;
+        }
+        Some(format!(
+            "{}/frame_{:05}.ppm",
+            self.directory, self.recorded_frame_count
+        ))
+    }
+
+    // Called once the frame at next_frame_path() is written
+    pub fn frame_recorded(&mut self) {
+        debug_assert!(!self.is_finished());
+        self.recorded_frame_count += 1;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.recorded_frame_count == self.frame_count
+    }
+
+    pub fn directory(&self) -> &str {
+        &self.directory
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    #[test]
+    fn frames_are_numbered_once_written() {
+        let test_directory = TestDirectory::new("frame_recorder");
+        let directory = test_directory.path("recording");
+        let settings = RecordingSettings {
+            directory: Some(directory.clone()),
+            frame_count: 2,
+            frame_rate: 60,
+        };
+        let mut recorder = FrameRecorder::new(&settings).unwrap().unwrap();
+        assert!(fs::metadata(&directory).is_ok_and(|metadata| metadata.is_dir()));
+
+        let first_path = format!("{directory}/frame_00000.ppm");
+        assert_eq!(recorder.next_frame_path(), Some(first_path.clone()));
+        // A frame that wasn't written is asked for again
+        assert_eq!(recorder.next_frame_path(), Some(first_path));
+        recorder.frame_recorded();
+        assert!(!recorder.is_finished());
+        assert_eq!(
+            recorder.next_frame_path(),
+            Some(format!("{directory}/frame_00001.ppm"))
+        );
+        recorder.frame_recorded();
+        assert!(recorder.is_finished());
+        assert_eq!(recorder.next_frame_path(), None);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_a_directory() {
+        let settings = RecordingSettings {
+            directory: None,
+            frame_count: 2,
+            frame_rate: 60,
+        };
+        assert!(FrameRecorder::new(&settings).unwrap().is_none());
+    }
+}
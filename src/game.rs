@@ -0,0 +1,34 @@
+use rs42::Result;
+use winit::event::WindowEvent;
+
+use crate::{config::Config, engine::Engine, scene::Scene};
+
+// Implemented by the applications built on the engine, every hook does nothing by default.
+// An error returned by a hook is printed and closes the window
+pub trait Game {
+    // Called once before the renderer is created, the meshes of the returned scene are the only
+    // ones that can be drawn
+    fn init(&mut self, config: &Config) -> Result<Scene> {
+        Scene::from_config(config)
+    }
+
+    // Called at the update rate of the config, so zero or more times per frame, delta_time is
+    // the fixed step in seconds.
+    // The objects are drawn in between their transforms of the last two updates
+    fn update(&mut self, _engine: &mut Engine, _delta_time: f32) -> Result<()> {
+        Ok(())
+    }
+
+    // Called for every window event after the engine handled it
+    fn handle_event(&mut self, _engine: &mut Engine, _event: &WindowEvent) -> Result<()> {
+        Ok(())
+    }
+
+    // Called right before the frame is drawn, after the updates of the frame
+    fn render(&mut self, _engine: &mut Engine) -> Result<()> {
+        Ok(())
+    }
+
+    // Called once before the engine is destroyed
+    fn shutdown(&mut self, _engine: &mut Engine) {}
+}
@@ -0,0 +1,20 @@
+use rs42::{scope_guard::Defer, Result};
+
+use crate::{camera::Camera, config::Config, scene::Scene, vulkan_renderer::VulkanRenderer};
+
+// Renders a single frame without creating a window and writes it to output_path as a PPM file
+pub fn render_to_file(output_path: &str, config: &Config, scene: Scene) -> Result<()> {
+    let mut vulkan_renderer = VulkanRenderer::new_headless(
+        config.extent,
+        config.assets.clone(),
+        config.shadows.clone(),
+        scene,
+        config.gpu.clone(),
+    )?
+    .defer(|mut vulkan_renderer| unsafe { vulkan_renderer.destroy() });
+    vulkan_renderer.wait_for_assets()?;
+
+    vulkan_renderer
+        .render_offscreen_frame(&Camera::default())?
+        .write_ppm(output_path)
+}
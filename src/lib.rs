@@ -0,0 +1,12 @@
+mod app;
+pub mod camera;
+pub mod config;
+mod engine;
+mod game;
+mod headless;
+pub mod scene;
+mod vulkan_renderer;
+
+pub use app::run;
+pub use engine::Engine;
+pub use game::Game;
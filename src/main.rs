@@ -1,17 +1,14 @@
-mod app;
-mod engine;
-mod vulkan_renderer;
+use hitchhikers_engine::{config::Config, Game};
 
-use app::App;
+// Displays the scene given on the command line, the engine already moves the camera
+struct Viewer;
 
-use winit::error::EventLoopError;
-use winit::event_loop::{ControlFlow, EventLoop};
+impl Game for Viewer {}
 
-fn main() -> Result<(), EventLoopError> {
-    let event_loop = EventLoop::new()?;
-    event_loop.set_control_flow(ControlFlow::Poll);
+fn main() -> rs42::Result<()> {
+    let Some(config) = Config::from_args(std::env::args().skip(1))? else {
+        return Ok(());
+    };
 
-    let mut app = App::default();
-
-    event_loop.run_app(&mut app)
+    hitchhikers_engine::run(config, Viewer)
 }
@@ -0,0 +1,492 @@
+mod errors;
+mod light;
+mod material;
+
+use std::{collections::HashMap, fs, path::Path};
+
+use linear_algebra::{Degree, Matrix};
+use rs42::Result;
+
+use crate::config::Config;
+use errors::{EmptyScene, FailedToReadSceneFile, InvalidSceneFileLine};
+use light::parse_light;
+pub use light::Light;
+use material::parse_material;
+pub use material::SceneMaterial;
+
+type Mat4 = Matrix<f32, 4, 4>;
+
+const DEFAULT_AMBIENT_COLOR: [f32; 3] = [0.1, 0.1, 0.1];
+
+// Objects reference meshes by index so that a model used by many objects is only loaded once
+pub struct Scene {
+    mesh_paths: Vec<String>,
+    objects: Vec<SceneObject>,
+    // Referenced by the objects that override the materials of their model
+    materials: Vec<SceneMaterial>,
+
+    lights: Vec<Light>,
+    // Light received by every surface, regardless of the lights
+    ambient_color: [f32; 3],
+}
+
+// The mesh and the material can't be changed once the object is added, as the renderer indexes
+// its resources with them
+pub struct SceneObject {
+    mesh: usize,
+    pub transform: Transform,
+    // None if the materials of the model are used
+    material: Option<usize>,
+    // The transform before the last simulation update, frames are drawn in between the two
+    previous_transform: Transform,
+}
+
+#[derive(Debug, Clone)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation_axis: [f32; 3],
+    pub rotation_angle: f32, // In degrees
+    pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0., 0., 0.],
+            rotation_axis: [0., 0., 1.],
+            rotation_angle: 0.,
+            scale: [1., 1., 1.],
+        }
+    }
+}
+
+impl SceneObject {
+    pub fn mesh(&self) -> usize {
+        self.mesh
+    }
+
+    pub fn material(&self) -> Option<usize> {
+        self.material
+    }
+
+    // interpolation goes from 0 for the previous transform to 1 for the current one
+    pub fn interpolated_model_matrix(&self, interpolation: f32) -> Mat4 {
+        self.previous_transform
+            .interpolate(&self.transform, interpolation)
+            .model_matrix()
+    }
+}
+
+impl Transform {
+    // The rotation angle is only interpolated around a common axis, otherwise the rotation of
+    // other is used
+    pub fn interpolate(&self, other: &Self, interpolation: f32) -> Self {
+        let lerp = |a: f32, b: f32| a + (b - a) * interpolation;
+        let lerp_array = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|i| lerp(a[i], b[i]));
+
+        let rotation_angle = if self.rotation_axis == other.rotation_axis {
+            lerp(self.rotation_angle, other.rotation_angle)
+        } else {
+            other.rotation_angle
+        };
+        Self {
+            translation: lerp_array(self.translation, other.translation),
+            rotation_axis: other.rotation_axis,
+            rotation_angle,
+            scale: lerp_array(self.scale, other.scale),
+        }
+    }
+
+    pub fn model_matrix(&self) -> Mat4 {
+        Matrix::model(
+            self.rotation_axis,
+            Degree::from(self.rotation_angle),
+            self.translation,
+            self.scale,
+        )
+    }
+}
+
+impl Scene {
+    // Loads the scene file if there is one, otherwise the scene only contains the model lit by
+    // the default light
+    pub fn from_config(config: &Config) -> Result<Self> {
+        if let Some(path) = config.scene.as_ref() {
+            return Self::from_file(path);
+        }
+
+        let mut scene = Self::default();
+        let mesh = scene.add_mesh(&config.assets.model);
+        scene.add_object(
+            mesh,
+            Transform {
+                rotation_angle: 90.,
+                ..Default::default()
+            },
+            None,
+        );
+        scene.add_light(Light::default());
+        Ok(scene)
+    }
+
+    // Each non empty line that isn't a # comment describes an object, a material, a light or the
+    // ambient color:
+    // object <model path> [translation <x> <y> <z>] [rotation <axis x> <axis y> <axis z> <degrees>]
+    //     [scale <x> <y> <z>] [material <name>]
+    // material <name> ..., see parse_material()
+    // light <directional|point|spot> ..., see parse_light()
+    // ambient <r> <g> <b>
+    // Model paths are relative to the scene file, the default light is used if there is no light
+    // Materials have to be defined before the objects that use them
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| FailedToReadSceneFile::new(path.to_owned(), err))?;
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let mut scene = Self::default();
+        let mut material_indices = HashMap::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid_line =
+                || InvalidSceneFileLine::new(path.to_owned(), line_number + 1, line.to_owned());
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("object") => {
+                    let (model_path, transform, material) =
+                        parse_object(words).ok_or_else(invalid_line)?;
+                    let material = match material {
+                        Some(name) => Some(*material_indices.get(name).ok_or_else(invalid_line)?),
+                        None => None,
+                    };
+                    let mesh = scene.add_mesh(&directory.join(model_path).to_string_lossy());
+                    scene.add_object(mesh, transform, material);
+                }
+                Some("material") => {
+                    let (name, material) = parse_material(words).ok_or_else(invalid_line)?;
+                    if material_indices.contains_key(name) {
+                        return Err(invalid_line().into());
+                    }
+                    material_indices.insert(name, scene.add_material(material));
+                }
+                Some("light") => scene.add_light(parse_light(words).ok_or_else(invalid_line)?),
+                Some("ambient") => {
+                    scene.ambient_color = parse_floats(&mut words)
+                        .filter(|_| words.next().is_none())
+                        .ok_or_else(invalid_line)?
+                }
+                _ => return Err(invalid_line().into()),
+            }
+        }
+
+        if scene.objects.is_empty() {
+            return Err(EmptyScene::new(path.to_owned()).into());
+        }
+        if scene.lights.is_empty() {
+            scene.add_light(Light::default());
+        }
+        Ok(scene)
+    }
+
+    // Returns the index of the mesh, adding it only if the path is new
+    pub fn add_mesh(&mut self, path: &str) -> usize {
+        if let Some(index) = self.mesh_paths.iter().position(|mesh| mesh == path) {
+            return index;
+        }
+        self.mesh_paths.push(path.to_owned());
+        self.mesh_paths.len() - 1
+    }
+
+    // material is the index of a material of the scene that replaces the ones of the model
+    pub fn add_object(&mut self, mesh: usize, transform: Transform, material: Option<usize>) {
+        assert!(
+            mesh < self.mesh_paths.len(),
+            "The mesh should be added first"
+        );
+        assert!(
+            material.is_none_or(|material| material < self.materials.len()),
+            "The material should be added first"
+        );
+
+        self.objects.push(SceneObject {
+            mesh,
+            previous_transform: transform.clone(),
+            transform,
+            material,
+        });
+    }
+
+    // Returns the index of the material
+    pub fn add_material(&mut self, material: SceneMaterial) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    pub fn mesh_paths(&self) -> &[String] {
+        &self.mesh_paths
+    }
+
+    pub fn objects(&self) -> &[SceneObject] {
+        &self.objects
+    }
+
+    pub fn materials(&self) -> &[SceneMaterial] {
+        &self.materials
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    // Called before every simulation update
+    pub(crate) fn save_previous_transforms(&mut self) {
+        for object in self.objects.iter_mut() {
+            object.previous_transform = object.transform.clone();
+        }
+    }
+
+    pub fn objects_mut(&mut self) -> &mut [SceneObject] {
+        &mut self.objects
+    }
+
+    pub fn lights_mut(&mut self) -> &mut [Light] {
+        &mut self.lights
+    }
+
+    pub fn ambient_color(&self) -> [f32; 3] {
+        self.ambient_color
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            mesh_paths: Vec::new(),
+            objects: Vec::new(),
+            materials: Vec::new(),
+            lights: Vec::new(),
+            ambient_color: DEFAULT_AMBIENT_COLOR,
+        }
+    }
+}
+
+// Returns the model path, the transform and the name of the material if there is one
+// The first word is expected to have been consumed already
+fn parse_object<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Option<(&'a str, Transform, Option<&'a str>)> {
+    let model_path = words.next()?;
+
+    let mut transform = Transform::default();
+    let mut material = None;
+    while let Some(word) = words.next() {
+        match word {
+            "translation" => transform.translation = parse_floats(&mut words)?,
+            "rotation" => {
+                transform.rotation_axis = parse_floats(&mut words)?;
+                [transform.rotation_angle] = parse_floats(&mut words)?;
+            }
+            "scale" => transform.scale = parse_floats(&mut words)?,
+            "material" => material = Some(words.next()?),
+            _ => return None,
+        }
+    }
+    Some((model_path, transform, material))
+}
+
+fn parse_floats<'a, const N: usize>(words: &mut impl Iterator<Item = &'a str>) -> Option<[f32; N]> {
+    let mut floats = [0.; N];
+    for float in floats.iter_mut() {
+        *float = words.next()?.parse().ok()?;
+    }
+    Some(floats)
+}
+
+#[cfg(test)]
+mod test {
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    #[test]
+    fn objects() {
+        let (model_path, transform, material) = parse_object(
+            "cube.obj translation 1 2 3 rotation 0 1 0 45 scale 2 2 2 material red"
+                .split_whitespace(),
+        )
+        .unwrap();
+        assert_eq!(model_path, "cube.obj");
+        assert_eq!(transform.translation, [1., 2., 3.]);
+        assert_eq!(transform.rotation_axis, [0., 1., 0.]);
+        assert_eq!(transform.rotation_angle, 45.);
+        assert_eq!(transform.scale, [2., 2., 2.]);
+        assert_eq!(material, Some("red"));
+
+        let (_, transform, material) = parse_object("cube.obj".split_whitespace()).unwrap();
+        assert_eq!(transform.scale, [1., 1., 1.]);
+        assert_eq!(material, None);
+
+        for line in [
+            "",
+            "cube.obj translation 1 2",
+            "cube.obj rotation 0 1 0",
+            "cube.obj scale 1 x 1",
+            "cube.obj material",
+            "cube.obj color 1 1 1",
+        ] {
+            assert!(parse_object(line.split_whitespace()).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn materials() {
+        let (name, material) = parse_material(
+            "red color 1 0 0 opacity 0.5 metallic 1 roughness 0.25".split_whitespace(),
+        )
+        .unwrap();
+        assert_eq!(name, "red");
+        assert_eq!(material.color, [1., 0., 0.]);
+        assert_eq!(material.opacity, 0.5);
+        assert_eq!(material.metallic, 1.);
+        assert_eq!(material.roughness, 0.25);
+
+        for line in [
+            "",
+            "red color 1 0",
+            "red metallic 2",
+            "red opacity -1",
+            "red shininess 1",
+        ] {
+            assert!(parse_material(line.split_whitespace()).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn from_file() {
+        let directory = TestDirectory::new("scene_from_file");
+        let path = directory.write(
+            "test.scene",
+            "# comment\n\
+             \n\
+             material red color 1 0 0\n\
+             object cube.obj translation 1 0 0 material red\n\
+             object cube.obj\n\
+             object sphere.obj scale 2 2 2\n\
+             ambient 0.2 0.2 0.2\n",
+        );
+        let scene = Scene::from_file(&path).unwrap();
+
+        assert_eq!(
+            scene.mesh_paths(),
+            [directory.path("cube.obj"), directory.path("sphere.obj")]
+        );
+        let meshes: Vec<_> = scene.objects().iter().map(SceneObject::mesh).collect();
+        assert_eq!(meshes, [0, 0, 1]);
+        assert_eq!(scene.objects()[0].material(), Some(0));
+        assert_eq!(scene.objects()[1].material(), None);
+        assert_eq!(scene.materials()[0].color, [1., 0., 0.]);
+        assert_eq!(scene.ambient_color(), [0.2, 0.2, 0.2]);
+        // The default light is added when the scene has none
+        assert_eq!(scene.lights().len(), 1);
+    }
+
+    fn assert_approximately_equal(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn interpolate() {
+        let previous = Transform {
+            translation: [0., 2., -4.],
+            rotation_axis: [0., 1., 0.],
+            rotation_angle: 10.,
+            scale: [1., 1., 1.],
+        };
+        let current = Transform {
+            translation: [4., 2., 0.],
+            rotation_axis: [0., 1., 0.],
+            rotation_angle: 90.,
+            scale: [3., 1., 2.],
+        };
+
+        let halfway = previous.interpolate(&current, 0.5);
+        assert_approximately_equal(halfway.translation, [2., 2., -2.]);
+        assert_approximately_equal(halfway.scale, [2., 1., 1.5]);
+        assert_eq!(halfway.rotation_angle, 50.);
+
+        let start = previous.interpolate(&current, 0.);
+        assert_approximately_equal(start.translation, previous.translation);
+        assert_eq!(start.rotation_angle, previous.rotation_angle);
+        let end = previous.interpolate(&current, 1.);
+        assert_approximately_equal(end.translation, current.translation);
+        assert_approximately_equal(end.scale, current.scale);
+        assert_eq!(end.rotation_angle, current.rotation_angle);
+    }
+
+    #[test]
+    fn interpolate_around_another_axis() {
+        let previous = Transform {
+            rotation_angle: 10.,
+            ..Default::default()
+        };
+        let current = Transform {
+            rotation_axis: [1., 0., 0.],
+            rotation_angle: 90.,
+            ..Default::default()
+        };
+
+        let halfway = previous.interpolate(&current, 0.5);
+        assert_eq!(halfway.rotation_axis, current.rotation_axis);
+        assert_eq!(halfway.rotation_angle, current.rotation_angle);
+    }
+
+    #[test]
+    #[should_panic(expected = "The mesh should be added first")]
+    fn objects_need_an_existing_mesh() {
+        let mut scene = Scene::default();
+        scene.add_mesh("cube.obj");
+        scene.add_object(1, Transform::default(), None);
+    }
+
+    #[test]
+    fn invalid_files() {
+        let directory = TestDirectory::new("scene_invalid_files");
+        for (i, content) in [
+            "object cube.obj\nteapot teapot.obj\n",
+            "object cube.obj material red\n",
+            "object cube.obj material red\nmaterial red\n",
+            "material red\nmaterial red\nobject cube.obj\n",
+            "object cube.obj\nambient 1 1\n",
+            "object cube.obj\nambient 1 1 1 1\n",
+        ]
+        .iter()
+        .enumerate()
+        {
+            let path = directory.write(&format!("invalid_{i}.scene"), content);
+            let err = Scene::from_file(&path).err().unwrap();
+            assert!(
+                err.downcast_ref::<InvalidSceneFileLine>().is_some(),
+                "{content}"
+            );
+        }
+
+        let path = directory.write("empty.scene", "# nothing\nmaterial red\n");
+        let err = Scene::from_file(&path).err().unwrap();
+        assert!(err.downcast_ref::<EmptyScene>().is_some());
+
+        let err = Scene::from_file("/nonexistent/hitchhikers.scene")
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<FailedToReadSceneFile>().is_some());
+    }
+}
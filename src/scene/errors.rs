@@ -0,0 +1,29 @@
+use rs42::error_struct_custom_display;
+
+error_struct_custom_display!(
+    FailedToReadSceneFile {
+        path: String,
+        err: std::io::Error,
+    },
+    "Failed to read scene file \"{}\": {}",
+    path,
+    err
+);
+
+error_struct_custom_display!(
+    InvalidSceneFileLine {
+        path: String,
+        line: usize,
+        content: String,
+    },
+    "Invalid line {} in scene file \"{}\": \"{}\"",
+    line,
+    path,
+    content
+);
+
+error_struct_custom_display!(
+    EmptyScene { path: String },
+    "Scene file \"{}\" doesn't contain any object",
+    path
+);
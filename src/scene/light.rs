@@ -0,0 +1,172 @@
+use super::parse_floats;
+
+#[derive(Debug, Clone)]
+pub enum Light {
+    // Lights the whole scene from a direction, like the sun
+    Directional {
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+    },
+    // Fades out with the distance, reaching 0 at range
+    Point {
+        position: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+    },
+    // A point light restricted to a cone, the cone angles are in degrees from the direction,
+    // the light fades between the inner and the outer angle
+    Spot {
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: f32,
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+const DEFAULT_COLOR: [f32; 3] = [1., 1., 1.];
+const DEFAULT_INTENSITY: f32 = 1.;
+const DEFAULT_RANGE: f32 = 10.;
+const DEFAULT_INNER_CONE_ANGLE: f32 = 20.;
+const DEFAULT_OUTER_CONE_ANGLE: f32 = 30.;
+
+impl Default for Light {
+    fn default() -> Self {
+        Self::Directional {
+            direction: [-1., -0.5, -1.],
+            color: DEFAULT_COLOR,
+            intensity: DEFAULT_INTENSITY,
+        }
+    }
+}
+
+// light directional direction <x> <y> <z> [color <r> <g> <b>] [intensity <i>]
+// light point position <x> <y> <z> [color <r> <g> <b>] [intensity <i>] [range <r>]
+// light spot position <x> <y> <z> direction <x> <y> <z> [color <r> <g> <b>] [intensity <i>]
+//     [range <r>] [cone <inner degrees> <outer degrees>]
+// The first word is expected to have been consumed already
+pub fn parse_light<'a>(mut words: impl Iterator<Item = &'a str>) -> Option<Light> {
+    let kind = words.next()?;
+
+    let mut position = None;
+    let mut direction = None;
+    let mut color = DEFAULT_COLOR;
+    let mut intensity = DEFAULT_INTENSITY;
+    let mut range = DEFAULT_RANGE;
+    let mut cone = [DEFAULT_INNER_CONE_ANGLE, DEFAULT_OUTER_CONE_ANGLE];
+    while let Some(word) = words.next() {
+        match word {
+            "position" => position = Some(parse_floats(&mut words)?),
+            "direction" => direction = Some(parse_floats(&mut words)?),
+            "color" => color = parse_floats(&mut words)?,
+            "intensity" => [intensity] = parse_floats(&mut words)?,
+            "range" => [range] = parse_floats(&mut words)?,
+            "cone" => cone = parse_floats(&mut words)?,
+            _ => return None,
+        }
+    }
+    // Equal cone angles would leave no room for the fade and a zero direction can't be normalized
+    if range <= 0. || cone[0] >= cone[1] {
+        return None;
+    }
+    if direction.is_some_and(|direction: [f32; 3]| direction.iter().all(|e| *e == 0.)) {
+        return None;
+    }
+
+    match kind {
+        "directional" if position.is_none() => Some(Light::Directional {
+            direction: direction?,
+            color,
+            intensity,
+        }),
+        "point" if direction.is_none() => Some(Light::Point {
+            position: position?,
+            color,
+            intensity,
+            range,
+        }),
+        "spot" => Some(Light::Spot {
+            position: position?,
+            direction: direction?,
+            color,
+            intensity,
+            range,
+            inner_cone_angle: cone[0],
+            outer_cone_angle: cone[1],
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(line: &str) -> Option<Light> {
+        parse_light(line.split_whitespace())
+    }
+
+    #[test]
+    fn lights() {
+        let Some(Light::Directional {
+            direction,
+            color,
+            intensity,
+        }) = parse("directional direction 0 -1 0 intensity 2")
+        else {
+            panic!("expected a directional light");
+        };
+        assert_eq!(direction, [0., -1., 0.]);
+        assert_eq!(color, DEFAULT_COLOR);
+        assert_eq!(intensity, 2.);
+
+        let Some(Light::Point {
+            position, range, ..
+        }) = parse("point position 1 2 3 range 5")
+        else {
+            panic!("expected a point light");
+        };
+        assert_eq!(position, [1., 2., 3.]);
+        assert_eq!(range, 5.);
+
+        let Some(Light::Spot {
+            color,
+            inner_cone_angle,
+            outer_cone_angle,
+            ..
+        }) = parse("spot position 0 1 0 direction 0 -1 0 color 1 0 0 cone 10 15")
+        else {
+            panic!("expected a spot light");
+        };
+        assert_eq!(color, [1., 0., 0.]);
+        assert_eq!((inner_cone_angle, outer_cone_angle), (10., 15.));
+    }
+
+    #[test]
+    fn invalid_lights() {
+        for line in [
+            "",
+            "ambient",
+            "directional",
+            "directional direction 0 0 0",
+            "directional direction 0 -1 0 position 0 0 0",
+            "point",
+            "point position 0 0 0 direction 0 -1 0",
+            "point position 0 0 0 range 0",
+            "point position 0 0 0 range -1",
+            "point position 0 0",
+            "point position 0 0 0 radius 1",
+            "spot position 0 0 0",
+            "spot position 0 0 0 direction 0 0 0",
+            "spot position 0 0 0 direction 0 -1 0 cone 30 20",
+            "spot position 0 0 0 direction 0 -1 0 cone 20 20",
+            "spot position 0 0 0 direction 0 -1 0 cone 20",
+        ] {
+            assert!(parse(line).is_none(), "{line}");
+        }
+    }
+}
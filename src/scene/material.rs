@@ -0,0 +1,54 @@
+use super::parse_floats;
+
+// Replaces the materials of the objects that use it, it has no textures and the vertex colors
+// of the models still tint its color
+#[derive(Debug, Clone)]
+pub struct SceneMaterial {
+    pub color: [f32; 3],
+    pub opacity: f32,
+    pub emissive: [f32; 3],
+    pub metallic: f32,
+    pub roughness: f32,
+}
+
+impl Default for SceneMaterial {
+    fn default() -> Self {
+        Self {
+            color: [1., 1., 1.],
+            opacity: 1.,
+            emissive: [0., 0., 0.],
+            metallic: 0.,
+            roughness: 1.,
+        }
+    }
+}
+
+// material <name> [color <r> <g> <b>] [opacity <a>] [emissive <r> <g> <b>] [metallic <m>]
+//     [roughness <r>]
+// The first word is expected to have been consumed already
+pub fn parse_material<'a>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Option<(&'a str, SceneMaterial)> {
+    let name = words.next()?;
+
+    let mut material = SceneMaterial::default();
+    while let Some(word) = words.next() {
+        match word {
+            "color" => material.color = parse_floats(&mut words)?,
+            "opacity" => [material.opacity] = parse_floats(&mut words)?,
+            "emissive" => material.emissive = parse_floats(&mut words)?,
+            "metallic" => [material.metallic] = parse_floats(&mut words)?,
+            "roughness" => [material.roughness] = parse_floats(&mut words)?,
+            _ => return None,
+        }
+    }
+
+    let is_factor = |factor: f32| (0. ..=1.).contains(&factor);
+    if !is_factor(material.opacity)
+        || !is_factor(material.metallic)
+        || !is_factor(material.roughness)
+    {
+        return None;
+    }
+    Some((name, material))
+}
@@ -1,25 +1,46 @@
+mod allocator;
 mod buffer;
+mod frame_capture;
+mod light_space;
+mod material_uniform;
 mod memory;
+mod push_constants;
 mod render_targets;
+mod shader_watcher;
 mod single_time_command;
 mod uniform_buffer_object;
+mod upload_batch;
 mod vulkan_context;
 mod vulkan_interface;
 
-use std::{ptr::copy_nonoverlapping, time::SystemTime};
+use std::ptr::copy_nonoverlapping;
 
+pub use allocator::AllocatorStats;
 use ash::{prelude::VkResult, vk};
-use linear_algebra::{Degree, Matrix};
+pub use frame_capture::FrameCapture;
+use frame_capture::UncapturableSwapchain;
+use light_space::light_space_matrix;
 use memory::Memory;
-use render_targets::RenderTargets;
+use push_constants::{ObjectPushConstants, ShadowPushConstants};
+use render_targets::{RenderTargets, OFFSCREEN_FORMAT};
 use rs42::{
     scope_guard::{Defer, ScopeGuard},
     Result,
 };
-use uniform_buffer_object::UniformBufferObject;
-use vulkan_context::{create_device, PhysicalDeviceData, SwapchainBuilder, VulkanContext};
+use shader_watcher::ShaderWatcher;
+use uniform_buffer_object::{LightUniform, UniformBufferObject, MAX_LIGHTS, MAX_SHADOW_MAPS};
+use vulkan_context::{
+    create_device, PhysicalDeviceData, PresentationSurface, QueueFamilies, SwapchainBuilder,
+    VulkanContext,
+};
 use vulkan_interface::VulkanInterface;
 
+use crate::{
+    camera::Camera,
+    config::{AssetPaths, DeviceSelector, ShadowSettings},
+    scene::Scene,
+};
+
 const NB_OF_FRAMES_IN_FLIGHT: u32 = 2;
 const NB_OF_FRAMES_IN_FLIGHT_USIZE: usize = NB_OF_FRAMES_IN_FLIGHT as usize;
 
@@ -29,15 +50,29 @@ pub struct VulkanRenderer {
     render_targets: RenderTargets,
     memory: Memory,
 
-    previous_frame_start_time: SystemTime,
+    assets: AssetPaths,
+    shadows: ShadowSettings,
+    scene: Scene,
+    // Also used if the device has to be picked again
+    device_selector: Option<DeviceSelector>,
+    shader_watcher: ShaderWatcher,
+    // Where the next presented frame is written
+    screenshot_paths: Vec<String>,
 
     current_frame: usize,
-
-    rotation: Degree<f32>,
 }
 
 type ShouldStopRenderingFrame = bool;
 
+pub enum RenderedFrame {
+    // The swapchain had to be recreated before anything was drawn
+    Skipped,
+    // The paths of the requested screenshots, with the result of writing them
+    Drawn {
+        screenshots: Vec<(String, Result<()>)>,
+    },
+}
+
 enum NextImage {
     Index(u32),
     ShouldStopRenderingFrame,
@@ -49,23 +84,99 @@ enum ShouldRecreateMemory {
 }
 
 impl VulkanRenderer {
-    pub fn new(window: &winit::window::Window) -> Result<Self> {
-        let (context, queue_families, swapchain_builder) = VulkanContext::new(window)?;
+    pub fn new(
+        window: &winit::window::Window,
+        assets: AssetPaths,
+        shadows: ShadowSettings,
+        scene: Scene,
+        device_selector: Option<DeviceSelector>,
+    ) -> Result<Self> {
+        let (context, queue_families, swapchain_builder) =
+            VulkanContext::new(window, &assets.pipeline_cache, device_selector.as_ref())?;
+
+        Self::init(
+            context,
+            queue_families,
+            assets,
+            shadows,
+            scene,
+            device_selector,
+            |context, assets, shadows| unsafe {
+                RenderTargets::new(context, swapchain_builder, assets, shadows)
+            },
+        )
+    }
+
+    // Renders to an offscreen image instead of a window surface, frames have to be retrieved
+    // with render_offscreen_frame()
+    pub fn new_headless(
+        extent: vk::Extent2D,
+        assets: AssetPaths,
+        shadows: ShadowSettings,
+        scene: Scene,
+        device_selector: Option<DeviceSelector>,
+    ) -> Result<Self> {
+        let (context, queue_families) =
+            VulkanContext::new_headless(&assets.pipeline_cache, device_selector.as_ref())?;
+
+        Self::init(
+            context,
+            queue_families,
+            assets,
+            shadows,
+            scene,
+            device_selector,
+            |context, assets, shadows| unsafe {
+                RenderTargets::new_offscreen(context, extent, assets, shadows)
+            },
+        )
+    }
+
+    // Used by --list-gpus, doesn't need a window
+    pub fn describe_physical_devices() -> Result<String> {
+        VulkanContext::describe_physical_devices()
+    }
+
+    fn init(
+        context: VulkanContext,
+        queue_families: QueueFamilies,
+        assets: AssetPaths,
+        shadows: ShadowSettings,
+        scene: Scene,
+        device_selector: Option<DeviceSelector>,
+        create_render_targets: impl FnOnce(
+            &VulkanContext,
+            &AssetPaths,
+            &ShadowSettings,
+        ) -> Result<RenderTargets>,
+    ) -> Result<Self> {
+        if scene.lights().len() > MAX_LIGHTS {
+            eprintln!(
+                "WARNING: The scene has {} lights, only the first {MAX_LIGHTS} will be used",
+                scene.lights().len()
+            );
+        }
+
         let context = context.defer(|mut context| unsafe { context.destroy() });
 
         let interface = unsafe { VulkanInterface::new(&context, queue_families)? }
             .defer(|mut interface| unsafe { interface.destroy(context.device()) });
 
-        let render_targets = unsafe { RenderTargets::new(&context, swapchain_builder)? }
+        let render_targets = create_render_targets(&context, &assets, &shadows)?
             .defer(|mut render_targets| unsafe { render_targets.destroy(&context) });
 
-        let memory = unsafe { Memory::new(&context, &interface, &render_targets)? }
-            .defer(|mut memory| unsafe { memory.destroy(context.device()) });
+        let memory =
+            unsafe { Memory::new(&context, &interface, &render_targets, &assets, &scene)? }
+                .defer(|mut memory| unsafe { memory.destroy(&context) });
 
         Ok(Self {
-            rotation: Degree::from(90.),
             current_frame: 0,
-            previous_frame_start_time: SystemTime::now(),
+            shader_watcher: ShaderWatcher::new(&assets)?,
+            screenshot_paths: Vec::new(),
+            assets,
+            shadows,
+            scene,
+            device_selector,
             memory: ScopeGuard::into_inner(memory),
             render_targets: ScopeGuard::into_inner(render_targets),
             interface: ScopeGuard::into_inner(interface),
@@ -73,27 +184,172 @@ impl VulkanRenderer {
         })
     }
 
-    pub fn render_frame(&mut self, window: &winit::window::Window) -> Result<()> {
+    // The assets are otherwise loaded in the background, placeholders are drawn in the meantime
+    pub fn wait_for_assets(&mut self) -> Result<()> {
+        unsafe {
+            self.memory
+                .wait_for_assets(&self.context, &self.interface, &self.render_targets)
+        }
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    // Gives the blocks of GPU memory that compacting the buffers frees back to the driver, waits
+    // for the device to be idle. It is done once after the assets are loaded if the memory is
+    // fragmented
+    pub fn defragment_memory(&mut self) -> Result<()> {
+        unsafe {
+            self.memory
+                .defragment(&self.context, &self.interface, &self.render_targets)
+        }
+    }
+
+    pub fn memory_stats(&self) -> AllocatorStats {
+        self.context.allocator().stats()
+    }
+
+    // interpolation is how far the frame is between the last two simulation updates
+    pub fn render_frame(
+        &mut self,
+        window: &winit::window::Window,
+        camera: &Camera,
+        interpolation: f32,
+    ) -> Result<RenderedFrame> {
+        self.reload_changed_shaders();
+        unsafe {
+            self.memory.load_finished_assets(
+                &self.context,
+                &self.interface,
+                &self.render_targets,
+            )?
+        }
+
         self.wait_for_in_flight_fence()?;
 
         let NextImage::Index(image_index) = self.acquire_next_image(window)? else {
-            return Ok(());
+            return Ok(RenderedFrame::Skipped);
         };
 
-        self.update_uniform_buffer();
+        let shadow_map_count = self.update_uniform_buffer(camera);
 
         self.reset_in_flight_fence()?;
 
         self.reset_command_buffer()?;
-        unsafe { self.record_command_buffer(image_index)? }
+        unsafe { self.record_command_buffer(image_index, shadow_map_count, interpolation)? }
 
         self.submit_command_buffer()?;
+        if !self.screenshot_paths.is_empty() {
+            self.wait_for_in_flight_fence()?;
+        }
+        let screenshots = std::mem::take(&mut self.screenshot_paths)
+            .into_iter()
+            .map(|path| {
+                let result = self.take_screenshot(image_index, &path);
+                (path, result)
+            })
+            .collect();
+        let rendered_frame = RenderedFrame::Drawn { screenshots };
         if self.present_image(image_index, window)? {
-            return Ok(());
+            return Ok(rendered_frame);
         }
 
         self.current_frame = (self.current_frame + 1) % NB_OF_FRAMES_IN_FLIGHT_USIZE;
-        Ok(())
+        Ok(rendered_frame)
+    }
+
+    // Should only be called on renderers created with new_headless()
+    pub fn render_offscreen_frame(&mut self, camera: &Camera) -> Result<FrameCapture> {
+        unsafe {
+            self.memory.load_finished_assets(
+                &self.context,
+                &self.interface,
+                &self.render_targets,
+            )?
+        }
+        self.wait_for_in_flight_fence()?;
+
+        let shadow_map_count = self.update_uniform_buffer(camera);
+
+        self.reset_in_flight_fence()?;
+
+        self.reset_command_buffer()?;
+        unsafe { self.record_command_buffer(0, shadow_map_count, 1.)? }
+
+        self.submit_offscreen_command_buffer()?;
+        self.wait_for_in_flight_fence()?;
+
+        let frame = unsafe {
+            FrameCapture::from_image(
+                &self.context,
+                &self.interface,
+                self.render_targets.offscreen_image().image(),
+                self.render_targets.extent(),
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                OFFSCREEN_FORMAT,
+            )?
+        };
+
+        self.current_frame = (self.current_frame + 1) % NB_OF_FRAMES_IN_FLIGHT_USIZE;
+        Ok(frame)
+    }
+
+    // The frame is written once it is rendered, right before it is presented, the requests
+    // are kept until a frame is drawn. A path requested several times is written once.
+    // Should only be called on renderers created with new()
+    pub fn request_screenshot(&mut self, path: String) {
+        if !self.screenshot_paths.contains(&path) {
+            self.screenshot_paths.push(path);
+        }
+    }
+
+    // The commands drawing the image have to be finished
+    fn take_screenshot(&self, image_index: u32, path: &str) -> Result<()> {
+        let image = self
+            .render_targets
+            .capturable_swapchain_image(image_index)
+            .ok_or(UncapturableSwapchain {})?;
+        let frame = unsafe {
+            FrameCapture::from_image(
+                &self.context,
+                &self.interface,
+                image,
+                self.render_targets.extent(),
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                self.render_targets.format(),
+            )?
+        };
+        frame.write_ppm(path)
+    }
+
+    // Errors are only reported, the previous pipelines keep being used until the shaders are
+    // fixed
+    fn reload_changed_shaders(&mut self) {
+        let (changed, compile_errors) = self.shader_watcher.poll();
+        for err in compile_errors {
+            eprintln!("ERROR: {err}");
+        }
+        if !changed.any() {
+            return;
+        }
+
+        match unsafe {
+            self.render_targets.reload_pipelines(
+                &self.context,
+                &self.assets,
+                &self.shadows,
+                changed,
+            )
+        } {
+            Ok(()) if cfg!(debug_assertions) => println!("Reloaded shaders"),
+            Ok(()) => {}
+            Err(err) => eprintln!("ERROR: Failed to reload shaders: {err}"),
+        }
     }
 
     fn wait_for_in_flight_fence(&self) -> VkResult<()> {
@@ -106,27 +362,44 @@ impl VulkanRenderer {
         }
     }
 
-    fn update_uniform_buffer(&mut self) {
-        let current_time = SystemTime::now();
-        let elapsed_time_sec = match current_time.duration_since(self.previous_frame_start_time) {
-            Ok(elapsed_time) => elapsed_time.as_secs_f32(),
-            Err(_) => 0., // TODO find a cleaner way to handle this
-        };
-        self.previous_frame_start_time = current_time;
-
-        *self.rotation += 45. * elapsed_time_sec;
-
-        let aspect_ratio = self.render_targets.swapchain_extent().width as f32
-            / self.render_targets.swapchain_extent().height as f32;
+    // Returns the number of shadow maps the lights use
+    fn update_uniform_buffer(&mut self, camera: &Camera) -> usize {
+        let aspect_ratio =
+            self.render_targets.extent().width as f32 / self.render_targets.extent().height as f32;
+        let camera_position = camera.position();
+        let ambient_color = self.scene.ambient_color();
+        let mut lights = [LightUniform::default(); MAX_LIGHTS];
+        let mut shadow_matrices = [[[0.; 4]; 4]; MAX_SHADOW_MAPS];
+        let mut shadow_map_count = 0;
+        for (light, scene_light) in lights.iter_mut().zip(self.scene.lights()) {
+            *light = scene_light.into();
+            // The lights past MAX_SHADOW_MAPS don't cast shadows
+            if shadow_map_count == MAX_SHADOW_MAPS {
+                continue;
+            }
+            if let Some(matrix) =
+                light_space_matrix(scene_light, camera_position, self.shadows.distance)
+            {
+                shadow_matrices[shadow_map_count] = matrix;
+                light.cone[2] = shadow_map_count as f32;
+                shadow_map_count += 1;
+            }
+        }
         let mut uniform_buffer_object = UniformBufferObject {
-            model: Matrix::model(
-                [0., 0., 1.],
-                self.rotation.clone(),
-                [0., 0., 0.],
-                [1., 1., 1.],
-            ),
-            view: Matrix::look_at([2., 2., 2.], [0., 0., 0.], [0., 0., 1.]),
-            proj: Matrix::perspective_opengl(Degree::from(45.), aspect_ratio, 0.1, 10.),
+            view: camera.view_matrix(),
+            proj: camera.projection_matrix(aspect_ratio),
+            shadow_matrices,
+            camera_position: [
+                camera_position[0],
+                camera_position[1],
+                camera_position[2],
+                1.,
+            ],
+            ambient_color: [ambient_color[0], ambient_color[1], ambient_color[2], 1.],
+            light_count: self.scene.lights().len().min(MAX_LIGHTS) as u32,
+            shadow_pcf_radius: self.shadows.pcf_radius,
+            _padding: [0; 2],
+            lights,
         };
         uniform_buffer_object.proj[1][1] *= -1.;
 
@@ -138,6 +411,7 @@ impl VulkanRenderer {
                 1,
             )
         };
+        shadow_map_count
     }
 
     fn reset_in_flight_fence(&self) -> VkResult<()> {
@@ -184,7 +458,106 @@ impl VulkanRenderer {
         }
     }
 
-    unsafe fn record_command_buffer(&self, image_index: u32) -> VkResult<()> {
+    // Every layer of the shadow maps goes through its render pass so that they all end up in
+    // the layout the main render pass samples them in, the unused ones are only cleared
+    unsafe fn record_shadow_passes(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        shadow_map_count: usize,
+        interpolation: f32,
+    ) {
+        let shadow_maps = self.render_targets.shadow_maps();
+        let device = self.context.device();
+
+        let clear_values = [vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue::default().depth(1.).stencil(0),
+        }];
+        let viewports = [vk::Viewport::default()
+            .x(0.)
+            .y(0.)
+            .width(shadow_maps.extent().width as f32)
+            .height(shadow_maps.extent().height as f32)
+            .min_depth(0.)
+            .max_depth(1.)];
+        let scissors = [vk::Rect2D::default()
+            .offset(vk::Offset2D { x: 0, y: 0 })
+            .extent(shadow_maps.extent())];
+
+        for (shadow_map_index, framebuffer) in shadow_maps.framebuffers().iter().enumerate() {
+            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                .render_pass(shadow_maps.render_pass())
+                .framebuffer(*framebuffer)
+                .render_area(scissors[0])
+                .clear_values(&clear_values);
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+
+            if shadow_map_index < shadow_map_count {
+                device.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    shadow_maps.pipeline(),
+                );
+                device.cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    shadow_maps.pipeline_layout(),
+                    0,
+                    &[self.memory.frame_descriptor_sets()[self.current_frame]],
+                    &[],
+                );
+                device.cmd_set_viewport(command_buffer, 0, &viewports);
+                device.cmd_set_scissor(command_buffer, 0, &scissors);
+
+                for object in self.scene.objects() {
+                    let push_constants = ShadowPushConstants {
+                        model: object.interpolated_model_matrix(interpolation),
+                        shadow_map_index: shadow_map_index as u32,
+                    };
+                    if !shadow_maps.push_constant_stages().is_empty() {
+                        device.cmd_push_constants(
+                            command_buffer,
+                            shadow_maps.pipeline_layout(),
+                            shadow_maps.push_constant_stages(),
+                            0,
+                            push_constants::as_bytes(&push_constants),
+                        );
+                    }
+
+                    let mesh = &self.memory.meshes()[object.mesh()];
+                    device.cmd_bind_vertex_buffers(command_buffer, 0, &[mesh.vertex_buffer], &[0]);
+                    device.cmd_bind_index_buffer(
+                        command_buffer,
+                        mesh.index_buffer,
+                        0,
+                        vk::IndexType::UINT32,
+                    );
+                    for sub_mesh in mesh.sub_meshes.iter() {
+                        device.cmd_draw_indexed(
+                            command_buffer,
+                            sub_mesh.index_count,
+                            1,
+                            sub_mesh.first_index,
+                            0,
+                            0,
+                        );
+                    }
+                }
+            }
+
+            device.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    unsafe fn record_command_buffer(
+        &self,
+        image_index: u32,
+        shadow_map_count: usize,
+        interpolation: f32,
+    ) -> VkResult<()> {
         // TODO refactor
         let begin_info = vk::CommandBufferBeginInfo::default();
         let command_buffer = self.interface.command_buffers()[self.current_frame];
@@ -193,6 +566,8 @@ impl VulkanRenderer {
             .device()
             .begin_command_buffer(command_buffer, &begin_info)?;
 
+        self.record_shadow_passes(command_buffer, shadow_map_count, interpolation);
+
         let clear_values = [
             vk::ClearValue {
                 color: vk::ClearColorValue {
@@ -208,7 +583,7 @@ impl VulkanRenderer {
             .framebuffer(self.render_targets.framebuffers()[image_index as usize])
             .render_area(vk::Rect2D {
                 offset: vk::Offset2D { x: 0, y: 0 },
-                extent: self.render_targets.swapchain_extent(),
+                extent: self.render_targets.extent(),
             })
             .clear_values(&clear_values);
 
@@ -223,51 +598,82 @@ impl VulkanRenderer {
             vk::PipelineBindPoint::GRAPHICS,
             self.render_targets.pipeline(),
         );
-
-        let vertex_buffers = [self.memory.vertex_buffer().buffer()];
-        let offsets = [0];
-        self.context
-            .device()
-            .cmd_bind_vertex_buffers(command_buffer, 0, &vertex_buffers, &offsets);
-        self.context.device().cmd_bind_index_buffer(
+        self.context.device().cmd_bind_descriptor_sets(
             command_buffer,
-            self.memory.index_buffer().buffer(),
+            vk::PipelineBindPoint::GRAPHICS,
+            self.render_targets.pipeline_layout(),
             0,
-            vk::IndexType::UINT32,
+            &[self.memory.frame_descriptor_sets()[self.current_frame]],
+            &[],
         );
 
         let viewports = [vk::Viewport::default()
             .x(0.)
             .y(0.)
-            .width(self.render_targets.swapchain_extent().width as f32)
-            .height(self.render_targets.swapchain_extent().height as f32)
+            .width(self.render_targets.extent().width as f32)
+            .height(self.render_targets.extent().height as f32)
             .min_depth(0.)
             .max_depth(1.)];
         let scissors = [vk::Rect2D::default()
             .offset(vk::Offset2D { x: 0, y: 0 })
-            .extent(self.render_targets.swapchain_extent())];
+            .extent(self.render_targets.extent())];
         self.context
             .device()
             .cmd_set_viewport(command_buffer, 0, &viewports);
         self.context
             .device()
             .cmd_set_scissor(command_buffer, 0, &scissors);
-        self.context.device().cmd_bind_descriptor_sets(
-            command_buffer,
-            vk::PipelineBindPoint::GRAPHICS,
-            self.render_targets.pipeline_layout(),
-            0,
-            &[self.memory.descriptor_sets()[self.current_frame]],
-            &[],
-        );
-        self.context.device().cmd_draw_indexed(
-            command_buffer,
-            self.memory.index_buffer_len(),
-            1,
-            0,
-            0,
-            0,
-        );
+        for object in self.scene.objects() {
+            let push_constants = ObjectPushConstants {
+                model: object.interpolated_model_matrix(interpolation),
+            };
+            let push_constant_stages = self.render_targets.push_constant_stages();
+            if !push_constant_stages.is_empty() {
+                self.context.device().cmd_push_constants(
+                    command_buffer,
+                    self.render_targets.pipeline_layout(),
+                    push_constant_stages,
+                    0,
+                    push_constants::as_bytes(&push_constants),
+                );
+            }
+
+            let mesh = &self.memory.meshes()[object.mesh()];
+            // Replaces the materials of every sub mesh
+            let material_set = object
+                .material()
+                .map(|material| self.memory.scene_material_sets()[material]);
+            self.context.device().cmd_bind_vertex_buffers(
+                command_buffer,
+                0,
+                &[mesh.vertex_buffer],
+                &[0],
+            );
+            self.context.device().cmd_bind_index_buffer(
+                command_buffer,
+                mesh.index_buffer,
+                0,
+                vk::IndexType::UINT32,
+            );
+            for sub_mesh in mesh.sub_meshes.iter() {
+                self.context.device().cmd_bind_descriptor_sets(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.render_targets.pipeline_layout(),
+                    1,
+                    &[material_set.unwrap_or(sub_mesh.material_set)],
+                    &[],
+                );
+                self.context.device().cmd_draw_indexed(
+                    command_buffer,
+                    sub_mesh.index_count,
+                    1,
+                    sub_mesh.first_index,
+                    0,
+                    0,
+                );
+            }
+        }
         self.context.device().cmd_end_render_pass(command_buffer);
         self.context.device().end_command_buffer(command_buffer)?;
         Ok(())
@@ -296,6 +702,21 @@ impl VulkanRenderer {
         }
     }
 
+    // No swapchain image is acquired or presented, so there are no semaphores to wait on
+    fn submit_offscreen_command_buffer(&self) -> VkResult<()> {
+        let command_buffers = [self.interface.command_buffers()[self.current_frame]];
+
+        let submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+
+        unsafe {
+            self.context.device().queue_submit(
+                self.interface.queues().graphics_queue(),
+                &[submit_info],
+                self.interface.sync_objects().in_flight_fences[self.current_frame],
+            )
+        }
+    }
+
     fn present_image(
         &mut self,
         image_index: u32,
@@ -354,21 +775,25 @@ impl VulkanRenderer {
         //      and destroy the old swap chain as soon as you've finished
         //      using it.
 
-        self.render_targets = RenderTargets::new(&self.context, swapchain_builder)?;
+        self.render_targets = RenderTargets::new(
+            &self.context,
+            swapchain_builder,
+            &self.assets,
+            &self.shadows,
+        )?;
         match should_recreate_memory {
             ShouldRecreateMemory::Entirely => {
-                self.memory = Memory::new(&self.context, &self.interface, &self.render_targets)?
-            }
-            ShouldRecreateMemory::OnlyDescriptors => unsafe {
-                let (descriptor_pool, descriptor_sets) = Memory::create_descriptors(
+                self.memory = Memory::new(
                     &self.context,
+                    &self.interface,
                     &self.render_targets,
-                    self.memory.uniform_buffers(),
-                    self.memory.texture().image_view(),
-                    self.memory.sampler(),
-                )?;
+                    &self.assets,
+                    &self.scene,
+                )?
+            }
+            ShouldRecreateMemory::OnlyDescriptors => unsafe {
                 self.memory
-                    .set_descriptors(descriptor_pool, descriptor_sets);
+                    .recreate_descriptors(&self.context, &self.render_targets)?;
             },
         }
         Ok(())
@@ -390,15 +815,18 @@ impl VulkanRenderer {
             return Ok((swapchain_builder, ShouldRecreateMemory::OnlyDescriptors));
         }
 
-        self.memory.destroy(self.context.device());
+        self.memory.destroy(&self.context);
         self.interface.destroy(self.context.device());
         self.context.destroy_device();
 
         let physical_device_data = PhysicalDeviceData::new(
-            self.context.surface_instance(),
             self.context.instance(),
-            self.context.surface(),
-            window_inner_size,
+            Some(&PresentationSurface {
+                surface_instance: self.context.surface_instance(),
+                surface: self.context.surface(),
+                window_inner_size,
+            }),
+            self.device_selector.as_ref(),
         )?;
 
         self.context.set_device(
@@ -412,7 +840,9 @@ impl VulkanRenderer {
         self.interface = VulkanInterface::new(&self.context, physical_device_data.queue_families)?;
 
         Ok((
-            physical_device_data.swapchain_builder,
+            physical_device_data
+                .swapchain_builder
+                .expect("A device picked for a surface should have a swapchain builder"),
             ShouldRecreateMemory::Entirely,
         ))
     }
@@ -424,7 +854,7 @@ impl VulkanRenderer {
                        Attempting to clean resources anyway..."
             );
         }
-        self.memory.destroy(self.context.device());
+        self.memory.destroy(&self.context);
         self.interface.destroy(self.context.device());
         self.render_targets.destroy(&self.context);
         self.context.destroy();
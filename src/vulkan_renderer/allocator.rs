@@ -0,0 +1,516 @@
+mod block;
+mod errors;
+
+use std::{ffi::c_void, fmt::Display};
+
+use ash::vk;
+use block::Block;
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+use errors::FailedToFindMemoryTypeIndex;
+
+// Size of the vk::DeviceMemory shared by the resources of a memory type
+const BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+// Small heaps, like the host visible device local heap of some GPUs, are split in more blocks
+const MIN_BLOCKS_PER_HEAP: vk::DeviceSize = 8;
+
+// Resources are sub-allocated from large memory blocks, as the number of allocations a driver
+// supports is limited by maxMemoryAllocationCount.
+// Host visible blocks stay mapped until they are freed.
+// Defragmenting only moves the allocations marked as movable, which the owner of the resources
+// has to copy, images and host visible buffers stay where they were allocated
+pub struct Allocator {
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    buffer_image_granularity: vk::DeviceSize,
+    // Indexed by memory type
+    blocks: Box<[Vec<Block>]>,
+}
+
+// Linear and optimal resources have to be buffer_image_granularity bytes apart in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    // Buffers and images with a linear tiling
+    Linear,
+    // Images with an optimal tiling
+    Optimal,
+}
+
+pub struct Allocation {
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    mapped_ptr: *mut c_void,
+    memory_type_index: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryStats {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    // Sum of the size of the blocks
+    pub reserved_bytes: vk::DeviceSize,
+    pub used_bytes: vk::DeviceSize,
+    pub free_range_count: usize,
+    pub largest_free_range: vk::DeviceSize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AllocatorStats {
+    pub total: MemoryStats,
+    // Only the memory types with at least one block
+    pub memory_types: Box<[(u32, MemoryStats)]>,
+}
+
+impl Allocator {
+    pub fn new(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        physical_device_properties: &vk::PhysicalDeviceProperties,
+    ) -> Self {
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        Self {
+            blocks: (0..memory_properties.memory_type_count)
+                .map(|_| Vec::new())
+                .collect(),
+            buffer_image_granularity: physical_device_properties
+                .limits
+                .buffer_image_granularity
+                .max(1),
+            memory_properties,
+        }
+    }
+
+    pub unsafe fn allocate(
+        &mut self,
+        device: &ash::Device,
+        requirements: vk::MemoryRequirements,
+        properties: vk::MemoryPropertyFlags,
+        kind: ResourceKind,
+    ) -> Result<Allocation> {
+        let memory_type_index =
+            self.find_memory_type_index(requirements.memory_type_bits, properties)?;
+        let block_size = self.block_size(memory_type_index);
+        let blocks = &mut self.blocks[memory_type_index as usize];
+
+        let existing_allocation = blocks.iter_mut().enumerate().find_map(|(index, block)| {
+            if block.is_being_emptied {
+                return None;
+            }
+            let offset = block.allocate(
+                requirements.size,
+                requirements.alignment,
+                kind,
+                self.buffer_image_granularity,
+            )?;
+            Some((index, offset))
+        });
+        let (index, offset) = match existing_allocation {
+            Some(allocation) => allocation,
+            None => {
+                // Resources larger than half a block get a block of their own
+                let size = if requirements.size > block_size / 2 {
+                    requirements.size
+                } else {
+                    block_size
+                };
+                let is_host_visible = self.memory_properties.memory_types
+                    [memory_type_index as usize]
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+                let mut block = create_block(device, memory_type_index, size, is_host_visible)?;
+                let offset = block
+                    .allocate(
+                        requirements.size,
+                        requirements.alignment,
+                        kind,
+                        self.buffer_image_granularity,
+                    )
+                    .expect("A new block should fit the allocation");
+                blocks.push(block);
+                (blocks.len() - 1, offset)
+            }
+        };
+        let block = &blocks[index];
+
+        Ok(Allocation {
+            memory: block.memory,
+            offset,
+            mapped_ptr: if block.mapped_ptr.is_null() {
+                block.mapped_ptr
+            } else {
+                block.mapped_ptr.byte_add(offset as usize)
+            },
+            memory_type_index,
+        })
+    }
+
+    // A block left empty is kept if it's the only empty one of its memory type, so that freeing
+    // and allocating resources again doesn't give memory back to the driver to ask for it again
+    pub unsafe fn free(&mut self, device: &ash::Device, allocation: &Allocation) {
+        let block_size = self.block_size(allocation.memory_type_index);
+        let Some(block) = self.find_block_mut(allocation) else {
+            debug_assert!(
+                false,
+                "Allocator::free() was called with an unknown allocation"
+            );
+            return;
+        };
+
+        block.free(allocation.offset);
+        if block.is_empty() {
+            release_empty_blocks(
+                device,
+                &mut self.blocks[allocation.memory_type_index as usize],
+                block_size,
+            );
+        }
+    }
+
+    // Allows a defragmentation to move the allocation to another block
+    pub fn set_movable(&mut self, allocation: &Allocation) {
+        match self.find_block_mut(allocation) {
+            Some(block) => block.set_movable(allocation.offset),
+            None => debug_assert!(
+                false,
+                "Allocator::set_movable() was called with an unknown allocation"
+            ),
+        }
+    }
+
+    // True if compacting the allocations of a memory type could give one of its blocks back
+    pub fn is_fragmented(&self) -> bool {
+        self.blocks
+            .iter()
+            .any(|blocks| are_blocks_fragmented(blocks))
+    }
+
+    // Chooses, for every memory type, a block whose allocations all fit in the other blocks.
+    // Nothing is allocated in the chosen blocks until finish_defragmentation() is called, so that
+    // moving their allocations frees them.
+    // Returns false if no block can be emptied
+    pub fn plan_defragmentation(&mut self) -> bool {
+        let mut is_block_chosen = false;
+        for blocks in self.blocks.iter_mut() {
+            if let Some(index) = choose_block_to_empty(blocks, self.buffer_image_granularity) {
+                blocks[index].is_being_emptied = true;
+                is_block_chosen = true;
+            }
+        }
+        is_block_chosen
+    }
+
+    // True if the allocation has to be moved by the current defragmentation
+    pub fn is_being_moved(&self, allocation: &Allocation) -> bool {
+        self.blocks[allocation.memory_type_index as usize]
+            .iter()
+            .any(|block| block.memory == allocation.memory && block.is_being_emptied)
+    }
+
+    // The blocks that were emptied are given back to the driver, the others can be allocated in
+    // again
+    pub unsafe fn finish_defragmentation(&mut self, device: &ash::Device) {
+        for memory_type_index in 0..self.blocks.len() {
+            let block_size = self.block_size(memory_type_index as u32);
+            let blocks = &mut self.blocks[memory_type_index];
+            for block in blocks.iter_mut() {
+                block.is_being_emptied = false;
+            }
+            release_empty_blocks(device, blocks, block_size);
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let mut total = MemoryStats::default();
+        let memory_types = self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, blocks)| !blocks.is_empty())
+            .map(|(memory_type_index, blocks)| {
+                let mut stats = MemoryStats::default();
+                for block in blocks {
+                    block.add_stats(&mut stats);
+                    block.add_stats(&mut total);
+                }
+                (memory_type_index as u32, stats)
+            })
+            .collect();
+
+        AllocatorStats {
+            total,
+            memory_types,
+        }
+    }
+
+    // Every allocation should have been freed before
+    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        let stats = self.stats();
+        if stats.total.allocation_count != 0 {
+            eprintln!(
+                "WARNING: {} GPU allocations were not freed",
+                stats.total.allocation_count
+            );
+        }
+        for blocks in self.blocks.iter_mut() {
+            for block in blocks.drain(..) {
+                destroy_block(device, &block);
+            }
+        }
+    }
+
+    fn find_memory_type_index(
+        &self,
+        memory_type_filter: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Result<u32, FailedToFindMemoryTypeIndex> {
+        self.memory_properties
+            .memory_types
+            .iter()
+            .take(self.memory_properties.memory_type_count as usize)
+            .enumerate()
+            .find(|(index, memory_type)| {
+                memory_type_filter & (1 << index) != 0
+                    && memory_type.property_flags & properties == properties
+            })
+            .map(|(index, _)| index as u32)
+            .ok_or(FailedToFindMemoryTypeIndex {})
+    }
+
+    fn find_block_mut(&mut self, allocation: &Allocation) -> Option<&mut Block> {
+        self.blocks[allocation.memory_type_index as usize]
+            .iter_mut()
+            .find(|block| block.memory == allocation.memory)
+    }
+
+    fn block_size(&self, memory_type_index: u32) -> vk::DeviceSize {
+        let heap_index = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+        BLOCK_SIZE.min(heap_size / MIN_BLOCKS_PER_HEAP)
+    }
+}
+
+impl Allocation {
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    // Null if the memory isn't host visible
+    pub fn mapped_ptr(&self) -> *mut c_void {
+        self.mapped_ptr
+    }
+}
+
+impl Display for MemoryStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} allocations in {} blocks, {:.1} / {:.1} MiB used, {} free ranges (largest {:.1} MiB)",
+            self.allocation_count,
+            self.block_count,
+            to_mebibytes(self.used_bytes),
+            to_mebibytes(self.reserved_bytes),
+            self.free_range_count,
+            to_mebibytes(self.largest_free_range),
+        )
+    }
+}
+
+impl Display for AllocatorStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GPU memory: {}", self.total)?;
+        for (memory_type_index, stats) in self.memory_types.iter() {
+            write!(f, "\n    memory type {memory_type_index}: {stats}")?;
+        }
+        Ok(())
+    }
+}
+
+unsafe fn create_block(
+    device: &ash::Device,
+    memory_type_index: u32,
+    size: vk::DeviceSize,
+    is_host_visible: bool,
+) -> Result<Block> {
+    let memory = device
+        .allocate_memory(
+            &vk::MemoryAllocateInfo::default()
+                .allocation_size(size)
+                .memory_type_index(memory_type_index),
+            None,
+        )?
+        .defer(|memory| device.free_memory(memory, None));
+
+    let mapped_ptr = if is_host_visible {
+        device.map_memory(*memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?
+    } else {
+        std::ptr::null_mut()
+    };
+
+    Ok(Block::new(ScopeGuard::into_inner(memory), size, mapped_ptr))
+}
+
+// Keeps at most one empty block, which isn't larger than block_size.
+// Blocks that are being emptied are kept until the end of the defragmentation
+unsafe fn release_empty_blocks(
+    device: &ash::Device,
+    blocks: &mut Vec<Block>,
+    block_size: vk::DeviceSize,
+) {
+    let mut is_spare_kept = false;
+    blocks.retain(|block| {
+        if !block.is_empty() || block.is_being_emptied {
+            return true;
+        }
+        if !is_spare_kept && block.size <= block_size {
+            is_spare_kept = true;
+            return true;
+        }
+        destroy_block(device, block);
+        false
+    });
+}
+
+// The free ranges of the blocks in use add up to the size of the largest of them
+fn are_blocks_fragmented(blocks: &[Block]) -> bool {
+    let (free_bytes, largest_block_size) = blocks.iter().filter(|block| !block.is_empty()).fold(
+        (0, 0),
+        |(free_bytes, largest_block_size), block| {
+            (
+                free_bytes + block.size - block.used_bytes(),
+                largest_block_size.max(block.size),
+            )
+        },
+    );
+    largest_block_size != 0 && free_bytes >= largest_block_size
+}
+
+// The least used block whose allocations are all movable and fit in the free ranges of the other
+// blocks that are in use, moving them to an empty block wouldn't free anything
+fn choose_block_to_empty(blocks: &[Block], granularity: vk::DeviceSize) -> Option<usize> {
+    let (index, allocations) = blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| !block.is_empty())
+        .filter_map(|(index, block)| Some((index, block.movable_allocations()?)))
+        .min_by_key(|(index, _)| blocks[*index].used_bytes())?;
+
+    let mut other_blocks: Vec<_> = blocks
+        .iter()
+        .enumerate()
+        .filter(|(other_index, block)| *other_index != index && !block.is_empty())
+        .map(|(_, block)| block.clone())
+        .collect();
+    let do_allocations_fit = allocations.into_iter().all(|(size, alignment, kind)| {
+        other_blocks
+            .iter_mut()
+            .any(|block| block.allocate(size, alignment, kind, granularity).is_some())
+    });
+    do_allocations_fit.then_some(index)
+}
+
+unsafe fn destroy_block(device: &ash::Device, block: &Block) {
+    if !block.mapped_ptr.is_null() {
+        device.unmap_memory(block.memory);
+    }
+    device.free_memory(block.memory, None);
+}
+
+fn to_mebibytes(bytes: vk::DeviceSize) -> f64 {
+    bytes as f64 / (1024. * 1024.)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GRANULARITY: vk::DeviceSize = 1024;
+
+    // Every allocation is linear, the movable ones are given by is_movable
+    fn block(size: vk::DeviceSize, allocations: &[(vk::DeviceSize, bool)]) -> Block {
+        let mut block = Block::new(vk::DeviceMemory::null(), size, std::ptr::null_mut());
+        for &(allocation_size, is_movable) in allocations {
+            let offset = block
+                .allocate(allocation_size, 1, ResourceKind::Linear, GRANULARITY)
+                .unwrap();
+            if is_movable {
+                block.set_movable(offset);
+            }
+        }
+        block
+    }
+
+    #[test]
+    fn fragmentation_needs_a_block_of_free_ranges() {
+        assert!(!are_blocks_fragmented(&[block(1024, &[(100, true)])]));
+        assert!(!are_blocks_fragmented(&[
+            block(1024, &[(600, true)]),
+            block(1024, &[(600, true)]),
+        ]));
+        assert!(are_blocks_fragmented(&[
+            block(1024, &[(500, true)]),
+            block(1024, &[(400, true)]),
+        ]));
+        // The spare empty block isn't fragmentation
+        assert!(!are_blocks_fragmented(&[
+            block(1024, &[(600, true)]),
+            block(1024, &[]),
+        ]));
+    }
+
+    #[test]
+    fn the_least_used_block_is_emptied() {
+        let blocks = [
+            block(1024, &[(500, true)]),
+            block(1024, &[(100, true), (100, true)]),
+            block(1024, &[(300, true)]),
+        ];
+
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), Some(1));
+    }
+
+    #[test]
+    fn blocks_with_unmovable_allocations_are_kept() {
+        let blocks = [
+            block(1024, &[(500, true)]),
+            block(1024, &[(100, true), (100, false)]),
+            block(1024, &[(300, true)]),
+        ];
+
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), Some(2));
+    }
+
+    #[test]
+    fn allocations_have_to_fit_in_the_other_blocks() {
+        let blocks = [
+            block(1024, &[(800, true)]),
+            block(1024, &[(300, true)]),
+            block(1024, &[(900, false)]),
+        ];
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), None);
+
+        // Each allocation fits somewhere
+        let blocks = [
+            block(1024, &[(800, true)]),
+            block(1024, &[(100, true), (100, true)]),
+            block(1024, &[(900, false)]),
+        ];
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), Some(1));
+    }
+
+    #[test]
+    fn empty_blocks_are_neither_emptied_nor_filled() {
+        let blocks = [block(1024, &[]), block(1024, &[(100, true)])];
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), None);
+
+        let blocks = [block(1024, &[])];
+        assert_eq!(choose_block_to_empty(&blocks, GRANULARITY), None);
+    }
+}
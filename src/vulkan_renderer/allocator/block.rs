@@ -0,0 +1,345 @@
+use std::ffi::c_void;
+
+use ash::vk;
+
+use super::{MemoryStats, ResourceKind};
+
+// A single vk::DeviceMemory split into chunks, each of them is either free or used by one
+// resource
+#[derive(Clone)]
+pub struct Block {
+    pub memory: vk::DeviceMemory,
+    pub size: vk::DeviceSize,
+    // Null if the memory type isn't host visible
+    pub mapped_ptr: *mut c_void,
+    // Nothing is allocated in a block that is being emptied by a defragmentation
+    pub is_being_emptied: bool,
+    // Sorted by offset and covering the whole block, two free chunks are never adjacent
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Chunk {
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    // None if the chunk is free
+    kind: Option<ResourceKind>,
+    // Whether the resource can be moved to another block by a defragmentation
+    is_movable: bool,
+}
+
+impl Chunk {
+    fn free(offset: vk::DeviceSize, size: vk::DeviceSize) -> Self {
+        Self {
+            offset,
+            size,
+            alignment: 1,
+            kind: None,
+            is_movable: false,
+        }
+    }
+
+    fn end(&self) -> vk::DeviceSize {
+        self.offset + self.size
+    }
+}
+
+impl Block {
+    pub fn new(memory: vk::DeviceMemory, size: vk::DeviceSize, mapped_ptr: *mut c_void) -> Self {
+        Self {
+            memory,
+            size,
+            mapped_ptr,
+            is_being_emptied: false,
+            chunks: vec![Chunk::free(0, size)],
+        }
+    }
+
+    // First fit, returns the offset of the allocation.
+    // Linear and optimal resources can't share a page of buffer_image_granularity bytes
+    pub fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+        kind: ResourceKind,
+        granularity: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, offset) = self.chunks.iter().enumerate().find_map(|(i, chunk)| {
+            if chunk.kind.is_some() || chunk.size < size {
+                return None;
+            }
+
+            let mut offset = align_up(chunk.offset, alignment);
+            let previous_kind = i
+                .checked_sub(1)
+                .and_then(|previous| self.chunks[previous].kind);
+            if previous_kind.is_some_and(|previous_kind| previous_kind != kind) {
+                offset = align_up(offset, granularity);
+            }
+            let end = offset.checked_add(size)?;
+            if end > chunk.end() {
+                return None;
+            }
+
+            let next_kind = self.chunks.get(i + 1).and_then(|next| next.kind);
+            if next_kind.is_some_and(|next_kind| next_kind != kind)
+                && align_down(end - 1, granularity) == align_down(chunk.end(), granularity)
+            {
+                return None;
+            }
+            Some((i, offset))
+        })?;
+
+        // The free chunk is split into the padding, the allocation and the remaining space
+        let chunk = self.chunks[index];
+        let used = Chunk {
+            offset,
+            size,
+            alignment,
+            kind: Some(kind),
+            is_movable: false,
+        };
+        let padding = Chunk::free(chunk.offset, offset - chunk.offset);
+        let remaining = Chunk::free(used.end(), chunk.end() - used.end());
+        let new_chunks = [padding, used, remaining]
+            .into_iter()
+            .filter(|chunk| chunk.size > 0);
+        self.chunks.splice(index..=index, new_chunks);
+        Some(offset)
+    }
+
+    // The freed range is merged with the free chunks around it
+    pub fn free(&mut self, offset: vk::DeviceSize) {
+        let Some(mut index) = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.offset == offset && chunk.kind.is_some())
+        else {
+            debug_assert!(false, "Block::free() was called with an unknown offset");
+            return;
+        };
+        self.chunks[index].kind = None;
+        self.chunks[index].is_movable = false;
+
+        if self
+            .chunks
+            .get(index + 1)
+            .is_some_and(|next| next.kind.is_none())
+        {
+            let next = self.chunks.remove(index + 1);
+            self.chunks[index].size += next.size;
+        }
+        if index > 0 && self.chunks[index - 1].kind.is_none() {
+            let chunk = self.chunks.remove(index);
+            index -= 1;
+            self.chunks[index].size += chunk.size;
+        }
+    }
+
+    pub fn set_movable(&mut self, offset: vk::DeviceSize) {
+        match self
+            .chunks
+            .iter_mut()
+            .find(|chunk| chunk.offset == offset && chunk.kind.is_some())
+        {
+            Some(chunk) => chunk.is_movable = true,
+            None => debug_assert!(
+                false,
+                "Block::set_movable() was called with an unknown offset"
+            ),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.iter().all(|chunk| chunk.kind.is_none())
+    }
+
+    pub fn used_bytes(&self) -> vk::DeviceSize {
+        self.used_chunks().map(|chunk| chunk.size).sum()
+    }
+
+    // The size, alignment and kind of every allocation, None if one of them can't be moved
+    pub fn movable_allocations(
+        &self,
+    ) -> Option<Vec<(vk::DeviceSize, vk::DeviceSize, ResourceKind)>> {
+        self.used_chunks()
+            .map(|chunk| {
+                chunk
+                    .is_movable
+                    .then_some((chunk.size, chunk.alignment, chunk.kind?))
+            })
+            .collect()
+    }
+
+    fn used_chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter().filter(|chunk| chunk.kind.is_some())
+    }
+
+    pub fn add_stats(&self, stats: &mut MemoryStats) {
+        stats.block_count += 1;
+        stats.reserved_bytes += self.size;
+        for chunk in self.chunks.iter() {
+            if chunk.kind.is_some() {
+                stats.allocation_count += 1;
+                stats.used_bytes += chunk.size;
+            } else {
+                stats.free_range_count += 1;
+                stats.largest_free_range = stats.largest_free_range.max(chunk.size);
+            }
+        }
+    }
+}
+
+// alignment is a power of two, as required by Vulkan
+fn align_up(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+fn align_down(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    offset & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const GRANULARITY: vk::DeviceSize = 1024;
+
+    fn block(size: vk::DeviceSize) -> Block {
+        Block::new(vk::DeviceMemory::null(), size, std::ptr::null_mut())
+    }
+
+    fn allocate_linear(
+        block: &mut Block,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        block.allocate(size, alignment, ResourceKind::Linear, GRANULARITY)
+    }
+
+    fn allocate_optimal(
+        block: &mut Block,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        block.allocate(size, alignment, ResourceKind::Optimal, GRANULARITY)
+    }
+
+    fn stats(block: &Block) -> MemoryStats {
+        let mut stats = MemoryStats::default();
+        block.add_stats(&mut stats);
+        stats
+    }
+
+    #[test]
+    fn allocations_are_aligned() {
+        let mut block = block(4096);
+
+        assert_eq!(allocate_linear(&mut block, 10, 1), Some(0));
+        assert_eq!(allocate_linear(&mut block, 10, 16), Some(16));
+        // The padding left by the previous alignment is reused
+        assert_eq!(allocate_linear(&mut block, 4, 4), Some(12));
+        assert_eq!(allocate_linear(&mut block, 1, 256), Some(256));
+        assert_eq!(stats(&block).allocation_count, 4);
+    }
+
+    #[test]
+    fn different_kinds_dont_share_a_page_with_the_previous_chunk() {
+        let mut block = block(4096);
+
+        assert_eq!(allocate_linear(&mut block, 100, 1), Some(0));
+        assert_eq!(allocate_optimal(&mut block, 100, 1), Some(1024));
+        // The same kind doesn't need to be on another page
+        assert_eq!(allocate_optimal(&mut block, 100, 1), Some(1124));
+        assert_eq!(allocate_linear(&mut block, 100, 1), Some(100));
+        assert_eq!(allocate_linear(&mut block, 900, 1), Some(2048));
+    }
+
+    #[test]
+    fn different_kinds_dont_share_a_page_with_the_next_chunk() {
+        let mut block = block(4096);
+
+        assert_eq!(allocate_optimal(&mut block, 100, 1), Some(0));
+        assert_eq!(allocate_optimal(&mut block, 100, 1), Some(100));
+        block.free(0);
+
+        // The free range before the optimal image shares its page
+        assert_eq!(allocate_linear(&mut block, 50, 1), Some(1024));
+        assert_eq!(allocate_optimal(&mut block, 50, 1), Some(0));
+    }
+
+    #[test]
+    fn granularity_is_ignored_for_a_single_kind() {
+        let mut block = block(4096);
+
+        for i in 0..4 {
+            assert_eq!(allocate_optimal(&mut block, 1000, 1), Some(i * 1000));
+        }
+    }
+
+    #[test]
+    fn freed_ranges_are_merged() {
+        let mut block = block(4096);
+        for i in 0..4 {
+            assert_eq!(allocate_linear(&mut block, 100, 1), Some(i * 100));
+        }
+
+        block.free(100);
+        block.free(200);
+        assert_eq!(stats(&block).free_range_count, 2);
+        assert_eq!(stats(&block).largest_free_range, 3696);
+
+        block.free(0);
+        assert_eq!(stats(&block).free_range_count, 2);
+        assert_eq!(allocate_linear(&mut block, 300, 1), Some(0));
+
+        block.free(0);
+        block.free(300);
+        assert!(block.is_empty());
+        assert_eq!(stats(&block).free_range_count, 1);
+        assert_eq!(stats(&block).largest_free_range, 4096);
+    }
+
+    #[test]
+    fn full_blocks_refuse_allocations() {
+        let mut block = block(256);
+
+        assert_eq!(allocate_linear(&mut block, 256, 1), Some(0));
+        assert_eq!(allocate_linear(&mut block, 1, 1), None);
+        assert_eq!(stats(&block).free_range_count, 0);
+
+        block.free(0);
+        assert_eq!(allocate_linear(&mut block, 257, 1), None);
+        assert_eq!(allocate_linear(&mut block, 128, 1), Some(0));
+        // The remaining range is too small once aligned
+        assert_eq!(allocate_linear(&mut block, 64, 256), None);
+        assert_eq!(allocate_linear(&mut block, 128, 128), Some(128));
+        assert_eq!(stats(&block).used_bytes, 256);
+    }
+
+    #[test]
+    fn only_movable_allocations_are_listed() {
+        let mut block = block(4096);
+        assert_eq!(allocate_linear(&mut block, 100, 4), Some(0));
+        assert_eq!(allocate_linear(&mut block, 200, 8), Some(104));
+
+        assert_eq!(block.movable_allocations(), None);
+        block.set_movable(0);
+        assert_eq!(block.movable_allocations(), None);
+        block.set_movable(104);
+        assert_eq!(
+            block.movable_allocations(),
+            Some(vec![
+                (100, 4, ResourceKind::Linear),
+                (200, 8, ResourceKind::Linear)
+            ])
+        );
+        assert_eq!(block.used_bytes(), 300);
+
+        // A range that is freed and allocated again isn't movable anymore
+        block.free(0);
+        assert_eq!(allocate_linear(&mut block, 100, 4), Some(0));
+        assert_eq!(block.movable_allocations(), None);
+    }
+}
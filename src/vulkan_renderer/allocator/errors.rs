@@ -0,0 +1,6 @@
+use rs42::error_struct_custom_display;
+
+error_struct_custom_display!(
+    FailedToFindMemoryTypeIndex,
+    "Failed to find memory type index when trying to allocate memory for a resource"
+);
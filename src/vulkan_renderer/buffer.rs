@@ -2,21 +2,21 @@ use std::{ffi::c_void, ptr::copy_nonoverlapping};
 
 use ash::vk;
 use rs42::{
-    defer,
     scope_guard::{Defer, ScopeGuard},
     Result,
 };
 
 use super::{
-    memory::Memory, single_time_command::SingleTimeCommand, vulkan_context::VulkanContext,
-    vulkan_interface::VulkanInterface,
+    allocator::{Allocation, ResourceKind},
+    vulkan_context::VulkanContext,
 };
 
 pub struct Buffer {
     buffer: vk::Buffer,
-    memory: vk::DeviceMemory,
-    #[cfg(debug_assertions)]
+    allocation: Allocation,
     size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    properties: vk::MemoryPropertyFlags,
     #[cfg(debug_assertions)]
     is_destroyed: bool,
 }
@@ -43,51 +43,82 @@ impl Buffer {
                 )?
                 .defer(|buffer| context.device().destroy_buffer(buffer, None));
 
-            let memory = Self::init_memory(context, *buffer, properties)?
-                .defer(|memory| context.device().free_memory(memory, None));
+            let allocation = context
+                .allocator()
+                .allocate(
+                    context.device(),
+                    context.device().get_buffer_memory_requirements(*buffer),
+                    properties,
+                    ResourceKind::Linear,
+                )?
+                .defer(|allocation| context.allocator().free(context.device(), &allocation));
 
-            context.device().bind_buffer_memory(*buffer, *memory, 0)?;
+            context.device().bind_buffer_memory(
+                *buffer,
+                allocation.memory(),
+                allocation.offset(),
+            )?;
 
             Ok(Buffer {
+                allocation: ScopeGuard::into_inner(allocation),
                 buffer: ScopeGuard::into_inner(buffer),
-                memory: ScopeGuard::into_inner(memory),
-                #[cfg(debug_assertions)]
                 size,
+                usage,
+                properties,
                 #[cfg(debug_assertions)]
                 is_destroyed: false,
             })
         }
     }
 
-    unsafe fn init_memory(
+    // Defragmenting the memory can replace the buffer with a copy, so the buffer is always a
+    // transfer source and destination
+    pub fn new_movable(
         context: &VulkanContext,
-        buffer: vk::Buffer,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
         properties: vk::MemoryPropertyFlags,
-    ) -> Result<vk::DeviceMemory> {
-        unsafe {
-            let memory_requirement = context.device().get_buffer_memory_requirements(buffer);
-
-            let memory = context.device().allocate_memory(
-                &vk::MemoryAllocateInfo::default()
-                    .allocation_size(memory_requirement.size)
-                    .memory_type_index(Memory::find_memory_type_index(
-                        context,
-                        memory_requirement.memory_type_bits,
-                        properties,
-                    )?),
-                None,
-            )?;
+    ) -> Result<Self> {
+        let buffer = Self::new(
+            context,
+            size,
+            usage | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::SharingMode::EXCLUSIVE,
+            properties,
+        )?;
+        context.allocator().set_movable(&buffer.allocation);
+        Ok(buffer)
+    }
 
-            Ok(memory)
-        }
+    // True if the current defragmentation wants the buffer to be replaced with a copy
+    pub fn is_being_moved(&self, context: &VulkanContext) -> bool {
+        context.allocator().is_being_moved(&self.allocation)
     }
 
-    pub unsafe fn copy_from_ram<T>(
+    // Records the copy of the whole buffer into a new movable buffer, which can be used once the
+    // command buffer is executed
+    pub unsafe fn record_copy_to_new_buffer(
         &self,
-        dst_offset: vk::DeviceSize,
-        src: &[T],
-        device: &ash::Device,
-    ) -> Result<()> {
+        context: &VulkanContext,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<Buffer> {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert!(!self.is_destroyed);
+        }
+
+        let copy = Self::new_movable(context, self.size, self.usage, self.properties)?;
+        context.device().cmd_copy_buffer(
+            command_buffer,
+            self.buffer,
+            copy.buffer,
+            &[vk::BufferCopy::default().size(self.size)],
+        );
+        Ok(copy)
+    }
+
+    // The buffer has to be host visible and host coherent
+    pub unsafe fn copy_from_ram<T>(&self, dst_offset: vk::DeviceSize, src: &[T]) -> Result<()> {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed);
@@ -97,55 +128,34 @@ impl Buffer {
             );
         }
 
-        let ptr = device.map_memory(
-            self.memory,
-            dst_offset,
-            vk::WHOLE_SIZE,
-            vk::MemoryMapFlags::empty(),
-        )?;
-        defer!(device.unmap_memory(self.memory));
-
         copy_nonoverlapping(
             src.as_ptr() as *const c_void,
-            ptr,
+            self.mapped_ptr().byte_add(dst_offset as usize),
             src.len() * size_of_val(&src[0]),
         );
 
         Ok(())
     }
 
-    pub unsafe fn copy_from_buffer(
+    // The buffer has to be host visible and host coherent
+    pub unsafe fn copy_to_ram<T: Copy>(
         &self,
-        dst_offset: vk::DeviceSize,
-        src: &Buffer,
         src_offset: vk::DeviceSize,
-        size_to_copy: vk::DeviceSize,
-        device: &ash::Device,
-        interface: &VulkanInterface,
+        dst: &mut [T],
     ) -> Result<()> {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed);
-            debug_assert!(self.size >= dst_offset);
-            debug_assert!(src.size >= src_offset);
-            debug_assert!(size_to_copy <= src.size - src_offset);
-            debug_assert!(size_to_copy <= self.size - dst_offset);
+            debug_assert!(self.size > src_offset);
+            debug_assert!((size_of_val(dst) as vk::DeviceSize) <= self.size - src_offset);
         }
 
-        let single_time_command = SingleTimeCommand::begin(device, interface)?;
-
-        device.cmd_copy_buffer(
-            *single_time_command,
-            src.buffer,
-            self.buffer,
-            &[vk::BufferCopy::default()
-                .src_offset(src_offset)
-                .dst_offset(dst_offset)
-                .size(size_to_copy)],
+        copy_nonoverlapping(
+            self.mapped_ptr().byte_add(src_offset as usize) as *const c_void,
+            dst.as_mut_ptr() as *mut c_void,
+            size_of_val(dst),
         );
 
-        single_time_command.submit()?;
-
         Ok(())
     }
 
@@ -157,21 +167,24 @@ impl Buffer {
         self.buffer
     }
 
-    pub fn memory(&self) -> vk::DeviceMemory {
+    // Host visible buffers stay mapped for their whole lifetime
+    pub fn mapped_ptr(&self) -> *mut c_void {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed);
         }
-        self.memory
+        let mapped_ptr = self.allocation.mapped_ptr();
+        assert!(!mapped_ptr.is_null(), "The buffer isn't host visible");
+        mapped_ptr
     }
 
-    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed);
             self.is_destroyed = true;
         }
-        device.destroy_buffer(self.buffer, None);
-        device.free_memory(self.memory, None);
+        context.device().destroy_buffer(self.buffer, None);
+        context.allocator().free(context.device(), &self.allocation);
     }
 }
@@ -0,0 +1,223 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use ash::vk;
+use rs42::{error_struct_custom_display, scope_guard::Defer, Result};
+
+use super::{
+    buffer::Buffer, single_time_command::SingleTimeCommand, vulkan_context::VulkanContext,
+    vulkan_interface::VulkanInterface,
+};
+
+error_struct_custom_display!(
+    UnsupportedCaptureFormat { format: vk::Format },
+    "Frames with the {:?} format can not be captured",
+    format
+);
+
+error_struct_custom_display!(
+    UncapturableSwapchain,
+    "Screenshots are not supported by the window surface"
+);
+
+error_struct_custom_display!(
+    FailedToWriteCapture {
+        path: String,
+        err: std::io::Error,
+    },
+    "Failed to write frame capture \"{}\": {}",
+    path,
+    err
+);
+
+type Rgba = [u8; 4];
+
+pub struct FrameCapture {
+    width: u32,
+    height: u32,
+    pixels: Box<[Rgba]>,
+}
+
+impl FrameCapture {
+    // The image is copied from layout and is put back in it afterwards, it has to be a transfer
+    // source and every command writing to it has to be finished
+    pub unsafe fn from_image(
+        context: &VulkanContext,
+        interface: &VulkanInterface,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        layout: vk::ImageLayout,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let swizzle = get_swizzle(format)?;
+        let pixel_count = (extent.width * extent.height) as usize;
+
+        let buffer = Buffer::new(
+            context,
+            (pixel_count * size_of::<Rgba>()) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+        .defer(|mut buffer| buffer.destroy(context));
+
+        copy_image_to_buffer(image, &buffer, extent, layout, context.device(), interface)?;
+
+        let mut pixels = vec![[0; 4]; pixel_count].into_boxed_slice();
+        buffer.copy_to_ram(0, &mut pixels)?;
+
+        for pixel in pixels.iter_mut() {
+            *pixel = swizzle.map(|channel| pixel[channel]);
+        }
+
+        Ok(Self {
+            width: extent.width,
+            height: extent.height,
+            pixels,
+        })
+    }
+
+    // Binary PPM without the alpha channel, the format the textures are read in
+    pub fn write_ppm(&self, path: &str) -> Result<()> {
+        let map_err = |err| FailedToWriteCapture::new(path.to_string(), err);
+
+        let mut writer = File::create(path).map(BufWriter::new).map_err(map_err)?;
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height).map_err(map_err)?;
+        for pixel in self.pixels.iter() {
+            writer.write_all(&pixel[..3]).map_err(map_err)?;
+        }
+        writer.flush().map_err(map_err)?;
+        Ok(())
+    }
+}
+
+unsafe fn copy_image_to_buffer(
+    image: vk::Image,
+    buffer: &Buffer,
+    extent: vk::Extent2D,
+    layout: vk::ImageLayout,
+    device: &ash::Device,
+    interface: &VulkanInterface,
+) -> Result<()> {
+    let single_time_command = SingleTimeCommand::begin(device, interface)?;
+
+    let subresource_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(1)
+        .base_array_layer(0)
+        .layer_count(1);
+    let needs_transition = layout != vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+    let barrier = vk::ImageMemoryBarrier::default()
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(subresource_range);
+
+    if needs_transition {
+        device.cmd_pipeline_barrier(
+            *single_time_command,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier
+                .old_layout(layout)
+                .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ)],
+        );
+    }
+
+    let region = vk::BufferImageCopy::default()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(
+            vk::ImageSubresourceLayers::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .mip_level(0)
+                .base_array_layer(0)
+                .layer_count(1),
+        )
+        .image_offset(vk::Offset3D::default().x(0).y(0).z(0))
+        .image_extent(
+            vk::Extent3D::default()
+                .width(extent.width)
+                .height(extent.height)
+                .depth(1),
+        );
+    device.cmd_copy_image_to_buffer(
+        *single_time_command,
+        image,
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        buffer.buffer(),
+        &[region],
+    );
+
+    if needs_transition {
+        device.cmd_pipeline_barrier(
+            *single_time_command,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier
+                .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .new_layout(layout)
+                .src_access_mask(vk::AccessFlags::TRANSFER_READ)],
+        );
+    }
+
+    single_time_command.submit()?;
+    Ok(())
+}
+
+// Indices of the red, green, blue and alpha channels in a pixel of the given format
+fn get_swizzle(format: vk::Format) -> Result<[usize; 4], UnsupportedCaptureFormat> {
+    match format {
+        vk::Format::R8G8B8A8_SRGB | vk::Format::R8G8B8A8_UNORM => Ok([0, 1, 2, 3]),
+        vk::Format::B8G8R8A8_SRGB | vk::Format::B8G8R8A8_UNORM => Ok([2, 1, 0, 3]),
+        _ => Err(UnsupportedCaptureFormat::new(format)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    #[test]
+    fn write_ppm() {
+        let capture = FrameCapture {
+            width: 2,
+            height: 1,
+            pixels: Box::new([[255, 0, 10, 255], [1, 2, 3, 0]]),
+        };
+        let directory = TestDirectory::new("frame_capture");
+        let path = directory.path("capture.ppm");
+
+        capture.write_ppm(&path).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        // The alpha channel is dropped
+        assert_eq!(content, b"P6\n2 1\n255\n\xff\x00\x0a\x01\x02\x03");
+
+        let err = capture.write_ppm("/nonexistent/capture.ppm").unwrap_err();
+        assert!(err.downcast_ref::<FailedToWriteCapture>().is_some());
+    }
+
+    #[test]
+    fn swizzle() {
+        let pixel = [10, 20, 30, 40];
+        let swizzle = get_swizzle(vk::Format::B8G8R8A8_SRGB).unwrap();
+        assert_eq!(swizzle.map(|channel| pixel[channel]), [30, 20, 10, 40]);
+        let swizzle = get_swizzle(vk::Format::R8G8B8A8_UNORM).unwrap();
+        assert_eq!(swizzle.map(|channel| pixel[channel]), pixel);
+        assert!(get_swizzle(vk::Format::R16G16B16A16_SFLOAT).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::scene::Light;
+
+// Column major, like the matrices of GLSL
+pub type LightSpaceMatrix = [[f32; 4]; 4];
+
+const UP: [f32; 3] = [0., 0., 1.];
+// Used when the light points straight up or down
+const FALLBACK_UP: [f32; 3] = [0., 1., 0.];
+
+// Objects closer than this to a spot light don't cast shadows
+const SPOT_LIGHT_NEAR_PLANE: f32 = 0.05;
+
+// Projects world positions into the shadow map of the light, in Vulkan clip space.
+// Directional lights cover a box of half size shadow_distance around center
+pub fn light_space_matrix(
+    light: &Light,
+    center: [f32; 3],
+    shadow_distance: f32,
+) -> Option<LightSpaceMatrix> {
+    match *light {
+        Light::Directional { direction, .. } => {
+            let direction = normalize(direction);
+            // Far enough back for the objects between the light and the box to cast shadows
+            let eye = add_scaled(center, direction, -2. * shadow_distance);
+            Some(multiply(
+                &orthographic(shadow_distance, 0., 3. * shadow_distance),
+                &look_at(eye, direction),
+            ))
+        }
+        Light::Spot {
+            position,
+            direction,
+            range,
+            outer_cone_angle,
+            ..
+        } => Some(multiply(
+            &perspective(
+                (2. * outer_cone_angle).clamp(1., 179.).to_radians(),
+                SPOT_LIGHT_NEAR_PLANE.min(range * 0.5),
+                range,
+            ),
+            &look_at(position, normalize(direction)),
+        )),
+        Light::Point { .. } => None,
+    }
+}
+
+fn look_at(eye: [f32; 3], forward: [f32; 3]) -> LightSpaceMatrix {
+    let up = if cross(forward, UP).iter().all(|scalar| scalar.abs() < 1e-4) {
+        FALLBACK_UP
+    } else {
+        UP
+    };
+    let side = normalize(cross(forward, up));
+    let up = cross(side, forward);
+
+    [
+        [side[0], up[0], -forward[0], 0.],
+        [side[1], up[1], -forward[1], 0.],
+        [side[2], up[2], -forward[2], 0.],
+        [-dot(side, eye), -dot(up, eye), dot(forward, eye), 1.],
+    ]
+}
+
+// The y axis is flipped and the depth goes from 0 to 1, as Vulkan expects
+fn orthographic(half_size: f32, near: f32, far: f32) -> LightSpaceMatrix {
+    [
+        [1. / half_size, 0., 0., 0.],
+        [0., -1. / half_size, 0., 0.],
+        [0., 0., -1. / (far - near), 0.],
+        [0., 0., -near / (far - near), 1.],
+    ]
+}
+
+// The y axis is flipped and the depth goes from 0 to 1, as Vulkan expects
+fn perspective(field_of_view: f32, near: f32, far: f32) -> LightSpaceMatrix {
+    let focal_length = 1. / (field_of_view / 2.).tan();
+    [
+        [focal_length, 0., 0., 0.],
+        [0., -focal_length, 0., 0.],
+        [0., 0., far / (near - far), -1.],
+        [0., 0., near * far / (near - far), 0.],
+    ]
+}
+
+fn multiply(a: &LightSpaceMatrix, b: &LightSpaceMatrix) -> LightSpaceMatrix {
+    std::array::from_fn(|column| {
+        std::array::from_fn(|row| (0..4).map(|i| a[i][row] * b[column][i]).sum())
+    })
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = dot(vector, vector).sqrt();
+    vector.map(|scalar| scalar / length)
+}
+
+fn add_scaled(a: [f32; 3], b: [f32; 3], scale: f32) -> [f32; 3] {
+    [
+        a[0] + b[0] * scale,
+        a[1] + b[1] * scale,
+        a[2] + b[2] * scale,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Returns the normalized device coordinates of the point
+    fn project(matrix: &LightSpaceMatrix, point: [f32; 3]) -> [f32; 3] {
+        let clip: [f32; 4] = std::array::from_fn(|row| {
+            (0..3).map(|i| matrix[i][row] * point[i]).sum::<f32>() + matrix[3][row]
+        });
+        [clip[0] / clip[3], clip[1] / clip[3], clip[2] / clip[3]]
+    }
+
+    fn assert_approximately_equal(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn look_at_moves_the_eye_to_the_origin() {
+        let eye = [1., 2., 3.];
+        let forward = normalize([1., 1., 0.]);
+        let view = look_at(eye, forward);
+
+        assert_approximately_equal(project(&view, eye), [0., 0., 0.]);
+        assert_approximately_equal(project(&view, add_scaled(eye, forward, 2.)), [0., 0., -2.]);
+        // Up stays up when it isn't parallel to the direction
+        assert_approximately_equal(project(&view, add_scaled(eye, UP, 1.)), [0., 1., 0.]);
+
+        // Looking straight down uses the fallback up vector
+        let view = look_at(eye, [0., 0., -1.]);
+        assert!(view.iter().flatten().all(|scalar| scalar.is_finite()));
+        assert_approximately_equal(project(&view, [1., 2., 1.]), [0., 0., -2.]);
+    }
+
+    #[test]
+    fn multiply_composes_the_transforms() {
+        let identity = std::array::from_fn(|column| {
+            std::array::from_fn(|row| if row == column { 1. } else { 0. })
+        });
+        let view = look_at([1., 2., 3.], normalize([0., 1., 1.]));
+        assert_eq!(multiply(&identity, &view), view);
+        assert_eq!(multiply(&view, &identity), view);
+
+        let projection = perspective(1., 0.1, 10.);
+        let point = [4., 5., 6.];
+        let view_point = project(&view, point);
+        assert_approximately_equal(
+            project(&multiply(&projection, &view), point),
+            project(&projection, view_point),
+        );
+    }
+
+    #[test]
+    fn directional_light_covers_the_box_around_the_center() {
+        let light = Light::Directional {
+            direction: [0., -2., 0.],
+            color: [1.; 3],
+            intensity: 1.,
+        };
+        let center = [1., 1., 1.];
+        let matrix = light_space_matrix(&light, center, 10.).unwrap();
+
+        // The center is 2 shadow distances away from the light, in a depth range of 3
+        assert_approximately_equal(project(&matrix, center), [0., 0., 2. / 3.]);
+        assert_approximately_equal(project(&matrix, [1., 1., 11.]), [0., -1., 2. / 3.]);
+        assert_approximately_equal(project(&matrix, [1., -9., 1.]), [0., 0., 1.]);
+        assert_approximately_equal(project(&matrix, [1., 21., 1.]), [0., 0., 0.]);
+    }
+
+    #[test]
+    fn spot_light_covers_its_cone() {
+        let light = Light::Spot {
+            position: [0., 1., 0.],
+            direction: [0., -1., 0.],
+            color: [1.; 3],
+            intensity: 1.,
+            range: 10.,
+            inner_cone_angle: 20.,
+            outer_cone_angle: 30.,
+        };
+        let matrix = light_space_matrix(&light, [0.; 3], 10.).unwrap();
+
+        assert_approximately_equal(project(&matrix, [0., -9., 0.]), [0., 0., 1.]);
+        assert_approximately_equal(
+            project(&matrix, [0., 1. - SPOT_LIGHT_NEAR_PLANE, 0.]),
+            [0., 0., 0.],
+        );
+        // A point on the outer cone is on the edge of the shadow map
+        let edge = 5. * 30_f32.to_radians().tan();
+        assert_eq!(project(&matrix, [edge, -4., 0.])[0].abs().round(), 1.);
+        assert_eq!(project(&matrix, [0., -4., edge])[1].abs().round(), 1.);
+
+        assert!(light_space_matrix(&Light::default(), [0.; 3], 10.).is_some());
+        let point_light = Light::Point {
+            position: [0.; 3],
+            color: [1.; 3],
+            intensity: 1.,
+            range: 1.,
+        };
+        assert!(light_space_matrix(&point_light, [0.; 3], 10.).is_none());
+    }
+}
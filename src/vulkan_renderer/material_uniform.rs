@@ -0,0 +1,85 @@
+use model::{Material, ShadingModel};
+
+use crate::scene::SceneMaterial;
+
+const BLINN_PHONG: u32 = 0;
+const METALLIC_ROUGHNESS: u32 = 1;
+
+// Factors of a material, follows the std140 layout
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialUniform {
+    // The base color of the material is already in the vertex colors, only the alpha is used
+    pub base_color_factor: [f32; 4],
+    // w is unused
+    pub emissive_factor: [f32; 4],
+    // Only used by Blinn-Phong materials, w is the shininess
+    pub specular_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub shading_model: u32,
+    pub _padding: [u32; 3],
+}
+
+impl MaterialUniform {
+    // Used by sub meshes without a material, they don't have any highlight
+    pub const DEFAULT: Self = Self {
+        base_color_factor: [1.; 4],
+        emissive_factor: [0.; 4],
+        specular_factor: [0., 0., 0., 1.],
+        metallic_factor: 0.,
+        roughness_factor: 1.,
+        normal_scale: 1.,
+        occlusion_strength: 1.,
+        shading_model: BLINN_PHONG,
+        _padding: [0; 3],
+    };
+}
+
+impl From<&Material> for MaterialUniform {
+    fn from(material: &Material) -> Self {
+        let [emissive_r, emissive_g, emissive_b] = material.emissive_color.clone().into_scalars();
+        let [specular_r, specular_g, specular_b] = material.specular_color.clone().into_scalars();
+        Self {
+            base_color_factor: [1., 1., 1., material.dissolve],
+            emissive_factor: [emissive_r, emissive_g, emissive_b, 0.],
+            // An exponent of 0 would light every fragment facing the light the same way
+            specular_factor: [
+                specular_r,
+                specular_g,
+                specular_b,
+                material.specular_exponent.max(1.),
+            ],
+            metallic_factor: material.metallic,
+            roughness_factor: material.roughness,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            shading_model: match material.shading_model {
+                ShadingModel::BlinnPhong => BLINN_PHONG,
+                ShadingModel::MetallicRoughness => METALLIC_ROUGHNESS,
+            },
+            _padding: [0; 3],
+        }
+    }
+}
+
+// Unlike the materials of the models, the color isn't in the vertex colors
+impl From<&SceneMaterial> for MaterialUniform {
+    fn from(material: &SceneMaterial) -> Self {
+        let [color_r, color_g, color_b] = material.color;
+        let [emissive_r, emissive_g, emissive_b] = material.emissive;
+        Self {
+            base_color_factor: [color_r, color_g, color_b, material.opacity],
+            emissive_factor: [emissive_r, emissive_g, emissive_b, 0.],
+            specular_factor: [0., 0., 0., 1.],
+            metallic_factor: material.metallic,
+            roughness_factor: material.roughness,
+            normal_scale: 1.,
+            occlusion_strength: 1.,
+            shading_model: METALLIC_ROUGHNESS,
+            _padding: [0; 3],
+        }
+    }
+}
@@ -1,159 +1,549 @@
+mod asset_loader;
 mod create_index_buffer;
+mod create_material_buffer;
+mod create_textures;
 mod create_uniform_buffers;
 mod create_vertex_buffer;
 mod descriptors;
 mod errors;
 mod image;
+mod model_resources;
 
 use std::ffi::c_void;
 
-use model::{Model, ObjFile};
 use rs42::{
     scope_guard::{Defer, ScopeGuard},
     Result,
 };
 
+use crate::{config::AssetPaths, scene::Scene};
+
 use super::{
-    buffer::Buffer, render_targets::RenderTargets, vulkan_context::VulkanContext,
-    vulkan_interface::VulkanInterface, NB_OF_FRAMES_IN_FLIGHT_USIZE,
+    buffer::Buffer,
+    material_uniform::MaterialUniform,
+    render_targets::RenderTargets,
+    single_time_command::SingleTimeCommand,
+    upload_batch::{PendingUpload, UploadBatch},
+    vulkan_context::VulkanContext,
+    vulkan_interface::VulkanInterface,
+    NB_OF_FRAMES_IN_FLIGHT_USIZE,
 };
 use ash::{prelude::VkResult, vk};
-use create_index_buffer::create_index_buffer;
+use asset_loader::{AssetLoader, LoadedAsset};
+use create_textures::{create_builtin_textures, create_default_texture, MaterialTextures};
 use create_uniform_buffers::create_uniform_buffers;
-use create_vertex_buffer::create_vertex_buffer;
-use descriptors::create_descriptor_pool;
-use descriptors::create_descriptor_sets;
+use descriptors::{create_frame_descriptors, FrameDescriptors};
+pub use descriptors::{FRAME_SET, FRAME_SET_BINDINGS, MATERIAL_SET, MATERIAL_SET_BINDINGS};
+use errors::FailedToLoadAsset;
 pub use image::{Image, ImageCreateInfo};
-use image_parser::ppm::PpmFilePath;
-use rs42::error_struct_custom_display;
-
-error_struct_custom_display!(
-    FailedToFindMemoryTypeIndex,
-    "Failed to find memory type index when trying to allocate memory for a buffer"
-);
-
-// TODO remove this
-pub const PPM_FILE_PATH: &str = "assets/textures/viking_room.ppm";
-pub const OBJ_FILE_PATH: &str = "assets/obj/viking_room.obj";
+use model_resources::{Materials, ModelResources};
 
+// Models and textures are loaded in the background, a placeholder cube and a checkerboard
+// texture are drawn in their place until they are uploaded
 pub struct Memory {
     is_destroyed: bool,
 
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    index_buffer_len: u32,
-
     uniform_buffers: [Buffer; NB_OF_FRAMES_IN_FLIGHT_USIZE],
     mapped_uniform_buffers: [*mut c_void; NB_OF_FRAMES_IN_FLIGHT_USIZE],
 
     descriptors_are_destroyed: bool,
-    descriptor_pool: vk::DescriptorPool,
-    descriptor_sets: [vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE],
+    frame_descriptors: FrameDescriptors,
 
-    texture: Image,
     sampler: vk::Sampler,
+    // The checkerboard, white and flat normal textures
+    builtin_textures: Box<[Image]>,
+    // Replaces the checkerboard in the default material once it is loaded
+    default_texture: Option<Image>,
+    // Used by the sub meshes without a material, the placeholder one samples the checkerboard.
+    // The placeholders are kept until the memory is destroyed as the frames in flight might
+    // still use them
+    placeholder_default_material: Materials,
+    default_material: Option<Materials>,
+    // Indexed like the materials of the scene, None if the scene has none
+    scene_materials: Option<Materials>,
+    placeholder_model: ModelResources,
+    // Indexed like the meshes of the scene, None until the model is loaded
+    models: Box<[Option<ModelResources>]>,
+    // Rebuilt every time an asset is loaded
+    meshes: Box<[MeshDraw]>,
+
+    asset_loader: AssetLoader,
+    // Their staging buffers are freed by load_finished_assets() once the GPU is done with them
+    pending_uploads: Vec<PendingUpload>,
+    // The memory is defragmented once every asset is loaded
+    is_defragmentation_pending: bool,
+}
+
+pub struct MeshDraw {
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub sub_meshes: Box<[SubMeshDraw]>,
+}
+
+pub struct SubMeshDraw {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub material_set: vk::DescriptorSet,
 }
 
 impl Memory {
+    // Returns as soon as the placeholders are uploaded, the assets of the scene are loaded by
+    // load_finished_assets() or wait_for_assets()
     pub unsafe fn new(
         context: &VulkanContext,
         interface: &VulkanInterface,
         render_targets: &RenderTargets,
+        assets: &AssetPaths,
+        scene: &Scene,
     ) -> Result<Self> {
-        let model = Model::try_from(ObjFile(OBJ_FILE_PATH))?;
-        let vertex_buffer = create_vertex_buffer(context, interface, model.vertices())?
-            .defer(|mut vertex_buffer| vertex_buffer.destroy(context.device()));
-
-        let index_buffer = create_index_buffer(context, interface, model.vertex_indices())?
-            .defer(|mut index_buffer| index_buffer.destroy(context.device()));
+        let asset_loader = AssetLoader::new(scene.mesh_paths(), &assets.texture)?;
 
         let (uniform_buffers, mapped_uniform_buffers) = create_uniform_buffers(context)?;
         let uniform_buffers = uniform_buffers.defer(|mut uniform_buffers| {
-            Self::destroy_uniform_buffers(context.device(), &mut uniform_buffers)
+            Self::destroy_uniform_buffers(context, &mut uniform_buffers)
         });
 
-        let image = image_parser::Image::try_from(PpmFilePath(PPM_FILE_PATH))?;
-        let texture = Image::from_texture_image(context, interface, &image)?
-            .defer(|mut texture| texture.destroy(context.device()));
-
         let sampler = Self::init_sampler(context)?
             .defer(|sampler| unsafe { context.device().destroy_sampler(sampler, None) });
 
-        let (descriptor_pool, descriptor_sets) = Self::create_descriptors(
+        let mut upload_batch = UploadBatch::begin(context, interface)?;
+
+        let builtin_textures = create_builtin_textures(context, &mut upload_batch)?
+            .into_boxed_slice()
+            .defer(|mut textures| {
+                for texture in textures.iter_mut() {
+                    texture.destroy(context);
+                }
+            });
+        let builtin_texture_views: Vec<_> =
+            builtin_textures.iter().map(Image::image_view).collect();
+
+        let placeholder_default_material = Self::create_default_material(
             context,
+            &mut upload_batch,
             render_targets,
-            &uniform_buffers,
-            texture.image_view(),
+            builtin_texture_views.into_boxed_slice(),
             *sampler,
-        )?;
-        let descriptor_pool = descriptor_pool.defer(|descriptor_pool| {
-            context
-                .device()
-                .destroy_descriptor_pool(descriptor_pool, None)
+        )?
+        .defer(|mut material| {
+            material.destroy_descriptors(context.device());
+            material.destroy(context);
+        });
+
+        let scene_materials = Self::create_scene_materials(
+            context,
+            &mut upload_batch,
+            render_targets,
+            scene,
+            &builtin_textures,
+            *sampler,
+        )?
+        .defer(|scene_materials| {
+            if let Some(mut scene_materials) = scene_materials {
+                scene_materials.destroy_descriptors(context.device());
+                scene_materials.destroy(context);
+            }
         });
 
-        Ok(Self {
+        let placeholder_model = ModelResources::placeholder(context, &mut upload_batch)?
+            .defer(|mut placeholder_model| placeholder_model.destroy(context));
+
+        // Waited on before anything else is destroyed if creating the memory fails
+        let pending_upload = upload_batch.submit()?.defer(|mut pending_upload| {
+            if let Err(err) = pending_upload.wait(context.device()) {
+                eprintln!("WARNING: Failed to wait for the upload to finish: {err}");
+            }
+            pending_upload.destroy(context);
+        });
+
+        let frame_descriptors =
+            create_frame_descriptors(context.device(), render_targets, &uniform_buffers)?;
+
+        let mut memory = Self {
+            is_defragmentation_pending: true,
+            pending_uploads: vec![ScopeGuard::into_inner(pending_upload)],
+            asset_loader,
+            meshes: Box::new([]),
+            models: scene.mesh_paths().iter().map(|_| None).collect(),
+            placeholder_model: ScopeGuard::into_inner(placeholder_model),
+            default_material: None,
+            scene_materials: ScopeGuard::into_inner(scene_materials),
+            placeholder_default_material: ScopeGuard::into_inner(placeholder_default_material),
+            default_texture: None,
+            builtin_textures: ScopeGuard::into_inner(builtin_textures),
             sampler: ScopeGuard::into_inner(sampler),
-            texture: ScopeGuard::into_inner(texture),
-            descriptor_sets,
-            descriptor_pool: ScopeGuard::into_inner(descriptor_pool),
+            frame_descriptors,
             descriptors_are_destroyed: false,
             mapped_uniform_buffers,
             uniform_buffers: ScopeGuard::into_inner(uniform_buffers),
-            index_buffer: ScopeGuard::into_inner(index_buffer),
-            index_buffer_len: model.vertex_indices().len() as u32,
-            vertex_buffer: ScopeGuard::into_inner(vertex_buffer),
             is_destroyed: false,
-        })
+        };
+        memory.update_mesh_draws();
+        Ok(memory)
+    }
+
+    // texture_views are the views the indices of MaterialTextures::DEFAULT refer to
+    unsafe fn create_default_material(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        render_targets: &RenderTargets,
+        texture_views: Box<[vk::ImageView]>,
+        sampler: vk::Sampler,
+    ) -> Result<Materials> {
+        Materials::new(
+            context,
+            upload_batch,
+            render_targets,
+            &[MaterialUniform::DEFAULT],
+            Box::new([MaterialTextures::DEFAULT]),
+            texture_views,
+            sampler,
+        )
+    }
+
+    // The materials only use the builtin textures
+    unsafe fn create_scene_materials(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        render_targets: &RenderTargets,
+        scene: &Scene,
+        builtin_textures: &[Image],
+        sampler: vk::Sampler,
+    ) -> Result<Option<Materials>> {
+        if scene.materials().is_empty() {
+            return Ok(None);
+        }
+
+        let material_uniforms: Vec<_> = scene
+            .materials()
+            .iter()
+            .map(MaterialUniform::from)
+            .collect();
+        let textures = material_uniforms
+            .iter()
+            .map(|_| MaterialTextures::UNTEXTURED)
+            .collect();
+        Materials::new(
+            context,
+            upload_batch,
+            render_targets,
+            &material_uniforms,
+            textures,
+            builtin_textures.iter().map(Image::image_view).collect(),
+            sampler,
+        )
+        .map(Some)
+    }
+
+    // Should be called every frame, uploads the assets the loader finished since the last call
+    // without waiting for the others
+    pub unsafe fn load_finished_assets(
+        &mut self,
+        context: &VulkanContext,
+        interface: &VulkanInterface,
+        render_targets: &RenderTargets,
+    ) -> Result<()> {
+        debug_assert!(!self.is_destroyed);
+
+        self.release_finished_uploads(context)?;
+        if self.asset_loader.is_done() {
+            if self.is_defragmentation_pending && self.pending_uploads.is_empty() {
+                self.is_defragmentation_pending = false;
+                // The models that failed to load leave free ranges between the others
+                if context.allocator().is_fragmented() {
+                    self.defragment(context, interface, render_targets)?;
+                }
+            }
+            return Ok(());
+        }
+        let loaded_assets = self.asset_loader.poll();
+        self.add_loaded_assets(context, interface, render_targets, loaded_assets)
+    }
+
+    // Blocks until every asset is loaded and uploaded, used when there is a single frame to
+    // render
+    pub unsafe fn wait_for_assets(
+        &mut self,
+        context: &VulkanContext,
+        interface: &VulkanInterface,
+        render_targets: &RenderTargets,
+    ) -> Result<()> {
+        debug_assert!(!self.is_destroyed);
+
+        let loaded_assets: Vec<_> = std::iter::from_fn(|| self.asset_loader.wait()).collect();
+        self.add_loaded_assets(context, interface, render_targets, loaded_assets)?;
+        for pending_upload in self.pending_uploads.iter() {
+            pending_upload.wait(context.device())?;
+        }
+        self.release_finished_uploads(context)
+    }
+
+    // Assets that failed to load keep their placeholder
+    unsafe fn add_loaded_assets(
+        &mut self,
+        context: &VulkanContext,
+        interface: &VulkanInterface,
+        render_targets: &RenderTargets,
+        loaded_assets: Vec<Result<LoadedAsset, FailedToLoadAsset>>,
+    ) -> Result<()> {
+        if loaded_assets.is_empty() {
+            return Ok(());
+        }
+
+        let mut upload_batch = UploadBatch::begin(context, interface)?;
+        let builtin_texture_views: Vec<_> = self
+            .builtin_textures
+            .iter()
+            .map(Image::image_view)
+            .collect();
+
+        // Only added to the memory once the batch is submitted
+        let mut models = Vec::new().defer(|mut models: Vec<(usize, ModelResources)>| {
+            for (_, model) in models.iter_mut() {
+                if let Some(materials) = model.materials.as_ref() {
+                    materials.destroy_descriptors(context.device());
+                }
+                model.destroy(context);
+            }
+        });
+        let mut default_texture = None.defer(|default_texture: Option<Image>| {
+            if let Some(mut default_texture) = default_texture {
+                default_texture.destroy(context);
+            }
+        });
+        for loaded_asset in loaded_assets {
+            match loaded_asset {
+                Ok(LoadedAsset::Mesh {
+                    mesh,
+                    model,
+                    textures,
+                }) => models.push((
+                    mesh,
+                    ModelResources::new(
+                        context,
+                        &mut upload_batch,
+                        render_targets,
+                        &model,
+                        &textures,
+                        &builtin_texture_views,
+                        self.sampler,
+                    )?,
+                )),
+                Ok(LoadedAsset::DefaultTexture(image)) => {
+                    *default_texture =
+                        Some(create_default_texture(context, &mut upload_batch, &image)?);
+                }
+                Err(err) => eprintln!("ERROR: {err}, a placeholder is drawn instead"),
+            }
+        }
+
+        let default_material = match default_texture.as_ref() {
+            Some(default_texture) => {
+                let mut texture_views = builtin_texture_views;
+                texture_views[0] = default_texture.image_view();
+                Some(Self::create_default_material(
+                    context,
+                    &mut upload_batch,
+                    render_targets,
+                    texture_views.into_boxed_slice(),
+                    self.sampler,
+                )?)
+            }
+            None => None,
+        }
+        .defer(|default_material: Option<Materials>| {
+            if let Some(mut default_material) = default_material {
+                default_material.destroy_descriptors(context.device());
+                default_material.destroy(context);
+            }
+        });
+
+        self.pending_uploads.push(upload_batch.submit()?);
+
+        for (mesh, model) in ScopeGuard::into_inner(models) {
+            self.models[mesh] = Some(model);
+        }
+        if let Some(default_material) = ScopeGuard::into_inner(default_material) {
+            self.default_material = Some(default_material);
+            self.default_texture = ScopeGuard::into_inner(default_texture);
+        }
+        self.update_mesh_draws();
+        Ok(())
+    }
+
+    unsafe fn release_finished_uploads(&mut self, context: &VulkanContext) -> Result<()> {
+        let mut i = 0;
+        while i < self.pending_uploads.len() {
+            if self.pending_uploads[i].is_finished(context.device())? {
+                self.pending_uploads.swap_remove(i).destroy(context);
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
     }
 
-    pub unsafe fn create_descriptors(
+    // Meshes that aren't loaded yet are drawn as the placeholder cube
+    fn update_mesh_draws(&mut self) {
+        let default_material_set = self
+            .default_material
+            .as_ref()
+            .unwrap_or(&self.placeholder_default_material)
+            .descriptor_sets()[0];
+
+        self.meshes = self
+            .models
+            .iter()
+            .map(|model| {
+                let model = model.as_ref().unwrap_or(&self.placeholder_model);
+                MeshDraw {
+                    vertex_buffer: model.vertex_buffer.buffer(),
+                    index_buffer: model.index_buffer.buffer(),
+                    sub_meshes: model
+                        .sub_meshes
+                        .iter()
+                        .map(|sub_mesh| SubMeshDraw {
+                            first_index: sub_mesh.first_index,
+                            index_count: sub_mesh.index_count,
+                            material_set: match (sub_mesh.material, model.materials.as_ref()) {
+                                (Some(material), Some(materials)) => {
+                                    materials.descriptor_sets()[material]
+                                }
+                                _ => default_material_set,
+                            },
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+    }
+
+    // Moves the buffers out of the blocks the allocator chose to empty, images aren't moved.
+    // Waits for the device to be idle, the descriptors are recreated as they refer to the buffers
+    pub unsafe fn defragment(
+        &mut self,
         context: &VulkanContext,
+        interface: &VulkanInterface,
         render_targets: &RenderTargets,
-        uniform_buffers: &[Buffer; NB_OF_FRAMES_IN_FLIGHT_USIZE],
-        texture_image_view: vk::ImageView,
-        texture_sampler: vk::Sampler,
-    ) -> Result<(
-        vk::DescriptorPool,
-        [vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE],
-    )> {
-        let descriptor_pool = create_descriptor_pool(context.device())?.defer(|descriptor_pool| {
-            context
-                .device()
-                .destroy_descriptor_pool(descriptor_pool, None)
+    ) -> Result<()> {
+        debug_assert!(!self.is_destroyed);
+
+        self.release_finished_uploads(context)?;
+        if !self.pending_uploads.is_empty() {
+            return Ok(());
+        }
+        context.device().device_wait_idle()?;
+        if !context.allocator().plan_defragmentation() {
+            return Ok(());
+        }
+        self.destroy_descriptors(context.device());
+        let result = self.move_buffers(context, interface);
+        context.allocator().finish_defragmentation(context.device());
+        self.recreate_descriptors(context, render_targets)?;
+        result
+    }
+
+    // The old buffers are only replaced once every copy is done
+    unsafe fn move_buffers(
+        &mut self,
+        context: &VulkanContext,
+        interface: &VulkanInterface,
+    ) -> Result<()> {
+        let command_buffer = SingleTimeCommand::begin(context.device(), interface)?;
+        let mut copies = Vec::new().defer(|mut copies: Vec<Buffer>| {
+            for copy in copies.iter_mut() {
+                copy.destroy(context);
+            }
         });
+        for buffer in self.movable_buffers_mut() {
+            if buffer.is_being_moved(context) {
+                copies.push(buffer.record_copy_to_new_buffer(context, *command_buffer)?);
+            }
+        }
+        if copies.is_empty() {
+            return Ok(());
+        }
+
+        context.device().cmd_pipeline_barrier(
+            *command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ)],
+            &[],
+            &[],
+        );
+        command_buffer.submit()?;
+
+        let mut copies = ScopeGuard::into_inner(copies).into_iter();
+        for buffer in self.movable_buffers_mut() {
+            if buffer.is_being_moved(context) {
+                let copy = copies
+                    .next()
+                    .expect("Every moved buffer should have a copy");
+                std::mem::replace(buffer, copy).destroy(context);
+            }
+        }
+        Ok(())
+    }
 
-        // Destroyed automatically when descriptor_pool is destroyed
-        let descriptor_sets = create_descriptor_sets(
-            context.device(),
-            render_targets.descriptor_set_layout(),
-            *descriptor_pool,
-            uniform_buffers,
-            texture_image_view,
-            texture_sampler,
-        )?;
-
-        Ok((ScopeGuard::into_inner(descriptor_pool), descriptor_sets))
+    // The vertex, index and material buffers, the uniform buffers are host visible
+    fn movable_buffers_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        let materials = std::iter::once(&mut self.placeholder_default_material)
+            .chain(self.default_material.as_mut())
+            .chain(self.scene_materials.as_mut())
+            .map(Materials::buffer_mut);
+        std::iter::once(&mut self.placeholder_model)
+            .chain(self.models.iter_mut().flatten())
+            .flat_map(ModelResources::buffers_mut)
+            .chain(materials)
     }
 
-    pub unsafe fn set_descriptors(
+    // The materials of the loaded models and of the scene, the default material and its
+    // placeholder
+    fn all_materials_mut(&mut self) -> impl Iterator<Item = &mut Materials> {
+        std::iter::once(&mut self.placeholder_default_material)
+            .chain(self.default_material.as_mut())
+            .chain(self.scene_materials.as_mut())
+            .chain(
+                self.models
+                    .iter_mut()
+                    .flatten()
+                    .filter_map(|model| model.materials.as_mut()),
+            )
+    }
+
+    // The descriptor set layouts belong to the render targets, so the descriptors have to be
+    // recreated with them
+    pub unsafe fn recreate_descriptors(
         &mut self,
-        pool: vk::DescriptorPool,
-        sets: [vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE],
-    ) {
+        context: &VulkanContext,
+        render_targets: &RenderTargets,
+    ) -> Result<()> {
         debug_assert!(!self.is_destroyed);
         debug_assert!(self.descriptors_are_destroyed);
 
-        self.descriptor_pool = pool;
-        self.descriptor_sets = sets;
+        self.frame_descriptors =
+            create_frame_descriptors(context.device(), render_targets, &self.uniform_buffers)?;
+        let sampler = self.sampler;
+        for materials in self.all_materials_mut() {
+            materials.recreate_descriptors(context.device(), render_targets, sampler)?;
+        }
         self.descriptors_are_destroyed = false;
+        self.update_mesh_draws();
+        Ok(())
     }
 
     pub unsafe fn destroy_descriptors(&mut self, device: &ash::Device) {
         debug_assert!(!self.is_destroyed);
         debug_assert!(!self.descriptors_are_destroyed);
 
-        device.destroy_descriptor_pool(self.descriptor_pool, None);
+        device.destroy_descriptor_pool(self.frame_descriptors.pool, None);
+        for materials in self.all_materials_mut() {
+            materials.destroy_descriptors(device);
+        }
         self.descriptors_are_destroyed = true;
     }
 
@@ -197,48 +587,20 @@ impl Memory {
         }
     }
 
-    pub fn find_memory_type_index(
-        context: &VulkanContext,
-        memory_type_filter: u32,
-        properties: vk::MemoryPropertyFlags,
-    ) -> Result<u32, FailedToFindMemoryTypeIndex> {
-        unsafe {
-            context
-                .instance()
-                .get_physical_device_memory_properties(context.physical_device())
-        }
-        .memory_types
-        .iter()
-        .enumerate()
-        .find(|(index, memory_type)| {
-            memory_type_filter & (1 << index) != 0
-                && memory_type.property_flags & properties == properties
-        })
-        .map(|(index, _)| index as u32)
-        .ok_or(FailedToFindMemoryTypeIndex {})
-    }
-
-    pub fn vertex_buffer(&self) -> &Buffer {
-        debug_assert!(!self.is_destroyed);
-
-        &self.vertex_buffer
-    }
-
-    pub fn index_buffer(&self) -> &Buffer {
+    pub fn meshes(&self) -> &[MeshDraw] {
         debug_assert!(!self.is_destroyed);
 
-        &self.index_buffer
+        &self.meshes
     }
 
-    pub fn index_buffer_len(&self) -> u32 {
-        debug_assert!(!self.is_destroyed);
-        self.index_buffer_len
-    }
-
-    pub fn uniform_buffers(&self) -> &[Buffer; NB_OF_FRAMES_IN_FLIGHT_USIZE] {
+    // Indexed like the materials of the scene
+    pub fn scene_material_sets(&self) -> &[vk::DescriptorSet] {
         debug_assert!(!self.is_destroyed);
+        debug_assert!(!self.descriptors_are_destroyed);
 
-        &self.uniform_buffers
+        self.scene_materials
+            .as_ref()
+            .map_or(&[], Materials::descriptor_sets)
     }
 
     pub fn mapped_uniform_buffers(&self) -> &[*mut c_void; NB_OF_FRAMES_IN_FLIGHT_USIZE] {
@@ -247,49 +609,54 @@ impl Memory {
         &self.mapped_uniform_buffers
     }
 
-    pub fn texture(&self) -> &Image {
-        debug_assert!(!self.is_destroyed);
-
-        &self.texture
-    }
-
-    pub fn sampler(&self) -> vk::Sampler {
-        debug_assert!(!self.is_destroyed);
-
-        self.sampler
-    }
-
-    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE] {
+    pub fn frame_descriptor_sets(&self) -> &[vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE] {
         debug_assert!(!self.is_destroyed);
         debug_assert!(!self.descriptors_are_destroyed);
 
-        &self.descriptor_sets
+        &self.frame_descriptors.sets
     }
 
-    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
         // If an error occurs during swapchain recreation this function might be called twice
         if self.is_destroyed {
             return;
         }
-        self.is_destroyed = true;
 
+        for mut pending_upload in self.pending_uploads.drain(..) {
+            if let Err(err) = pending_upload.wait(context.device()) {
+                eprintln!("WARNING: Failed to wait for the upload to finish: {err}");
+            }
+            pending_upload.destroy(context);
+        }
         if !self.descriptors_are_destroyed {
-            device.destroy_descriptor_pool(self.descriptor_pool, None);
-            self.descriptors_are_destroyed = true;
+            self.destroy_descriptors(context.device());
         }
-        Self::destroy_uniform_buffers(device, &mut self.uniform_buffers);
-        self.vertex_buffer.destroy(device);
-        self.index_buffer.destroy(device);
-        self.texture.destroy(device);
-        device.destroy_sampler(self.sampler, None);
+        self.is_destroyed = true;
+
+        for model in self.models.iter_mut().flatten() {
+            model.destroy(context);
+        }
+        self.placeholder_model.destroy(context);
+        if let Some(default_material) = self.default_material.as_mut() {
+            default_material.destroy(context);
+        }
+        if let Some(scene_materials) = self.scene_materials.as_mut() {
+            scene_materials.destroy(context);
+        }
+        self.placeholder_default_material.destroy(context);
+        if let Some(default_texture) = self.default_texture.as_mut() {
+            default_texture.destroy(context);
+        }
+        for texture in self.builtin_textures.iter_mut() {
+            texture.destroy(context);
+        }
+        Self::destroy_uniform_buffers(context, &mut self.uniform_buffers);
+        context.device().destroy_sampler(self.sampler, None);
     }
 
-    pub unsafe fn destroy_uniform_buffers(device: &ash::Device, buffers: &mut [Buffer]) {
+    pub unsafe fn destroy_uniform_buffers(context: &VulkanContext, buffers: &mut [Buffer]) {
         for buffer in buffers {
-            unsafe {
-                device.unmap_memory(buffer.memory());
-                buffer.destroy(device);
-            }
+            unsafe { buffer.destroy(context) }
         }
     }
 }
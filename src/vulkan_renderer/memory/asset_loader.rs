@@ -0,0 +1,378 @@
+use std::{
+    collections::HashMap,
+    num::NonZero,
+    sync::{mpsc, Arc, Mutex, OnceLock, PoisonError},
+    thread,
+};
+
+use image_parser::ppm::PpmFilePath;
+use model::{GltfFile, Model, ObjFile};
+use rs42::Result;
+
+use super::errors::FailedToLoadAsset;
+
+// The textures that could be decoded, by path
+pub type DecodedTextures = HashMap<String, Arc<image_parser::Image>>;
+
+pub enum LoadedAsset {
+    // textures holds every texture of the materials of the model that could be decoded
+    Mesh {
+        mesh: usize,
+        model: Model,
+        textures: DecodedTextures,
+    },
+    // Used by the sub meshes without a material
+    DefaultTexture(image_parser::Image),
+}
+
+enum Job {
+    Mesh { mesh: usize, path: String },
+    DefaultTexture { path: String },
+}
+
+// Shared by the workers so that a texture used by several models is only decoded once, by the
+// first worker that needs it. None if the texture couldn't be decoded
+type TextureCache = Mutex<HashMap<String, Arc<OnceLock<Option<Arc<image_parser::Image>>>>>>;
+
+// Parses the models of the scene and decodes their textures on worker threads, the render loop
+// polls the results and uploads them.
+// The workers aren't joined, they exit once every job is done or once the loader is dropped
+pub struct AssetLoader {
+    loaded_assets: mpsc::Receiver<Result<LoadedAsset, FailedToLoadAsset>>,
+    remaining_asset_count: usize,
+}
+
+impl AssetLoader {
+    // The meshes are identified by their index in mesh_paths
+    pub fn new(mesh_paths: &[String], default_texture_path: &str) -> Result<Self> {
+        let (job_sender, jobs) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+        let texture_cache = Arc::new(TextureCache::default());
+        let (loaded_asset_sender, loaded_assets) = mpsc::channel();
+
+        let default_texture = Job::DefaultTexture {
+            path: default_texture_path.to_owned(),
+        };
+        let meshes = mesh_paths.iter().enumerate().map(|(mesh, path)| Job::Mesh {
+            mesh,
+            path: path.clone(),
+        });
+        let mut job_count = 0;
+        for job in std::iter::once(default_texture).chain(meshes) {
+            job_sender
+                .send(job)
+                .expect("The jobs receiver can't be dropped yet");
+            job_count += 1;
+        }
+        drop(job_sender);
+
+        let worker_count = thread::available_parallelism()
+            .map_or(1, NonZero::get)
+            .min(job_count);
+        for i in 0..worker_count {
+            let jobs = Arc::clone(&jobs);
+            let texture_cache = Arc::clone(&texture_cache);
+            let loaded_asset_sender = loaded_asset_sender.clone();
+            thread::Builder::new()
+                .name(format!("asset loader {i}"))
+                .spawn(move || run_worker(&jobs, &texture_cache, &loaded_asset_sender))?;
+        }
+
+        Ok(Self {
+            loaded_assets,
+            remaining_asset_count: job_count,
+        })
+    }
+
+    // Returns the assets loaded since the last call without blocking
+    pub fn poll(&mut self) -> Vec<Result<LoadedAsset, FailedToLoadAsset>> {
+        let loaded_assets: Vec<_> = self.loaded_assets.try_iter().collect();
+        self.remaining_asset_count -= loaded_assets.len();
+        loaded_assets
+    }
+
+    // Blocks until the next asset is loaded, returns None once every asset was returned
+    pub fn wait(&mut self) -> Option<Result<LoadedAsset, FailedToLoadAsset>> {
+        if self.remaining_asset_count == 0 {
+            return None;
+        }
+        // Only fails if every worker panicked
+        let loaded_asset = self.loaded_assets.recv().ok()?;
+        self.remaining_asset_count -= 1;
+        Some(loaded_asset)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.remaining_asset_count == 0
+    }
+}
+
+fn run_worker(
+    jobs: &Mutex<mpsc::Receiver<Job>>,
+    texture_cache: &TextureCache,
+    loaded_assets: &mpsc::Sender<Result<LoadedAsset, FailedToLoadAsset>>,
+) {
+    // Every job is queued before the workers start, so recv() never blocks
+    while let Ok(Ok(job)) = jobs.lock().map(|jobs| jobs.recv()) {
+        let (path, loaded_asset) = match job {
+            Job::Mesh { mesh, path } => {
+                let loaded_asset = load_mesh(mesh, &path, texture_cache);
+                (path, loaded_asset)
+            }
+            Job::DefaultTexture { path } => {
+                let loaded_asset = image_parser::Image::try_from(PpmFilePath(&path))
+                    .map(LoadedAsset::DefaultTexture)
+                    .map_err(|err| err.into());
+                (path, loaded_asset)
+            }
+        };
+
+        let loaded_asset =
+            loaded_asset.map_err(|err| FailedToLoadAsset::new(path, err.to_string()));
+        // The loader was dropped, nobody needs the remaining assets
+        if loaded_assets.send(loaded_asset).is_err() {
+            return;
+        }
+    }
+}
+
+fn load_mesh(mesh: usize, path: &str, texture_cache: &TextureCache) -> Result<LoadedAsset> {
+    let model = Model::load_with_cache(path, &format!("{path}.cache"), load_model)?;
+
+    // Embedded glTF images and anything that isn't a PPM file can't be decoded, the materials
+    // using them fall back to the default texture
+    for unsupported_texture in model.unsupported_textures() {
+        eprintln!("WARNING: \"{path}\": {unsupported_texture}, which is not supported");
+    }
+
+    let mut textures = DecodedTextures::new();
+    for material in model.materials() {
+        for texture_path in [
+            &material.diffuse_texture,
+            &material.metallic_roughness_texture,
+            &material.normal_texture,
+            &material.occlusion_texture,
+            &material.emissive_texture,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if textures.contains_key(texture_path) {
+                continue;
+            }
+            // Textures that fail to load are replaced by a fallback when the model is uploaded
+            if let Some(image) = decode_texture(texture_cache, texture_path, &material.name) {
+                textures.insert(texture_path.clone(), image);
+            }
+        }
+    }
+
+    Ok(LoadedAsset::Mesh {
+        mesh,
+        model,
+        textures,
+    })
+}
+
+// The warnings only name the material of the first model using the texture, as the other ones
+// get the cached result
+fn decode_texture(
+    texture_cache: &TextureCache,
+    path: &str,
+    material_name: &str,
+) -> Option<Arc<image_parser::Image>> {
+    let texture = Arc::clone(
+        texture_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(path.to_owned())
+            .or_default(),
+    );
+
+    // Blocks while another worker decodes the texture
+    let image = texture.get_or_init(|| {
+        if !path.to_lowercase().ends_with(".ppm") {
+            eprintln!(
+                "WARNING: Texture \"{path}\" of material \"{material_name}\" isn't a PPM file, which is the only supported format"
+            );
+            return None;
+        }
+        match image_parser::Image::try_from(PpmFilePath(path)) {
+            Ok(image) => Some(Arc::new(image)),
+            Err(err) => {
+                eprintln!(
+                    "WARNING: Failed to load texture \"{path}\" of material \"{material_name}\": {err}"
+                );
+                None
+            }
+        }
+    });
+    image.clone()
+}
+
+// The format is picked from the extension, anything that isn't glTF is parsed as OBJ
+fn load_model(path: &str) -> Result<Model> {
+    if path.ends_with(".gltf") || path.ends_with(".glb") {
+        Ok(Model::try_from(GltfFile(path))?)
+    } else {
+        Ok(Model::try_from(ObjFile(path))?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    const OBJ: &str = "mtllib test.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl shared\nf 1 2 3\n";
+    const MTL: &str = "newmtl shared\nmap_Kd shared.ppm\nnorm normal.png\n";
+
+    fn test_directory(name: &str) -> TestDirectory {
+        let directory = TestDirectory::new(&format!("asset_loader_{name}"));
+        directory.write("first.obj", OBJ);
+        directory.write("second.obj", OBJ);
+        directory.write("test.mtl", MTL);
+        directory
+    }
+
+    // Returns the meshes that were loaded and the errors
+    fn sort(
+        loaded_assets: Vec<Result<LoadedAsset, FailedToLoadAsset>>,
+    ) -> (Vec<usize>, Vec<String>) {
+        let mut meshes = Vec::new();
+        let mut errors = Vec::new();
+        for loaded_asset in loaded_assets {
+            match loaded_asset {
+                Ok(LoadedAsset::Mesh { mesh, .. }) => meshes.push(mesh),
+                Ok(LoadedAsset::DefaultTexture(_)) => panic!("The default texture doesn't exist"),
+                Err(err) => errors.push(err.to_string()),
+            }
+        }
+        meshes.sort();
+        errors.sort();
+        (meshes, errors)
+    }
+
+    #[test]
+    fn wait_returns_every_asset_once() {
+        let directory = test_directory("wait");
+        let mesh_paths = [
+            directory.path("first.obj"),
+            directory.path("missing.obj"),
+            directory.path("second.obj"),
+        ];
+        let mut loader = AssetLoader::new(&mesh_paths, &directory.path("missing.ppm")).unwrap();
+        assert_eq!(loader.remaining_asset_count, 4);
+        assert!(!loader.is_done());
+
+        let loaded_assets: Vec<_> = std::iter::from_fn(|| loader.wait()).collect();
+        assert!(loader.is_done());
+        assert!(loader.wait().is_none());
+        assert!(loader.poll().is_empty());
+
+        let (meshes, errors) = sort(loaded_assets);
+        assert_eq!(meshes, [0, 2]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains(&mesh_paths[1]));
+        assert!(errors[1].contains(&directory.path("missing.ppm")));
+    }
+
+    #[test]
+    fn poll_returns_the_assets_loaded_so_far() {
+        let directory = test_directory("poll");
+        let mesh_paths = [directory.path("first.obj"), directory.path("missing.obj")];
+        let mut loader = AssetLoader::new(&mesh_paths, &directory.path("missing.ppm")).unwrap();
+
+        let mut loaded_assets = Vec::new();
+        for _ in 0..10_000 {
+            if loader.is_done() {
+                break;
+            }
+            let polled = loader.poll();
+            assert_eq!(
+                loader.remaining_asset_count,
+                3 - loaded_assets.len() - polled.len()
+            );
+            loaded_assets.extend(polled);
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert!(loader.is_done());
+        assert!(loader.poll().is_empty());
+
+        let (meshes, errors) = sort(loaded_assets);
+        assert_eq!(meshes, [0]);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn textures_are_decoded_once() {
+        let directory = test_directory("textures");
+        let texture_cache = TextureCache::default();
+
+        for path in ["first.obj", "second.obj"] {
+            let Ok(LoadedAsset::Mesh { textures, .. }) =
+                load_mesh(0, &directory.path(path), &texture_cache)
+            else {
+                panic!("The model should load");
+            };
+            // Neither texture exists
+            assert!(textures.is_empty());
+        }
+
+        let texture_cache = texture_cache.into_inner().unwrap();
+        let mut paths: Vec<_> = texture_cache.keys().cloned().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [directory.path("normal.png"), directory.path("shared.ppm")]
+        );
+        assert!(texture_cache
+            .values()
+            .all(|texture| texture.get().is_some_and(Option::is_none)));
+    }
+
+    #[test]
+    fn workers_exit_once_the_jobs_are_done() {
+        let (job_sender, jobs) = mpsc::channel();
+        for path in ["first.obj", "second.obj"] {
+            job_sender
+                .send(Job::Mesh {
+                    mesh: 0,
+                    path: path.to_owned(),
+                })
+                .unwrap();
+        }
+        drop(job_sender);
+        let (loaded_asset_sender, loaded_assets) = mpsc::channel();
+
+        run_worker(
+            &Mutex::new(jobs),
+            &TextureCache::default(),
+            &loaded_asset_sender,
+        );
+        assert_eq!(loaded_assets.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn workers_exit_once_the_loader_is_dropped() {
+        let (job_sender, jobs) = mpsc::channel();
+        for path in ["first.obj", "second.obj"] {
+            job_sender
+                .send(Job::Mesh {
+                    mesh: 0,
+                    path: path.to_owned(),
+                })
+                .unwrap();
+        }
+        let (loaded_asset_sender, loaded_assets) = mpsc::channel();
+        drop(loaded_assets);
+
+        // The job sender is still alive, so the worker would block if it didn't stop
+        let jobs = Mutex::new(jobs);
+        run_worker(&jobs, &TextureCache::default(), &loaded_asset_sender);
+        assert!(jobs.lock().unwrap().try_recv().is_ok());
+    }
+}
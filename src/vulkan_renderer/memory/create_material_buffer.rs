@@ -0,0 +1,54 @@
+use ash::vk;
+
+use crate::vulkan_renderer::{
+    buffer::Buffer, material_uniform::MaterialUniform, push_constants::as_bytes,
+    upload_batch::UploadBatch, vulkan_context::VulkanContext,
+};
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+// Every material is bound at a multiple of stride
+pub struct MaterialBuffer {
+    pub buffer: Buffer,
+    pub stride: vk::DeviceSize,
+}
+
+pub unsafe fn create_material_buffer(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+    materials: &[MaterialUniform],
+) -> Result<MaterialBuffer> {
+    let alignment = context
+        .physical_device_properties()
+        .limits
+        .min_uniform_buffer_offset_alignment;
+    let stride = (size_of::<MaterialUniform>() as vk::DeviceSize).next_multiple_of(alignment);
+
+    let mut data = vec![0_u8; stride as usize * materials.len()];
+    for (material, chunk) in materials.iter().zip(data.chunks_mut(stride as usize)) {
+        chunk[..size_of::<MaterialUniform>()].copy_from_slice(as_bytes(material));
+    }
+    let buffer_size = data.len() as vk::DeviceSize;
+
+    let material_buffer = Buffer::new_movable(
+        context,
+        buffer_size,
+        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::UNIFORM_BUFFER,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?
+    .defer(|mut material_buffer| unsafe { material_buffer.destroy(context) });
+
+    upload_batch.copy_to_buffer(
+        &material_buffer,
+        &data,
+        vk::PipelineStageFlags::VERTEX_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        vk::AccessFlags::UNIFORM_READ,
+    )?;
+
+    Ok(MaterialBuffer {
+        buffer: ScopeGuard::into_inner(material_buffer),
+        stride,
+    })
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use ash::vk;
+use model::Material;
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+use crate::vulkan_renderer::{upload_batch::UploadBatch, vulkan_context::VulkanContext};
+
+use super::{asset_loader::DecodedTextures, Image};
+
+// Used as the base color of sub meshes without a material, a checkerboard until the default
+// texture is loaded
+const DEFAULT_TEXTURE_INDEX: usize = 0;
+// Used by materials without a texture, the factors of the material are used as is
+const WHITE_TEXTURE_INDEX: usize = 1;
+// Used by materials without a normal texture, keeps the normals of the vertices
+const FLAT_NORMAL_TEXTURE_INDEX: usize = 2;
+pub const BUILTIN_TEXTURE_COUNT: usize = 3;
+
+// In pixels, the checkerboard has 8 by 8 tiles
+const CHECKERBOARD_SIZE: u32 = 64;
+const CHECKERBOARD_TILE_SIZE: u32 = 8;
+
+const COLOR_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+const DATA_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+pub const MATERIAL_TEXTURE_COUNT: usize = 5;
+
+// Indices of the textures used by a material, in the order of the descriptor bindings
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialTextures {
+    pub base_color: usize,
+    pub metallic_roughness: usize,
+    pub normal: usize,
+    pub occlusion: usize,
+    pub emissive: usize,
+}
+
+impl MaterialTextures {
+    // Used by sub meshes without a material
+    pub const DEFAULT: Self = Self {
+        base_color: DEFAULT_TEXTURE_INDEX,
+        metallic_roughness: WHITE_TEXTURE_INDEX,
+        normal: FLAT_NORMAL_TEXTURE_INDEX,
+        occlusion: WHITE_TEXTURE_INDEX,
+        emissive: WHITE_TEXTURE_INDEX,
+    };
+
+    // Used by the materials of the scene
+    pub const UNTEXTURED: Self = Self {
+        base_color: WHITE_TEXTURE_INDEX,
+        ..Self::DEFAULT
+    };
+
+    pub fn as_array(&self) -> [usize; MATERIAL_TEXTURE_COUNT] {
+        [
+            self.base_color,
+            self.metallic_roughness,
+            self.normal,
+            self.occlusion,
+            self.emissive,
+        ]
+    }
+}
+
+// The checkerboard placeholder, white and flat normal textures, in the order of their indices
+pub unsafe fn create_builtin_textures(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+) -> Result<Vec<Image>> {
+    let mut textures = Vec::<Image>::with_capacity(BUILTIN_TEXTURE_COUNT).defer(|mut textures| {
+        for texture in textures.iter_mut() {
+            texture.destroy(context);
+        }
+    });
+
+    textures.push(create_checkerboard_texture(context, upload_batch)?);
+    textures.push(create_single_pixel_texture(
+        context,
+        upload_batch,
+        [u8::MAX; 4],
+    )?);
+    textures.push(create_single_pixel_texture(
+        context,
+        upload_batch,
+        [128, 128, 255, 255],
+    )?);
+
+    Ok(ScopeGuard::into_inner(textures))
+}
+
+// Replaces the checkerboard once it is loaded
+pub unsafe fn create_default_texture(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+    image: &image_parser::Image,
+) -> Result<Image> {
+    Image::from_texture_image(context, upload_batch, image, COLOR_FORMAT)
+}
+
+// Returns the textures of a model and the textures used by each of its materials.
+// The indices start at BUILTIN_TEXTURE_COUNT, the lower ones refer to the builtin textures.
+// decoded_textures holds the textures the asset loader managed to decode, the missing ones are
+// replaced by fallbacks
+pub unsafe fn create_model_textures(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+    materials: &[Material],
+    decoded_textures: &DecodedTextures,
+) -> Result<(Vec<Image>, Vec<MaterialTextures>)> {
+    let mut textures = Vec::<Image>::with_capacity(materials.len()).defer(|mut textures| {
+        for texture in textures.iter_mut() {
+            texture.destroy(context);
+        }
+    });
+
+    let mut loader = TextureLoader {
+        context,
+        upload_batch,
+        decoded_textures,
+        textures: &mut textures,
+        loaded_textures: HashMap::new(),
+    };
+    let materials_textures = materials
+        .iter()
+        .map(|material| {
+            Ok(MaterialTextures {
+                base_color: loader.load(
+                    material.diffuse_texture.as_ref(),
+                    COLOR_FORMAT,
+                    WHITE_TEXTURE_INDEX,
+                )?,
+                metallic_roughness: loader.load(
+                    material.metallic_roughness_texture.as_ref(),
+                    DATA_FORMAT,
+                    WHITE_TEXTURE_INDEX,
+                )?,
+                normal: loader.load(
+                    material.normal_texture.as_ref(),
+                    DATA_FORMAT,
+                    FLAT_NORMAL_TEXTURE_INDEX,
+                )?,
+                occlusion: loader.load(
+                    material.occlusion_texture.as_ref(),
+                    DATA_FORMAT,
+                    WHITE_TEXTURE_INDEX,
+                )?,
+                emissive: loader.load(
+                    material.emissive_texture.as_ref(),
+                    COLOR_FORMAT,
+                    WHITE_TEXTURE_INDEX,
+                )?,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok((ScopeGuard::into_inner(textures), materials_textures))
+}
+
+struct TextureLoader<'a, 'b> {
+    context: &'a VulkanContext,
+    upload_batch: &'a mut UploadBatch<'b>,
+    decoded_textures: &'a DecodedTextures,
+    textures: &'a mut Vec<Image>,
+    // Textures used by several materials are only uploaded once
+    loaded_textures: HashMap<(String, vk::Format), usize>,
+}
+
+impl TextureLoader<'_, '_> {
+    // Textures that couldn't be decoded are replaced by the fallback
+    unsafe fn load(
+        &mut self,
+        path: Option<&String>,
+        format: vk::Format,
+        fallback: usize,
+    ) -> Result<usize> {
+        let Some(path) = path else {
+            return Ok(fallback);
+        };
+        if let Some(index) = self.loaded_textures.get(&(path.clone(), format)) {
+            return Ok(*index);
+        }
+
+        let index = match self.decoded_textures.get(path) {
+            Some(image) => {
+                self.textures.push(Image::from_texture_image(
+                    self.context,
+                    self.upload_batch,
+                    image,
+                    format,
+                )?);
+                BUILTIN_TEXTURE_COUNT + self.textures.len() - 1
+            }
+            None => fallback,
+        };
+        self.loaded_textures.insert((path.clone(), format), index);
+        Ok(index)
+    }
+}
+
+unsafe fn create_single_pixel_texture(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+    pixel: [u8; 4],
+) -> Result<Image> {
+    Image::from_pixels(
+        context,
+        upload_batch,
+        vk::Extent2D {
+            width: 1,
+            height: 1,
+        },
+        &[pixel],
+        DATA_FORMAT,
+    )
+}
+
+unsafe fn create_checkerboard_texture(
+    context: &VulkanContext,
+    upload_batch: &mut UploadBatch,
+) -> Result<Image> {
+    let pixels: Vec<[u8; 4]> = (0..CHECKERBOARD_SIZE * CHECKERBOARD_SIZE)
+        .map(|i| {
+            let (x, y) = (i % CHECKERBOARD_SIZE, i / CHECKERBOARD_SIZE);
+            if (x / CHECKERBOARD_TILE_SIZE) % 2 == (y / CHECKERBOARD_TILE_SIZE) % 2 {
+                [u8::MAX, 0, u8::MAX, u8::MAX]
+            } else {
+                [0, 0, 0, u8::MAX]
+            }
+        })
+        .collect();
+
+    Image::from_pixels(
+        context,
+        upload_batch,
+        vk::Extent2D {
+            width: CHECKERBOARD_SIZE,
+            height: CHECKERBOARD_SIZE,
+        },
+        &pixels,
+        COLOR_FORMAT,
+    )
+}
@@ -3,7 +3,7 @@ use std::{
     mem::{self, MaybeUninit},
 };
 
-use ash::{prelude::VkResult, vk};
+use ash::vk;
 
 use crate::vulkan_renderer::{
     buffer::Buffer, uniform_buffer_object::UniformBufferObject, vulkan_context::VulkanContext,
@@ -24,17 +24,11 @@ pub fn create_uniform_buffers(
 
     for i in 0..NB_OF_FRAMES_IN_FLIGHT_USIZE {
         create_buffer(context)
-            .inspect_err(|_| unsafe {
-                destroy_uniform_buffers(context.device(), &mut buffers[..i])
-            })?
+            .inspect_err(|_| unsafe { destroy_uniform_buffers(context, &mut buffers[..i]) })?
             .pipe(|buffer| buffers[i].write(buffer));
 
-        unsafe { create_mapped_memory(context, buffers[i].assume_init_ref()) }
-            .inspect_err(|_| unsafe {
-                buffers[i].assume_init_mut().destroy(context.device());
-                destroy_uniform_buffers(context.device(), &mut buffers[..i])
-            })?
-            .pipe(|mapped_buffer| mapped_buffers[i].write(mapped_buffer));
+        // The memory of host visible buffers stays mapped
+        mapped_buffers[i].write(unsafe { buffers[i].assume_init_ref() }.mapped_ptr());
     }
 
     unsafe {
@@ -61,17 +55,8 @@ fn create_buffer(context: &VulkanContext) -> Result<Buffer> {
     )
 }
 
-unsafe fn create_mapped_memory(context: &VulkanContext, buffer: &Buffer) -> VkResult<*mut c_void> {
-    context
-        .device()
-        .map_memory(buffer.memory(), 0, BUFFER_SIZE, vk::MemoryMapFlags::empty())
-}
-
-unsafe fn destroy_uniform_buffers(device: &ash::Device, buffers: &mut [MaybeUninit<Buffer>]) {
+unsafe fn destroy_uniform_buffers(context: &VulkanContext, buffers: &mut [MaybeUninit<Buffer>]) {
     for buffer in buffers {
-        unsafe {
-            device.unmap_memory(buffer.assume_init_ref().memory());
-            buffer.assume_init_mut().destroy(device);
-        }
+        unsafe { buffer.assume_init_mut().destroy(context) }
     }
 }
@@ -1,41 +1,161 @@
 use ash::{prelude::VkResult, vk};
 
 use crate::vulkan_renderer::buffer::Buffer;
+use crate::vulkan_renderer::material_uniform::MaterialUniform;
+use crate::vulkan_renderer::render_targets::{PipelineReflection, RenderTargets};
 use crate::vulkan_renderer::uniform_buffer_object::UniformBufferObject;
 use crate::vulkan_renderer::{NB_OF_FRAMES_IN_FLIGHT, NB_OF_FRAMES_IN_FLIGHT_USIZE};
-use rs42::{extensions::PipeLine, Result};
+use rs42::{
+    extensions::PipeLine,
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
 
+use super::create_material_buffer::MaterialBuffer;
+use super::create_textures::{MaterialTextures, MATERIAL_TEXTURE_COUNT};
 use super::errors::FailedToConvertDescriptorSetsVecToArray;
 
-pub fn create_descriptor_pool(device: &ash::Device) -> VkResult<vk::DescriptorPool> {
-    let pool_sizes = [
-        vk::DescriptorPoolSize::default()
-            .ty(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(NB_OF_FRAMES_IN_FLIGHT),
-        vk::DescriptorPoolSize::default()
-            .ty(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(NB_OF_FRAMES_IN_FLIGHT),
-    ];
+pub const FRAME_SET: u32 = 0;
+pub const MATERIAL_SET: u32 = 1;
+
+// Descriptors written by the renderer, the shaders may only use some of them
+pub const FRAME_SET_BINDINGS: [(u32, vk::DescriptorType); 2] = [
+    (0, vk::DescriptorType::UNIFORM_BUFFER),
+    // Shadow maps
+    (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+];
+// The material uniform buffer then the base color, metallic-roughness, normal, occlusion and
+// emissive textures
+pub const MATERIAL_SET_BINDINGS: [(u32, vk::DescriptorType); MATERIAL_TEXTURE_COUNT + 1] = [
+    (0, vk::DescriptorType::UNIFORM_BUFFER),
+    (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (3, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (4, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (5, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+];
+
+// Set 0, the uniform buffer of each frame in flight
+pub struct FrameDescriptors {
+    pub pool: vk::DescriptorPool,
+    pub sets: [vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE],
+}
+
+// Set 1, the factors and textures of each material of a model.
+// Every model has its own pool so that a model can be added without touching the sets the
+// frames in flight use
+pub struct MaterialDescriptors {
+    // Null if there are no materials
+    pub pool: vk::DescriptorPool,
+    pub sets: Box<[vk::DescriptorSet]>,
+}
+
+pub unsafe fn create_frame_descriptors(
+    device: &ash::Device,
+    render_targets: &RenderTargets,
+    uniform_buffers: &[Buffer; NB_OF_FRAMES_IN_FLIGHT_USIZE],
+) -> Result<FrameDescriptors> {
+    let pool = create_descriptor_pool(
+        device,
+        render_targets.reflection(),
+        FRAME_SET,
+        NB_OF_FRAMES_IN_FLIGHT,
+    )?
+    .defer(|pool| device.destroy_descriptor_pool(pool, None));
+
+    // Destroyed automatically when the pool is destroyed
+    let sets = create_frame_descriptor_sets(device, render_targets, *pool, uniform_buffers)?;
+
+    Ok(FrameDescriptors {
+        pool: ScopeGuard::into_inner(pool),
+        sets,
+    })
+}
+
+// The indices of materials_textures refer to texture_views
+pub unsafe fn create_material_descriptors(
+    device: &ash::Device,
+    render_targets: &RenderTargets,
+    material_buffer: &MaterialBuffer,
+    materials_textures: &[MaterialTextures],
+    texture_views: &[vk::ImageView],
+    texture_sampler: vk::Sampler,
+) -> Result<MaterialDescriptors> {
+    if materials_textures.is_empty() {
+        return Ok(MaterialDescriptors {
+            pool: vk::DescriptorPool::null(),
+            sets: Box::new([]),
+        });
+    }
+
+    let pool = create_descriptor_pool(
+        device,
+        render_targets.reflection(),
+        MATERIAL_SET,
+        materials_textures.len() as u32,
+    )?
+    .defer(|pool| device.destroy_descriptor_pool(pool, None));
+
+    // Destroyed automatically when the pool is destroyed
+    let sets = create_material_descriptor_sets(
+        device,
+        render_targets,
+        *pool,
+        material_buffer,
+        materials_textures,
+        texture_views,
+        texture_sampler,
+    )?;
+
+    Ok(MaterialDescriptors {
+        pool: ScopeGuard::into_inner(pool),
+        sets,
+    })
+}
+
+// Sized from the bindings the shaders use
+fn create_descriptor_pool(
+    device: &ash::Device,
+    reflection: &PipelineReflection,
+    set: u32,
+    set_count: u32,
+) -> VkResult<vk::DescriptorPool> {
+    let mut pool_sizes: Vec<vk::DescriptorPoolSize> = Vec::new();
+    for binding in reflection.descriptor_bindings(set) {
+        let descriptor_count = binding.count * set_count;
+        match pool_sizes
+            .iter_mut()
+            .find(|pool_size| pool_size.ty == binding.descriptor_type)
+        {
+            Some(pool_size) => pool_size.descriptor_count += descriptor_count,
+            None => pool_sizes.push(
+                vk::DescriptorPoolSize::default()
+                    .ty(binding.descriptor_type)
+                    .descriptor_count(descriptor_count),
+            ),
+        }
+    }
+    pool_sizes.retain(|pool_size| pool_size.descriptor_count > 0);
 
     unsafe {
         device.create_descriptor_pool(
             &vk::DescriptorPoolCreateInfo::default()
                 .pool_sizes(&pool_sizes)
-                .max_sets(NB_OF_FRAMES_IN_FLIGHT),
+                .max_sets(set_count),
             None,
         )
     }
 }
 
-pub unsafe fn create_descriptor_sets(
+unsafe fn create_frame_descriptor_sets(
     device: &ash::Device,
-    descriptor_set_layout: vk::DescriptorSetLayout,
+    render_targets: &RenderTargets,
     descriptor_pool: vk::DescriptorPool,
     uniform_buffers: &[Buffer; NB_OF_FRAMES_IN_FLIGHT_USIZE],
-    texture_image_view: vk::ImageView,
-    texture_sampler: vk::Sampler,
 ) -> Result<[vk::DescriptorSet; NB_OF_FRAMES_IN_FLIGHT_USIZE]> {
-    let layouts = [descriptor_set_layout; NB_OF_FRAMES_IN_FLIGHT_USIZE];
+    let reflection = render_targets.reflection();
+    let shadow_maps = render_targets.shadow_maps();
+    let layouts = [render_targets.frame_descriptor_set_layout(); NB_OF_FRAMES_IN_FLIGHT_USIZE];
 
     let allocate_info = vk::DescriptorSetAllocateInfo::default()
         .descriptor_pool(descriptor_pool)
@@ -56,12 +176,10 @@ pub unsafe fn create_descriptor_sets(
             .range(size_of::<UniformBufferObject>() as vk::DeviceSize)]
     });
 
-    let image_infos = std::array::from_fn::<_, NB_OF_FRAMES_IN_FLIGHT_USIZE, _>(|_| {
-        [vk::DescriptorImageInfo::default()
-            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-            .image_view(texture_image_view)
-            .sampler(texture_sampler)]
-    });
+    let shadow_maps_info = [vk::DescriptorImageInfo::default()
+        .image_layout(shadow_maps.image_layout())
+        .image_view(shadow_maps.image_view())
+        .sampler(shadow_maps.sampler())];
 
     let descriptor_writes = (0..NB_OF_FRAMES_IN_FLIGHT_USIZE)
         .flat_map(|i| {
@@ -79,12 +197,88 @@ pub unsafe fn create_descriptor_sets(
                     .dst_array_element(0)
                     .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
                     .descriptor_count(1)
-                    .image_info(&image_infos[i]),
+                    .image_info(&shadow_maps_info),
             ]
         })
+        .filter(|write| reflection.uses_binding(FRAME_SET, write.dst_binding))
         .collect::<Vec<vk::WriteDescriptorSet>>();
 
     device.update_descriptor_sets(&descriptor_writes, &[]);
 
     Ok(descriptor_sets)
 }
+
+unsafe fn create_material_descriptor_sets(
+    device: &ash::Device,
+    render_targets: &RenderTargets,
+    descriptor_pool: vk::DescriptorPool,
+    material_buffer: &MaterialBuffer,
+    materials_textures: &[MaterialTextures],
+    texture_views: &[vk::ImageView],
+    texture_sampler: vk::Sampler,
+) -> Result<Box<[vk::DescriptorSet]>> {
+    let reflection = render_targets.reflection();
+    let layouts = vec![render_targets.material_descriptor_set_layout(); materials_textures.len()];
+
+    let allocate_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&layouts);
+
+    let descriptor_sets = device.allocate_descriptor_sets(&allocate_info)?;
+
+    let buffer_infos = (0..materials_textures.len())
+        .map(|i| {
+            [vk::DescriptorBufferInfo::default()
+                .buffer(material_buffer.buffer.buffer())
+                .offset(i as vk::DeviceSize * material_buffer.stride)
+                .range(size_of::<MaterialUniform>() as vk::DeviceSize)]
+        })
+        .collect::<Vec<_>>();
+
+    let image_infos = materials_textures
+        .iter()
+        .map(|material_textures| {
+            material_textures.as_array().map(|texture| {
+                [vk::DescriptorImageInfo::default()
+                    .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .image_view(texture_views[texture])
+                    .sampler(texture_sampler)]
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let descriptor_writes = descriptor_sets
+        .iter()
+        .enumerate()
+        .flat_map(|(i, descriptor_set)| {
+            let buffer_write = vk::WriteDescriptorSet::default()
+                .dst_set(*descriptor_set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .buffer_info(&buffer_infos[i]);
+
+            let image_writes =
+                image_infos[i]
+                    .iter()
+                    .enumerate()
+                    .map(move |(binding, image_info)| {
+                        vk::WriteDescriptorSet::default()
+                            .dst_set(*descriptor_set)
+                            .dst_binding(binding as u32 + 1)
+                            .dst_array_element(0)
+                            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                            .descriptor_count(1)
+                            .image_info(image_info)
+                    });
+
+            std::iter::once(buffer_write).chain(image_writes)
+        })
+        .filter(|write| reflection.uses_binding(MATERIAL_SET, write.dst_binding))
+        .collect::<Vec<vk::WriteDescriptorSet>>();
+
+    device.update_descriptor_sets(&descriptor_writes, &[]);
+
+    Ok(descriptor_sets.into_boxed_slice())
+}
@@ -9,3 +9,13 @@ error_struct_custom_display!(
     vec_len,
     expected_len,
 );
+
+error_struct_custom_display!(
+    FailedToLoadAsset {
+        path: String,
+        reason: String,
+    },
+    "Failed to load \"{}\": {}",
+    path,
+    reason,
+);
@@ -1,49 +1,33 @@
 mod from_texture_image;
 mod new;
 
-use crate::vulkan_renderer::{
-    buffer::Buffer, single_time_command::SingleTimeCommand, vulkan_context::VulkanContext,
-    vulkan_interface::VulkanInterface,
-};
-use ash::vk;
+use crate::vulkan_renderer::{allocator::Allocation, vulkan_context::VulkanContext};
+use ash::{prelude::VkResult, vk};
 pub use new::ImageCreateInfo;
-use rs42::Result;
 
 pub struct Image {
     image: vk::Image,
-    memory: vk::DeviceMemory,
+    allocation: Allocation,
     image_view: vk::ImageView,
     mip_levels: u32,
     #[cfg(debug_assertions)]
     is_destroyed: bool,
 }
 
-struct TransitionImageLayoutInfo {
-    _format: vk::Format,
-    old_layout: vk::ImageLayout,
-    new_layout: vk::ImageLayout,
-    src_access_mask: vk::AccessFlags,
-    dst_access_mask: vk::AccessFlags,
-    src_stage_mask: vk::PipelineStageFlags,
-    dst_stage_mask: vk::PipelineStageFlags,
-}
-
 impl Image {
-    fn copy_from_buffer(
+    // The image has to be in the TRANSFER_DST_OPTIMAL layout, only the first mip level is written
+    unsafe fn record_copy_from_buffer(
         &self,
-        buffer: &Buffer,
-        width: u32,
-        height: u32,
+        command_buffer: vk::CommandBuffer,
+        buffer: vk::Buffer,
+        extent: vk::Extent2D,
         device: &ash::Device,
-        interface: &VulkanInterface,
-    ) -> Result<()> {
+    ) {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed)
         }
 
-        let single_time_command = SingleTimeCommand::begin(device, interface)?;
-
         let region = vk::BufferImageCopy::default()
             .buffer_offset(0)
             .buffer_row_length(0)
@@ -56,20 +40,29 @@ impl Image {
                     .layer_count(1),
             )
             .image_offset(vk::Offset3D::default().x(0).y(0).z(0))
-            .image_extent(vk::Extent3D::default().width(width).height(height).depth(1));
-
-        unsafe {
-            device.cmd_copy_buffer_to_image(
-                *single_time_command,
-                buffer.buffer(),
-                self.image,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                &[region],
+            .image_extent(
+                vk::Extent3D::default()
+                    .width(extent.width)
+                    .height(extent.height)
+                    .depth(1),
             );
+
+        device.cmd_copy_buffer_to_image(
+            command_buffer,
+            buffer,
+            self.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            &[region],
+        );
+    }
+
+    pub fn image(&self) -> vk::Image {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert!(!self.is_destroyed)
         }
 
-        single_time_command.submit()?;
-        Ok(())
+        self.image
     }
 
     pub fn image_view(&self) -> vk::ImageView {
@@ -81,6 +74,30 @@ impl Image {
         self.image_view
     }
 
+    // The view only sees a single layer of the image, it has to be destroyed by the caller
+    pub unsafe fn create_layer_view(
+        &self,
+        device: &ash::Device,
+        format: vk::Format,
+        aspect_mask: vk::ImageAspectFlags,
+        layer: u32,
+    ) -> VkResult<vk::ImageView> {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert!(!self.is_destroyed)
+        }
+
+        new::init_image_view(
+            device,
+            self.image,
+            vk::ImageViewType::TYPE_2D,
+            format,
+            aspect_mask,
+            self.mip_levels,
+            layer..layer + 1,
+        )
+    }
+
     pub fn find_supported_format(
         context: &VulkanContext,
         candidates: &[vk::Format],
@@ -108,15 +125,15 @@ impl Image {
         None
     }
 
-    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
         #[cfg(debug_assertions)]
         {
             debug_assert!(!self.is_destroyed);
             self.is_destroyed = true;
         }
 
-        device.destroy_image_view(self.image_view, None);
-        device.destroy_image(self.image, None);
-        device.free_memory(self.memory, None);
+        context.device().destroy_image_view(self.image_view, None);
+        context.device().destroy_image(self.image, None);
+        context.allocator().free(context.device(), &self.allocation);
     }
 }
@@ -1,7 +1,4 @@
-use ash::{
-    prelude::VkResult,
-    vk::{self, Offset3D},
-};
+use ash::vk::{self, Offset3D};
 
 use rs42::{
     extensions::PipeLine,
@@ -9,58 +6,55 @@ use rs42::{
     Result,
 };
 
-use crate::vulkan_renderer::{
-    buffer::Buffer, single_time_command::SingleTimeCommand, vulkan_context::VulkanContext,
-    vulkan_interface::VulkanInterface,
-};
+use crate::vulkan_renderer::{upload_batch::UploadBatch, vulkan_context::VulkanContext};
 
-use super::{Image, ImageCreateInfo, TransitionImageLayoutInfo};
+use super::{Image, ImageCreateInfo};
 
 impl Image {
     pub unsafe fn from_texture_image(
         context: &VulkanContext,
-        interface: &VulkanInterface,
+        upload_batch: &mut UploadBatch,
         texture: &image_parser::Image,
+        format: vk::Format,
+    ) -> Result<Self> {
+        let extent = vk::Extent2D {
+            width: texture.width() as u32,
+            height: texture.height() as u32,
+        };
+        Self::from_pixels(context, upload_batch, extent, &texture[..], format)
+    }
+
+    // Pixels are expected to be R8G8B8A8, format should either be the SRGB or the UNORM variant.
+    // The image can be used once the upload batch is submitted
+    pub unsafe fn from_pixels<T>(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        extent: vk::Extent2D,
+        pixels: &[T],
+        image_format: vk::Format,
     ) -> Result<Self> {
-        let image_format = vk::Format::R8G8B8A8_SRGB;
-        let mip_levels = get_mip_level(context, texture, image_format);
+        let mip_levels = get_mip_level(context, extent, image_format);
 
-        let staging_buffer = create_staging_buffer(context, texture)?
-            .defer(|mut staging_buffer| staging_buffer.destroy(context.device()));
+        let staging_buffer = upload_batch.stage(pixels)?;
 
-        let image = create_image(context, texture, mip_levels, image_format)?
-            .defer(|mut image| image.destroy(context.device()));
+        let image = create_image(context, extent, mip_levels, image_format)?
+            .defer(|mut image| image.destroy(context));
 
-        copy_staging_buffer_to_image_and_generate_mip_maps(
+        record_upload_and_mip_maps_generation(
             &image,
-            &staging_buffer,
+            staging_buffer,
             context.device(),
-            interface,
-            texture,
-        )?;
+            upload_batch,
+            extent,
+        );
 
         ScopeGuard::into_inner(image).pipe(Ok)
     }
 }
 
-fn create_staging_buffer(context: &VulkanContext, texture: &image_parser::Image) -> Result<Buffer> {
-    let staging_buffer = Buffer::new(
-        context,
-        (texture.width() * texture.height() * size_of_val(&texture[0])) as vk::DeviceSize,
-        vk::BufferUsageFlags::TRANSFER_SRC,
-        vk::SharingMode::EXCLUSIVE,
-        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-    )?
-    .defer(|mut staging_buffer| unsafe { staging_buffer.destroy(context.device()) });
-
-    unsafe { staging_buffer.copy_from_ram(0, texture, context.device())? }
-
-    ScopeGuard::into_inner(staging_buffer).pipe(Ok)
-}
-
 unsafe fn get_mip_level(
     context: &VulkanContext,
-    texture: &image_parser::Image,
+    extent: vk::Extent2D,
     image_format: vk::Format,
 ) -> u32 {
     let format_properties = context
@@ -74,8 +68,8 @@ unsafe fn get_mip_level(
         return 1;
     }
 
-    (texture.width() as f32)
-        .max(texture.height() as f32)
+    (extent.width as f32)
+        .max(extent.height as f32)
         .log2()
         .floor() as u32
         + 1
@@ -83,7 +77,7 @@ unsafe fn get_mip_level(
 
 fn create_image(
     context: &VulkanContext,
-    texture: &image_parser::Image,
+    extent: vk::Extent2D,
     mip_levels: u32,
     image_format: vk::Format,
 ) -> Result<Image> {
@@ -91,11 +85,9 @@ fn create_image(
         context,
         ImageCreateInfo {
             mip_levels,
+            array_layers: 1,
             sample_count: vk::SampleCountFlags::TYPE_1,
-            extent: vk::Extent2D {
-                width: texture.width() as u32,
-                height: texture.height() as u32,
-            },
+            extent,
             format: image_format,
             tiling: vk::ImageTiling::OPTIMAL,
             usage: vk::ImageUsageFlags::TRANSFER_SRC
@@ -107,115 +99,66 @@ fn create_image(
     )
 }
 
-unsafe fn copy_staging_buffer_to_image_and_generate_mip_maps(
+// The copy is recorded on the transfer queue, the mip maps are generated on the graphics queue
+unsafe fn record_upload_and_mip_maps_generation(
     image: &Image,
-    staging_buffer: &Buffer,
+    staging_buffer: vk::Buffer,
     device: &ash::Device,
-    interface: &VulkanInterface,
-    texture: &image_parser::Image,
-) -> Result<()> {
-    // TODO the next 3 function call all create a SingleTimeCommand, make them share a single
-    // command buffer
-    // Might be worth looking into creating a single SingleTimeCommand per frame
+    upload_batch: &UploadBatch,
+    extent: vk::Extent2D,
+) {
+    let subresource_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .base_mip_level(0)
+        .level_count(image.mip_levels)
+        .base_array_layer(0)
+        .layer_count(1);
 
-    transition_image_layout_from_undefined_to_transfer_dst_optimal(
-        image,
-        device,
-        interface,
-        vk::Format::R8G8B8A8_SRGB,
-    )?;
+    device.cmd_pipeline_barrier(
+        upload_batch.transfer_command_buffer(),
+        vk::PipelineStageFlags::TOP_OF_PIPE,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::DependencyFlags::empty(),
+        &[],
+        &[],
+        &[vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image.image)
+            .subresource_range(subresource_range)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)],
+    );
 
-    image.copy_from_buffer(
+    image.record_copy_from_buffer(
+        upload_batch.transfer_command_buffer(),
         staging_buffer,
-        texture.width() as u32,
-        texture.height() as u32,
+        extent,
         device,
-        interface,
-    )?;
+    );
+
+    upload_batch.transfer_image_ownership(
+        image.image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        subresource_range,
+    );
 
     generate_mip_maps(
         image,
-        vk::Extent2D {
-            width: texture.width() as u32,
-            height: texture.height() as u32,
-        },
+        extent,
         device,
-        interface,
-    )?;
-
-    Ok(())
-}
-
-fn transition_image_layout_from_undefined_to_transfer_dst_optimal(
-    image: &Image,
-    device: &ash::Device,
-    interface: &VulkanInterface,
-    format: vk::Format,
-) -> VkResult<()> {
-    unsafe {
-        transition_image_layout(
-            image,
-            device,
-            interface,
-            TransitionImageLayoutInfo {
-                _format: format,
-                old_layout: vk::ImageLayout::UNDEFINED,
-                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                src_access_mask: vk::AccessFlags::empty(),
-                dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-                src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
-                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
-            },
-        )
-    }
-}
-
-unsafe fn transition_image_layout(
-    image: &Image,
-    device: &ash::Device,
-    interface: &VulkanInterface,
-    info: TransitionImageLayoutInfo,
-) -> VkResult<()> {
-    let single_time_command = SingleTimeCommand::begin(device, interface)?;
-
-    let barrier = vk::ImageMemoryBarrier::default()
-        .old_layout(info.old_layout)
-        .new_layout(info.new_layout)
-        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-        .image(image.image)
-        .subresource_range(
-            vk::ImageSubresourceRange::default()
-                .aspect_mask(vk::ImageAspectFlags::COLOR)
-                .base_mip_level(0)
-                .level_count(image.mip_levels)
-                .base_array_layer(0)
-                .layer_count(1),
-        )
-        .src_access_mask(info.src_access_mask)
-        .dst_access_mask(info.dst_access_mask);
-
-    device.cmd_pipeline_barrier(
-        *single_time_command,
-        info.src_stage_mask,
-        info.dst_stage_mask,
-        vk::DependencyFlags::empty(),
-        &[],
-        &[],
-        &[barrier],
+        upload_batch.graphics_command_buffer(),
     );
-
-    single_time_command.submit()?;
-    Ok(())
 }
 
 unsafe fn generate_mip_maps(
     image: &Image,
     extent: vk::Extent2D,
     device: &ash::Device,
-    interface: &VulkanInterface,
-) -> VkResult<()> {
-    let single_time_command = SingleTimeCommand::begin(device, interface)?;
+    command_buffer: vk::CommandBuffer,
+) {
     let mut mip_width = extent.width as i32;
     let mut mip_height = extent.height as i32;
 
@@ -239,7 +182,7 @@ unsafe fn generate_mip_maps(
         barrier[0].dst_access_mask = vk::AccessFlags::TRANSFER_READ;
 
         device.cmd_pipeline_barrier(
-            *single_time_command,
+            command_buffer,
             vk::PipelineStageFlags::TRANSFER,
             vk::PipelineStageFlags::TRANSFER,
             vk::DependencyFlags::empty(),
@@ -281,7 +224,7 @@ unsafe fn generate_mip_maps(
             )];
 
         device.cmd_blit_image(
-            *single_time_command,
+            command_buffer,
             image.image,
             vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
             image.image,
@@ -296,7 +239,7 @@ unsafe fn generate_mip_maps(
         barrier[0].dst_access_mask = vk::AccessFlags::SHADER_READ;
 
         device.cmd_pipeline_barrier(
-            *single_time_command,
+            command_buffer,
             vk::PipelineStageFlags::TRANSFER,
             vk::PipelineStageFlags::FRAGMENT_SHADER,
             vk::DependencyFlags::empty(),
@@ -320,7 +263,7 @@ unsafe fn generate_mip_maps(
     barrier[0].dst_access_mask = vk::AccessFlags::SHADER_READ;
 
     device.cmd_pipeline_barrier(
-        *single_time_command,
+        command_buffer,
         vk::PipelineStageFlags::TRANSFER,
         vk::PipelineStageFlags::FRAGMENT_SHADER,
         vk::DependencyFlags::empty(),
@@ -328,7 +271,4 @@ unsafe fn generate_mip_maps(
         &[],
         &barrier,
     );
-
-    single_time_command.submit()?;
-    Ok(())
 }
@@ -0,0 +1,331 @@
+use ash::vk;
+use model::{Model, SubMesh, Vertex};
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+use crate::vulkan_renderer::{
+    buffer::Buffer, material_uniform::MaterialUniform, render_targets::RenderTargets,
+    upload_batch::UploadBatch, vulkan_context::VulkanContext,
+};
+
+use super::{
+    asset_loader::DecodedTextures,
+    create_index_buffer::create_index_buffer,
+    create_material_buffer::{create_material_buffer, MaterialBuffer},
+    create_textures::{create_model_textures, MaterialTextures},
+    create_vertex_buffer::create_vertex_buffer,
+    descriptors::{create_material_descriptors, MaterialDescriptors},
+    Image,
+};
+
+// Half the length of the edges of the placeholder cube
+const PLACEHOLDER_HALF_SIZE: f32 = 0.5;
+
+// The GPU resources of a model of the scene, or of the placeholder cube drawn until a model is
+// loaded
+pub struct ModelResources {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    // Sub meshes without a material use the default material of the memory
+    pub sub_meshes: Box<[SubMesh]>,
+    textures: Box<[Image]>,
+    // None if the model has no materials
+    pub materials: Option<Materials>,
+}
+
+// The factors and textures of the materials of a model, or of the default material
+pub struct Materials {
+    buffer: MaterialBuffer,
+    textures: Box<[MaterialTextures]>,
+    // The views the indices of textures refer to
+    texture_views: Box<[vk::ImageView]>,
+    descriptors: MaterialDescriptors,
+}
+
+impl ModelResources {
+    // builtin_texture_views are used by the materials that lack some textures
+    pub unsafe fn new(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        render_targets: &RenderTargets,
+        model: &Model,
+        decoded_textures: &DecodedTextures,
+        builtin_texture_views: &[vk::ImageView],
+        sampler: vk::Sampler,
+    ) -> Result<Self> {
+        let mut resources = Self::from_geometry(
+            context,
+            upload_batch,
+            model.vertices(),
+            model.vertex_indices(),
+            model.sub_meshes().into(),
+        )?
+        .defer(|mut resources| resources.destroy(context));
+        if model.materials().is_empty() {
+            return Ok(ScopeGuard::into_inner(resources));
+        }
+
+        let (textures, materials_textures) =
+            create_model_textures(context, upload_batch, model.materials(), decoded_textures)?;
+        resources.textures = textures.into_boxed_slice();
+
+        let texture_views = builtin_texture_views
+            .iter()
+            .copied()
+            .chain(resources.textures.iter().map(Image::image_view))
+            .collect();
+        let material_uniforms: Vec<_> = model
+            .materials()
+            .iter()
+            .map(MaterialUniform::from)
+            .collect();
+        resources.materials = Some(Materials::new(
+            context,
+            upload_batch,
+            render_targets,
+            &material_uniforms,
+            materials_textures.into_boxed_slice(),
+            texture_views,
+            sampler,
+        )?);
+
+        Ok(ScopeGuard::into_inner(resources))
+    }
+
+    // A cube of one unit, drawn with the default material
+    pub unsafe fn placeholder(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+    ) -> Result<Self> {
+        let (vertices, indices) = placeholder_cube();
+
+        Self::from_geometry(
+            context,
+            upload_batch,
+            &vertices,
+            &indices,
+            Box::new([SubMesh {
+                first_index: 0,
+                index_count: indices.len() as u32,
+                material: None,
+            }]),
+        )
+    }
+
+    unsafe fn from_geometry(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        vertices: &[Vertex],
+        indices: &[u32],
+        sub_meshes: Box<[SubMesh]>,
+    ) -> Result<Self> {
+        let vertex_buffer = create_vertex_buffer(context, upload_batch, vertices)?
+            .defer(|mut vertex_buffer| vertex_buffer.destroy(context));
+        let index_buffer = create_index_buffer(context, upload_batch, indices)?;
+
+        Ok(Self {
+            vertex_buffer: ScopeGuard::into_inner(vertex_buffer),
+            index_buffer,
+            sub_meshes,
+            textures: Box::new([]),
+            materials: None,
+        })
+    }
+
+    // The buffers a defragmentation can move, the textures stay where they are
+    pub fn buffers_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        [&mut self.vertex_buffer, &mut self.index_buffer]
+            .into_iter()
+            .chain(self.materials.as_mut().map(Materials::buffer_mut))
+    }
+
+    // The descriptors have to be destroyed before
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
+        if let Some(materials) = self.materials.as_mut() {
+            materials.destroy(context);
+        }
+        for texture in self.textures.iter_mut() {
+            texture.destroy(context);
+        }
+        self.index_buffer.destroy(context);
+        self.vertex_buffer.destroy(context);
+    }
+}
+
+impl Materials {
+    pub unsafe fn new(
+        context: &VulkanContext,
+        upload_batch: &mut UploadBatch,
+        render_targets: &RenderTargets,
+        material_uniforms: &[MaterialUniform],
+        textures: Box<[MaterialTextures]>,
+        texture_views: Box<[vk::ImageView]>,
+        sampler: vk::Sampler,
+    ) -> Result<Self> {
+        let buffer = create_material_buffer(context, upload_batch, material_uniforms)?
+            .defer(|mut buffer| buffer.buffer.destroy(context));
+
+        let descriptors = create_material_descriptors(
+            context.device(),
+            render_targets,
+            &buffer,
+            &textures,
+            &texture_views,
+            sampler,
+        )?;
+
+        Ok(Self {
+            buffer: ScopeGuard::into_inner(buffer),
+            textures,
+            texture_views,
+            descriptors,
+        })
+    }
+
+    // The descriptors have to be recreated if the buffer is replaced
+    pub fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffer.buffer
+    }
+
+    // Indexed like the materials
+    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet] {
+        &self.descriptors.sets
+    }
+
+    // The descriptor set layouts belong to the render targets, so the descriptors have to be
+    // recreated with them
+    pub unsafe fn recreate_descriptors(
+        &mut self,
+        device: &ash::Device,
+        render_targets: &RenderTargets,
+        sampler: vk::Sampler,
+    ) -> Result<()> {
+        self.descriptors = create_material_descriptors(
+            device,
+            render_targets,
+            &self.buffer,
+            &self.textures,
+            &self.texture_views,
+            sampler,
+        )?;
+        Ok(())
+    }
+
+    pub unsafe fn destroy_descriptors(&self, device: &ash::Device) {
+        device.destroy_descriptor_pool(self.descriptors.pool, None);
+    }
+
+    // The descriptors have to be destroyed before
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
+        self.buffer.buffer.destroy(context);
+    }
+}
+
+// Every face has its own vertices so that its normal is flat
+fn placeholder_cube() -> (Vec<Vertex>, Vec<u32>) {
+    let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+        // Normal, then the two axes the face spans
+        ([1., 0., 0.], [0., 1., 0.], [0., 0., 1.]),
+        ([-1., 0., 0.], [0., 0., 1.], [0., 1., 0.]),
+        ([0., 1., 0.], [0., 0., 1.], [1., 0., 0.]),
+        ([0., -1., 0.], [1., 0., 0.], [0., 0., 1.]),
+        ([0., 0., 1.], [1., 0., 0.], [0., 1., 0.]),
+        ([0., 0., -1.], [0., 1., 0.], [1., 0., 0.]),
+    ];
+    let corners = [
+        ([-1., -1.], [0., 1.]),
+        ([1., -1.], [1., 1.]),
+        ([1., 1.], [1., 0.]),
+        ([-1., 1.], [0., 0.]),
+    ];
+
+    let mut vertices = Vec::with_capacity(faces.len() * corners.len());
+    let mut indices = Vec::with_capacity(faces.len() * 6);
+    for (normal, u_axis, v_axis) in faces {
+        let first_vertex = vertices.len() as u32;
+        for ([u, v], texture_coordinate) in corners {
+            let position: [f32; 3] = std::array::from_fn(|i| {
+                (normal[i] + u * u_axis[i] + v * v_axis[i]) * PLACEHOLDER_HALF_SIZE
+            });
+            vertices.push(Vertex::new(
+                position,
+                [1., 1., 1.],
+                texture_coordinate,
+                normal,
+            ));
+        }
+        indices.extend([0, 1, 2, 2, 3, 0].map(|index| first_vertex + index));
+    }
+    (vertices, indices)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn subtract(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        std::array::from_fn(|i| a[i] - b[i])
+    }
+
+    fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    }
+
+    #[test]
+    fn placeholder_cube_has_flat_faces() {
+        let (vertices, indices) = placeholder_cube();
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        assert!(indices
+            .iter()
+            .all(|&index| (index as usize) < vertices.len()));
+
+        for vertex in vertices.iter() {
+            let position = vertex.position().clone().into_scalars();
+            let normal = vertex.normal().clone().into_scalars();
+            assert!(position
+                .iter()
+                .all(|coordinate| coordinate.abs() == PLACEHOLDER_HALF_SIZE));
+            // The vertex is on the face its normal points out of
+            for i in 0..3 {
+                if normal[i] != 0. {
+                    assert_eq!(position[i], normal[i] * PLACEHOLDER_HALF_SIZE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn placeholder_cube_triangles_face_outwards() {
+        let (vertices, indices) = placeholder_cube();
+        let mut normals = Vec::new();
+
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| {
+                vertices[triangle[i] as usize]
+                    .position()
+                    .clone()
+                    .into_scalars()
+            });
+            let normal = vertices[triangle[0] as usize]
+                .normal()
+                .clone()
+                .into_scalars();
+            // Counter-clockwise when seen from outside the cube
+            let face_normal = cross(subtract(b, a), subtract(c, a));
+            let scale = PLACEHOLDER_HALF_SIZE * PLACEHOLDER_HALF_SIZE * 4.;
+            assert_eq!(face_normal, normal.map(|coordinate| coordinate * scale));
+            normals.push(normal);
+        }
+
+        normals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        normals.dedup();
+        assert_eq!(normals.len(), 6);
+    }
+}
@@ -0,0 +1,18 @@
+type Mat4 = linear_algebra::Matrix<f32, 4, 4>;
+
+// Pushed before drawing each object of the scene, read by the vertex shader
+#[repr(C)]
+pub struct ObjectPushConstants {
+    pub model: Mat4,
+}
+
+// Pushed before drawing each object into a shadow map, read by the shadow vertex shader
+#[repr(C)]
+pub struct ShadowPushConstants {
+    pub model: Mat4,
+    pub shadow_map_index: u32,
+}
+
+pub fn as_bytes<T>(push_constants: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts((push_constants as *const T).cast::<u8>(), size_of::<T>()) }
+}
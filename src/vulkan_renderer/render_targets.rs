@@ -1,55 +1,85 @@
 mod create_color_buffer;
 mod create_depth_buffer;
 mod create_framebuffers;
+mod create_offscreen_image;
 mod create_render_pass;
 mod errors;
 mod graphics_pipeline;
 mod image_views;
+mod shadow_maps;
 
 use ash::{prelude::VkResult, vk};
 use create_color_buffer::create_color_buffer;
 use create_depth_buffer::create_depth_buffer;
 use create_framebuffers::create_framebuffers;
+use create_offscreen_image::create_offscreen_image;
 use create_render_pass::create_render_pass;
 use graphics_pipeline::create_graphics_pipeline;
+pub use graphics_pipeline::PipelineReflection;
 use image_views::create_image_views;
 use rs42::{
     scope_guard::{Defer, ScopeGuard},
     Result,
 };
+pub use shadow_maps::ShadowMaps;
+
+use crate::config::{AssetPaths, ShadowSettings};
+
+use super::shader_watcher::ChangedPipelines;
+use errors::DescriptorBindingsChanged;
 
 use super::{
-    memory::Image,
+    memory::{Image, FRAME_SET, FRAME_SET_BINDINGS, MATERIAL_SET, MATERIAL_SET_BINDINGS},
     vulkan_context::{SwapchainBuilder, VulkanContext},
 };
 
+// Format of the image rendered to when there is no swapchain
+pub const OFFSCREEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_SRGB;
+
 pub struct RenderTargets {
     is_destroyed: bool,
 
-    swapchain_device: ash::khr::swapchain::Device,
-    swapchain: vk::SwapchainKHR,
-    #[allow(dead_code)]
-    swapchain_images: Box<[vk::Image]>,
-    #[allow(dead_code)]
-    swapchain_format: vk::Format,
-    swapchain_extent: vk::Extent2D,
-    swapchain_image_views: Box<[vk::ImageView]>,
+    presentation_target: PresentationTarget,
+    format: vk::Format,
+    extent: vk::Extent2D,
 
     render_pass: vk::RenderPass,
-    descriptor_set_layout: vk::DescriptorSetLayout,
+    // Interface of the main shaders, the descriptor set layouts are built from it
+    reflection: PipelineReflection,
+    frame_descriptor_set_layout: vk::DescriptorSetLayout,
+    material_descriptor_set_layout: vk::DescriptorSetLayout,
     pipeline_layout: vk::PipelineLayout,
     pipeline: vk::Pipeline,
 
+    shadow_maps: ShadowMaps,
+
     depth_buffer: Image,
     color_buffer: Image,
 
     framebuffers: Box<[vk::Framebuffer]>,
 }
 
+// The images the multisampled color buffer is resolved into
+enum PresentationTarget {
+    Swapchain {
+        swapchain_device: ash::khr::swapchain::Device,
+        swapchain: vk::SwapchainKHR,
+        swapchain_images: Box<[vk::Image]>,
+        swapchain_image_views: Box<[vk::ImageView]>,
+        // False if the surface doesn't allow the images to be transfer sources
+        are_images_capturable: bool,
+    },
+    Offscreen {
+        image: Image,
+    },
+}
+
 impl RenderTargets {
     pub unsafe fn new(
         context: &VulkanContext,
         swapchain_builder: SwapchainBuilder,
+        assets: &AssetPaths,
+        shadows: &ShadowSettings,
     ) -> Result<Self> {
         let (swapchain, swapchain_device) =
             swapchain_builder.build(context.instance(), context.surface(), context.device())?;
@@ -61,26 +91,81 @@ impl RenderTargets {
                 .get_swapchain_images(*swapchain)?
                 .into_boxed_slice();
         let swapchain_format = swapchain_builder.format.format;
+        let are_images_capturable = swapchain_builder.supports_capture();
         let swapchain_extent = swapchain_builder.extent;
         let swapchain_image_views =
             create_image_views(context.device(), &swapchain_images, swapchain_format)?
                 .defer(|image_views| Self::destroy_image_views(&image_views, context));
 
-        let render_pass = create_render_pass(context, swapchain_format)?
+        Self::from_presentation_target(
+            context,
+            PresentationTarget::Swapchain {
+                swapchain_image_views: ScopeGuard::into_inner(swapchain_image_views),
+                swapchain_images,
+                are_images_capturable,
+                swapchain: ScopeGuard::into_inner(swapchain),
+                swapchain_device,
+            },
+            swapchain_format,
+            swapchain_extent,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            assets,
+            shadows,
+        )
+    }
+
+    pub unsafe fn new_offscreen(
+        context: &VulkanContext,
+        extent: vk::Extent2D,
+        assets: &AssetPaths,
+        shadows: &ShadowSettings,
+    ) -> Result<Self> {
+        Self::from_presentation_target(
+            context,
+            PresentationTarget::Offscreen {
+                image: create_offscreen_image(context, extent, OFFSCREEN_FORMAT)?,
+            },
+            OFFSCREEN_FORMAT,
+            extent,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            assets,
+            shadows,
+        )
+    }
+
+    unsafe fn from_presentation_target(
+        context: &VulkanContext,
+        presentation_target: PresentationTarget,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        resolve_final_layout: vk::ImageLayout,
+        assets: &AssetPaths,
+        shadows: &ShadowSettings,
+    ) -> Result<Self> {
+        let presentation_target = presentation_target
+            .defer(|mut presentation_target| presentation_target.destroy(context));
+
+        let render_pass = create_render_pass(context, format, resolve_final_layout)?
             .defer(|render_pass| context.device().destroy_render_pass(render_pass, None));
 
-        let descriptor_set_layout =
-            Self::create_descriptor_set_layout(context.device())?.defer(|descriptor_set_layout| {
-                context
-                    .device()
-                    .destroy_descriptor_set_layout(descriptor_set_layout, None)
-            });
+        let reflection = Self::reflect_main_shaders(assets)?;
+        let frame_descriptor_set_layout =
+            Self::create_descriptor_set_layout(context.device(), &reflection, FRAME_SET)?
+                .defer(|layout| context.device().destroy_descriptor_set_layout(layout, None));
+        let material_descriptor_set_layout =
+            Self::create_descriptor_set_layout(context.device(), &reflection, MATERIAL_SET)?
+                .defer(|layout| context.device().destroy_descriptor_set_layout(layout, None));
 
         let (pipeline_layout, pipeline) = create_graphics_pipeline(
             context,
-            &swapchain_extent,
+            &extent,
             *render_pass,
-            *descriptor_set_layout,
+            &[
+                *frame_descriptor_set_layout,
+                *material_descriptor_set_layout,
+            ],
+            &reflection,
+            assets,
         )?;
         let pipeline_layout = pipeline_layout.defer(|pipeline_layout| {
             context
@@ -89,16 +174,26 @@ impl RenderTargets {
         });
         let pipeline = pipeline.defer(|pipeline| context.device().destroy_pipeline(pipeline, None));
 
-        let color_buffer = create_color_buffer(context, swapchain_extent, swapchain_format)?
-            .defer(|mut depth_buffer| depth_buffer.destroy(context.device()));
-        let depth_buffer = create_depth_buffer(context, swapchain_extent)?
-            .defer(|mut depth_buffer| depth_buffer.destroy(context.device()));
+        let shadow_reflection = Self::reflect_shadow_shader(assets, &reflection)?;
+        let shadow_maps = ShadowMaps::new(
+            context,
+            *frame_descriptor_set_layout,
+            &shadow_reflection,
+            assets,
+            shadows,
+        )?
+        .defer(|mut shadow_maps| shadow_maps.destroy(context));
+
+        let color_buffer = create_color_buffer(context, extent, format)?
+            .defer(|mut depth_buffer| depth_buffer.destroy(context));
+        let depth_buffer = create_depth_buffer(context, extent)?
+            .defer(|mut depth_buffer| depth_buffer.destroy(context));
 
         let framebuffers = create_framebuffers(
             context.device(),
             *render_pass,
-            swapchain_extent,
-            &swapchain_image_views,
+            extent,
+            &presentation_target.image_views(),
             depth_buffer.image_view(),
             color_buffer.image_view(),
         )?
@@ -108,38 +203,139 @@ impl RenderTargets {
             framebuffers: ScopeGuard::into_inner(framebuffers),
             color_buffer: ScopeGuard::into_inner(color_buffer),
             depth_buffer: ScopeGuard::into_inner(depth_buffer),
+            shadow_maps: ScopeGuard::into_inner(shadow_maps),
             pipeline: ScopeGuard::into_inner(pipeline),
             pipeline_layout: ScopeGuard::into_inner(pipeline_layout),
-            descriptor_set_layout: ScopeGuard::into_inner(descriptor_set_layout),
+            material_descriptor_set_layout: ScopeGuard::into_inner(material_descriptor_set_layout),
+            frame_descriptor_set_layout: ScopeGuard::into_inner(frame_descriptor_set_layout),
+            reflection,
             render_pass: ScopeGuard::into_inner(render_pass),
-            swapchain_image_views: ScopeGuard::into_inner(swapchain_image_views),
-            swapchain: ScopeGuard::into_inner(swapchain),
-            swapchain_extent,
-            swapchain_format,
-            swapchain_images,
-            swapchain_device,
+            presentation_target: ScopeGuard::into_inner(presentation_target),
+            extent,
+            format,
             is_destroyed: false,
         })
     }
 
+    // Builds the pipelines whose shaders changed, they only replace the current ones once they
+    // were all created successfully
+    pub unsafe fn reload_pipelines(
+        &mut self,
+        context: &VulkanContext,
+        assets: &AssetPaths,
+        shadows: &ShadowSettings,
+        changed: ChangedPipelines,
+    ) -> Result<()> {
+        debug_assert!(!self.is_destroyed);
+
+        let destroy_pipeline = |(pipeline_layout, pipeline): (vk::PipelineLayout, vk::Pipeline)| {
+            context.device().destroy_pipeline(pipeline, None);
+            context
+                .device()
+                .destroy_pipeline_layout(pipeline_layout, None);
+        };
+
+        // The descriptor set layouts and the descriptors are kept, so the shaders have to keep
+        // the same bindings
+        let reflection = if changed.main {
+            let reflection = Self::reflect_main_shaders(assets)?;
+            if !reflection.has_same_descriptor_bindings(&self.reflection) {
+                return Err(DescriptorBindingsChanged {}.into());
+            }
+            Some(reflection)
+        } else {
+            None
+        };
+        let shadow_reflection = if changed.shadow {
+            Some(Self::reflect_shadow_shader(
+                assets,
+                reflection.as_ref().unwrap_or(&self.reflection),
+            )?)
+        } else {
+            None
+        };
+
+        let main_pipeline = if let Some(reflection) = reflection.as_ref() {
+            Some(create_graphics_pipeline(
+                context,
+                &self.extent,
+                self.render_pass,
+                &[
+                    self.frame_descriptor_set_layout,
+                    self.material_descriptor_set_layout,
+                ],
+                reflection,
+                assets,
+            )?)
+        } else {
+            None
+        }
+        .defer(|main_pipeline| main_pipeline.into_iter().for_each(destroy_pipeline));
+        let shadow_pipeline = if let Some(shadow_reflection) = shadow_reflection.as_ref() {
+            Some(self.shadow_maps.create_pipeline(
+                context,
+                self.frame_descriptor_set_layout,
+                shadow_reflection,
+                assets,
+                shadows,
+            )?)
+        } else {
+            None
+        }
+        .defer(|shadow_pipeline| shadow_pipeline.into_iter().for_each(destroy_pipeline));
+
+        // The previous pipelines might still be used by the frames in flight
+        context.device().device_wait_idle()?;
+
+        if let Some((pipeline_layout, pipeline)) = ScopeGuard::into_inner(main_pipeline) {
+            context.device().destroy_pipeline(self.pipeline, None);
+            context
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout, None);
+            self.pipeline_layout = pipeline_layout;
+            self.pipeline = pipeline;
+        }
+        if let Some(reflection) = reflection {
+            self.reflection = reflection;
+        }
+        if let (Some(shadow_pipeline), Some(shadow_reflection)) =
+            (ScopeGuard::into_inner(shadow_pipeline), shadow_reflection)
+        {
+            self.shadow_maps.replace_pipeline(
+                context.device(),
+                shadow_pipeline,
+                shadow_reflection.push_constant_stages(),
+            );
+        }
+        Ok(())
+    }
+
+    // The main shaders may only use the descriptors written by the renderer
+    fn reflect_main_shaders(assets: &AssetPaths) -> Result<PipelineReflection> {
+        let reflection =
+            PipelineReflection::from_files(&[&assets.vertex_shader, &assets.fragment_shader])?;
+        reflection.check_provided_bindings(&[&FRAME_SET_BINDINGS, &MATERIAL_SET_BINDINGS])?;
+        Ok(reflection)
+    }
+
+    // The shadow pipeline is built with the frame descriptor set layout of the main pipeline
+    fn reflect_shadow_shader(
+        assets: &AssetPaths,
+        main_reflection: &PipelineReflection,
+    ) -> Result<PipelineReflection> {
+        let reflection = PipelineReflection::from_files(&[&assets.shadow_vertex_shader])?;
+        reflection.check_compatible_sets(main_reflection, FRAME_SET + 1)?;
+        Ok(reflection)
+    }
+
     unsafe fn create_descriptor_set_layout(
         device: &ash::Device,
+        reflection: &PipelineReflection,
+        set: u32,
     ) -> VkResult<vk::DescriptorSetLayout> {
-        let ubo_layout_binding = vk::DescriptorSetLayoutBinding::default()
-            .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::VERTEX);
-
-        let sampler_layout_binding = vk::DescriptorSetLayoutBinding::default()
-            .binding(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-            .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
-
         device.create_descriptor_set_layout(
             &vk::DescriptorSetLayoutCreateInfo::default()
-                .bindings(&[ubo_layout_binding, sampler_layout_binding]),
+                .bindings(&reflection.set_layout_bindings(set)),
             None,
         )
     }
@@ -160,12 +356,12 @@ impl RenderTargets {
         &self.framebuffers
     }
 
-    pub fn swapchain_extent(&self) -> vk::Extent2D {
+    pub fn extent(&self) -> vk::Extent2D {
         debug_assert!(
             !self.is_destroyed,
-            "RenderTargets::swapchain_extent() was called after render_targets destruction"
+            "RenderTargets::extent() was called after render_targets destruction"
         );
-        self.swapchain_extent
+        self.extent
     }
 
     pub fn pipeline(&self) -> vk::Pipeline {
@@ -184,12 +380,43 @@ impl RenderTargets {
         self.pipeline_layout
     }
 
+    pub fn reflection(&self) -> &PipelineReflection {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::reflection() was called after render_targets destruction"
+        );
+        &self.reflection
+    }
+
+    // Empty when the main shaders don't use push constants
+    pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::push_constant_stages() was called after render_targets destruction"
+        );
+        self.reflection.push_constant_stages()
+    }
+
+    pub fn shadow_maps(&self) -> &ShadowMaps {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::shadow_maps() was called after render_targets destruction"
+        );
+        &self.shadow_maps
+    }
+
     pub fn swapchain_device(&self) -> &ash::khr::swapchain::Device {
         debug_assert!(
             !self.is_destroyed,
             "RenderTargets::swapchain_device() was called after render_targets destruction"
         );
-        &self.swapchain_device
+        let PresentationTarget::Swapchain {
+            swapchain_device, ..
+        } = &self.presentation_target
+        else {
+            panic!("RenderTargets::swapchain_device() was called on offscreen render targets");
+        };
+        swapchain_device
     }
 
     pub fn swapchain(&self) -> vk::SwapchainKHR {
@@ -197,11 +424,56 @@ impl RenderTargets {
             !self.is_destroyed,
             "RenderTargets::swapchain() was called after render_targets destruction"
         );
-        self.swapchain
+        let PresentationTarget::Swapchain { swapchain, .. } = &self.presentation_target else {
+            panic!("RenderTargets::swapchain() was called on offscreen render targets");
+        };
+        *swapchain
+    }
+
+    // None if the swapchain images can't be copied
+    pub fn capturable_swapchain_image(&self, image_index: u32) -> Option<vk::Image> {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::capturable_swapchain_image() was called after render_targets destruction"
+        );
+        let PresentationTarget::Swapchain {
+            swapchain_images,
+            are_images_capturable,
+            ..
+        } = &self.presentation_target
+        else {
+            panic!(
+                "RenderTargets::capturable_swapchain_image() was called on offscreen render targets"
+            );
+        };
+        are_images_capturable.then(|| swapchain_images[image_index as usize])
+    }
+
+    pub fn format(&self) -> vk::Format {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::format() was called after render_targets destruction"
+        );
+        self.format
+    }
+
+    pub fn offscreen_image(&self) -> &Image {
+        debug_assert!(
+            !self.is_destroyed,
+            "RenderTargets::offscreen_image() was called after render_targets destruction"
+        );
+        let PresentationTarget::Offscreen { image } = &self.presentation_target else {
+            panic!("RenderTargets::offscreen_image() was called on swapchain render targets");
+        };
+        image
     }
 
-    pub fn descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
-        self.descriptor_set_layout
+    pub fn frame_descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.frame_descriptor_set_layout
+    }
+
+    pub fn material_descriptor_set_layout(&self) -> vk::DescriptorSetLayout {
+        self.material_descriptor_set_layout
     }
 
     pub unsafe fn destroy(&mut self, context: &VulkanContext) {
@@ -212,19 +484,21 @@ impl RenderTargets {
         self.is_destroyed = true;
 
         Self::destroy_framebuffers(&self.framebuffers, context);
-        self.color_buffer.destroy(context.device());
-        self.depth_buffer.destroy(context.device());
+        self.color_buffer.destroy(context);
+        self.depth_buffer.destroy(context);
+        self.shadow_maps.destroy(context);
         context.device().destroy_pipeline(self.pipeline, None);
         context
             .device()
             .destroy_pipeline_layout(self.pipeline_layout, None);
         context.device().destroy_render_pass(self.render_pass, None);
-        Self::destroy_image_views(&self.swapchain_image_views, context);
-        ash::khr::swapchain::Device::new(context.instance(), context.device())
-            .destroy_swapchain(self.swapchain, None);
+        self.presentation_target.destroy(context);
+        context
+            .device()
+            .destroy_descriptor_set_layout(self.frame_descriptor_set_layout, None);
         context
             .device()
-            .destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+            .destroy_descriptor_set_layout(self.material_descriptor_set_layout, None);
     }
 
     unsafe fn destroy_framebuffers(framebuffers: &[vk::Framebuffer], context: &VulkanContext) {
@@ -239,3 +513,30 @@ impl RenderTargets {
         }
     }
 }
+
+impl PresentationTarget {
+    fn image_views(&self) -> Box<[vk::ImageView]> {
+        match self {
+            PresentationTarget::Swapchain {
+                swapchain_image_views,
+                ..
+            } => swapchain_image_views.clone(),
+            PresentationTarget::Offscreen { image } => Box::new([image.image_view()]),
+        }
+    }
+
+    unsafe fn destroy(&mut self, context: &VulkanContext) {
+        match self {
+            PresentationTarget::Swapchain {
+                swapchain_device,
+                swapchain,
+                swapchain_image_views,
+                ..
+            } => {
+                RenderTargets::destroy_image_views(swapchain_image_views, context);
+                swapchain_device.destroy_swapchain(*swapchain, None);
+            }
+            PresentationTarget::Offscreen { image } => image.destroy(context),
+        }
+    }
+}
@@ -15,6 +15,7 @@ pub fn create_color_buffer(
         context,
         ImageCreateInfo {
             mip_levels: 1,
+            array_layers: 1,
             sample_count: context.physical_device_max_sample_count(),
             extent: swapchain_extent,
             format: swapchain_image_format,
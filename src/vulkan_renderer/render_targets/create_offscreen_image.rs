@@ -0,0 +1,28 @@
+use ash::vk;
+
+use crate::vulkan_renderer::{
+    memory::{Image, ImageCreateInfo},
+    vulkan_context::VulkanContext,
+};
+use rs42::Result;
+
+pub fn create_offscreen_image(
+    context: &VulkanContext,
+    extent: vk::Extent2D,
+    format: vk::Format,
+) -> Result<Image> {
+    Image::new(
+        context,
+        ImageCreateInfo {
+            mip_levels: 1,
+            array_layers: 1,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+            extent,
+            format,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+        },
+    )
+}
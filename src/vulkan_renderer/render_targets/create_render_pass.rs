@@ -5,12 +5,15 @@ use rs42::Result;
 use super::create_depth_buffer::find_depth_buffer_format;
 
 const DEPTH_BUFFER_LAYOUT: vk::ImageLayout = vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+pub const SHADOW_MAP_LAYOUT: vk::ImageLayout = vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL;
 
 pub unsafe fn create_render_pass(
     context: &VulkanContext,
-    swapchain_format: vk::Format,
+    format: vk::Format,
+    resolve_final_layout: vk::ImageLayout,
 ) -> Result<vk::RenderPass> {
-    let attachment_descriptions = get_attachment_descriptions(context, swapchain_format)?;
+    let attachment_descriptions =
+        get_attachment_descriptions(context, format, resolve_final_layout)?;
 
     let color_attachment_references = [vk::AttachmentReference::default()
         .attachment(0)
@@ -28,7 +31,7 @@ pub unsafe fn create_render_pass(
         .depth_stencil_attachment(&depth_attachment_reference)
         .resolve_attachments(&color_attachment_resolve_reference)];
 
-    let dependencies = get_dependencies();
+    let dependencies = get_dependencies(resolve_final_layout);
 
     let render_pass_create_info = vk::RenderPassCreateInfo::default()
         .attachments(&attachment_descriptions)
@@ -44,10 +47,11 @@ pub unsafe fn create_render_pass(
 
 fn get_attachment_descriptions(
     context: &VulkanContext,
-    swapchain_format: vk::Format,
+    format: vk::Format,
+    resolve_final_layout: vk::ImageLayout,
 ) -> Result<[vk::AttachmentDescription; 3]> {
     let color_attachment = vk::AttachmentDescription::default()
-        .format(swapchain_format)
+        .format(format)
         .samples(context.physical_device_max_sample_count())
         .load_op(vk::AttachmentLoadOp::CLEAR)
         .store_op(vk::AttachmentStoreOp::STORE)
@@ -67,20 +71,20 @@ fn get_attachment_descriptions(
         .final_layout(DEPTH_BUFFER_LAYOUT);
 
     let color_attachment_resolve = vk::AttachmentDescription::default()
-        .format(swapchain_format)
+        .format(format)
         .samples(vk::SampleCountFlags::TYPE_1)
         .load_op(vk::AttachmentLoadOp::DONT_CARE)
         .store_op(vk::AttachmentStoreOp::STORE)
         .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
         .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
         .initial_layout(vk::ImageLayout::UNDEFINED)
-        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        .final_layout(resolve_final_layout);
 
     Ok([color_attachment, depth_attachment, color_attachment_resolve])
 }
 
-fn get_dependencies() -> [vk::SubpassDependency; 1] {
-    [vk::SubpassDependency::default()
+fn get_dependencies(resolve_final_layout: vk::ImageLayout) -> Vec<vk::SubpassDependency> {
+    let mut dependencies = vec![vk::SubpassDependency::default()
         .src_subpass(vk::SUBPASS_EXTERNAL)
         .dst_subpass(0)
         .src_stage_mask(
@@ -95,5 +99,70 @@ fn get_dependencies() -> [vk::SubpassDependency; 1] {
         .dst_access_mask(
             vk::AccessFlags::COLOR_ATTACHMENT_WRITE
                 | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
-        )]
+        )];
+
+    // The resolved image is read back to the host once the render pass is over
+    if resolve_final_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL {
+        dependencies.push(
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+        );
+    }
+
+    dependencies
+}
+
+// Single depth attachment, left ready to be sampled by the main render pass
+pub unsafe fn create_shadow_render_pass(
+    device: &ash::Device,
+    format: vk::Format,
+) -> Result<vk::RenderPass> {
+    let attachment_descriptions = [vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(SHADOW_MAP_LAYOUT)];
+
+    let depth_attachment_reference = vk::AttachmentReference::default()
+        .attachment(0)
+        .layout(DEPTH_BUFFER_LAYOUT);
+
+    let subpass = [vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .depth_stencil_attachment(&depth_attachment_reference)];
+
+    // The previous frame has to be done sampling the shadow map before it is cleared, and the
+    // main render pass has to wait for it to be written
+    let dependencies = [
+        vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_stage_mask(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS)
+            .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+        vk::SubpassDependency::default()
+            .src_subpass(0)
+            .dst_subpass(vk::SUBPASS_EXTERNAL)
+            .src_stage_mask(vk::PipelineStageFlags::LATE_FRAGMENT_TESTS)
+            .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+            .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ),
+    ];
+
+    let render_pass_create_info = vk::RenderPassCreateInfo::default()
+        .attachments(&attachment_descriptions)
+        .subpasses(&subpass)
+        .dependencies(&dependencies);
+
+    Ok(unsafe { device.create_render_pass(&render_pass_create_info, None)? })
 }
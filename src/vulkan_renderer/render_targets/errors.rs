@@ -1,13 +1,17 @@
 use rs42::error_struct_custom_display;
 
 error_struct_custom_display!(FailedToReadShaderCode {
-    shader_file_path: &'static str,
+    shader_file_path: String,
     error: Box<dyn std::error::Error>,
 }, "Failed to read shader \"{}\": {}", shader_file_path, error);
 
-error_struct_custom_display!(ShaderCodeBadLen {
-    shader_file_path: &'static str,
-}, "The number of bytes in the \"{}\" shader is not a multiple of 4", shader_file_path);
+error_struct_custom_display!(
+    ShaderCodeBadLen {
+        shader_file_path: String,
+    },
+    "The number of bytes in the \"{}\" shader is not a multiple of 4",
+    shader_file_path
+);
 
 error_struct_custom_display!(FailedToCreatePipeline {
     error: (Vec<ash::vk::Pipeline>, ash::vk::Result),
@@ -17,3 +21,86 @@ error_struct_custom_display!(
     FailedToFindSupportedFormatForDepthBuffer,
     "No supported format for depth buffer",
 );
+
+error_struct_custom_display!(
+    FailedToFindSupportedFormatForShadowMap,
+    "No supported format for shadow maps",
+);
+
+error_struct_custom_display!(
+    InvalidSpirv { reason: String },
+    "Invalid SPIR-V: {}",
+    reason
+);
+
+error_struct_custom_display!(
+    UnsupportedShaderStage {
+        shader_file_path: String,
+        execution_model: u32,
+    },
+    "Unsupported execution model {} in shader \"{}\"",
+    execution_model,
+    shader_file_path
+);
+
+error_struct_custom_display!(
+    UnsupportedDescriptorBinding {
+        shader_file_path: String,
+        set: u32,
+        binding: u32,
+    },
+    "Unsupported descriptor type for set {} binding {} in shader \"{}\"",
+    set,
+    binding,
+    shader_file_path
+);
+
+error_struct_custom_display!(
+    DescriptorBindingMismatch {
+        set: u32,
+        binding: u32,
+    },
+    "Descriptor set {} binding {} doesn't match the descriptors provided by the renderer",
+    set,
+    binding
+);
+
+error_struct_custom_display!(
+    DescriptorBindingsChanged,
+    "The descriptor bindings of the reloaded shaders changed, restart to apply them",
+);
+
+error_struct_custom_display!(
+    PushConstantsTooLarge {
+        shader_size: u32,
+        provided_size: u32,
+    },
+    "The shaders use {} bytes of push constants but only {} are provided",
+    shader_size,
+    provided_size
+);
+
+error_struct_custom_display!(
+    UnsupportedShaderInput {
+        shader_file_path: String,
+        location: u32,
+    },
+    "Unsupported type for the input at location {} of shader \"{}\"",
+    location,
+    shader_file_path
+);
+
+error_struct_custom_display!(
+    VertexAttributeMismatch {
+        shader_file_path: String,
+        location: u32,
+        shader_format: ash::vk::Format,
+        // Debug representation of the attribute format, or "nothing"
+        vertex_format: String,
+    },
+    "The input at location {} of shader \"{}\" expects {:?} but the Vertex layout provides {}",
+    location,
+    shader_file_path,
+    shader_format,
+    vertex_format
+);
@@ -1,3 +1,5 @@
+use crate::config::AssetPaths;
+use crate::vulkan_renderer::push_constants::ObjectPushConstants;
 use crate::vulkan_renderer::vulkan_context::VulkanContext;
 
 use super::super::errors::FailedToCreatePipeline;
@@ -9,6 +11,7 @@ use super::multisampling::multisample_state_create_info;
 use super::pipeline_layout::create_pipeline_layout;
 use super::rasterizer::rasterizer_state_create_info;
 use super::shader::ShaderStageCreateInfos;
+use super::shader_reflection::PipelineReflection;
 use super::vertex_input::vertex_input_state_create_info;
 use super::viewport::ViewportStateCreateInfo;
 use ash::vk;
@@ -19,22 +22,33 @@ pub unsafe fn create_graphics_pipeline(
     context: &VulkanContext,
     swapchain_extent: &vk::Extent2D,
     render_pass: vk::RenderPass,
-    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    reflection: &PipelineReflection,
+    assets: &AssetPaths,
 ) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
-    let shader_stage_create_infos = ShaderStageCreateInfos::new(context.device())?;
+    let shader_stage_create_infos = ShaderStageCreateInfos::new(
+        context.device(),
+        &assets.vertex_shader,
+        &assets.fragment_shader,
+    )?;
     let binding_descriptions = [Vertex::get_binding_description()];
-    let attributes_description = Vertex::get_attributes_descriptions();
+    let attributes_description =
+        reflection.vertex_attributes(&Vertex::get_attributes_descriptions())?;
     let vertex_input_state_create_info =
         vertex_input_state_create_info(&binding_descriptions, &attributes_description);
     let input_assembly_state_create_info = input_assembly_state_create_info();
     let viewport_state_create_info = ViewportStateCreateInfo::new(swapchain_extent);
-    let rasterizer_state_create_info = rasterizer_state_create_info();
+    let rasterizer_state_create_info = rasterizer_state_create_info(None);
     let multisample_state_create_info =
         multisample_state_create_info(context.physical_device_max_sample_count());
     let color_blend_state_create_info = ColorBlendStateCreateInfo::new();
     let dynamic_state_create_info = DynamicStateCreateInfo::new();
     let depth_stencil_state_create_info = depth_stencil_state_create_info();
-    let pipeline_layout = create_pipeline_layout(context.device(), descriptor_set_layout)?;
+    let pipeline_layout = create_pipeline_layout(
+        context.device(),
+        descriptor_set_layouts,
+        reflection.push_constant_range(size_of::<ObjectPushConstants>() as u32)?,
+    )?;
 
     let create_infos = [vk::GraphicsPipelineCreateInfo::default()
         .stages(shader_stage_create_infos.create_infos())
@@ -53,7 +67,7 @@ pub unsafe fn create_graphics_pipeline(
     let graphics_pipeline = unsafe {
         context
             .device()
-            .create_graphics_pipelines(vk::PipelineCache::null(), &create_infos, None)
+            .create_graphics_pipelines(context.pipeline_cache(), &create_infos, None)
             .map_err(|err| {
                 context
                     .device()
@@ -0,0 +1,76 @@
+use crate::config::{AssetPaths, ShadowSettings};
+use crate::vulkan_renderer::push_constants::ShadowPushConstants;
+use crate::vulkan_renderer::vulkan_context::VulkanContext;
+
+use super::super::errors::FailedToCreatePipeline;
+use super::depth_stencil_state_create_info::depth_stencil_state_create_info;
+use super::dynamic_state::DynamicStateCreateInfo;
+use super::input_assembly::input_assembly_state_create_info;
+use super::multisampling::multisample_state_create_info;
+use super::pipeline_layout::create_pipeline_layout;
+use super::rasterizer::rasterizer_state_create_info;
+use super::shader::ShaderStageCreateInfos;
+use super::shader_reflection::PipelineReflection;
+use super::vertex_input::vertex_input_state_create_info;
+use super::viewport::ViewportStateCreateInfo;
+use ash::vk;
+use model::Vertex;
+use rs42::Result;
+
+// Depth only pipeline rendering the scene from the point of view of a light
+pub unsafe fn create_shadow_pipeline(
+    context: &VulkanContext,
+    shadow_map_extent: &vk::Extent2D,
+    render_pass: vk::RenderPass,
+    descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    reflection: &PipelineReflection,
+    assets: &AssetPaths,
+    settings: &ShadowSettings,
+) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+    let shader_stage_create_infos =
+        ShaderStageCreateInfos::vertex_only(context.device(), &assets.shadow_vertex_shader)?;
+    let binding_descriptions = [Vertex::get_binding_description()];
+    let attributes_description =
+        reflection.vertex_attributes(&Vertex::get_attributes_descriptions())?;
+    let vertex_input_state_create_info =
+        vertex_input_state_create_info(&binding_descriptions, &attributes_description);
+    let input_assembly_state_create_info = input_assembly_state_create_info();
+    let viewport_state_create_info = ViewportStateCreateInfo::new(shadow_map_extent);
+    let rasterizer_state_create_info = rasterizer_state_create_info(Some(settings));
+    let multisample_state_create_info = multisample_state_create_info(vk::SampleCountFlags::TYPE_1);
+    let dynamic_state_create_info = DynamicStateCreateInfo::new();
+    let depth_stencil_state_create_info = depth_stencil_state_create_info();
+    let pipeline_layout = create_pipeline_layout(
+        context.device(),
+        descriptor_set_layouts,
+        reflection.push_constant_range(size_of::<ShadowPushConstants>() as u32)?,
+    )?;
+
+    // There is no color attachment, so no color blend state
+    let create_infos = [vk::GraphicsPipelineCreateInfo::default()
+        .stages(shader_stage_create_infos.create_infos())
+        .vertex_input_state(&vertex_input_state_create_info)
+        .input_assembly_state(&input_assembly_state_create_info)
+        .viewport_state(viewport_state_create_info.create_info())
+        .rasterization_state(&rasterizer_state_create_info)
+        .multisample_state(&multisample_state_create_info)
+        .dynamic_state(dynamic_state_create_info.create_info())
+        .depth_stencil_state(&depth_stencil_state_create_info)
+        .layout(pipeline_layout)
+        .render_pass(render_pass)
+        .subpass(0)];
+
+    let shadow_pipeline = unsafe {
+        context
+            .device()
+            .create_graphics_pipelines(context.pipeline_cache(), &create_infos, None)
+            .map_err(|err| {
+                context
+                    .device()
+                    .destroy_pipeline_layout(pipeline_layout, None);
+                FailedToCreatePipeline::new(err)
+            })?[0]
+    };
+
+    Ok((pipeline_layout, shadow_pipeline))
+}
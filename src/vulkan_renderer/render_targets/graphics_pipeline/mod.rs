@@ -1,5 +1,6 @@
 mod color_blending;
 mod create_graphics_pipeline;
+mod create_shadow_pipeline;
 mod depth_stencil_state_create_info;
 mod dynamic_state;
 mod input_assembly;
@@ -7,7 +8,10 @@ mod multisampling;
 mod pipeline_layout;
 mod rasterizer;
 mod shader;
+mod shader_reflection;
 mod vertex_input;
 mod viewport;
 
 pub use create_graphics_pipeline::create_graphics_pipeline;
+pub use create_shadow_pipeline::create_shadow_pipeline;
+pub use shader_reflection::PipelineReflection;
@@ -1,13 +1,19 @@
 use ash::prelude::VkResult;
 use ash::vk;
 
+// The push constant range is None when the shaders don't declare push constants
 pub fn create_pipeline_layout(
     device: &ash::Device,
-    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_set_layouts: &[vk::DescriptorSetLayout],
+    push_constant_range: Option<vk::PushConstantRange>,
 ) -> VkResult<vk::PipelineLayout> {
+    let push_constant_ranges: Vec<_> = push_constant_range.into_iter().collect();
+
     unsafe {
         device.create_pipeline_layout(
-            &vk::PipelineLayoutCreateInfo::default().set_layouts(&[descriptor_set_layout]),
+            &vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(descriptor_set_layouts)
+                .push_constant_ranges(&push_constant_ranges),
             None,
         )
     }
@@ -1,6 +1,11 @@
 use ash::vk;
 
-pub fn rasterizer_state_create_info<'a>() -> vk::PipelineRasterizationStateCreateInfo<'a> {
+use crate::config::ShadowSettings;
+
+// Only the shadow maps are rendered with a depth bias, it keeps surfaces from shadowing themselves
+pub fn rasterizer_state_create_info<'a>(
+    depth_bias: Option<&ShadowSettings>,
+) -> vk::PipelineRasterizationStateCreateInfo<'a> {
     vk::PipelineRasterizationStateCreateInfo::default()
         .depth_clamp_enable(false)
         .rasterizer_discard_enable(false)
@@ -8,8 +13,8 @@ pub fn rasterizer_state_create_info<'a>() -> vk::PipelineRasterizationStateCreat
         .line_width(1.)
         .cull_mode(vk::CullModeFlags::BACK)
         .front_face(vk::FrontFace::COUNTER_CLOCKWISE)
-        .depth_bias_enable(false)
-        .depth_bias_constant_factor(0.)
+        .depth_bias_enable(depth_bias.is_some())
+        .depth_bias_constant_factor(depth_bias.map_or(0., |bias| bias.depth_bias_constant))
         .depth_bias_clamp(0.)
-        .depth_bias_slope_factor(0.)
+        .depth_bias_slope_factor(depth_bias.map_or(0., |bias| bias.depth_bias_slope))
 }
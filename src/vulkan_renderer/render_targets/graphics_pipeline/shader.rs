@@ -3,15 +3,12 @@ use ash::vk;
 use rs42::{extensions::PipeLine, Result};
 use std::{fs::File, io::Read};
 
-const VERT_SHADER_PATH: &str = "./shaders/build/shader.vert.spv";
-const FRAG_SHADER_PATH: &str = "./shaders/build/shader.frag.spv";
-
 pub struct ShaderStageCreateInfos<'a> {
-    create_infos: [vk::PipelineShaderStageCreateInfo<'a>; 2],
+    create_infos: Vec<vk::PipelineShaderStageCreateInfo<'a>>,
     #[allow(dead_code)]
     vertex_shader_module: ShaderModule<'a>,
     #[allow(dead_code)]
-    fragment_shader_module: ShaderModule<'a>,
+    fragment_shader_module: Option<ShaderModule<'a>>,
 }
 
 struct ShaderModule<'a> {
@@ -19,12 +16,16 @@ struct ShaderModule<'a> {
     device: &'a ash::Device,
 }
 
-struct ShaderCode(Vec<u8>);
+pub(super) struct ShaderCode(Vec<u8>);
 
 impl<'a> ShaderStageCreateInfos<'a> {
-    pub fn new(device: &'a ash::Device) -> Result<Self> {
-        let vertex_shader_module = ShaderModule::new(device, VERT_SHADER_PATH)?;
-        let fragment_shader_module = ShaderModule::new(device, FRAG_SHADER_PATH)?;
+    pub fn new(
+        device: &'a ash::Device,
+        vertex_shader_path: &str,
+        fragment_shader_path: &str,
+    ) -> Result<Self> {
+        let vertex_shader_module = ShaderModule::new(device, vertex_shader_path)?;
+        let fragment_shader_module = ShaderModule::new(device, fragment_shader_path)?;
 
         let vertex_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
             .stage(vk::ShaderStageFlags::VERTEX)
@@ -35,7 +36,7 @@ impl<'a> ShaderStageCreateInfos<'a> {
             .module(fragment_shader_module.module())
             .name(c"main");
 
-        let create_infos = [
+        let create_infos = vec![
             vertex_shader_stage_create_info,
             fragment_shader_stage_create_info,
         ];
@@ -43,7 +44,23 @@ impl<'a> ShaderStageCreateInfos<'a> {
         Ok(Self {
             create_infos,
             vertex_shader_module,
-            fragment_shader_module,
+            fragment_shader_module: Some(fragment_shader_module),
+        })
+    }
+
+    // Used by depth only passes, which don't need a fragment shader
+    pub fn vertex_only(device: &'a ash::Device, vertex_shader_path: &str) -> Result<Self> {
+        let vertex_shader_module = ShaderModule::new(device, vertex_shader_path)?;
+
+        let vertex_shader_stage_create_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(vertex_shader_module.module())
+            .name(c"main");
+
+        Ok(Self {
+            create_infos: vec![vertex_shader_stage_create_info],
+            vertex_shader_module,
+            fragment_shader_module: None,
         })
     }
 
@@ -53,7 +70,7 @@ impl<'a> ShaderStageCreateInfos<'a> {
 }
 
 impl<'a> ShaderModule<'a> {
-    fn new(device: &'a ash::Device, shader_binary_path: &'static str) -> Result<Self> {
+    fn new(device: &'a ash::Device, shader_binary_path: &str) -> Result<Self> {
         ShaderCode::new(shader_binary_path)?
             .pipe(|code| {
                 Self::shader_module_create_info(&code)
@@ -79,21 +96,21 @@ impl Drop for ShaderModule<'_> {
 }
 
 impl ShaderCode {
-    fn new(shader_file_path: &'static str) -> Result<Self> {
+    pub(super) fn new(shader_file_path: &str) -> Result<Self> {
         let mut u8_data = Vec::new();
         File::open(shader_file_path)
-            .map_err(|error| FailedToReadShaderCode::new(shader_file_path, error))?
+            .map_err(|error| FailedToReadShaderCode::new(shader_file_path.to_owned(), error))?
             .read_to_end(&mut u8_data)
-            .map_err(|error| FailedToReadShaderCode::new(shader_file_path, error))?;
+            .map_err(|error| FailedToReadShaderCode::new(shader_file_path.to_owned(), error))?;
 
         if u8_data.len() % 4 != 0 {
-            return Err(ShaderCodeBadLen::new(shader_file_path).into());
+            return Err(ShaderCodeBadLen::new(shader_file_path.to_owned()).into());
         }
 
         Ok(Self(u8_data))
     }
 
-    fn as_u32_slice(&self) -> &[u32] {
+    pub(super) fn as_u32_slice(&self) -> &[u32] {
         unsafe { std::slice::from_raw_parts(self.0.as_ptr() as *const u32, self.0.len() / 4) }
     }
 }
@@ -0,0 +1,660 @@
+mod spirv;
+
+use ash::vk;
+use rs42::Result;
+
+use super::super::errors::{
+    DescriptorBindingMismatch, InvalidSpirv, PushConstantsTooLarge, UnsupportedDescriptorBinding,
+    UnsupportedShaderInput, UnsupportedShaderStage, VertexAttributeMismatch,
+};
+use super::shader::ShaderCode;
+use spirv::{decoration, dim, execution_model, storage_class, Module, Type};
+
+// Interface of the shaders of a pipeline, read from their SPIR-V
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineReflection {
+    // Sorted by set then binding
+    descriptor_bindings: Vec<DescriptorBinding>,
+    push_constants: Option<PushConstants>,
+    // Inputs of the vertex shader, sorted by location
+    vertex_inputs: Vec<VertexInput>,
+    vertex_shader_path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stage_flags: vk::ShaderStageFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PushConstants {
+    size: u32,
+    stage_flags: vk::ShaderStageFlags,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VertexInput {
+    location: u32,
+    format: vk::Format,
+}
+
+impl PipelineReflection {
+    pub fn from_files(shader_paths: &[&str]) -> Result<Self> {
+        let modules = shader_paths
+            .iter()
+            .map(|path| {
+                let code = ShaderCode::new(path)?;
+                let module = Module::parse(code.as_u32_slice())
+                    .map_err(|err| InvalidSpirv::new(format!("\"{path}\": {}", err.reason)))?;
+                Ok((module, *path))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::from_modules(&modules)
+    }
+
+    // Each module comes with the path it was read from
+    fn from_modules(modules: &[(Module, &str)]) -> Result<Self> {
+        let mut reflection = Self {
+            descriptor_bindings: Vec::new(),
+            push_constants: None,
+            vertex_inputs: Vec::new(),
+            vertex_shader_path: String::new(),
+        };
+        for (module, path) in modules {
+            reflection.add_shader(module, path)?;
+        }
+        reflection
+            .descriptor_bindings
+            .sort_by_key(|binding| (binding.set, binding.binding));
+        reflection.vertex_inputs.sort_by_key(|input| input.location);
+        Ok(reflection)
+    }
+
+    fn add_shader(&mut self, module: &Module, path: &str) -> Result<()> {
+        let stage = match module.execution_model {
+            execution_model::VERTEX => vk::ShaderStageFlags::VERTEX,
+            execution_model::TESSELLATION_CONTROL => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            execution_model::TESSELLATION_EVALUATION => {
+                vk::ShaderStageFlags::TESSELLATION_EVALUATION
+            }
+            execution_model::GEOMETRY => vk::ShaderStageFlags::GEOMETRY,
+            execution_model::FRAGMENT => vk::ShaderStageFlags::FRAGMENT,
+            execution_model::GL_COMPUTE => vk::ShaderStageFlags::COMPUTE,
+            execution_model => {
+                return Err(UnsupportedShaderStage::new(path.to_owned(), execution_model).into())
+            }
+        };
+
+        for variable in module.variables.iter() {
+            let invalid = || InvalidSpirv::new(format!("\"{path}\": variable {}", variable.id));
+            let pointee = module.pointee(variable.pointer_type).ok_or_else(invalid)?;
+
+            match variable.storage_class {
+                storage_class::UNIFORM_CONSTANT
+                | storage_class::UNIFORM
+                | storage_class::STORAGE_BUFFER => {
+                    let (Some(set), Some(binding)) = (
+                        module.decoration(variable.id, decoration::DESCRIPTOR_SET),
+                        module.decoration(variable.id, decoration::BINDING),
+                    ) else {
+                        continue;
+                    };
+                    let (descriptor_type, count) =
+                        descriptor_type(module, pointee, variable.storage_class).ok_or_else(
+                            || UnsupportedDescriptorBinding::new(path.to_owned(), set, binding),
+                        )?;
+                    self.add_descriptor_binding(DescriptorBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stage_flags: stage,
+                    })?;
+                }
+                storage_class::PUSH_CONSTANT => {
+                    let size = module.size_of(pointee, None).ok_or_else(invalid)?;
+                    let push_constants = self.push_constants.get_or_insert(PushConstants {
+                        size: 0,
+                        stage_flags: vk::ShaderStageFlags::empty(),
+                    });
+                    push_constants.size = push_constants.size.max(size);
+                    push_constants.stage_flags |= stage;
+                }
+                storage_class::INPUT if stage == vk::ShaderStageFlags::VERTEX => {
+                    if module
+                        .decoration(variable.id, decoration::BUILT_IN)
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    let location = module
+                        .decoration(variable.id, decoration::LOCATION)
+                        .ok_or_else(invalid)?;
+                    let format = input_format(module, pointee)
+                        .ok_or_else(|| UnsupportedShaderInput::new(path.to_owned(), location))?;
+                    self.vertex_inputs.push(VertexInput { location, format });
+                    self.vertex_shader_path = path.to_string();
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Bindings shared by several stages have to be declared the same way in all of them
+    fn add_descriptor_binding(&mut self, new_binding: DescriptorBinding) -> Result<()> {
+        let Some(binding) = self.descriptor_bindings.iter_mut().find(|binding| {
+            binding.set == new_binding.set && binding.binding == new_binding.binding
+        }) else {
+            self.descriptor_bindings.push(new_binding);
+            return Ok(());
+        };
+
+        if binding.descriptor_type != new_binding.descriptor_type
+            || binding.count != new_binding.count
+        {
+            return Err(DescriptorBindingMismatch::new(binding.set, binding.binding).into());
+        }
+        binding.stage_flags |= new_binding.stage_flags;
+        Ok(())
+    }
+
+    pub fn descriptor_bindings(&self, set: u32) -> impl Iterator<Item = &DescriptorBinding> {
+        self.descriptor_bindings
+            .iter()
+            .filter(move |binding| binding.set == set)
+    }
+
+    pub fn set_layout_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding<'static>> {
+        self.descriptor_bindings(set)
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.descriptor_type)
+                    .descriptor_count(binding.count)
+                    .stage_flags(binding.stage_flags)
+            })
+            .collect()
+    }
+
+    pub fn uses_binding(&self, set: u32, binding: u32) -> bool {
+        self.descriptor_bindings(set)
+            .any(|descriptor_binding| descriptor_binding.binding == binding)
+    }
+
+    // provided is indexed by set, every binding used by the shaders has to be one of the
+    // descriptors written by the renderer, with a single descriptor
+    pub fn check_provided_bindings(
+        &self,
+        provided: &[&[(u32, vk::DescriptorType)]],
+    ) -> Result<(), DescriptorBindingMismatch> {
+        for binding in self.descriptor_bindings.iter() {
+            let is_provided = provided
+                .get(binding.set as usize)
+                .is_some_and(|set| set.contains(&(binding.binding, binding.descriptor_type)));
+            if binding.count != 1 || !is_provided {
+                return Err(DescriptorBindingMismatch::new(binding.set, binding.binding));
+            }
+        }
+        Ok(())
+    }
+
+    // Used by pipelines built with the first set_count descriptor set layouts of another pipeline
+    pub fn check_compatible_sets(
+        &self,
+        other: &PipelineReflection,
+        set_count: u32,
+    ) -> Result<(), DescriptorBindingMismatch> {
+        for binding in self.descriptor_bindings.iter() {
+            let is_compatible = binding.set < set_count
+                && other.descriptor_bindings(binding.set).any(|other_binding| {
+                    other_binding.binding == binding.binding
+                        && other_binding.descriptor_type == binding.descriptor_type
+                        && other_binding.count == binding.count
+                        && other_binding.stage_flags.contains(binding.stage_flags)
+                });
+            if !is_compatible {
+                return Err(DescriptorBindingMismatch::new(binding.set, binding.binding));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_same_descriptor_bindings(&self, other: &PipelineReflection) -> bool {
+        self.descriptor_bindings == other.descriptor_bindings
+    }
+
+    // Empty when the shaders don't declare push constants
+    pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+        self.push_constants
+            .map_or(vk::ShaderStageFlags::empty(), |push_constants| {
+                push_constants.stage_flags
+            })
+    }
+
+    // The range covers the whole provided struct, the shaders may only use its beginning
+    pub fn push_constant_range(
+        &self,
+        provided_size: u32,
+    ) -> Result<Option<vk::PushConstantRange>, PushConstantsTooLarge> {
+        let Some(push_constants) = self.push_constants else {
+            return Ok(None);
+        };
+        if push_constants.size > provided_size {
+            return Err(PushConstantsTooLarge::new(
+                push_constants.size,
+                provided_size,
+            ));
+        }
+        Ok(Some(
+            vk::PushConstantRange::default()
+                .stage_flags(push_constants.stage_flags)
+                .offset(0)
+                .size(provided_size),
+        ))
+    }
+
+    // Keeps the attributes read by the vertex shader, each of them has to match the type of the
+    // shader input at the same location
+    pub fn vertex_attributes(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Result<Vec<vk::VertexInputAttributeDescription>, VertexAttributeMismatch> {
+        self.vertex_inputs
+            .iter()
+            .map(|input| {
+                let attribute = attributes
+                    .iter()
+                    .find(|attribute| attribute.location == input.location);
+                match attribute {
+                    Some(attribute) if attribute.format == input.format => Ok(*attribute),
+                    _ => Err(VertexAttributeMismatch::new(
+                        self.vertex_shader_path.clone(),
+                        input.location,
+                        input.format,
+                        attribute.map_or("nothing".to_owned(), |attribute| {
+                            format!("{:?}", attribute.format)
+                        }),
+                    )),
+                }
+            })
+            .collect()
+    }
+}
+
+fn descriptor_type(
+    module: &Module,
+    type_id: u32,
+    storage_class: u32,
+) -> Option<(vk::DescriptorType, u32)> {
+    let (type_id, count) = match module.types.get(&type_id)? {
+        Type::Array { element, length } => (*element, *length),
+        _ => (type_id, 1),
+    };
+
+    let descriptor_type = match (storage_class, module.types.get(&type_id)?) {
+        (storage_class::UNIFORM_CONSTANT, Type::SampledImage) => {
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+        }
+        (storage_class::UNIFORM_CONSTANT, Type::Sampler) => vk::DescriptorType::SAMPLER,
+        (storage_class::UNIFORM_CONSTANT, Type::Image { dim, sampled }) => match (*dim, *sampled) {
+            (dim::SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+            (dim::BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            (dim::BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+            _ => vk::DescriptorType::SAMPLED_IMAGE,
+        },
+        (storage_class::UNIFORM, Type::Struct { .. }) => {
+            if module
+                .decoration(type_id, decoration::BUFFER_BLOCK)
+                .is_some()
+            {
+                vk::DescriptorType::STORAGE_BUFFER
+            } else {
+                vk::DescriptorType::UNIFORM_BUFFER
+            }
+        }
+        (storage_class::STORAGE_BUFFER, Type::Struct { .. }) => vk::DescriptorType::STORAGE_BUFFER,
+        _ => return None,
+    };
+    Some((descriptor_type, count))
+}
+
+fn input_format(module: &Module, type_id: u32) -> Option<vk::Format> {
+    let (component, count) = match module.types.get(&type_id)? {
+        Type::Vector { component, count } => (*component, *count),
+        _ => (type_id, 1),
+    };
+
+    let formats = match module.types.get(&component)? {
+        Type::Float { width: 32 } => [
+            vk::Format::R32_SFLOAT,
+            vk::Format::R32G32_SFLOAT,
+            vk::Format::R32G32B32_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        Type::Int {
+            width: 32,
+            is_signed: true,
+        } => [
+            vk::Format::R32_SINT,
+            vk::Format::R32G32_SINT,
+            vk::Format::R32G32B32_SINT,
+            vk::Format::R32G32B32A32_SINT,
+        ],
+        Type::Int {
+            width: 32,
+            is_signed: false,
+        } => [
+            vk::Format::R32_UINT,
+            vk::Format::R32G32_UINT,
+            vk::Format::R32G32B32_UINT,
+            vk::Format::R32G32B32A32_UINT,
+        ],
+        _ => return None,
+    };
+    formats.get(count.checked_sub(1)? as usize).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use super::spirv::{op, test::assemble};
+    use super::*;
+
+    const DIM_2D: u32 = 1;
+
+    // Types shared by the test shaders
+    const TYPES: [(u32, &[u32]); 14] = [
+        (op::TYPE_FLOAT, &[2, 32]),
+        (op::TYPE_VECTOR, &[3, 2, 3]),
+        (op::TYPE_VECTOR, &[4, 2, 4]),
+        (op::TYPE_MATRIX, &[5, 4, 4]),
+        (op::TYPE_INT, &[6, 32, 0]),
+        (op::CONSTANT, &[6, 7, 4]),
+        (op::TYPE_IMAGE, &[8, 2, DIM_2D, 0, 0, 0, 1, 0]),
+        (op::TYPE_SAMPLED_IMAGE, &[9, 8]),
+        (op::TYPE_ARRAY, &[10, 9, 7]),
+        // Push constants: mat4 model;
+        (op::TYPE_STRUCT, &[11, 5]),
+        (op::MEMBER_DECORATE, &[11, 0, decoration::OFFSET, 0]),
+        (op::MEMBER_DECORATE, &[11, 0, decoration::MATRIX_STRIDE, 16]),
+        // Uniform buffer: vec4 color;
+        (op::TYPE_STRUCT, &[12, 4]),
+        (op::TYPE_IMAGE, &[13, 2, DIM_2D, 0, 0, 0, 2, 0]),
+    ];
+
+    fn variable(id: u32, storage_class: u32, pointee: u32) -> [(u32, Vec<u32>); 2] {
+        [
+            (op::TYPE_POINTER, vec![id + 100, storage_class, pointee]),
+            (op::VARIABLE, vec![id + 100, id, storage_class]),
+        ]
+    }
+
+    fn binding(id: u32, set: u32, binding: u32) -> [(u32, Vec<u32>); 2] {
+        [
+            (op::DECORATE, vec![id, decoration::DESCRIPTOR_SET, set]),
+            (op::DECORATE, vec![id, decoration::BINDING, binding]),
+        ]
+    }
+
+    fn module(execution_model: u32, instructions: &[(u32, Vec<u32>)]) -> Module {
+        let entry_point = [execution_model, 1];
+        let instructions: Vec<(u32, &[u32])> = [(op::ENTRY_POINT, &entry_point[..])]
+            .into_iter()
+            .chain(TYPES)
+            .chain(
+                instructions
+                    .iter()
+                    .map(|(opcode, operands)| (*opcode, operands.as_slice())),
+            )
+            .collect();
+        Module::parse(&assemble(&instructions)).unwrap()
+    }
+
+    // vec3 position at location 0, a built in input, push constants and a uniform buffer at
+    // set 0 binding 0
+    fn vertex_shader() -> Module {
+        let instructions: Vec<_> = [
+            variable(20, storage_class::INPUT, 3),
+            [
+                (op::DECORATE, vec![20, decoration::LOCATION, 0]),
+                (op::DECORATE, vec![21, decoration::BUILT_IN, 42]),
+            ],
+            variable(21, storage_class::INPUT, 6),
+            variable(22, storage_class::PUSH_CONSTANT, 11),
+            variable(23, storage_class::UNIFORM, 12),
+            binding(23, 0, 0),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        module(execution_model::VERTEX, &instructions)
+    }
+
+    // The uniform buffer of the vertex shader and a sampled image at set 1 binding 1
+    fn fragment_shader() -> Module {
+        let instructions: Vec<_> = [
+            variable(20, storage_class::UNIFORM, 12),
+            binding(20, 0, 0),
+            variable(21, storage_class::UNIFORM_CONSTANT, 9),
+            binding(21, 1, 1),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        module(execution_model::FRAGMENT, &instructions)
+    }
+
+    fn reflection() -> PipelineReflection {
+        PipelineReflection::from_modules(&[
+            (fragment_shader(), "shader.frag"),
+            (vertex_shader(), "shader.vert"),
+        ])
+        .unwrap()
+    }
+
+    fn attribute(location: u32, format: vk::Format) -> vk::VertexInputAttributeDescription {
+        vk::VertexInputAttributeDescription::default()
+            .location(location)
+            .format(format)
+    }
+
+    #[test]
+    fn descriptor_bindings() {
+        let reflection = reflection();
+
+        assert_eq!(
+            reflection.descriptor_bindings,
+            [
+                DescriptorBinding {
+                    set: 0,
+                    binding: 0,
+                    descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                    count: 1,
+                    stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                },
+                DescriptorBinding {
+                    set: 1,
+                    binding: 1,
+                    descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    count: 1,
+                    stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                },
+            ]
+        );
+        assert!(reflection.uses_binding(1, 1));
+        assert!(!reflection.uses_binding(1, 0));
+
+        let uniform_buffer = (0, vk::DescriptorType::UNIFORM_BUFFER);
+        let sampler = (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
+        assert!(reflection
+            .check_provided_bindings(&[&[uniform_buffer], &[(0, sampler.1), sampler]])
+            .is_ok());
+        for provided in [
+            &[&[uniform_buffer][..]][..],
+            &[&[uniform_buffer], &[(1, vk::DescriptorType::SAMPLED_IMAGE)]],
+            &[&[], &[sampler]],
+        ] {
+            let err = reflection.check_provided_bindings(provided).unwrap_err();
+            assert!(err.set <= 1);
+        }
+    }
+
+    #[test]
+    fn descriptor_binding_mismatch() {
+        // The fragment shader uses the binding of the uniform buffer for an image
+        let instructions: Vec<_> = [
+            variable(20, storage_class::UNIFORM_CONSTANT, 9),
+            binding(20, 0, 0),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let fragment_shader = module(execution_model::FRAGMENT, &instructions);
+        let err = PipelineReflection::from_modules(&[
+            (vertex_shader(), "shader.vert"),
+            (fragment_shader, "shader.frag"),
+        ])
+        .unwrap_err();
+        let err = err.downcast_ref::<DescriptorBindingMismatch>().unwrap();
+        assert_eq!((err.set, err.binding), (0, 0));
+
+        // Arrays of descriptors are reflected but never provided
+        let instructions: Vec<_> = [
+            variable(20, storage_class::UNIFORM_CONSTANT, 10),
+            binding(20, 1, 1),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let fragment_shader = module(execution_model::FRAGMENT, &instructions);
+        let reflection =
+            PipelineReflection::from_modules(&[(fragment_shader, "shader.frag")]).unwrap();
+        assert_eq!(reflection.descriptor_bindings[0].count, 4);
+        let sampler = (1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER);
+        assert!(reflection
+            .check_provided_bindings(&[&[], &[sampler]])
+            .is_err());
+    }
+
+    #[test]
+    fn descriptor_types() {
+        let module = module(
+            execution_model::FRAGMENT,
+            &[
+                (op::DECORATE, vec![14, decoration::BUFFER_BLOCK]),
+                (op::TYPE_STRUCT, vec![14, 4]),
+                (op::TYPE_SAMPLER, vec![15]),
+            ],
+        );
+        let descriptor_type = |type_id, storage_class| {
+            descriptor_type(&module, type_id, storage_class)
+                .map(|(descriptor_type, _)| descriptor_type)
+        };
+
+        assert_eq!(
+            descriptor_type(12, storage_class::UNIFORM),
+            Some(vk::DescriptorType::UNIFORM_BUFFER)
+        );
+        assert_eq!(
+            descriptor_type(14, storage_class::UNIFORM),
+            Some(vk::DescriptorType::STORAGE_BUFFER)
+        );
+        assert_eq!(
+            descriptor_type(12, storage_class::STORAGE_BUFFER),
+            Some(vk::DescriptorType::STORAGE_BUFFER)
+        );
+        assert_eq!(
+            descriptor_type(8, storage_class::UNIFORM_CONSTANT),
+            Some(vk::DescriptorType::SAMPLED_IMAGE)
+        );
+        assert_eq!(
+            descriptor_type(13, storage_class::UNIFORM_CONSTANT),
+            Some(vk::DescriptorType::STORAGE_IMAGE)
+        );
+        assert_eq!(
+            descriptor_type(15, storage_class::UNIFORM_CONSTANT),
+            Some(vk::DescriptorType::SAMPLER)
+        );
+        assert_eq!(
+            descriptor_type(10, storage_class::UNIFORM_CONSTANT),
+            Some(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+        );
+        assert_eq!(descriptor_type(2, storage_class::UNIFORM), None);
+    }
+
+    #[test]
+    fn push_constants() {
+        let reflection = reflection();
+
+        assert_eq!(
+            reflection.push_constant_stages(),
+            vk::ShaderStageFlags::VERTEX
+        );
+        let range = reflection.push_constant_range(80).unwrap().unwrap();
+        assert_eq!(range.stage_flags, vk::ShaderStageFlags::VERTEX);
+        assert_eq!((range.offset, range.size), (0, 80));
+        let err = reflection.push_constant_range(32).unwrap_err();
+        assert_eq!((err.shader_size, err.provided_size), (64, 32));
+
+        let reflection =
+            PipelineReflection::from_modules(&[(fragment_shader(), "shader.frag")]).unwrap();
+        assert!(reflection.push_constant_stages().is_empty());
+        assert!(reflection.push_constant_range(64).unwrap().is_none());
+    }
+
+    #[test]
+    fn vertex_attributes() {
+        let reflection = reflection();
+
+        let attributes = [
+            attribute(1, vk::Format::R32G32_SFLOAT),
+            attribute(0, vk::Format::R32G32B32_SFLOAT),
+        ];
+        let used = reflection.vertex_attributes(&attributes).unwrap();
+        assert_eq!(used.len(), 1);
+        assert_eq!(used[0].location, 0);
+
+        let err = reflection
+            .vertex_attributes(&[attribute(0, vk::Format::R32G32_SFLOAT)])
+            .unwrap_err();
+        assert_eq!(err.location, 0);
+        assert_eq!(err.shader_file_path, "shader.vert");
+        assert!(reflection
+            .vertex_attributes(&[attribute(1, vk::Format::R32G32B32_SFLOAT)])
+            .is_err());
+    }
+
+    #[test]
+    fn input_formats() {
+        let module = module(
+            execution_model::VERTEX,
+            &[
+                (op::TYPE_INT, vec![14, 32, 1]),
+                (op::TYPE_VECTOR, vec![15, 14, 2]),
+                (op::TYPE_FLOAT, vec![16, 64]),
+            ],
+        );
+
+        assert_eq!(input_format(&module, 2), Some(vk::Format::R32_SFLOAT));
+        assert_eq!(
+            input_format(&module, 4),
+            Some(vk::Format::R32G32B32A32_SFLOAT)
+        );
+        assert_eq!(input_format(&module, 6), Some(vk::Format::R32_UINT));
+        assert_eq!(input_format(&module, 15), Some(vk::Format::R32G32_SINT));
+        assert_eq!(input_format(&module, 16), None);
+        assert_eq!(input_format(&module, 5), None);
+    }
+
+    #[test]
+    fn unsupported_stage() {
+        let module = module(6, &[]);
+        let err = PipelineReflection::from_modules(&[(module, "shader.comp")]).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedShaderStage>().is_some());
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+
+use super::super::super::errors::InvalidSpirv;
+
+pub const MAGIC_NUMBER: u32 = 0x0723_0203;
+const HEADER_LEN: usize = 5;
+
+// Only the instructions needed to find the interface of a shader are parsed
+pub mod op {
+    pub const ENTRY_POINT: u32 = 15;
+    pub const TYPE_INT: u32 = 21;
+    pub const TYPE_FLOAT: u32 = 22;
+    pub const TYPE_VECTOR: u32 = 23;
+    pub const TYPE_MATRIX: u32 = 24;
+    pub const TYPE_IMAGE: u32 = 25;
+    pub const TYPE_SAMPLER: u32 = 26;
+    pub const TYPE_SAMPLED_IMAGE: u32 = 27;
+    pub const TYPE_ARRAY: u32 = 28;
+    pub const TYPE_RUNTIME_ARRAY: u32 = 29;
+    pub const TYPE_STRUCT: u32 = 30;
+    pub const TYPE_POINTER: u32 = 32;
+    pub const CONSTANT: u32 = 43;
+    pub const SPEC_CONSTANT: u32 = 50;
+    pub const VARIABLE: u32 = 59;
+    pub const DECORATE: u32 = 71;
+    pub const MEMBER_DECORATE: u32 = 72;
+}
+
+pub mod decoration {
+    pub const BUFFER_BLOCK: u32 = 3;
+    pub const ARRAY_STRIDE: u32 = 6;
+    pub const MATRIX_STRIDE: u32 = 7;
+    pub const BUILT_IN: u32 = 11;
+    pub const LOCATION: u32 = 30;
+    pub const BINDING: u32 = 33;
+    pub const DESCRIPTOR_SET: u32 = 34;
+    pub const OFFSET: u32 = 35;
+}
+
+pub mod storage_class {
+    pub const UNIFORM_CONSTANT: u32 = 0;
+    pub const INPUT: u32 = 1;
+    pub const UNIFORM: u32 = 2;
+    pub const PUSH_CONSTANT: u32 = 9;
+    pub const STORAGE_BUFFER: u32 = 12;
+}
+
+pub mod execution_model {
+    pub const VERTEX: u32 = 0;
+    pub const TESSELLATION_CONTROL: u32 = 1;
+    pub const TESSELLATION_EVALUATION: u32 = 2;
+    pub const GEOMETRY: u32 = 3;
+    pub const FRAGMENT: u32 = 4;
+    pub const GL_COMPUTE: u32 = 5;
+}
+
+pub mod dim {
+    pub const BUFFER: u32 = 5;
+    pub const SUBPASS_DATA: u32 = 6;
+}
+
+#[derive(Debug, Clone)]
+pub enum Type {
+    Int { width: u32, is_signed: bool },
+    Float { width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    // sampled is 1 for images used with a sampler and 2 for storage images
+    Image { dim: u32, sampled: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray,
+    Struct { members: Box<[u32]> },
+    Pointer { pointee: u32 },
+}
+
+pub struct Variable {
+    pub id: u32,
+    pub pointer_type: u32,
+    pub storage_class: u32,
+}
+
+pub struct Module {
+    pub execution_model: u32,
+    pub types: HashMap<u32, Type>,
+    // Only the first word of the value is kept, which is enough for array lengths
+    pub constants: HashMap<u32, u32>,
+    // First literal of each decoration, 0 for decorations without literal
+    pub decorations: HashMap<(u32, u32), u32>,
+    pub member_decorations: HashMap<(u32, u32, u32), u32>,
+    pub variables: Vec<Variable>,
+}
+
+impl Module {
+    pub fn parse(code: &[u32]) -> Result<Self, InvalidSpirv> {
+        let invalid = |reason: &str| InvalidSpirv::new(reason.to_owned());
+
+        if code.len() < HEADER_LEN || code[0] != MAGIC_NUMBER {
+            return Err(invalid("missing SPIR-V header"));
+        }
+
+        let mut module = Self {
+            execution_model: u32::MAX,
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            member_decorations: HashMap::new(),
+            variables: Vec::new(),
+        };
+        let mut execution_models = Vec::new();
+
+        let mut words = &code[HEADER_LEN..];
+        while !words.is_empty() {
+            let word_count = (words[0] >> 16) as usize;
+            let opcode = words[0] & 0xffff;
+            if word_count == 0 || word_count > words.len() {
+                return Err(invalid("truncated instruction"));
+            }
+            let operands = &words[1..word_count];
+            words = &words[word_count..];
+
+            module
+                .parse_instruction(opcode, operands, &mut execution_models)
+                .ok_or_else(|| invalid(&format!("instruction {opcode} is missing operands")))?;
+        }
+
+        module.execution_model = match execution_models.as_slice() {
+            [execution_model] => *execution_model,
+            [] => return Err(invalid("no entry point")),
+            _ => return Err(invalid("more than one entry point")),
+        };
+        Ok(module)
+    }
+
+    // Returns None if an operand is missing
+    fn parse_instruction(
+        &mut self,
+        opcode: u32,
+        operands: &[u32],
+        execution_models: &mut Vec<u32>,
+    ) -> Option<()> {
+        let operand = |i: usize| operands.get(i).copied();
+
+        match opcode {
+            op::ENTRY_POINT => execution_models.push(operand(0)?),
+            op::TYPE_INT => {
+                let (width, is_signed) = (operand(1)?, operand(2)? == 1);
+                self.types
+                    .insert(operand(0)?, Type::Int { width, is_signed });
+            }
+            op::TYPE_FLOAT => {
+                self.types
+                    .insert(operand(0)?, Type::Float { width: operand(1)? });
+            }
+            op::TYPE_VECTOR => {
+                let (component, count) = (operand(1)?, operand(2)?);
+                self.types
+                    .insert(operand(0)?, Type::Vector { component, count });
+            }
+            op::TYPE_MATRIX => {
+                let (column, count) = (operand(1)?, operand(2)?);
+                self.types
+                    .insert(operand(0)?, Type::Matrix { column, count });
+            }
+            op::TYPE_IMAGE => {
+                let (dim, sampled) = (operand(2)?, operand(6)?);
+                self.types.insert(operand(0)?, Type::Image { dim, sampled });
+            }
+            op::TYPE_SAMPLER => {
+                self.types.insert(operand(0)?, Type::Sampler);
+            }
+            op::TYPE_SAMPLED_IMAGE => {
+                self.types.insert(operand(0)?, Type::SampledImage);
+            }
+            op::TYPE_ARRAY => {
+                let element = operand(1)?;
+                // Constants are declared before the types using them
+                let length = *self.constants.get(&operand(2)?)?;
+                self.types
+                    .insert(operand(0)?, Type::Array { element, length });
+            }
+            op::TYPE_RUNTIME_ARRAY => {
+                self.types.insert(operand(0)?, Type::RuntimeArray);
+            }
+            op::TYPE_STRUCT => {
+                let members = operands.get(1..)?.into();
+                self.types.insert(operand(0)?, Type::Struct { members });
+            }
+            op::TYPE_POINTER => {
+                self.types.insert(
+                    operand(0)?,
+                    Type::Pointer {
+                        pointee: operand(2)?,
+                    },
+                );
+            }
+            op::CONSTANT | op::SPEC_CONSTANT => {
+                self.constants.insert(operand(1)?, operand(2)?);
+            }
+            op::VARIABLE => self.variables.push(Variable {
+                pointer_type: operand(0)?,
+                id: operand(1)?,
+                storage_class: operand(2)?,
+            }),
+            op::DECORATE => {
+                self.decorations
+                    .insert((operand(0)?, operand(1)?), operand(2).unwrap_or(0));
+            }
+            op::MEMBER_DECORATE => {
+                self.member_decorations.insert(
+                    (operand(0)?, operand(1)?, operand(2)?),
+                    operand(3).unwrap_or(0),
+                );
+            }
+            _ => {}
+        }
+        Some(())
+    }
+
+    pub fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations.get(&(id, decoration)).copied()
+    }
+
+    pub fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<u32> {
+        self.member_decorations
+            .get(&(id, member, decoration))
+            .copied()
+    }
+
+    pub fn pointee(&self, pointer_type: u32) -> Option<u32> {
+        match self.types.get(&pointer_type)? {
+            Type::Pointer { pointee } => Some(*pointee),
+            _ => None,
+        }
+    }
+
+    // Size in bytes of a type with an explicit layout, like the blocks of push constants
+    pub fn size_of(&self, type_id: u32, matrix_stride: Option<u32>) -> Option<u32> {
+        match self.types.get(&type_id)? {
+            Type::Int { width, .. } | Type::Float { width } => Some(width / 8),
+            Type::Vector { component, count } => Some(count * self.size_of(*component, None)?),
+            Type::Matrix { column, count } => {
+                Some(count * matrix_stride.or_else(|| self.size_of(*column, None))?)
+            }
+            Type::Array { element, length } => {
+                let stride = self
+                    .decoration(type_id, decoration::ARRAY_STRIDE)
+                    .or_else(|| self.size_of(*element, matrix_stride))?;
+                Some(length * stride)
+            }
+            Type::Struct { members } => members
+                .iter()
+                .enumerate()
+                .map(|(i, member)| {
+                    let i = i as u32;
+                    let offset = self
+                        .member_decoration(type_id, i, decoration::OFFSET)
+                        .unwrap_or(0);
+                    let matrix_stride =
+                        self.member_decoration(type_id, i, decoration::MATRIX_STRIDE);
+                    Some(offset + self.size_of(*member, matrix_stride)?)
+                })
+                .try_fold(0, |size, member_end| Some(size.max(member_end?))),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    // Builds a module from (opcode, operands) pairs
+    pub fn assemble(instructions: &[(u32, &[u32])]) -> Vec<u32> {
+        let mut code = vec![MAGIC_NUMBER, 0x0001_0000, 0, 100, 0];
+        for (opcode, operands) in instructions {
+            code.push(((operands.len() as u32 + 1) << 16) | opcode);
+            code.extend_from_slice(operands);
+        }
+        code
+    }
+
+    const ENTRY_POINT: (u32, &[u32]) = (op::ENTRY_POINT, &[execution_model::FRAGMENT, 1]);
+
+    fn parse_error(code: &[u32]) -> String {
+        Module::parse(code).err().unwrap().reason
+    }
+
+    #[test]
+    fn invalid_modules() {
+        let code = assemble(&[ENTRY_POINT, (op::TYPE_FLOAT, &[2, 32])]);
+
+        assert_eq!(parse_error(&[]), "missing SPIR-V header");
+        assert_eq!(
+            parse_error(&code[..HEADER_LEN - 1]),
+            "missing SPIR-V header"
+        );
+        let mut bad_magic = code.clone();
+        bad_magic[0] = MAGIC_NUMBER.swap_bytes();
+        assert_eq!(parse_error(&bad_magic), "missing SPIR-V header");
+
+        assert_eq!(
+            parse_error(&code[..code.len() - 1]),
+            "truncated instruction"
+        );
+        let mut zero_word_count = code.clone();
+        zero_word_count.push(op::TYPE_SAMPLER);
+        assert_eq!(parse_error(&zero_word_count), "truncated instruction");
+
+        assert_eq!(
+            parse_error(&assemble(&[ENTRY_POINT, (op::TYPE_FLOAT, &[2])])),
+            format!("instruction {} is missing operands", op::TYPE_FLOAT)
+        );
+        // Array lengths have to be declared before the array
+        assert_eq!(
+            parse_error(&assemble(&[ENTRY_POINT, (op::TYPE_ARRAY, &[3, 2, 4])])),
+            format!("instruction {} is missing operands", op::TYPE_ARRAY)
+        );
+        assert_eq!(
+            parse_error(&assemble(&[(op::TYPE_FLOAT, &[2, 32])])),
+            "no entry point"
+        );
+        assert_eq!(
+            parse_error(&assemble(&[ENTRY_POINT, ENTRY_POINT])),
+            "more than one entry point"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let module = Module::parse(&assemble(&[
+            ENTRY_POINT,
+            // Unknown instructions are skipped
+            (0xffff, &[1, 2, 3]),
+            (op::TYPE_FLOAT, &[2, 32]),
+            (op::TYPE_POINTER, &[3, storage_class::INPUT, 2]),
+            (op::VARIABLE, &[3, 4, storage_class::INPUT]),
+            (op::DECORATE, &[4, decoration::LOCATION, 2]),
+            (op::DECORATE, &[5, decoration::BUFFER_BLOCK]),
+            (op::MEMBER_DECORATE, &[5, 1, decoration::OFFSET, 16]),
+        ]))
+        .unwrap();
+
+        assert_eq!(module.execution_model, execution_model::FRAGMENT);
+        assert_eq!(module.pointee(3), Some(2));
+        assert_eq!(module.pointee(2), None);
+        assert_eq!(module.variables.len(), 1);
+        assert_eq!(module.variables[0].storage_class, storage_class::INPUT);
+        assert_eq!(module.decoration(4, decoration::LOCATION), Some(2));
+        assert_eq!(module.decoration(5, decoration::BUFFER_BLOCK), Some(0));
+        assert_eq!(module.decoration(4, decoration::BINDING), None);
+        assert_eq!(module.member_decoration(5, 1, decoration::OFFSET), Some(16));
+    }
+
+    #[test]
+    fn size_of() {
+        let module = Module::parse(&assemble(&[
+            ENTRY_POINT,
+            (op::TYPE_FLOAT, &[2, 32]),
+            (op::TYPE_VECTOR, &[3, 2, 3]),
+            (op::TYPE_VECTOR, &[4, 2, 4]),
+            (op::TYPE_MATRIX, &[5, 4, 4]),
+            (op::TYPE_INT, &[6, 32, 0]),
+            (op::CONSTANT, &[6, 7, 3]),
+            (op::TYPE_ARRAY, &[8, 3, 7]),
+            (op::DECORATE, &[8, decoration::ARRAY_STRIDE, 16]),
+            (op::TYPE_ARRAY, &[9, 2, 7]),
+            // mat4 model; vec3 positions[3]; float scale;
+            (op::TYPE_STRUCT, &[10, 5, 8, 2]),
+            (op::MEMBER_DECORATE, &[10, 0, decoration::OFFSET, 0]),
+            (op::MEMBER_DECORATE, &[10, 0, decoration::MATRIX_STRIDE, 16]),
+            (op::MEMBER_DECORATE, &[10, 1, decoration::OFFSET, 64]),
+            (op::MEMBER_DECORATE, &[10, 2, decoration::OFFSET, 112]),
+            (op::TYPE_SAMPLER, &[11]),
+        ]))
+        .unwrap();
+
+        assert_eq!(module.size_of(2, None), Some(4));
+        assert_eq!(module.size_of(3, None), Some(12));
+        assert_eq!(module.size_of(5, None), Some(64));
+        assert_eq!(module.size_of(5, Some(32)), Some(128));
+        assert_eq!(module.size_of(8, None), Some(48));
+        // Tightly packed without an array stride
+        assert_eq!(module.size_of(9, None), Some(12));
+        assert_eq!(module.size_of(10, None), Some(116));
+        assert_eq!(module.size_of(11, None), None);
+        assert_eq!(module.size_of(12, None), None);
+    }
+}
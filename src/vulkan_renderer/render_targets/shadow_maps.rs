@@ -0,0 +1,283 @@
+use ash::{prelude::VkResult, vk};
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+use crate::{
+    config::{AssetPaths, ShadowSettings},
+    vulkan_renderer::{
+        memory::{Image, ImageCreateInfo},
+        uniform_buffer_object::MAX_SHADOW_MAPS,
+        vulkan_context::VulkanContext,
+    },
+};
+
+use super::{
+    create_render_pass::{create_shadow_render_pass, SHADOW_MAP_LAYOUT},
+    errors::FailedToFindSupportedFormatForShadowMap,
+    graphics_pipeline::{create_shadow_pipeline, PipelineReflection},
+    RenderTargets,
+};
+
+// Depth of the scene seen from each shadow casting light, one layer of the image per light
+pub struct ShadowMaps {
+    extent: vk::Extent2D,
+    image: Image,
+    layer_views: Box<[vk::ImageView]>,
+
+    render_pass: vk::RenderPass,
+    framebuffers: Box<[vk::Framebuffer]>,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    push_constant_stages: vk::ShaderStageFlags,
+
+    // Compares the depth of the fragments with the depth stored in the shadow map
+    sampler: vk::Sampler,
+}
+
+impl ShadowMaps {
+    pub unsafe fn new(
+        context: &VulkanContext,
+        frame_descriptor_set_layout: vk::DescriptorSetLayout,
+        reflection: &PipelineReflection,
+        assets: &AssetPaths,
+        settings: &ShadowSettings,
+    ) -> Result<Self> {
+        let extent = vk::Extent2D {
+            width: settings.map_size,
+            height: settings.map_size,
+        };
+        let format = find_shadow_map_format(context)?;
+
+        let image = create_shadow_map_image(context, extent, format)?
+            .defer(|mut image| image.destroy(context));
+        let layer_views = create_layer_views(context.device(), &image, format)?
+            .defer(|layer_views| RenderTargets::destroy_image_views(&layer_views, context));
+
+        let render_pass = create_shadow_render_pass(context.device(), format)?
+            .defer(|render_pass| context.device().destroy_render_pass(render_pass, None));
+
+        let (pipeline_layout, pipeline) = create_shadow_pipeline(
+            context,
+            &extent,
+            *render_pass,
+            &[frame_descriptor_set_layout],
+            reflection,
+            assets,
+            settings,
+        )?;
+        let pipeline_layout = pipeline_layout.defer(|pipeline_layout| {
+            context
+                .device()
+                .destroy_pipeline_layout(pipeline_layout, None)
+        });
+        let pipeline = pipeline.defer(|pipeline| context.device().destroy_pipeline(pipeline, None));
+
+        let framebuffers =
+            create_shadow_framebuffers(context.device(), *render_pass, extent, &layer_views)?
+                .defer(|framebuffers| RenderTargets::destroy_framebuffers(&framebuffers, context));
+
+        let sampler = create_shadow_sampler(context.device())?
+            .defer(|sampler| context.device().destroy_sampler(sampler, None));
+
+        Ok(Self {
+            sampler: ScopeGuard::into_inner(sampler),
+            pipeline: ScopeGuard::into_inner(pipeline),
+            push_constant_stages: reflection.push_constant_stages(),
+            pipeline_layout: ScopeGuard::into_inner(pipeline_layout),
+            framebuffers: ScopeGuard::into_inner(framebuffers),
+            render_pass: ScopeGuard::into_inner(render_pass),
+            layer_views: ScopeGuard::into_inner(layer_views),
+            image: ScopeGuard::into_inner(image),
+            extent,
+        })
+    }
+
+    // Used to reload the shadow vertex shader, the current pipeline is kept until
+    // replace_pipeline() is called
+    pub unsafe fn create_pipeline(
+        &self,
+        context: &VulkanContext,
+        frame_descriptor_set_layout: vk::DescriptorSetLayout,
+        reflection: &PipelineReflection,
+        assets: &AssetPaths,
+        settings: &ShadowSettings,
+    ) -> Result<(vk::PipelineLayout, vk::Pipeline)> {
+        create_shadow_pipeline(
+            context,
+            &self.extent,
+            self.render_pass,
+            &[frame_descriptor_set_layout],
+            reflection,
+            assets,
+            settings,
+        )
+    }
+
+    // The previous pipeline must not be in use anymore
+    pub unsafe fn replace_pipeline(
+        &mut self,
+        device: &ash::Device,
+        (pipeline_layout, pipeline): (vk::PipelineLayout, vk::Pipeline),
+        push_constant_stages: vk::ShaderStageFlags,
+    ) {
+        device.destroy_pipeline(self.pipeline, None);
+        device.destroy_pipeline_layout(self.pipeline_layout, None);
+        self.pipeline_layout = pipeline_layout;
+        self.pipeline = pipeline;
+        self.push_constant_stages = push_constant_stages;
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    // Indexed like the layers of the shadow map image
+    pub fn framebuffers(&self) -> &[vk::Framebuffer] {
+        &self.framebuffers
+    }
+
+    pub fn pipeline_layout(&self) -> vk::PipelineLayout {
+        self.pipeline_layout
+    }
+
+    pub fn pipeline(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    // Empty when the shadow vertex shader doesn't use push constants
+    pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+        self.push_constant_stages
+    }
+
+    // View of every layer, sampled by the main render pass
+    pub fn image_view(&self) -> vk::ImageView {
+        self.image.image_view()
+    }
+
+    pub fn image_layout(&self) -> vk::ImageLayout {
+        SHADOW_MAP_LAYOUT
+    }
+
+    pub fn sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
+        context.device().destroy_sampler(self.sampler, None);
+        RenderTargets::destroy_framebuffers(&self.framebuffers, context);
+        context.device().destroy_pipeline(self.pipeline, None);
+        context
+            .device()
+            .destroy_pipeline_layout(self.pipeline_layout, None);
+        context.device().destroy_render_pass(self.render_pass, None);
+        RenderTargets::destroy_image_views(&self.layer_views, context);
+        self.image.destroy(context);
+    }
+}
+
+fn find_shadow_map_format(
+    context: &VulkanContext,
+) -> Result<vk::Format, FailedToFindSupportedFormatForShadowMap> {
+    Image::find_supported_format(
+        context,
+        &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM],
+        vk::ImageTiling::OPTIMAL,
+        vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+    )
+    .ok_or(FailedToFindSupportedFormatForShadowMap {})
+}
+
+fn create_shadow_map_image(
+    context: &VulkanContext,
+    extent: vk::Extent2D,
+    format: vk::Format,
+) -> Result<Image> {
+    Image::new(
+        context,
+        ImageCreateInfo {
+            mip_levels: 1,
+            array_layers: MAX_SHADOW_MAPS as u32,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+            extent,
+            format,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+        },
+    )
+}
+
+// Each layer is rendered to through its own framebuffer
+unsafe fn create_layer_views(
+    device: &ash::Device,
+    image: &Image,
+    format: vk::Format,
+) -> VkResult<Box<[vk::ImageView]>> {
+    let mut layer_views = Vec::with_capacity(MAX_SHADOW_MAPS);
+
+    for layer in 0..MAX_SHADOW_MAPS as u32 {
+        let layer_view = image
+            .create_layer_view(device, format, vk::ImageAspectFlags::DEPTH, layer)
+            .inspect_err(|_| {
+                for layer_view in layer_views.iter() {
+                    device.destroy_image_view(*layer_view, None);
+                }
+            })?;
+        layer_views.push(layer_view);
+    }
+    Ok(layer_views.into_boxed_slice())
+}
+
+unsafe fn create_shadow_framebuffers(
+    device: &ash::Device,
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    layer_views: &[vk::ImageView],
+) -> VkResult<Box<[vk::Framebuffer]>> {
+    let mut framebuffers = Vec::with_capacity(layer_views.len());
+
+    for layer_view in layer_views {
+        let attachments = [*layer_view];
+        let create_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = device
+            .create_framebuffer(&create_info, None)
+            .inspect_err(|_| {
+                for framebuffer in framebuffers.iter() {
+                    device.destroy_framebuffer(*framebuffer, None);
+                }
+            })?;
+        framebuffers.push(framebuffer);
+    }
+    Ok(framebuffers.into_boxed_slice())
+}
+
+// Fragments outside of the shadow map are lit, the filtering is done in the fragment shader
+unsafe fn create_shadow_sampler(device: &ash::Device) -> VkResult<vk::Sampler> {
+    device.create_sampler(
+        &vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::NEAREST)
+            .min_filter(vk::Filter::NEAREST)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .min_lod(0.)
+            .max_lod(0.),
+        None,
+    )
+}
@@ -0,0 +1,272 @@
+mod errors;
+
+use std::{
+    error::Error,
+    fs,
+    path::Path,
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rs42::Result;
+
+use crate::config::AssetPaths;
+use errors::{FailedToCompileShader, FailedToRunShaderCompiler};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const SHADER_COMPILER: &str = "glslc";
+
+// Sent by the compiler thread
+pub type CompileError = Box<dyn Error + Send + Sync>;
+
+// Compiles the GLSL source at the first path to the SPIR-V file at the second one
+type CompileFn = fn(&str, &str) -> Result<(), CompileError>;
+
+// Polls the modification time of the SPIR-V files used by the pipelines.
+// The GLSL sources are watched too when they are laid out like the Makefile does it,
+// "<dir>/build/<name>.spv" is compiled from "<dir>/<name>".
+// The sources are compiled on a thread so that the render loop doesn't wait for the compiler,
+// the new SPIR-V files are picked up by a later poll. The thread exits with the watcher
+pub struct ShaderWatcher {
+    shaders: Box<[WatchedShader]>,
+    last_poll: Instant,
+    compile_jobs: mpsc::Sender<CompileJob>,
+    compile_errors: mpsc::Receiver<CompileError>,
+}
+
+struct CompileJob {
+    source_path: String,
+    spirv_path: String,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChangedPipelines {
+    pub main: bool,
+    pub shadow: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pipeline {
+    Main,
+    Shadow,
+}
+
+struct WatchedShader {
+    pipeline: Pipeline,
+    spirv: WatchedFile,
+    source: Option<WatchedFile>,
+}
+
+struct WatchedFile {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderWatcher {
+    pub fn new(assets: &AssetPaths) -> Result<Self> {
+        Self::with_compiler(assets, compile)
+    }
+
+    fn with_compiler(assets: &AssetPaths, compile: CompileFn) -> Result<Self> {
+        let shaders = [
+            (Pipeline::Main, &assets.vertex_shader),
+            (Pipeline::Main, &assets.fragment_shader),
+            (Pipeline::Shadow, &assets.shadow_vertex_shader),
+        ]
+        .into_iter()
+        .map(|(pipeline, spirv_path)| WatchedShader {
+            pipeline,
+            source: source_path(spirv_path).map(WatchedFile::new),
+            spirv: WatchedFile::new(spirv_path.clone()),
+        })
+        .collect();
+
+        let (compile_jobs, jobs) = mpsc::channel();
+        let (error_sender, compile_errors) = mpsc::channel();
+        thread::Builder::new()
+            .name("shader compiler".to_owned())
+            .spawn(move || run_compiler(&jobs, &error_sender, compile))?;
+
+        Ok(Self {
+            shaders,
+            last_poll: Instant::now(),
+            compile_jobs,
+            compile_errors,
+        })
+    }
+
+    // Returns the pipelines whose SPIR-V files changed since the last call and the compilation
+    // errors that happened since then. The sources that changed are queued for compilation.
+    // A compilation error is only reported once per change
+    pub fn poll(&mut self) -> (ChangedPipelines, Vec<CompileError>) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return (ChangedPipelines::default(), Vec::new());
+        }
+        self.last_poll = Instant::now();
+        self.poll_files()
+    }
+
+    fn poll_files(&mut self) -> (ChangedPipelines, Vec<CompileError>) {
+        let mut changed = ChangedPipelines::default();
+        for shader in self.shaders.iter_mut() {
+            if let Some(source) = shader.source.as_mut() {
+                if source.update() {
+                    // Only fails if the compiler thread panicked
+                    let _ = self.compile_jobs.send(CompileJob {
+                        source_path: source.path.clone(),
+                        spirv_path: shader.spirv.path.clone(),
+                    });
+                }
+            }
+            if shader.spirv.update() {
+                match shader.pipeline {
+                    Pipeline::Main => changed.main = true,
+                    Pipeline::Shadow => changed.shadow = true,
+                }
+            }
+        }
+        (changed, self.compile_errors.try_iter().collect())
+    }
+}
+
+fn run_compiler(
+    jobs: &mpsc::Receiver<CompileJob>,
+    errors: &mpsc::Sender<CompileError>,
+    compile: CompileFn,
+) {
+    // recv() fails once the watcher is dropped
+    while let Ok(job) = jobs.recv() {
+        if let Err(err) = compile(&job.source_path, &job.spirv_path) {
+            if errors.send(err).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+impl ChangedPipelines {
+    pub fn any(&self) -> bool {
+        self.main || self.shadow
+    }
+}
+
+impl WatchedFile {
+    fn new(path: String) -> Self {
+        Self {
+            modified: modification_time(&path),
+            path,
+        }
+    }
+
+    // Returns true if the file was modified since the last update
+    fn update(&mut self) -> bool {
+        let modified = modification_time(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        // A file being rewritten can briefly disappear
+        modified.is_some()
+    }
+}
+
+fn modification_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn source_path(spirv_path: &str) -> Option<String> {
+    let spirv_path = Path::new(spirv_path);
+    let build_dir = spirv_path.parent()?;
+    if build_dir.file_name()? != "build" {
+        return None;
+    }
+    let source_file_name = spirv_path.file_name()?.to_str()?.strip_suffix(".spv")?;
+    let source_path = build_dir.parent()?.join(source_file_name);
+
+    source_path
+        .is_file()
+        .then(|| source_path.to_str().map(str::to_owned))
+        .flatten()
+}
+
+fn compile(source_path: &str, spirv_path: &str) -> Result<(), CompileError> {
+    let output = Command::new(SHADER_COMPILER)
+        .arg(source_path)
+        .arg("-o")
+        .arg(spirv_path)
+        .output()
+        .map_err(|err| FailedToRunShaderCompiler::new(source_path.to_owned(), err))?;
+
+    if !output.status.success() {
+        return Err(FailedToCompileShader::new(
+            source_path.to_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::File;
+
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    fn failing_compile(source_path: &str, _spirv_path: &str) -> Result<(), CompileError> {
+        Err(FailedToCompileShader::new(source_path.to_owned(), "syntax error".to_owned()).into())
+    }
+
+    // The mtime resolution of some file systems is coarse, so it is set rather than waited for
+    fn set_modified(path: &str, time: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(time))
+            .unwrap();
+    }
+
+    #[test]
+    fn compile_errors_dont_hide_changes() {
+        let directory = TestDirectory::new("shader_watcher");
+        fs::create_dir(directory.path("build")).unwrap();
+        let source = directory.write("shader.vert", "");
+        let spirv = directory.write("build/shader.vert.spv", "");
+
+        let mut watcher = ShaderWatcher::with_compiler(
+            &AssetPaths {
+                vertex_shader: spirv.clone(),
+                ..AssetPaths::default()
+            },
+            failing_compile,
+        )
+        .unwrap();
+        let (changed, errors) = watcher.poll_files();
+        assert!(!changed.any());
+        assert!(errors.is_empty());
+
+        let modified = SystemTime::now() + Duration::from_secs(60);
+        set_modified(&source, modified);
+        set_modified(&spirv, modified);
+        let (changed, mut errors) = watcher.poll_files();
+        assert!(changed.main);
+        assert!(!changed.shadow);
+
+        // The compiler thread may not be done yet
+        if errors.is_empty() {
+            errors.push(watcher.compile_errors.recv().unwrap());
+        }
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].downcast_ref::<FailedToCompileShader>().is_some());
+
+        let (changed, errors) = watcher.poll_files();
+        assert!(!changed.any());
+        assert!(errors.is_empty());
+    }
+}
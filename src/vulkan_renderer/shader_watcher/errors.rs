@@ -0,0 +1,21 @@
+use rs42::error_struct_custom_display;
+
+error_struct_custom_display!(
+    FailedToRunShaderCompiler {
+        source_path: String,
+        err: std::io::Error,
+    },
+    "Failed to run glslc on \"{}\": {}",
+    source_path,
+    err
+);
+
+error_struct_custom_display!(
+    FailedToCompileShader {
+        source_path: String,
+        output: String,
+    },
+    "Failed to compile shader \"{}\":\n{}",
+    source_path,
+    output
+);
@@ -1,8 +1,91 @@
+use crate::scene::Light;
+
+use super::light_space::LightSpaceMatrix;
+
 type Mat4 = linear_algebra::Matrix<f32, 4, 4>;
 
+// Has to match MAX_LIGHTS in shader.frag
+pub const MAX_LIGHTS: usize = 16;
+// Has to match MAX_SHADOW_MAPS in shader.frag and shadow.vert
+pub const MAX_SHADOW_MAPS: usize = 4;
+
+// Stored in the cone of lights without a shadow map
+pub const NO_SHADOW_MAP: f32 = -1.;
+
+const DIRECTIONAL_LIGHT: f32 = 0.;
+const POINT_LIGHT: f32 = 1.;
+const SPOT_LIGHT: f32 = 2.;
+
+// Follows the std140 layout, which is why everything is a vec4
 #[repr(C)]
 pub struct UniformBufferObject {
-    pub model: Mat4,
     pub view: Mat4,
     pub proj: Mat4,
+    pub shadow_matrices: [LightSpaceMatrix; MAX_SHADOW_MAPS],
+    pub camera_position: [f32; 4],
+    pub ambient_color: [f32; 4],
+    pub light_count: u32,
+    pub shadow_pcf_radius: u32,
+    pub _padding: [u32; 2],
+    pub lights: [LightUniform; MAX_LIGHTS],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LightUniform {
+    // w is the type of the light
+    pub position: [f32; 4],
+    // w is the range
+    pub direction: [f32; 4],
+    // w is the intensity
+    pub color: [f32; 4],
+    // Cosines of the inner and outer angles, z is the index of the shadow map
+    pub cone: [f32; 4],
+}
+
+impl From<&Light> for LightUniform {
+    fn from(light: &Light) -> Self {
+        match *light {
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+            } => Self {
+                position: [0., 0., 0., DIRECTIONAL_LIGHT],
+                direction: [direction[0], direction[1], direction[2], 0.],
+                color: [color[0], color[1], color[2], intensity],
+                cone: [0., 0., NO_SHADOW_MAP, 0.],
+            },
+            Light::Point {
+                position,
+                color,
+                intensity,
+                range,
+            } => Self {
+                position: [position[0], position[1], position[2], POINT_LIGHT],
+                direction: [0., 0., 0., range],
+                color: [color[0], color[1], color[2], intensity],
+                cone: [0., 0., NO_SHADOW_MAP, 0.],
+            },
+            Light::Spot {
+                position,
+                direction,
+                color,
+                intensity,
+                range,
+                inner_cone_angle,
+                outer_cone_angle,
+            } => Self {
+                position: [position[0], position[1], position[2], SPOT_LIGHT],
+                direction: [direction[0], direction[1], direction[2], range],
+                color: [color[0], color[1], color[2], intensity],
+                cone: [
+                    inner_cone_angle.to_radians().cos(),
+                    outer_cone_angle.to_radians().cos(),
+                    NO_SHADOW_MAP,
+                    0.,
+                ],
+            },
+        }
+    }
 }
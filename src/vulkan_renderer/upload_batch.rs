@@ -0,0 +1,345 @@
+use ash::{prelude::VkResult, vk};
+use rs42::{
+    scope_guard::{Defer, ScopeGuard},
+    Result,
+};
+
+use super::{buffer::Buffer, vulkan_context::VulkanContext, vulkan_interface::VulkanInterface};
+
+// Records the staging copies, layout transitions and mip map generation of many resources so
+// that they are all submitted at once.
+// With a dedicated transfer queue the copies run on it, then the ownership of the resources is
+// given to the graphics queue, which also generates the mip maps as blits need a graphics queue
+pub struct UploadBatch<'a> {
+    context: &'a VulkanContext,
+    interface: &'a VulkanInterface,
+    transfer: Recording,
+    // None without a dedicated transfer queue, everything is recorded in transfer instead
+    graphics: Option<Recording>,
+    staging_buffers: Vec<Buffer>,
+}
+
+// Each recording has its own pool as the transfer and graphics queues can be in different
+// families
+struct Recording {
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+}
+
+// Uploads that were submitted and might still be running on the GPU.
+// Commands submitted to the graphics queue afterwards are ordered after the uploads by the
+// barriers recorded in the batch, the fence is only used to know when the staging buffers can be
+// freed
+pub struct PendingUpload {
+    fence: vk::Fence,
+    // Null without a dedicated transfer queue
+    semaphore: vk::Semaphore,
+    command_pools: Vec<vk::CommandPool>,
+    staging_buffers: Vec<Buffer>,
+}
+
+impl<'a> UploadBatch<'a> {
+    pub unsafe fn begin(
+        context: &'a VulkanContext,
+        interface: &'a VulkanInterface,
+    ) -> Result<Self> {
+        let queue_families = interface.queue_families();
+
+        let transfer = Recording::begin(context.device(), queue_families.transfer_index)?
+            .defer(|recording| recording.destroy(context.device()));
+        let graphics = if queue_families.has_dedicated_transfer_queue() {
+            Some(Recording::begin(
+                context.device(),
+                queue_families.graphics_index,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            context,
+            interface,
+            transfer: ScopeGuard::into_inner(transfer),
+            graphics,
+            staging_buffers: Vec::new(),
+        })
+    }
+
+    // Copies, layout transitions to TRANSFER_DST_OPTIMAL and ownership releases
+    pub fn transfer_command_buffer(&self) -> vk::CommandBuffer {
+        self.transfer.command_buffer
+    }
+
+    // Ownership acquisitions, mip map generation and the final layout transitions
+    pub fn graphics_command_buffer(&self) -> vk::CommandBuffer {
+        self.graphics
+            .as_ref()
+            .unwrap_or(&self.transfer)
+            .command_buffer
+    }
+
+    // The returned buffer holds a copy of data until the upload is finished
+    pub fn stage<T>(&mut self, data: &[T]) -> Result<vk::Buffer> {
+        let staging_buffer = Buffer::new(
+            self.context,
+            size_of_val(data) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )?
+        .defer(|mut staging_buffer| unsafe { staging_buffer.destroy(self.context) });
+
+        unsafe { staging_buffer.copy_from_ram(0, data)? }
+
+        let buffer = staging_buffer.buffer();
+        self.staging_buffers
+            .push(ScopeGuard::into_inner(staging_buffer));
+        Ok(buffer)
+    }
+
+    // dst_stage_mask and dst_access_mask describe how the buffer is used once uploaded
+    pub unsafe fn copy_to_buffer<T>(
+        &mut self,
+        dst: &Buffer,
+        data: &[T],
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) -> Result<()> {
+        let size = size_of_val(data) as vk::DeviceSize;
+        let staging_buffer = self.stage(data)?;
+        let device = self.context.device();
+
+        device.cmd_copy_buffer(
+            self.transfer.command_buffer,
+            staging_buffer,
+            dst.buffer(),
+            &[vk::BufferCopy::default().size(size)],
+        );
+
+        let barrier = vk::BufferMemoryBarrier::default()
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(dst.buffer())
+            .offset(0)
+            .size(vk::WHOLE_SIZE);
+        let Some(graphics) = &self.graphics else {
+            device.cmd_pipeline_barrier(
+                self.transfer.command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[barrier
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(dst_access_mask)],
+                &[],
+            );
+            return Ok(());
+        };
+
+        let queue_families = self.interface.queue_families();
+        let barrier = barrier
+            .src_queue_family_index(queue_families.transfer_index)
+            .dst_queue_family_index(queue_families.graphics_index);
+        device.cmd_pipeline_barrier(
+            self.transfer.command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[barrier.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)],
+            &[],
+        );
+        device.cmd_pipeline_barrier(
+            graphics.command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[barrier.dst_access_mask(dst_access_mask)],
+            &[],
+        );
+        Ok(())
+    }
+
+    // Gives the image from the transfer queue to the graphics queue, the image keeps its layout.
+    // Does nothing without a dedicated transfer queue
+    pub unsafe fn transfer_image_ownership(
+        &self,
+        image: vk::Image,
+        layout: vk::ImageLayout,
+        subresource_range: vk::ImageSubresourceRange,
+    ) {
+        let Some(graphics) = &self.graphics else {
+            return;
+        };
+        let queue_families = self.interface.queue_families();
+        let device = self.context.device();
+
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(layout)
+            .new_layout(layout)
+            .src_queue_family_index(queue_families.transfer_index)
+            .dst_queue_family_index(queue_families.graphics_index)
+            .image(image)
+            .subresource_range(subresource_range);
+        device.cmd_pipeline_barrier(
+            self.transfer.command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier.src_access_mask(vk::AccessFlags::TRANSFER_WRITE)],
+        );
+        device.cmd_pipeline_barrier(
+            graphics.command_buffer,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE)],
+        );
+    }
+
+    // The graphics submission waits on the transfer one through a semaphore, the fence is
+    // signaled once both are finished
+    pub unsafe fn submit(mut self) -> Result<PendingUpload> {
+        let device = self.context.device();
+        let queues = self.interface.queues();
+
+        let fence = device
+            .create_fence(&vk::FenceCreateInfo::default(), None)?
+            .defer(|fence| device.destroy_fence(fence, None));
+        let semaphore = if self.graphics.is_some() {
+            device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?
+        } else {
+            vk::Semaphore::null()
+        }
+        .defer(|semaphore| device.destroy_semaphore(semaphore, None));
+
+        device.end_command_buffer(self.transfer.command_buffer)?;
+        let transfer_command_buffers = [self.transfer.command_buffer];
+        let signal_semaphores = [*semaphore];
+
+        match &self.graphics {
+            None => device.queue_submit(
+                queues.graphics_queue(),
+                &[vk::SubmitInfo::default().command_buffers(&transfer_command_buffers)],
+                *fence,
+            )?,
+            Some(graphics) => {
+                device.end_command_buffer(graphics.command_buffer)?;
+                device.queue_submit(
+                    queues.transfer_queue(),
+                    &[vk::SubmitInfo::default()
+                        .command_buffers(&transfer_command_buffers)
+                        .signal_semaphores(&signal_semaphores)],
+                    vk::Fence::null(),
+                )?;
+                device.queue_submit(
+                    queues.graphics_queue(),
+                    &[vk::SubmitInfo::default()
+                        .wait_semaphores(&signal_semaphores)
+                        .wait_dst_stage_mask(&[vk::PipelineStageFlags::ALL_COMMANDS])
+                        .command_buffers(&[graphics.command_buffer])],
+                    *fence,
+                )?;
+            }
+        }
+
+        // The recordings are now owned by the pending upload, which frees them once the fence
+        // is signaled
+        let command_pools = std::iter::once(&self.transfer)
+            .chain(self.graphics.as_ref())
+            .map(|recording| recording.command_pool)
+            .collect();
+        self.transfer.command_pool = vk::CommandPool::null();
+        self.graphics = None;
+
+        Ok(PendingUpload {
+            staging_buffers: std::mem::take(&mut self.staging_buffers),
+            command_pools,
+            semaphore: ScopeGuard::into_inner(semaphore),
+            fence: ScopeGuard::into_inner(fence),
+        })
+    }
+}
+
+impl Drop for UploadBatch<'_> {
+    // Only reached without a call to submit() if an upload failed to be recorded
+    fn drop(&mut self) {
+        let device = self.context.device();
+        unsafe {
+            if self.transfer.command_pool != vk::CommandPool::null() {
+                self.transfer.destroy(device);
+            }
+            if let Some(graphics) = &self.graphics {
+                graphics.destroy(device);
+            }
+            for staging_buffer in self.staging_buffers.iter_mut() {
+                staging_buffer.destroy(self.context);
+            }
+        }
+    }
+}
+
+impl Recording {
+    unsafe fn begin(device: &ash::Device, queue_family_index: u32) -> VkResult<Self> {
+        let command_pool = device
+            .create_command_pool(
+                &vk::CommandPoolCreateInfo::default()
+                    .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+                    .queue_family_index(queue_family_index),
+                None,
+            )?
+            .defer(|command_pool| device.destroy_command_pool(command_pool, None));
+
+        let command_buffer = device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_pool(*command_pool)
+                .command_buffer_count(1),
+        )?[0];
+        device.begin_command_buffer(
+            command_buffer,
+            &vk::CommandBufferBeginInfo::default()
+                .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+        )?;
+
+        Ok(Self {
+            command_pool: ScopeGuard::into_inner(command_pool),
+            command_buffer,
+        })
+    }
+
+    // The command buffer is freed with its pool
+    unsafe fn destroy(&self, device: &ash::Device) {
+        device.destroy_command_pool(self.command_pool, None);
+    }
+}
+
+impl PendingUpload {
+    pub unsafe fn is_finished(&self, device: &ash::Device) -> VkResult<bool> {
+        device.get_fence_status(self.fence)
+    }
+
+    pub unsafe fn wait(&self, device: &ash::Device) -> VkResult<()> {
+        device.wait_for_fences(&[self.fence], true, u64::MAX)
+    }
+
+    // The upload has to be finished
+    pub unsafe fn destroy(&mut self, context: &VulkanContext) {
+        for staging_buffer in self.staging_buffers.iter_mut() {
+            staging_buffer.destroy(context);
+        }
+        self.staging_buffers.clear();
+        for command_pool in self.command_pools.drain(..) {
+            context.device().destroy_command_pool(command_pool, None);
+        }
+        context.device().destroy_semaphore(self.semaphore, None);
+        context.device().destroy_fence(self.fence, None);
+    }
+}
@@ -1,12 +1,16 @@
 mod device;
 mod errors;
 mod instance;
+mod pipeline_cache;
 mod queue_families;
 mod validation_layers;
 
+use std::cell::{RefCell, RefMut};
+
 use ash::{prelude::VkResult, vk};
-pub use device::{create_device, PhysicalDeviceData, SwapchainBuilder};
+pub use device::{create_device, PhysicalDeviceData, PresentationSurface, SwapchainBuilder};
 use instance::create_instance;
+use pipeline_cache::PipelineCache;
 pub use queue_families::QueueFamilies;
 use rs42::{
     scope_guard::{Defer, ScopeGuard},
@@ -14,6 +18,9 @@ use rs42::{
 };
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
+use super::allocator::Allocator;
+use crate::config::DeviceSelector;
+
 pub struct VulkanContext {
     #[allow(dead_code)]
     entry: ash::Entry,
@@ -22,7 +29,8 @@ pub struct VulkanContext {
     #[cfg(feature = "validation_layers")]
     debug_messenger: vk::DebugUtilsMessengerEXT,
 
-    surface: vk::SurfaceKHR,
+    // None for headless contexts
+    surface: Option<vk::SurfaceKHR>,
     surface_instance: ash::khr::surface::Instance,
     physical_device: vk::PhysicalDevice,
     physical_device_properties: vk::PhysicalDeviceProperties,
@@ -30,12 +38,62 @@ pub struct VulkanContext {
     physical_device_max_sample_count: vk::SampleCountFlags,
     device: ash::Device,
     is_device_destroyed: bool,
+
+    // Both are recreated with the device
+    allocator: RefCell<Allocator>,
+    pipeline_cache: PipelineCache,
+    pipeline_cache_directory: String,
 }
 
 impl VulkanContext {
-    pub fn new(window: &winit::window::Window) -> Result<(Self, QueueFamilies, SwapchainBuilder)> {
-        let display_handle = window.display_handle()?.into();
-        let window_handle = window.window_handle()?.into();
+    pub fn new(
+        window: &winit::window::Window,
+        pipeline_cache_directory: &str,
+        device_selector: Option<&DeviceSelector>,
+    ) -> Result<(Self, QueueFamilies, SwapchainBuilder)> {
+        let (context, queue_families, swapchain_builder) =
+            Self::create(Some(window), pipeline_cache_directory, device_selector)?;
+        Ok((
+            context,
+            queue_families,
+            swapchain_builder.expect("A windowed context should always have a swapchain builder"),
+        ))
+    }
+
+    pub fn new_headless(
+        pipeline_cache_directory: &str,
+        device_selector: Option<&DeviceSelector>,
+    ) -> Result<(Self, QueueFamilies)> {
+        let (context, queue_families, _) =
+            Self::create(None, pipeline_cache_directory, device_selector)?;
+        Ok((context, queue_families))
+    }
+
+    // Only creates an instance, the devices are described without checking presentation support
+    pub fn describe_physical_devices() -> Result<String> {
+        let entry = unsafe { ash::Entry::load()? };
+
+        let (instance, debug_messenger) = create_instance(&entry, None)?;
+        let instance = instance.defer(|instance| unsafe { instance.destroy_instance(None) });
+        let _debug_messenger = debug_messenger.defer(|debug_messenger| unsafe {
+            if let Some(debug_messenger) = debug_messenger {
+                ash::ext::debug_utils::Instance::new(&entry, &instance)
+                    .destroy_debug_utils_messenger(debug_messenger, None);
+            }
+        });
+
+        PhysicalDeviceData::describe_all(&instance)
+    }
+
+    fn create(
+        window: Option<&winit::window::Window>,
+        pipeline_cache_directory: &str,
+        device_selector: Option<&DeviceSelector>,
+    ) -> Result<(Self, QueueFamilies, Option<SwapchainBuilder>)> {
+        let display_handle = match window {
+            Some(window) => Some(window.display_handle()?.into()),
+            None => None,
+        };
 
         let entry = unsafe { ash::Entry::load()? };
 
@@ -50,15 +108,48 @@ impl VulkanContext {
 
         let surface_instance = ash::khr::surface::Instance::new(&entry, &instance);
 
-        let surface = unsafe {
-            ash_window::create_surface(&entry, &instance, display_handle, window_handle, None)?
-                .defer(|surface| surface_instance.destroy_surface(surface, None))
-        };
+        let surface = match (window, display_handle) {
+            (Some(window), Some(display_handle)) => Some(unsafe {
+                ash_window::create_surface(
+                    &entry,
+                    &instance,
+                    display_handle,
+                    window.window_handle()?.into(),
+                    None,
+                )?
+            }),
+            _ => None,
+        }
+        .defer(|surface| unsafe {
+            if let Some(surface) = surface {
+                surface_instance.destroy_surface(surface, None);
+            }
+        });
 
+        let presentation_surface =
+            window
+                .zip(*surface)
+                .map(|(window, surface)| PresentationSurface {
+                    surface_instance: &surface_instance,
+                    surface,
+                    window_inner_size: window.inner_size(),
+                });
         let physical_device_data =
-            PhysicalDeviceData::new(&surface_instance, &instance, *surface, window.inner_size())?;
+            PhysicalDeviceData::new(&instance, presentation_surface.as_ref(), device_selector)?;
         let device = unsafe { create_device(&instance, &physical_device_data)? }
             .defer(|device| unsafe { device.destroy_device(None) });
+        let allocator = Allocator::new(
+            &instance,
+            physical_device_data.physical_device,
+            &physical_device_data.physical_device_properties,
+        );
+        let pipeline_cache = unsafe {
+            PipelineCache::load(
+                &device,
+                &physical_device_data.physical_device_properties,
+                pipeline_cache_directory,
+            )
+        };
 
         #[cfg(not(feature = "validation_layers"))]
         {
@@ -66,6 +157,9 @@ impl VulkanContext {
         }
         Ok((
             VulkanContext {
+                pipeline_cache_directory: pipeline_cache_directory.to_owned(),
+                pipeline_cache,
+                allocator: RefCell::new(allocator),
                 device: ScopeGuard::into_inner(device),
                 physical_device_max_sample_count: physical_device_data.max_sample_count,
                 physical_device_features: physical_device_data.physical_device_features,
@@ -121,6 +215,18 @@ impl VulkanContext {
         self.physical_device_properties = physical_device_properties;
         self.physical_device_features = physical_device_features;
         self.physical_device_max_sample_count = physical_device_max_sample_count;
+        self.allocator = RefCell::new(Allocator::new(
+            &self.instance,
+            physical_device,
+            &physical_device_properties,
+        ));
+        self.pipeline_cache = unsafe {
+            PipelineCache::load(
+                &self.device,
+                &self.physical_device_properties,
+                &self.pipeline_cache_directory,
+            )
+        };
         self.is_device_destroyed = false;
     }
 
@@ -160,12 +266,33 @@ impl VulkanContext {
         self.physical_device_max_sample_count
     }
 
+    // Buffers and images get their memory from it
+    pub fn allocator(&self) -> RefMut<'_, Allocator> {
+        debug_assert!(
+            !self.is_device_destroyed,
+            "VulkanContext::allocator() was called after device destruction"
+        );
+
+        self.allocator.borrow_mut()
+    }
+
+    // Given to every pipeline creation
+    pub fn pipeline_cache(&self) -> vk::PipelineCache {
+        debug_assert!(
+            !self.is_device_destroyed,
+            "VulkanContext::pipeline_cache() was called after device destruction"
+        );
+
+        self.pipeline_cache.cache()
+    }
+
     pub fn instance(&self) -> &ash::Instance {
         &self.instance
     }
 
     pub fn surface(&self) -> vk::SurfaceKHR {
         self.surface
+            .expect("VulkanContext::surface() was called on a headless context")
     }
 
     pub fn surface_instance(&self) -> &ash::khr::surface::Instance {
@@ -177,6 +304,9 @@ impl VulkanContext {
             !self.is_device_destroyed,
             "VulkanContext::destroy_device() was called after device destruction"
         );
+        self.pipeline_cache
+            .destroy(&self.device, &self.physical_device_properties);
+        self.allocator.get_mut().destroy(&self.device);
         self.device.destroy_device(None);
         self.is_device_destroyed = true;
     }
@@ -192,7 +322,9 @@ impl VulkanContext {
             ash::ext::debug_utils::Instance::new(&self.entry, &self.instance)
                 .destroy_debug_utils_messenger(self.debug_messenger, None);
         }
-        self.surface_instance.destroy_surface(self.surface, None);
+        if let Some(surface) = self.surface {
+            self.surface_instance.destroy_surface(surface, None);
+        }
         self.instance.destroy_instance(None);
     }
 }
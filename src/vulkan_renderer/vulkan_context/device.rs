@@ -4,16 +4,26 @@ mod swapchain_builder;
 use ash::vk;
 use std::ffi::c_char;
 
-pub use physical_device::PhysicalDeviceData;
+pub use physical_device::{PhysicalDeviceData, PresentationSurface};
 use rs42::{extensions::PipeLine, Result};
 pub use swapchain_builder::SwapchainBuilder;
 
 pub const REQUIRED_EXTENSIONS: &[*const c_char] = &[
     #[cfg(target_os = "macos")]
     vk::KHR_PORTABILITY_SUBSET_NAME.as_ptr(),
-    vk::KHR_SWAPCHAIN_NAME.as_ptr(),
 ];
 
+// Only required when the device presents to a surface
+pub const PRESENTATION_REQUIRED_EXTENSIONS: &[*const c_char] = &[vk::KHR_SWAPCHAIN_NAME.as_ptr()];
+
+pub fn get_required_extensions(presents_to_surface: bool) -> Vec<*const c_char> {
+    let mut required_extensions = REQUIRED_EXTENSIONS.to_vec();
+    if presents_to_surface {
+        required_extensions.extend_from_slice(PRESENTATION_REQUIRED_EXTENSIONS);
+    }
+    required_extensions
+}
+
 pub unsafe fn create_device(
     instance: &ash::Instance,
     device_data: &PhysicalDeviceData,
@@ -27,7 +37,9 @@ pub unsafe fn create_device(
         .collect();
 
     let device_features = get_device_features(&device_data.physical_device_features);
-    let device_create_info = get_device_create_info(&queue_create_infos, &device_features);
+    let required_extensions = get_required_extensions(device_data.swapchain_builder.is_some());
+    let device_create_info =
+        get_device_create_info(&queue_create_infos, &device_features, &required_extensions);
 
     unsafe {
         instance
@@ -55,9 +67,10 @@ fn get_device_features(
 fn get_device_create_info<'a>(
     queue_create_infos: &'a [vk::DeviceQueueCreateInfo],
     device_features: &'a vk::PhysicalDeviceFeatures,
+    required_extensions: &'a [*const c_char],
 ) -> vk::DeviceCreateInfo<'a> {
     vk::DeviceCreateInfo::default()
         .queue_create_infos(queue_create_infos)
         .enabled_features(device_features)
-        .enabled_extension_names(REQUIRED_EXTENSIONS)
+        .enabled_extension_names(required_extensions)
 }
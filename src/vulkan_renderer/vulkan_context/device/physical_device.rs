@@ -1,12 +1,14 @@
+use crate::config::DeviceSelector;
 use crate::vulkan_renderer::vulkan_context::errors::{
-    NoSuitablePhysicalDevice, PhysicalDeviceIsNotSuitable,
+    NoSuitablePhysicalDevice, NoSuitableSelectedPhysicalDevice, PhysicalDeviceIsNotSuitable,
 };
 use std::collections::HashSet;
 use std::ffi::CStr;
+use std::fmt::Write;
 
 use ash::vk;
 
-use super::super::device::REQUIRED_EXTENSIONS;
+use super::super::device::get_required_extensions;
 use crate::vulkan_renderer::vulkan_context::device::swapchain_builder::SwapchainBuilder;
 use crate::vulkan_renderer::vulkan_context::queue_families::{QueueFamilies, QueueFamiliesBuilder};
 use rs42::Result;
@@ -19,7 +21,14 @@ pub struct PhysicalDeviceData {
     pub physical_device_features: vk::PhysicalDeviceFeatures,
     pub max_sample_count: vk::SampleCountFlags,
     pub queue_families: QueueFamilies,
-    pub swapchain_builder: SwapchainBuilder,
+    // None when the device was picked for headless rendering
+    pub swapchain_builder: Option<SwapchainBuilder>,
+}
+
+pub struct PresentationSurface<'a> {
+    pub surface_instance: &'a ash::khr::surface::Instance,
+    pub surface: vk::SurfaceKHR,
+    pub window_inner_size: winit::dpi::PhysicalSize<u32>,
 }
 
 #[derive(Ord, Eq, PartialEq, PartialOrd)]
@@ -31,60 +40,122 @@ struct ScoredPhysicalDeviceData {
 }
 
 impl PhysicalDeviceData {
+    // The best scored device is picked, among the ones matching selector if there is one
     pub fn new(
-        surface_instance: &ash::khr::surface::Instance,
         instance: &ash::Instance,
-        surface: vk::SurfaceKHR,
-        window_inner_size: winit::dpi::PhysicalSize<u32>,
+        presentation_surface: Option<&PresentationSurface>,
+        selector: Option<&DeviceSelector>,
     ) -> Result<PhysicalDeviceData> {
-        unsafe { instance.enumerate_physical_devices()? }
+        let best_device = unsafe { instance.enumerate_physical_devices()? }
             .into_iter()
-            .filter_map(|device| {
-                match ScoredPhysicalDeviceData::new(
-                    instance,
-                    surface_instance,
-                    surface,
-                    window_inner_size,
-                    device,
-                ) {
+            .enumerate()
+            .filter(|(index, device)| {
+                let device_properties = unsafe { instance.get_physical_device_properties(*device) };
+                selector.is_none_or(|selector| is_selected(selector, *index, &device_properties))
+            })
+            .filter_map(|(index, device)| {
+                match ScoredPhysicalDeviceData::new(instance, presentation_surface, device) {
                     Ok(scored_device) => Some(scored_device),
                     Err(err) => {
-                        println!("Failed to score device {device:?}: {err}");
+                        eprintln!("WARNING: Physical device {index} can't be used: {err}");
                         None
                     }
                 }
             })
             .max_by(|left, right| left.score.cmp(&right.score))
-            .map(|scored_device_data| scored_device_data.physical_device_data)
-            .ok_or(NoSuitablePhysicalDevice::new().into())
+            .map(|scored_device_data| scored_device_data.physical_device_data);
+
+        match (best_device, selector) {
+            (Some(device), _) => Ok(device),
+            (None, Some(selector)) => {
+                Err(NoSuitableSelectedPhysicalDevice::new(selector.to_string()).into())
+            }
+            (None, None) => Err(NoSuitablePhysicalDevice::new().into()),
+        }
     }
+
+    // One line per device with its score, or the reason it can't be used.
+    // Presentation support isn't checked as there is no surface to present to
+    pub fn describe_all(instance: &ash::Instance) -> Result<String> {
+        let mut description = String::new();
+        for (index, device) in unsafe { instance.enumerate_physical_devices()? }
+            .into_iter()
+            .enumerate()
+        {
+            let device_properties = unsafe { instance.get_physical_device_properties(device) };
+            let status = match ScoredPhysicalDeviceData::new(instance, None, device) {
+                Ok(scored_device) => format!(
+                    "score {}, {:?} samples",
+                    scored_device.score.0, scored_device.physical_device_data.max_sample_count
+                ),
+                Err(err) => format!("can't be used: {err}"),
+            };
+            writeln!(
+                description,
+                "{index}: {} ({:?}), Vulkan {}.{}.{}, {status}",
+                device_name(&device_properties),
+                device_properties.device_type,
+                vk::api_version_major(device_properties.api_version),
+                vk::api_version_minor(device_properties.api_version),
+                vk::api_version_patch(device_properties.api_version),
+            )?;
+        }
+        Ok(description)
+    }
+}
+
+fn is_selected(
+    selector: &DeviceSelector,
+    index: usize,
+    device_properties: &vk::PhysicalDeviceProperties,
+) -> bool {
+    match selector {
+        DeviceSelector::Index(selected_index) => index == *selected_index,
+        DeviceSelector::Type(device_type) => device_properties.device_type == *device_type,
+        DeviceSelector::Name(name) => device_name(device_properties)
+            .to_lowercase()
+            .contains(&name.to_lowercase()),
+    }
+}
+
+fn device_name(device_properties: &vk::PhysicalDeviceProperties) -> String {
+    device_properties.device_name_as_c_str().map_or_else(
+        |_| "<invalid name>".to_owned(),
+        |name| name.to_string_lossy().into_owned(),
+    )
 }
 
 impl ScoredPhysicalDeviceData {
     fn new(
         instance: &ash::Instance,
-        surface_instance: &ash::khr::surface::Instance,
-        surface: vk::SurfaceKHR,
-        window_inner_size: winit::dpi::PhysicalSize<u32>,
+        presentation_surface: Option<&PresentationSurface>,
         device: vk::PhysicalDevice,
     ) -> Result<ScoredPhysicalDeviceData> {
         let device_properties = unsafe { instance.get_physical_device_properties(device) };
         let device_features = unsafe { instance.get_physical_device_features(device) };
         let (max_sample_count, sample_count_score) =
             Self::get_max_usable_sample_count(device_properties);
-        let queue_families =
-            Self::find_queue_families(instance, surface_instance, surface, device)?;
-
-        Self::check_device_suitability(instance, device, device_features)?;
+        let queue_families = Self::find_queue_families(instance, presentation_surface, device)?;
 
-        let swapchain_builder = SwapchainBuilder::new(
+        Self::check_device_suitability(
+            instance,
             device,
-            queue_families,
-            surface_instance,
-            surface,
-            window_inner_size,
+            device_features,
+            presentation_surface.is_some(),
         )?;
 
+        let swapchain_builder = presentation_surface
+            .map(|presentation_surface| {
+                SwapchainBuilder::new(
+                    device,
+                    queue_families,
+                    presentation_surface.surface_instance,
+                    presentation_surface.surface,
+                    presentation_surface.window_inner_size,
+                )
+            })
+            .transpose()?;
+
         let score = Self::score_device(device_properties, device_features, sample_count_score);
 
         Ok(ScoredPhysicalDeviceData {
@@ -104,18 +175,20 @@ impl ScoredPhysicalDeviceData {
         instance: &ash::Instance,
         device: vk::PhysicalDevice,
         _device_features: vk::PhysicalDeviceFeatures,
+        presents_to_surface: bool,
     ) -> Result<()> {
-        Self::check_device_available_extensions(instance, device)?;
+        Self::check_device_available_extensions(instance, device, presents_to_surface)?;
         Ok(())
     }
 
     fn check_device_available_extensions(
         instance: &ash::Instance,
         device: vk::PhysicalDevice,
+        presents_to_surface: bool,
     ) -> Result<()> {
         let set_of_available_extensions =
             Self::get_set_of_available_device_extensions(instance, device)?;
-        for extension in REQUIRED_EXTENSIONS.iter() {
+        for extension in get_required_extensions(presents_to_surface).iter() {
             let extension = unsafe { CStr::from_ptr(*extension).to_str()? };
             if !set_of_available_extensions.contains(extension) {
                 Err(PhysicalDeviceIsNotSuitable::new(
@@ -156,36 +229,55 @@ impl ScoredPhysicalDeviceData {
 
     fn find_queue_families(
         instance: &ash::Instance,
-        surface_instance: &ash::khr::surface::Instance,
-        surface: vk::SurfaceKHR,
+        presentation_surface: Option<&PresentationSurface>,
         device: vk::PhysicalDevice,
     ) -> Result<QueueFamilies> {
         let queue_families =
             unsafe { instance.get_physical_device_queue_family_properties(device) };
-        let has_present_queue = |index| unsafe {
-            surface_instance.get_physical_device_surface_support(device, index as u32, surface)
+        let has_present_queue = |index| {
+            let Some(presentation_surface) = presentation_surface else {
+                return Ok(false);
+            };
+            unsafe {
+                presentation_surface
+                    .surface_instance
+                    .get_physical_device_surface_support(
+                        device,
+                        index as u32,
+                        presentation_surface.surface,
+                    )
+            }
         };
 
-        queue_families
-            .into_iter()
-            .enumerate()
-            .try_fold(
-                QueueFamiliesBuilder::default(),
-                |mut acc, (index, queue_family)| -> Result<QueueFamiliesBuilder, vk::Result> {
-                    // TODO try == vk::QueueFlags::GRAPHICS
-                    if queue_family.queue_flags & vk::QueueFlags::GRAPHICS
-                        != vk::QueueFlags::default()
-                    {
-                        acc.graphics_index = Some(index);
-                    }
-                    if has_present_queue(index)? {
-                        acc.present_index = Some(index);
-                    }
+        let queue_families_builder = queue_families.into_iter().enumerate().try_fold(
+            QueueFamiliesBuilder::default(),
+            |mut acc, (index, queue_family)| -> Result<QueueFamiliesBuilder, vk::Result> {
+                // TODO try == vk::QueueFlags::GRAPHICS
+                if queue_family.queue_flags & vk::QueueFlags::GRAPHICS != vk::QueueFlags::default()
+                {
+                    acc.graphics_index = Some(index);
+                }
+                if has_present_queue(index)? {
+                    acc.present_index = Some(index);
+                }
+                // Families that can't do graphics nor compute are usually backed by the DMA
+                // engines, so they are preferred
+                if queue_family.queue_flags.contains(vk::QueueFlags::TRANSFER)
+                    && !queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                    && (acc.transfer_index.is_none()
+                        || !queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE))
+                {
+                    acc.transfer_index = Some(index);
+                }
+
+                Ok(acc)
+            },
+        )?;
 
-                    Ok(acc)
-                },
-            )?
-            .build(device)
+        if presentation_surface.is_none() {
+            return queue_families_builder.build_headless(device);
+        }
+        queue_families_builder.build(device)
     }
 
     fn get_max_usable_sample_count(
@@ -220,3 +312,47 @@ impl ScoredPhysicalDeviceData {
         (vk::SampleCountFlags::TYPE_1, DeviceScore(1))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn properties(
+        name: &CStr,
+        device_type: vk::PhysicalDeviceType,
+    ) -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties::default()
+            .device_name(name)
+            .unwrap()
+            .device_type(device_type)
+    }
+
+    #[test]
+    fn selected_devices() {
+        let discrete = properties(
+            c"NVIDIA GeForce RTX 3090",
+            vk::PhysicalDeviceType::DISCRETE_GPU,
+        );
+        let integrated = properties(
+            c"Intel(R) UHD Graphics 630",
+            vk::PhysicalDeviceType::INTEGRATED_GPU,
+        );
+
+        let by_index = DeviceSelector::Index(1);
+        assert!(!is_selected(&by_index, 0, &discrete));
+        assert!(is_selected(&by_index, 1, &integrated));
+
+        let by_type = DeviceSelector::Type(vk::PhysicalDeviceType::DISCRETE_GPU);
+        assert!(is_selected(&by_type, 0, &discrete));
+        assert!(!is_selected(&by_type, 1, &integrated));
+
+        let by_name = DeviceSelector::Name("3090".to_owned());
+        assert!(is_selected(&by_name, 0, &discrete));
+        assert!(!is_selected(&by_name, 1, &integrated));
+
+        // Names are case insensitive
+        let by_name = DeviceSelector::Name("uhd graphics".to_owned());
+        assert!(!is_selected(&by_name, 0, &discrete));
+        assert!(is_selected(&by_name, 1, &integrated));
+    }
+}
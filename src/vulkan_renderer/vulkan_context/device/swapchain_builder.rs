@@ -89,7 +89,7 @@ impl SwapchainBuilder {
             .image_color_space(self.format.color_space)
             .image_extent(self.extent)
             .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(self.image_usage())
             .pre_transform(self.capabilities.current_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(self.present_mode)
@@ -105,6 +105,21 @@ impl SwapchainBuilder {
         create_info.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
     }
 
+    // Screenshots copy the swapchain images, which needs them to be transfer sources
+    pub fn supports_capture(&self) -> bool {
+        self.capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    }
+
+    fn image_usage(&self) -> vk::ImageUsageFlags {
+        if self.supports_capture() {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC
+        } else {
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+        }
+    }
+
     fn choose_surface_format(
         surface_instance: &ash::khr::surface::Instance,
         device: vk::PhysicalDevice,
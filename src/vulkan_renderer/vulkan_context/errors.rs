@@ -10,6 +10,12 @@ error_struct_custom_display!(
     "Could not find any suitable physical device"
 );
 
+error_struct_custom_display!(
+    NoSuitableSelectedPhysicalDevice { selector: String },
+    "Could not find any suitable physical device matching {}, use --list-gpus to list them",
+    selector
+);
+
 error_struct_custom_display!(
     PhysicalDeviceIsNotSuitable {
         device: ash::vk::PhysicalDevice,
@@ -23,3 +29,9 @@ error_struct_custom_display!(
 error_struct_custom_display!(ValidationLayerNotFound {
     validation_layer_name: &'static CStr,
 }, "Could not find validation layer: {:?}", validation_layer_name);
+
+error_struct_custom_display!(
+    InvalidPipelineCache { reason: String },
+    "Invalid pipeline cache: {}",
+    reason
+);
@@ -22,7 +22,7 @@ const REQUIRED_EXTENSIONS: &[&CStr] = &[
 
 pub fn create_instance(
     entry: &ash::Entry,
-    display_handle: RawDisplayHandle,
+    display_handle: Option<RawDisplayHandle>,
 ) -> Result<(ash::Instance, Option<vk::DebugUtilsMessengerEXT>)> {
     if cfg!(feature = "validation_layers") {
         check_validation_layers(entry)?;
@@ -43,13 +43,16 @@ pub fn create_instance(
 
 fn get_required_extensions(
     entry: &ash::Entry,
-    display_handle: RawDisplayHandle,
+    display_handle: Option<RawDisplayHandle>,
 ) -> Result<Vec<*const c_char>> {
     let mut required_extensions = REQUIRED_EXTENSIONS
         .iter()
         .map(|elem| elem.as_ptr())
         .collect::<Vec<*const c_char>>();
-    required_extensions.extend(ash_window::enumerate_required_extensions(display_handle)?);
+    // Headless contexts don't need any of the surface extensions
+    if let Some(display_handle) = display_handle {
+        required_extensions.extend(ash_window::enumerate_required_extensions(display_handle)?);
+    }
 
     check_extensions_support(entry, &required_extensions)?;
     Ok(required_extensions)
@@ -0,0 +1,362 @@
+use std::{fs, io::ErrorKind, path::Path};
+
+use ash::vk;
+use model::fnv1a;
+use rs42::Result;
+
+use super::errors::InvalidPipelineCache;
+
+const MAGIC: [u8; 8] = *b"HHPCACHE";
+// Must be incremented every time the layout of the file changes
+const VERSION: u32 = 1;
+// magic, version, vendor id, device id, driver version, pipeline cache UUID, data len, data hash
+const HEADER_LEN: usize = 8 + 4 * 4 + vk::UUID_SIZE + 8 + 8;
+// Header written by the driver at the start of the data, see VkPipelineCacheHeaderVersionOne
+const DRIVER_HEADER_LEN: usize = 16 + vk::UUID_SIZE;
+
+// Compiled pipelines shared between runs, one file per device and driver version as the data
+// can only be used by the driver that wrote it
+pub struct PipelineCache {
+    cache: vk::PipelineCache,
+    path: String,
+}
+
+impl PipelineCache {
+    // Cache errors are only warnings, pipelines are compiled from scratch instead
+    pub unsafe fn load(
+        device: &ash::Device,
+        properties: &vk::PhysicalDeviceProperties,
+        directory: &str,
+    ) -> Self {
+        let path = format!(
+            "{directory}/{:04x}_{:04x}_{:08x}.bin",
+            properties.vendor_id, properties.device_id, properties.driver_version
+        );
+
+        let data = match read_cache(&path, properties) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("WARNING: Rejected pipeline cache \"{path}\": {err}");
+                Vec::new()
+            }
+        };
+
+        let cache = create_pipeline_cache(device, &data).unwrap_or_else(|err| {
+            eprintln!("WARNING: Failed to create pipeline cache from \"{path}\": {err}");
+            create_pipeline_cache(device, &[]).unwrap_or_else(|err| {
+                eprintln!("WARNING: Failed to create an empty pipeline cache: {err}");
+                vk::PipelineCache::null()
+            })
+        });
+
+        Self { cache, path }
+    }
+
+    pub fn cache(&self) -> vk::PipelineCache {
+        self.cache
+    }
+
+    // Writes the cache back to its file then destroys it
+    pub unsafe fn destroy(
+        &mut self,
+        device: &ash::Device,
+        properties: &vk::PhysicalDeviceProperties,
+    ) {
+        if self.cache == vk::PipelineCache::null() {
+            return;
+        }
+        if let Err(err) = self.save(device, properties) {
+            eprintln!(
+                "WARNING: Failed to save pipeline cache \"{}\": {err}",
+                self.path
+            );
+        }
+        device.destroy_pipeline_cache(self.cache, None);
+        self.cache = vk::PipelineCache::null();
+    }
+
+    unsafe fn save(
+        &self,
+        device: &ash::Device,
+        properties: &vk::PhysicalDeviceProperties,
+    ) -> Result<()> {
+        let file = encode(&device.get_pipeline_cache_data(self.cache)?, properties);
+
+        if let Some(directory) = Path::new(&self.path).parent() {
+            fs::create_dir_all(directory)?;
+        }
+        // Written to a temporary file first so that an interrupted write can't leave a truncated
+        // cache behind
+        let temporary_path = format!("{}.tmp", self.path);
+        fs::write(&temporary_path, file)?;
+        fs::rename(&temporary_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn encode(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let mut file = Vec::with_capacity(HEADER_LEN + data.len());
+    file.extend_from_slice(&MAGIC);
+    file.extend_from_slice(&VERSION.to_le_bytes());
+    file.extend_from_slice(&properties.vendor_id.to_le_bytes());
+    file.extend_from_slice(&properties.device_id.to_le_bytes());
+    file.extend_from_slice(&properties.driver_version.to_le_bytes());
+    file.extend_from_slice(&properties.pipeline_cache_uuid);
+    file.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    file.extend_from_slice(&fnv1a(data).to_le_bytes());
+    file.extend_from_slice(data);
+    file
+}
+
+unsafe fn create_pipeline_cache(
+    device: &ash::Device,
+    initial_data: &[u8],
+) -> ash::prelude::VkResult<vk::PipelineCache> {
+    device.create_pipeline_cache(
+        &vk::PipelineCacheCreateInfo::default().initial_data(initial_data),
+        None,
+    )
+}
+
+// Returns the data to give to the driver, empty if there is no cache yet
+fn read_cache(path: &str, properties: &vk::PhysicalDeviceProperties) -> Result<Vec<u8>> {
+    let file = match fs::read(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+    let invalid = |reason: &str| InvalidPipelineCache::new(reason.to_owned());
+
+    if file.len() < HEADER_LEN {
+        return Err(invalid("truncated header").into());
+    }
+    let (header, data) = file.split_at(HEADER_LEN);
+    let u32_at = |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+    let uuid_offset = 8 + 4 * 4;
+    let len_offset = uuid_offset + vk::UUID_SIZE;
+
+    if header[..8] != MAGIC {
+        return Err(invalid("invalid magic").into());
+    }
+    if u32_at(8) != VERSION {
+        return Err(invalid(&format!("unsupported version {}", u32_at(8))).into());
+    }
+    if u32_at(12) != properties.vendor_id
+        || u32_at(16) != properties.device_id
+        || u32_at(20) != properties.driver_version
+        || header[uuid_offset..len_offset] != properties.pipeline_cache_uuid
+    {
+        return Err(invalid("written by another device or driver").into());
+    }
+    if u64_at(len_offset) != data.len() as u64 {
+        return Err(invalid("truncated data").into());
+    }
+    if u64_at(len_offset + 8) != fnv1a(data) {
+        return Err(invalid("checksum mismatch").into());
+    }
+    check_driver_header(data, properties)?;
+
+    Ok(data.to_vec())
+}
+
+// Drivers are supposed to reject data they didn't write, but some of them crash instead
+fn check_driver_header(
+    data: &[u8],
+    properties: &vk::PhysicalDeviceProperties,
+) -> Result<(), InvalidPipelineCache> {
+    let invalid = |reason: &str| InvalidPipelineCache::new(reason.to_owned());
+
+    if data.is_empty() {
+        return Ok(());
+    }
+    if data.len() < DRIVER_HEADER_LEN {
+        return Err(invalid("truncated driver header"));
+    }
+    // Written in the byte order of the host
+    let u32_at = |offset: usize| u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    let header_len = u32_at(0);
+    let header_version = u32_at(4);
+    if (header_len as usize) < DRIVER_HEADER_LEN || header_len as usize > data.len() {
+        return Err(invalid("invalid driver header length"));
+    }
+    if header_version != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+        return Err(invalid("unsupported driver header version"));
+    }
+    if u32_at(8) != properties.vendor_id
+        || u32_at(12) != properties.device_id
+        || data[16..DRIVER_HEADER_LEN] != properties.pipeline_cache_uuid
+    {
+        return Err(invalid("driver header doesn't match the device"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use model::test_utils::TestDirectory;
+
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2204,
+            driver_version: 42,
+            pipeline_cache_uuid: [7; vk::UUID_SIZE],
+            ..Default::default()
+        }
+    }
+
+    // Data starting with the header a driver would write
+    fn driver_data(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(DRIVER_HEADER_LEN as u32).to_ne_bytes());
+        data.extend_from_slice(
+            &(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_ne_bytes(),
+        );
+        data.extend_from_slice(&properties.vendor_id.to_ne_bytes());
+        data.extend_from_slice(&properties.device_id.to_ne_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(b"compiled pipelines");
+        data
+    }
+
+    // Writes the file and returns the reason read_cache() rejected it
+    fn read_error(name: &str, file: &[u8], properties: &vk::PhysicalDeviceProperties) -> String {
+        let directory = TestDirectory::new(&format!("pipeline_cache_{name}"));
+        let path = directory.write("pipeline_cache.bin", file);
+        let err = read_cache(&path, properties).unwrap_err();
+        err.downcast_ref::<InvalidPipelineCache>()
+            .unwrap()
+            .reason
+            .clone()
+    }
+
+    #[test]
+    fn round_trip() {
+        let properties = properties();
+        let data = driver_data(&properties);
+        let directory = TestDirectory::new("pipeline_cache_round_trip");
+        let path = directory.path("pipeline_cache.bin");
+
+        assert!(read_cache(&path, &properties).unwrap().is_empty());
+        directory.write("pipeline_cache.bin", encode(&data, &properties));
+        assert_eq!(read_cache(&path, &properties).unwrap(), data);
+        directory.write("pipeline_cache.bin", encode(&[], &properties));
+        assert!(read_cache(&path, &properties).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_files() {
+        let properties = properties();
+        let file = encode(&driver_data(&properties), &properties);
+
+        let mut bad_magic = file.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(
+            read_error("magic", &bad_magic, &properties),
+            "invalid magic"
+        );
+
+        let mut wrong_version = file.clone();
+        wrong_version[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            read_error("version", &wrong_version, &properties),
+            format!("unsupported version {}", VERSION + 1)
+        );
+
+        assert_eq!(
+            read_error("header", &file[..HEADER_LEN - 1], &properties),
+            "truncated header"
+        );
+        assert_eq!(
+            read_error("data", &file[..file.len() - 1], &properties),
+            "truncated data"
+        );
+
+        let mut corrupted = file.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            read_error("checksum", &corrupted, &properties),
+            "checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn other_device_or_driver() {
+        let properties = properties();
+        let file = encode(&driver_data(&properties), &properties);
+
+        let others = [
+            vk::PhysicalDeviceProperties {
+                vendor_id: 0x1002,
+                ..properties
+            },
+            vk::PhysicalDeviceProperties {
+                device_id: 0x2206,
+                ..properties
+            },
+            vk::PhysicalDeviceProperties {
+                driver_version: 43,
+                ..properties
+            },
+            vk::PhysicalDeviceProperties {
+                pipeline_cache_uuid: [8; vk::UUID_SIZE],
+                ..properties
+            },
+        ];
+        for (i, other) in others.iter().enumerate() {
+            assert_eq!(
+                read_error(&format!("device_{i}"), &file, other),
+                "written by another device or driver"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_driver_headers() {
+        let properties = properties();
+        let data = driver_data(&properties);
+        assert!(check_driver_header(&data, &properties).is_ok());
+        assert!(check_driver_header(&[], &properties).is_ok());
+
+        let reason = |data: &[u8]| check_driver_header(data, &properties).unwrap_err().reason;
+        assert_eq!(
+            reason(&data[..DRIVER_HEADER_LEN - 1]),
+            "truncated driver header"
+        );
+
+        let mut too_long = data.clone();
+        too_long[0..4].copy_from_slice(&(data.len() as u32 + 1).to_ne_bytes());
+        assert_eq!(reason(&too_long), "invalid driver header length");
+        let mut too_short = data.clone();
+        too_short[0..4].copy_from_slice(&4_u32.to_ne_bytes());
+        assert_eq!(reason(&too_short), "invalid driver header length");
+
+        let mut wrong_version = data.clone();
+        wrong_version[4..8].copy_from_slice(&2_u32.to_ne_bytes());
+        assert_eq!(reason(&wrong_version), "unsupported driver header version");
+
+        for offset in [8, 12, 16, DRIVER_HEADER_LEN - 1] {
+            let mut other_device = data.clone();
+            other_device[offset] ^= 1;
+            assert_eq!(
+                reason(&other_device),
+                "driver header doesn't match the device"
+            );
+        }
+
+        // The driver header is checked once the file itself is valid
+        let other_driver = vk::PhysicalDeviceProperties {
+            pipeline_cache_uuid: [8; vk::UUID_SIZE],
+            ..properties
+        };
+        let file = encode(&driver_data(&other_driver), &properties);
+        assert_eq!(
+            read_error("driver_header", &file, &properties),
+            "driver header doesn't match the device"
+        );
+    }
+}
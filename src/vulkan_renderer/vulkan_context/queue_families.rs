@@ -9,20 +9,27 @@ use rs42::{
 pub struct QueueFamilies {
     pub graphics_index: u32,
     pub present_index: u32,
+    // Same as graphics_index if the device has no queue family dedicated to transfers
+    pub transfer_index: u32,
 }
 
 #[derive(Default)]
 pub struct QueueFamiliesBuilder {
     pub graphics_index: Option<usize>,
     pub present_index: Option<usize>,
+    pub transfer_index: Option<usize>,
 }
 
 impl QueueFamilies {
     pub fn as_vec_of_unique_indexes(&self) -> Vec<u32> {
-        [self.graphics_index, self.present_index]
+        [self.graphics_index, self.present_index, self.transfer_index]
             .into_iter()
             .get_all_uniques()
     }
+
+    pub fn has_dedicated_transfer_queue(&self) -> bool {
+        self.transfer_index != self.graphics_index
+    }
 }
 
 impl QueueFamiliesBuilder {
@@ -38,9 +45,32 @@ impl QueueFamiliesBuilder {
                     .pipe(Ok)
             };
 
+        let graphics_index = option_to_u32(self.graphics_index, "graphics")?;
+
         Ok(QueueFamilies {
-            graphics_index: option_to_u32(self.graphics_index, "graphics")?,
+            graphics_index,
             present_index: option_to_u32(self.present_index, "present")?,
+            transfer_index: self.transfer_index_or(graphics_index),
+        })
+    }
+
+    // Without a surface nothing is presented, the graphics queue is used in place of the
+    // present queue
+    pub fn build_headless(&self, device: vk::PhysicalDevice) -> Result<QueueFamilies> {
+        let graphics_index = self.graphics_index.ok_or(PhysicalDeviceIsNotSuitable::new(
+            device,
+            "graphics queue is not supported".to_string(),
+        ))? as u32;
+
+        Ok(QueueFamilies {
+            graphics_index,
+            present_index: graphics_index,
+            transfer_index: self.transfer_index_or(graphics_index),
         })
     }
+
+    fn transfer_index_or(&self, graphics_index: u32) -> u32 {
+        self.transfer_index
+            .map_or(graphics_index, |transfer_index| transfer_index as u32)
+    }
 }
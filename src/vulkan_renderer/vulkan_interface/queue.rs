@@ -4,6 +4,8 @@ use ash::vk;
 pub struct Queues {
     graphics_queue: vk::Queue,
     present_queue: vk::Queue,
+    // Same as graphics_queue if the device has no queue family dedicated to transfers
+    transfer_queue: vk::Queue,
 }
 
 impl Queues {
@@ -15,6 +17,9 @@ impl Queues {
             present_queue: context
                 .device()
                 .get_device_queue(queue_families.present_index, 0),
+            transfer_queue: context
+                .device()
+                .get_device_queue(queue_families.transfer_index, 0),
         }
     }
 
@@ -25,4 +30,8 @@ impl Queues {
     pub fn present_queue(&self) -> vk::Queue {
         self.present_queue
     }
+
+    pub fn transfer_queue(&self) -> vk::Queue {
+        self.transfer_queue
+    }
 }